@@ -44,6 +44,11 @@ impl Default for Camera {
 }
 
 impl Camera {
+    #[must_use]
+    pub fn position(&self) -> Pos3 {
+        self.position
+    }
+
     pub fn matrix(&self) -> Matrix4<f32> {
         let (roll, pitch, yaw) = self.rotation.euler_angles();
         let (sin_pitch, cos_pitch) = pitch.sin_cos();
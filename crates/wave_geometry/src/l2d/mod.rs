@@ -1,9 +1,15 @@
 pub mod plane;
 pub mod ring;
+pub mod tiled;
+pub mod tilemap;
 
 pub mod prelude {
     pub use super::{
         plane::{Plane, PlaneBuilder},
         ring::{Ring, RingBuilder},
+        tilemap::{
+            Layer, MapObject, ObjectLayer, ObjectShape, Property, Tile, TileLayer, Tilemap,
+            TilemapId, Tileset,
+        },
     };
 }
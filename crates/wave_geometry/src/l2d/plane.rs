@@ -1,4 +1,4 @@
-use crate::{vector2, VerticesAndIndices, WHITE};
+use crate::{utility, vector2, VerticesAndIndices, WHITE};
 use derive_builder::Builder;
 use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
 use wave_space::{
@@ -6,7 +6,7 @@ use wave_space::{
     vertex,
 };
 
-const SQUARE_INDICES: [u16; 6] = [0, 1, 2, 2, 3, 0];
+pub(crate) const SQUARE_INDICES: [u16; 6] = [0, 1, 2, 2, 3, 0];
 
 #[derive(Builder, Default, Debug, Clone)]
 #[builder(default, build_fn(skip))]
@@ -43,7 +43,11 @@ impl Plane {
             vert.pos += self.position.coords;
         });
 
-        VerticesAndIndices::new(vertices, SQUARE_INDICES.to_vec().into())
+        let mut vertices_and_indices =
+            VerticesAndIndices::new(vertices, SQUARE_INDICES.to_vec().into());
+        utility::generate_tangents(&mut vertices_and_indices);
+
+        vertices_and_indices
     }
 }
 
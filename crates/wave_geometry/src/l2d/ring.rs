@@ -61,9 +61,12 @@ impl Ring {
             ));
         }
 
-        VerticesAndIndices::new(
+        let mut vertices_and_indices = VerticesAndIndices::new(
             vertices.into(),
             utility::spherical_indices(self.sector_count, 2),
-        )
+        );
+        utility::generate_tangents(&mut vertices_and_indices);
+
+        vertices_and_indices
     }
 }
@@ -0,0 +1,691 @@
+//! Import of maps made with the Tiled editor, saved as XML (`.tmx`) or JSON (`.tmj`).
+//!
+//! Orthogonal, finite maps are read with their tile and object layers, tilesets, including
+//! external `.tsx` and `.tsj` ones, and custom properties. Group layers are flattened into
+//! their children and image layers are skipped.
+
+use super::tilemap::{
+    Layer, MapObject, ObjectLayer, ObjectShape, Properties, Property, Tile, TileLayer, Tilemap,
+    Tileset,
+};
+use crate::vector2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use nalgebra::Point2;
+use roxmltree::{Document, Node};
+use serde_json::Value;
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+    str::FromStr,
+};
+use wave_window::sprite::{SpriteTextureId, Sprites};
+
+/// Loads the tileset images of a map, given their paths
+type ImageLoader<'a> = dyn FnMut(&Path) -> io::Result<SpriteTextureId> + 'a;
+
+/// Loads a `.tmx` or `.tmj` map, adding its tileset images to `sprites`
+pub fn load(path: impl AsRef<Path>, sprites: &mut Sprites) -> io::Result<Tilemap> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut image = |image: &Path| fs::read(image).map(|bytes| sprites.add_texture_bytes(bytes));
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("tmx") => parse_tmx(&text, dir, &mut image),
+        Some("tmj" | "json") => parse_tmj(&text, dir, &mut image),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a .tmx or .tmj map", path.display()),
+        )),
+    }
+}
+
+fn invalid(message: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Checks the map is one the importer can read
+fn check_map(orientation: &str, infinite: bool) -> io::Result<()> {
+    if orientation != "orthogonal" {
+        return Err(invalid(format!("{orientation} maps are not supported")));
+    }
+    if infinite {
+        return Err(invalid("infinite maps are not supported"));
+    }
+    Ok(())
+}
+
+/// Loads an external tileset, which may be saved in either format
+fn load_tileset(dir: &Path, source: &str, image: &mut ImageLoader) -> io::Result<Tileset> {
+    let path = dir.join(source);
+    let text = fs::read_to_string(&path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    if path.extension().is_some_and(|extension| extension == "tsx") {
+        let document = Document::parse(&text).map_err(invalid)?;
+        xml_tileset(document.root_element(), dir, image)
+    } else {
+        json_tileset(&serde_json::from_str(&text)?, dir, image)
+    }
+}
+
+/// Tiles of a layer's data, encoded as comma separated values or base64 of little endian ids
+/// that may be compressed
+fn decode_tiles(encoding: &str, compression: &str, data: &str) -> io::Result<Vec<Tile>> {
+    if encoding == "csv" {
+        return data
+            .split(',')
+            .map(|gid| gid.trim().parse().map(Tile).map_err(invalid))
+            .collect();
+    }
+
+    if encoding != "base64" {
+        return Err(invalid(format!("unknown tile encoding {encoding:?}")));
+    }
+
+    let data = data
+        .chars()
+        .filter(|character| !character.is_whitespace())
+        .collect::<String>();
+    let bytes = STANDARD.decode(data).map_err(invalid)?;
+
+    let bytes = match compression {
+        "" => bytes,
+        "zlib" => {
+            let mut decoded = Vec::new();
+            ZlibDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+            decoded
+        }
+        "gzip" => {
+            let mut decoded = Vec::new();
+            GzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+            decoded
+        }
+        _ => {
+            return Err(invalid(format!(
+                "{compression} compression is not supported"
+            )))
+        }
+    };
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|gid| Tile(u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]])))
+        .collect())
+}
+
+/// Reads a property value written as text, as both formats do for colours and XML does for
+/// everything
+fn parse_property(kind: &str, value: &str) -> io::Result<Property> {
+    Ok(match kind {
+        "int" => Property::Int(value.parse().map_err(invalid)?),
+        "float" => Property::Float(value.parse().map_err(invalid)?),
+        "bool" => Property::Bool(value == "true"),
+        "color" => Property::Colour(parse_colour(value)?),
+        "file" => Property::File(value.to_owned()),
+        "object" => Property::Object(value.parse().map_err(invalid)?),
+        _ => Property::String(value.to_owned()),
+    })
+}
+
+/// RGBA of a `#AARRGGBB` or `#RRGGBB` colour, transparent when empty
+fn parse_colour(colour: &str) -> io::Result<[u8; 4]> {
+    let hex = colour.trim_start_matches('#');
+    let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).map_err(invalid);
+
+    match hex.len() {
+        0 => Ok([0; 4]),
+        6 => Ok([channel(0)?, channel(2)?, channel(4)?, 255]),
+        8 => Ok([channel(2)?, channel(4)?, channel(6)?, channel(0)?]),
+        _ => Err(invalid(format!("invalid colour {colour:?}"))),
+    }
+}
+
+fn parse_tmx(xml: &str, dir: &Path, image: &mut ImageLoader) -> io::Result<Tilemap> {
+    let document = Document::parse(xml).map_err(invalid)?;
+    let root = document.root_element();
+
+    if !root.has_tag_name("map") {
+        return Err(invalid("the document is not a map"));
+    }
+
+    check_map(
+        root.attribute("orientation").unwrap_or_default(),
+        root.attribute("infinite") == Some("1"),
+    )?;
+
+    let mut map = Tilemap::new(
+        required(root, "width")?,
+        required(root, "height")?,
+        vector2!(
+            required::<u32>(root, "tilewidth")? as f32,
+            required::<u32>(root, "tileheight")? as f32
+        ),
+    );
+
+    root.children()
+        .filter(Node::is_element)
+        .try_for_each(|node| -> io::Result<()> {
+            match node.tag_name().name() {
+                "properties" => map.properties = xml_properties(node)?,
+                "tileset" => {
+                    let mut tileset = match node.attribute("source") {
+                        Some(source) => load_tileset(dir, source, image)?,
+                        None => xml_tileset(node, dir, image)?,
+                    };
+                    tileset.first_gid = required(node, "firstgid")?;
+                    map.insert_tileset(tileset);
+                }
+                _ => xml_layers(node, true)?.into_iter().for_each(|layer| {
+                    map.add_layer(layer);
+                }),
+            }
+            Ok(())
+        })?;
+
+    Ok(map)
+}
+
+fn required<T: FromStr>(node: Node, name: &str) -> io::Result<T> {
+    node.attribute(name)
+        .ok_or_else(|| invalid(format!("<{}> is missing {name}", node.tag_name().name())))?
+        .parse()
+        .map_err(|_| {
+            invalid(format!(
+                "<{}> has an invalid {name}",
+                node.tag_name().name()
+            ))
+        })
+}
+
+fn optional<T: FromStr>(node: Node, name: &str, default: T) -> T {
+    node.attribute(name)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn xml_properties(node: Node) -> io::Result<Properties> {
+    node.children()
+        .filter(|property| property.has_tag_name("property"))
+        .map(|property| {
+            let kind = property.attribute("type").unwrap_or("string");
+            let value = if kind == "class" {
+                Property::Class(
+                    child(property, "properties")
+                        .map_or_else(|| Ok(Properties::new()), xml_properties)?,
+                )
+            } else {
+                // Multiline strings are written as the element's text
+                let value = property
+                    .attribute("value")
+                    .or_else(|| property.text())
+                    .unwrap_or_default();
+                parse_property(kind, value)?
+            };
+
+            Ok((required(property, "name")?, value))
+        })
+        .collect()
+}
+
+/// Properties in the `<properties>` child of `node`
+fn xml_child_properties(node: Node) -> io::Result<Properties> {
+    child(node, "properties").map_or_else(|| Ok(Properties::new()), xml_properties)
+}
+
+fn xml_tileset(node: Node, dir: &Path, image: &mut ImageLoader) -> io::Result<Tileset> {
+    let picture = child(node, "image")
+        .ok_or_else(|| invalid("tilesets of separate images are not supported"))?;
+
+    let mut tileset = Tileset::new(
+        image(&dir.join(required::<String>(picture, "source")?))?,
+        [required(node, "tilewidth")?, required(node, "tileheight")?],
+        [required(picture, "width")?, required(picture, "height")?],
+    )
+    .name(node.attribute("name").unwrap_or_default())
+    .margin(optional(node, "margin", 0))
+    .spacing(optional(node, "spacing", 0));
+
+    tileset.properties = xml_child_properties(node)?;
+    tileset.tile_properties = node
+        .children()
+        .filter(|tile| tile.has_tag_name("tile"))
+        .map(|tile| Ok((required(tile, "id")?, xml_child_properties(tile)?)))
+        .collect::<io::Result<_>>()?;
+
+    Ok(tileset)
+}
+
+/// Layers of a layer element, several for a group. Layers of hidden groups are hidden.
+fn xml_layers(node: Node, visible: bool) -> io::Result<Vec<Layer>> {
+    let visible = visible && node.attribute("visible") != Some("0");
+    let name = node.attribute("name").unwrap_or_default();
+    let properties = xml_child_properties(node)?;
+
+    Ok(match node.tag_name().name() {
+        "layer" => {
+            let data = child(node, "data").ok_or_else(|| invalid("tile layer without data"))?;
+            let tiles = match data.attribute("encoding") {
+                Some(encoding) => decode_tiles(
+                    encoding,
+                    data.attribute("compression").unwrap_or_default(),
+                    data.text().unwrap_or_default(),
+                )?,
+                None => data
+                    .children()
+                    .filter(|tile| tile.has_tag_name("tile"))
+                    .map(|tile| Tile(optional(tile, "gid", 0)))
+                    .collect(),
+            };
+
+            let mut layer =
+                TileLayer::new(name, required(node, "width")?, required(node, "height")?);
+            layer.set_tiles(tiles);
+            layer.visible = visible;
+            layer.properties = properties;
+            vec![Layer::Tiles(layer)]
+        }
+        "objectgroup" => vec![Layer::Objects(ObjectLayer {
+            name: name.to_owned(),
+            objects: node
+                .children()
+                .filter(|object| object.has_tag_name("object"))
+                .map(xml_object)
+                .collect::<io::Result<_>>()?,
+            visible,
+            properties,
+        })],
+        "group" => node
+            .children()
+            .filter(Node::is_element)
+            .map(|layer| xml_layers(layer, visible))
+            .collect::<io::Result<Vec<_>>>()?
+            .concat(),
+        _ => Vec::new(),
+    })
+}
+
+fn xml_object(node: Node) -> io::Result<MapObject> {
+    let points = |shape: Node| -> io::Result<Vec<Point2<f32>>> {
+        required::<String>(shape, "points")?
+            .split_whitespace()
+            .map(|point| {
+                let (x, y) = point
+                    .split_once(',')
+                    .ok_or_else(|| invalid(format!("invalid point {point:?}")))?;
+                Ok(Point2::new(
+                    x.parse().map_err(invalid)?,
+                    y.parse().map_err(invalid)?,
+                ))
+            })
+            .collect()
+    };
+
+    let shape = node
+        .children()
+        .filter(Node::is_element)
+        .find_map(|shape| {
+            Some(match shape.tag_name().name() {
+                "ellipse" => Ok(ObjectShape::Ellipse),
+                "point" => Ok(ObjectShape::Point),
+                "polygon" => points(shape).map(ObjectShape::Polygon),
+                "polyline" => points(shape).map(ObjectShape::Polyline),
+                "text" => Ok(ObjectShape::Text(
+                    shape.text().unwrap_or_default().to_owned(),
+                )),
+                _ => return None,
+            })
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(MapObject {
+        id: optional(node, "id", 0),
+        name: node.attribute("name").unwrap_or_default().to_owned(),
+        class: node
+            .attribute("type")
+            .or_else(|| node.attribute("class"))
+            .unwrap_or_default()
+            .to_owned(),
+        position: Point2::new(optional(node, "x", 0.), optional(node, "y", 0.)),
+        size: vector2!(optional(node, "width", 0.), optional(node, "height", 0.)),
+        rotation: optional(node, "rotation", 0.),
+        tile: node
+            .attribute("gid")
+            .map(|_| Tile(optional(node, "gid", 0))),
+        shape,
+        visible: node.attribute("visible") != Some("0"),
+        properties: xml_child_properties(node)?,
+    })
+}
+
+fn parse_tmj(json: &str, dir: &Path, image: &mut ImageLoader) -> io::Result<Tilemap> {
+    let root = serde_json::from_str::<Value>(json)?;
+
+    check_map(
+        string(&root, "orientation"),
+        root["infinite"].as_bool().unwrap_or_default(),
+    )?;
+
+    let mut map = Tilemap::new(
+        number(&root, "width")?,
+        number(&root, "height")?,
+        vector2!(
+            number::<u32>(&root, "tilewidth")? as f32,
+            number::<u32>(&root, "tileheight")? as f32
+        ),
+    );
+    map.properties = json_properties(&root)?;
+
+    array(&root, "tilesets").try_for_each(|node| -> io::Result<()> {
+        let mut tileset = match node["source"].as_str() {
+            Some(source) => load_tileset(dir, source, image)?,
+            None => json_tileset(node, dir, image)?,
+        };
+        tileset.first_gid = number(node, "firstgid")?;
+        map.insert_tileset(tileset);
+        Ok(())
+    })?;
+
+    array(&root, "layers").try_for_each(|node| -> io::Result<()> {
+        json_layers(node, true)?.into_iter().for_each(|layer| {
+            map.add_layer(layer);
+        });
+        Ok(())
+    })?;
+
+    Ok(map)
+}
+
+fn number<T: TryFrom<u64>>(node: &Value, name: &str) -> io::Result<T> {
+    node[name]
+        .as_u64()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| invalid(format!("missing or invalid {name}")))
+}
+
+fn float(node: &Value, name: &str) -> f32 {
+    node[name].as_f64().unwrap_or_default() as f32
+}
+
+fn string<'a>(node: &'a Value, name: &str) -> &'a str {
+    node[name].as_str().unwrap_or_default()
+}
+
+/// Elements of the array `name`, none when it is missing
+fn array<'a>(node: &'a Value, name: &str) -> impl Iterator<Item = &'a Value> {
+    node[name].as_array().into_iter().flatten()
+}
+
+fn json_properties(node: &Value) -> io::Result<Properties> {
+    array(node, "properties")
+        .map(|property| {
+            let value = &property["value"];
+            let value = match string(property, "type") {
+                "class" => Property::Class(json_members(value)),
+                "int" => Property::Int(value.as_i64().unwrap_or_default()),
+                "float" => Property::Float(value.as_f64().unwrap_or_default()),
+                "bool" => Property::Bool(value.as_bool().unwrap_or_default()),
+                "object" => Property::Object(number(property, "value").unwrap_or_default()),
+                kind => parse_property(kind, value.as_str().unwrap_or_default())?,
+            };
+
+            Ok((string(property, "name").to_owned(), value))
+        })
+        .collect()
+}
+
+/// Members of a class property, which JSON stores without their types
+fn json_members(value: &Value) -> Properties {
+    value
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| {
+            let value = match value {
+                Value::Bool(value) => Property::Bool(*value),
+                Value::Number(number) => number.as_i64().map_or_else(
+                    || Property::Float(number.as_f64().unwrap_or_default()),
+                    Property::Int,
+                ),
+                Value::Object(_) => Property::Class(json_members(value)),
+                _ => Property::String(value.as_str().unwrap_or_default().to_owned()),
+            };
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+fn json_tileset(node: &Value, dir: &Path, image: &mut ImageLoader) -> io::Result<Tileset> {
+    let source = node["image"]
+        .as_str()
+        .ok_or_else(|| invalid("tilesets of separate images are not supported"))?;
+
+    let mut tileset = Tileset::new(
+        image(&dir.join(source))?,
+        [number(node, "tilewidth")?, number(node, "tileheight")?],
+        [number(node, "imagewidth")?, number(node, "imageheight")?],
+    )
+    .name(string(node, "name"))
+    .margin(number(node, "margin").unwrap_or_default())
+    .spacing(number(node, "spacing").unwrap_or_default());
+
+    tileset.properties = json_properties(node)?;
+    tileset.tile_properties = array(node, "tiles")
+        .map(|tile| Ok((number(tile, "id")?, json_properties(tile)?)))
+        .collect::<io::Result<_>>()?;
+
+    Ok(tileset)
+}
+
+/// Layers of a layer object, several for a group. Layers of hidden groups are hidden.
+fn json_layers(node: &Value, visible: bool) -> io::Result<Vec<Layer>> {
+    let visible = visible && node["visible"].as_bool().unwrap_or(true);
+    let name = string(node, "name");
+    let properties = json_properties(node)?;
+
+    Ok(match string(node, "type") {
+        "tilelayer" => {
+            let tiles = match &node["data"] {
+                Value::String(data) => {
+                    decode_tiles(string(node, "encoding"), string(node, "compression"), data)?
+                }
+                data => data
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|gid| Tile(gid.as_u64().unwrap_or_default() as u32))
+                    .collect(),
+            };
+
+            let mut layer = TileLayer::new(name, number(node, "width")?, number(node, "height")?);
+            layer.set_tiles(tiles);
+            layer.visible = visible;
+            layer.properties = properties;
+            vec![Layer::Tiles(layer)]
+        }
+        "objectgroup" => vec![Layer::Objects(ObjectLayer {
+            name: name.to_owned(),
+            objects: array(node, "objects")
+                .map(json_object)
+                .collect::<io::Result<_>>()?,
+            visible,
+            properties,
+        })],
+        "group" => array(node, "layers")
+            .map(|layer| json_layers(layer, visible))
+            .collect::<io::Result<Vec<_>>>()?
+            .concat(),
+        _ => Vec::new(),
+    })
+}
+
+fn json_object(node: &Value) -> io::Result<MapObject> {
+    let points = |name| {
+        array(node, name)
+            .map(|point| Point2::new(float(point, "x"), float(point, "y")))
+            .collect()
+    };
+
+    let shape = if node["ellipse"].as_bool() == Some(true) {
+        ObjectShape::Ellipse
+    } else if node["point"].as_bool() == Some(true) {
+        ObjectShape::Point
+    } else if node["polygon"].is_array() {
+        ObjectShape::Polygon(points("polygon"))
+    } else if node["polyline"].is_array() {
+        ObjectShape::Polyline(points("polyline"))
+    } else if node["text"].is_object() {
+        ObjectShape::Text(string(&node["text"], "text").to_owned())
+    } else {
+        ObjectShape::Rectangle
+    };
+
+    let class = match string(node, "type") {
+        "" => string(node, "class"),
+        class => class,
+    };
+
+    Ok(MapObject {
+        id: number(node, "id").unwrap_or_default(),
+        name: string(node, "name").to_owned(),
+        class: class.to_owned(),
+        position: Point2::new(float(node, "x"), float(node, "y")),
+        size: vector2!(float(node, "width"), float(node, "height")),
+        rotation: float(node, "rotation"),
+        tile: node["gid"].as_u64().map(|gid| Tile(gid as u32)),
+        shape,
+        visible: node["visible"].as_bool().unwrap_or(true),
+        properties: json_properties(node)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMX: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="8" tileheight="8" infinite="0">
+ <properties>
+  <property name="music" value="cave.ogg"/>
+ </properties>
+ <tileset firstgid="1" name="terrain" tilewidth="8" tileheight="8" spacing="1" margin="1">
+  <image source="terrain.png" width="19" height="10"/>
+  <tile id="1"><properties><property name="solid" type="bool" value="true"/></properties></tile>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">1,2,0,
+2147483650,0,1</data>
+ </layer>
+ <group name="hidden" visible="0">
+  <layer id="2" name="decoration" width="3" height="2">
+   <data encoding="base64" compression="zlib">eJxjZGBgYGLABAAAWAAE</data>
+  </layer>
+ </group>
+ <objectgroup name="spawns">
+  <object id="3" name="player" type="spawn" x="4" y="12.5">
+   <properties>
+    <property name="tint" type="color" value="#80ff0000"/>
+    <property name="stats" type="class"><properties><property name="lives" type="int" value="3"/></properties></property>
+   </properties>
+   <point/>
+  </object>
+  <object id="4" x="0" y="0"><polygon points="0,0 8,0 8,8"/></object>
+ </objectgroup>
+</map>"##;
+
+    const TMJ: &str = r#"{
+ "orientation": "orthogonal", "infinite": false,
+ "width": 2, "height": 1, "tilewidth": 16, "tileheight": 16,
+ "properties": [{ "name": "gravity", "type": "float", "value": 9.5 }],
+ "tilesets": [{
+  "firstgid": 5, "name": "props", "image": "props.png",
+  "imagewidth": 32, "imageheight": 16, "tilewidth": 16, "tileheight": 16,
+  "tiles": [{ "id": 0, "properties": [{ "name": "door", "type": "object", "value": 7 }] }]
+ }],
+ "layers": [
+  { "type": "tilelayer", "name": "walls", "width": 2, "height": 1, "data": [6, 1073741829] },
+  { "type": "objectgroup", "name": "triggers", "objects": [
+   { "id": 7, "name": "exit", "class": "door", "x": 16, "y": 0, "width": 16, "height": 16,
+     "gid": 5, "properties": [{ "name": "target", "type": "string", "value": "level2" }] }
+  ]}
+ ]
+}"#;
+
+    #[test]
+    fn test_tmx_maps_are_imported_with_layers_objects_and_properties() {
+        let mut images = Vec::new();
+        let mut sprites = Sprites::default();
+        let texture = sprites.add_texture_bytes(Vec::new());
+        let map = parse_tmx(TMX, Path::new("maps"), &mut |path| {
+            images.push(path.to_owned());
+            Ok(texture)
+        })
+        .unwrap();
+
+        assert_eq!(images, [Path::new("maps/terrain.png")]);
+        assert_eq!(map.properties["music"], Property::String("cave.ogg".into()));
+
+        let tileset = &map.tilesets()[0];
+        assert_eq!((tileset.columns(), tileset.tile_count()), (2, 2));
+        assert_eq!(tileset.tile_properties[&1]["solid"], Property::Bool(true));
+
+        assert_eq!(map.tile(0, 1, 0), Some(Tile(2)));
+        assert_eq!(map.tile(0, 0, 1), Some(Tile(2).flipped_horizontally()));
+        let Layer::Tiles(decoration) = &map.layers()[1] else {
+            panic!("The group's tile layer is flattened into the map");
+        };
+        assert!(!decoration.visible);
+        assert_eq!(decoration.tiles()[..3], [Tile(1), Tile(2), Tile::EMPTY]);
+
+        let objects = map.objects().collect::<Vec<_>>();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].class, "spawn");
+        assert_eq!(objects[0].shape, ObjectShape::Point);
+        assert_eq!(objects[0].position, Point2::new(4., 12.5));
+        assert_eq!(
+            objects[0].properties["tint"],
+            Property::Colour([255, 0, 0, 128])
+        );
+        let Property::Class(stats) = &objects[0].properties["stats"] else {
+            panic!("Class properties keep their members");
+        };
+        assert_eq!(stats["lives"], Property::Int(3));
+        assert_eq!(
+            objects[1].shape,
+            ObjectShape::Polygon(vec![
+                Point2::new(0., 0.),
+                Point2::new(8., 0.),
+                Point2::new(8., 8.)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tmj_maps_are_imported_with_layers_objects_and_properties() {
+        let mut sprites = Sprites::default();
+        let texture = sprites.add_texture_bytes(Vec::new());
+        let map = parse_tmj(TMJ, Path::new(""), &mut |_| Ok(texture)).unwrap();
+
+        assert_eq!(map.tile_size, vector2!(16., 16.));
+        assert_eq!(map.properties["gravity"], Property::Float(9.5));
+        assert_eq!(map.tilesets()[0].first_gid, 5);
+        assert_eq!(
+            map.tilesets()[0].tile_properties[&0]["door"],
+            Property::Object(7)
+        );
+        assert_eq!(map.tile(0, 1, 0), Some(Tile(5).flipped_vertically()));
+
+        let exit = map.objects().next().unwrap();
+        assert_eq!(exit.class, "door");
+        assert_eq!(exit.tile, Some(Tile(5)));
+        assert_eq!(exit.size, vector2!(16., 16.));
+        assert_eq!(exit.properties["target"], Property::String("level2".into()));
+    }
+}
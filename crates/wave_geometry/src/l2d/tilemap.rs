@@ -0,0 +1,645 @@
+use crate::{l2d::plane::SQUARE_INDICES, vector2, WHITE};
+use nalgebra::{Point2, Point3, Vector2, Vector3};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use wave_space::{space::VerticesAndIndices, vertex};
+use wave_window::{
+    sprite::{SpriteTextureId, UvRect},
+    tilemap::{TileChunk, TileChunkKey},
+};
+
+/// Largest chunk side, so a chunk's vertices stay within 16 bit indices
+const MAX_CHUNK_SIZE: u32 = 128;
+
+/// Custom property of a map, layer, tileset, tile or object, as set in the Tiled editor
+#[derive(Clone, Debug, PartialEq)]
+pub enum Property {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    /// RGBA
+    Colour([u8; 4]),
+    /// Path relative to the file the property was read from
+    File(String),
+    /// Id of an object in the map, zero for none
+    Object(u32),
+    /// Members of a custom class
+    Class(Properties),
+}
+
+pub type Properties = HashMap<String, Property>;
+
+/// Tile of a layer: a global id into the map's tilesets, with Tiled's flip flags in the top
+/// bits. Id zero is an empty tile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tile(pub u32);
+
+impl Tile {
+    pub const EMPTY: Self = Self(0);
+
+    const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+    const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+    const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+    /// Every flag bit, including the hexagonal rotation flag, which orthogonal maps ignore
+    const FLAGS: u32 = 0xf000_0000;
+
+    #[must_use]
+    pub fn gid(self) -> u32 {
+        self.0 & !Self::FLAGS
+    }
+
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self.gid() == 0
+    }
+
+    #[must_use]
+    pub fn flipped_horizontally(self) -> Self {
+        Self(self.0 ^ Self::FLIPPED_HORIZONTALLY)
+    }
+
+    #[must_use]
+    pub fn flipped_vertically(self) -> Self {
+        Self(self.0 ^ Self::FLIPPED_VERTICALLY)
+    }
+
+    /// Swaps the tile's x and y axes, before any horizontal or vertical flip
+    #[must_use]
+    pub fn flipped_diagonally(self) -> Self {
+        Self(self.0 ^ Self::FLIPPED_DIAGONALLY)
+    }
+
+    /// Texture coordinates shown at the top left, top right, bottom right and bottom left of
+    /// the tile, given the tile's region of its tileset
+    fn corner_uvs(self, region: UvRect) -> [Vector2<f32>; 4] {
+        [(0., 0.), (1., 0.), (1., 1.), (0., 1.)].map(|(mut s, mut t)| {
+            if self.0 & Self::FLIPPED_HORIZONTALLY != 0 {
+                s = 1. - s;
+            }
+            if self.0 & Self::FLIPPED_VERTICALLY != 0 {
+                t = 1. - t;
+            }
+            if self.0 & Self::FLIPPED_DIAGONALLY != 0 {
+                (s, t) = (t, s);
+            }
+
+            region.min.coords + (region.max - region.min).component_mul(&vector2!(s, t))
+        })
+    }
+}
+
+/// Texture divided into tiles, numbered row by row from the top left
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tileset {
+    pub name: String,
+    pub texture: SpriteTextureId,
+    /// Global id of the first tile, assigned when the tileset is added to a map
+    pub first_gid: u32,
+    /// Size of each tile in pixels
+    pub tile_size: [u32; 2],
+    pub image_size: [u32; 2],
+    /// Pixels around the tiles
+    pub margin: u32,
+    /// Pixels between neighbouring tiles
+    pub spacing: u32,
+    pub properties: Properties,
+    /// Properties of individual tiles, by local id
+    pub tile_properties: HashMap<u32, Properties>,
+}
+
+impl Tileset {
+    #[must_use]
+    pub fn new(texture: SpriteTextureId, tile_size: [u32; 2], image_size: [u32; 2]) -> Self {
+        Self {
+            name: String::new(),
+            texture,
+            first_gid: 1,
+            tile_size: tile_size.map(|side| side.max(1)),
+            image_size,
+            margin: 0,
+            spacing: 0,
+            properties: Properties::new(),
+            tile_properties: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    #[must_use]
+    pub fn margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    #[must_use]
+    pub fn spacing(mut self, spacing: u32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    #[must_use]
+    pub fn columns(&self) -> u32 {
+        self.fitting(0)
+    }
+
+    #[must_use]
+    pub fn tile_count(&self) -> u32 {
+        self.columns() * self.fitting(1)
+    }
+
+    /// Global id of the tile `index` tiles into the tileset
+    #[must_use]
+    pub fn tile(&self, index: u32) -> Tile {
+        Tile(self.first_gid + index)
+    }
+
+    /// Region of the tile `index` tiles into the tileset
+    #[must_use]
+    pub fn region(&self, index: u32) -> UvRect {
+        let columns = self.columns().max(1);
+        let [width, height] = self.tile_size;
+        let [image_width, image_height] = self.image_size.map(|side| side.max(1) as f32);
+
+        let x = self.margin + (index % columns) * (width + self.spacing);
+        let y = self.margin + (index / columns) * (height + self.spacing);
+
+        UvRect {
+            min: Point2::new(x as f32 / image_width, y as f32 / image_height),
+            max: Point2::new(
+                (x + width) as f32 / image_width,
+                (y + height) as f32 / image_height,
+            ),
+        }
+    }
+
+    /// How many tiles fit along `axis` of the image
+    fn fitting(&self, axis: usize) -> u32 {
+        let tile = self.tile_size[axis] + self.spacing;
+        (self.image_size[axis] + self.spacing).saturating_sub(2 * self.margin) / tile
+    }
+
+    fn contains(&self, gid: u32) -> bool {
+        (self.first_gid..self.first_gid + self.tile_count()).contains(&gid)
+    }
+}
+
+/// Grid of tiles, numbered row by row from the top left
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    tiles: Vec<Tile>,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+impl TileLayer {
+    #[must_use]
+    pub fn new(name: impl Into<String>, width: u32, height: u32) -> Self {
+        Self {
+            name: name.into(),
+            width,
+            height,
+            tiles: vec![Tile::EMPTY; (width * height) as usize],
+            visible: true,
+            properties: Properties::new(),
+        }
+    }
+
+    /// Tile at column `x` and row `y`, or `None` outside the layer
+    #[must_use]
+    pub fn tile(&self, x: u32, y: u32) -> Option<Tile> {
+        (x < self.width && y < self.height).then(|| self.tiles[(y * self.width + x) as usize])
+    }
+
+    #[must_use]
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    /// Replaces every tile, given row by row. Missing tiles are left empty and extra ones are
+    /// dropped.
+    pub(crate) fn set_tiles(&mut self, tiles: impl IntoIterator<Item = Tile>) {
+        let count = self.tiles.len();
+        self.tiles.clear();
+        self.tiles.extend(tiles.into_iter().take(count));
+        self.tiles.resize(count, Tile::EMPTY);
+    }
+}
+
+/// Outline of a [`MapObject`], relative to its position
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ObjectShape {
+    #[default]
+    Rectangle,
+    Ellipse,
+    Point,
+    Polygon(Vec<Point2<f32>>),
+    Polyline(Vec<Point2<f32>>),
+    Text(String),
+}
+
+/// Object placed in an object layer, such as a spawn point or trigger area. Positions and
+/// sizes are in pixels from the map's top left, with y pointing down as in Tiled.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    /// Class, called type before Tiled 1.9
+    pub class: String,
+    pub position: Point2<f32>,
+    pub size: Vector2<f32>,
+    /// Clockwise, in degrees
+    pub rotation: f32,
+    /// Tile drawn for the object, if it is a tile object
+    pub tile: Option<Tile>,
+    pub shape: ObjectShape,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<MapObject>,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Layer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+impl Layer {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Tiles(layer) => &layer.name,
+            Self::Objects(layer) => &layer.name,
+        }
+    }
+
+    #[must_use]
+    pub fn properties(&self) -> &Properties {
+        match self {
+            Self::Tiles(layer) => &layer.properties,
+            Self::Objects(layer) => &layer.properties,
+        }
+    }
+}
+
+/// Layers of tiles and objects over a grid, meshed in square chunks that are only rebuilt
+/// when their tiles change.
+///
+/// The map's top left corner sits at [`Tilemap::position`] and rows go down the screen, so
+/// with the default 2D camera one world unit is one pixel of the tilesets.
+#[derive(Clone, Debug)]
+pub struct Tilemap {
+    /// Size in tiles
+    pub width: u32,
+    pub height: u32,
+    /// Size of a tile in world units
+    pub tile_size: Vector2<f32>,
+    pub position: Point2<f32>,
+    pub properties: Properties,
+    tilesets: Vec<Tileset>,
+    layers: Vec<Layer>,
+    chunk_size: u32,
+    /// Index given by [`Tilemaps`], keeping chunk keys of different maps apart
+    map: usize,
+    chunks: BTreeMap<TileChunkKey, TileChunk>,
+    /// Layer and chunk position of the chunks to mesh again
+    dirty: BTreeSet<(usize, [u32; 2])>,
+    generation: u64,
+}
+
+impl Tilemap {
+    #[must_use]
+    pub fn new(width: u32, height: u32, tile_size: Vector2<f32>) -> Self {
+        Self {
+            width,
+            height,
+            tile_size,
+            position: Point2::origin(),
+            properties: Properties::new(),
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            chunk_size: 16,
+            map: 0,
+            chunks: BTreeMap::new(),
+            dirty: BTreeSet::new(),
+            generation: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn position(mut self, position: Point2<f32>) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Tiles along each side of a chunk, up to 128. Smaller chunks are cheaper to rebuild
+    /// when a tile changes, larger ones take fewer draws.
+    #[must_use]
+    pub fn chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size.clamp(1, MAX_CHUNK_SIZE);
+        self.dirty_all();
+        self
+    }
+
+    /// Adds `tileset` after the existing ones, returning the global id of its first tile
+    pub fn add_tileset(&mut self, mut tileset: Tileset) -> u32 {
+        tileset.first_gid = self
+            .tilesets
+            .last()
+            .map_or(1, |last| last.first_gid + last.tile_count());
+        self.insert_tileset(tileset)
+    }
+
+    /// Adds `tileset` keeping its first global id, as given by an imported map
+    pub(crate) fn insert_tileset(&mut self, tileset: Tileset) -> u32 {
+        let first_gid = tileset.first_gid;
+        let index = self
+            .tilesets
+            .partition_point(|existing| existing.first_gid < first_gid);
+        self.tilesets.insert(index, tileset);
+        self.dirty_all();
+        first_gid
+    }
+
+    #[must_use]
+    pub fn tilesets(&self) -> &[Tileset] {
+        &self.tilesets
+    }
+
+    /// Adds `layer` over the existing ones, returning its index
+    pub fn add_layer(&mut self, layer: Layer) -> usize {
+        self.layers.push(layer);
+        let index = self.layers.len() - 1;
+        self.dirty_layer(index);
+        index
+    }
+
+    /// Adds an empty tile layer the size of the map over the existing layers
+    pub fn add_tile_layer(&mut self, name: impl Into<String>) -> usize {
+        self.add_layer(Layer::Tiles(TileLayer::new(name, self.width, self.height)))
+    }
+
+    #[must_use]
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// First layer called `name`
+    #[must_use]
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name() == name)
+    }
+
+    /// Every object of the object layers, from the bottom layer up
+    pub fn objects(&self) -> impl Iterator<Item = &MapObject> {
+        self.layers.iter().flat_map(|layer| match layer {
+            Layer::Objects(layer) => layer.objects.as_slice(),
+            Layer::Tiles(_) => &[],
+        })
+    }
+
+    /// Tile at column `x` and row `y` of layer `layer`, or `None` when there is no such tile
+    #[must_use]
+    pub fn tile(&self, layer: usize, x: u32, y: u32) -> Option<Tile> {
+        match self.layers.get(layer)? {
+            Layer::Tiles(layer) => layer.tile(x, y),
+            Layer::Objects(_) => None,
+        }
+    }
+
+    /// Replaces a tile, so its chunk is meshed again. Positions outside the layer and object
+    /// layers are ignored.
+    pub fn set_tile(&mut self, layer: usize, x: u32, y: u32, tile: Tile) {
+        let Some(Layer::Tiles(tiles)) = self.layers.get_mut(layer) else {
+            return;
+        };
+
+        if x >= tiles.width || y >= tiles.height {
+            return;
+        }
+
+        let slot = &mut tiles.tiles[(y * tiles.width + x) as usize];
+        if *slot != tile {
+            *slot = tile;
+            self.dirty
+                .insert((layer, [x / self.chunk_size, y / self.chunk_size]));
+        }
+    }
+
+    /// Shows or hides a layer
+    pub fn set_visible(&mut self, layer: usize, visible: bool) {
+        match self.layers.get_mut(layer) {
+            Some(Layer::Tiles(tiles)) if tiles.visible != visible => {
+                tiles.visible = visible;
+                self.dirty_layer(layer);
+            }
+            Some(Layer::Objects(objects)) => objects.visible = visible,
+            _ => {}
+        }
+    }
+
+    /// Meshes the chunks whose tiles changed since the last call
+    pub fn mesh(&mut self) {
+        std::mem::take(&mut self.dirty)
+            .into_iter()
+            .for_each(|(layer, chunk)| {
+                self.chunks
+                    .retain(|key, _| key.layer != layer || key.chunk != chunk);
+
+                self.generation += 1;
+                self.mesh_chunk(layer, chunk).into_iter().for_each(|mesh| {
+                    self.chunks.insert(mesh.key, mesh);
+                });
+            });
+    }
+
+    /// Meshed chunks, from the bottom layer up. Call [`Tilemap::mesh`] first to include the
+    /// latest changes.
+    pub fn chunks(&self) -> impl Iterator<Item = &TileChunk> {
+        self.chunks.values()
+    }
+
+    fn mesh_chunk(&self, layer: usize, chunk: [u32; 2]) -> Vec<TileChunk> {
+        let tiles = match self.layers.get(layer) {
+            Some(Layer::Tiles(tiles)) if tiles.visible => tiles,
+            _ => return Vec::new(),
+        };
+
+        let [start_x, start_y] = chunk.map(|side| side * self.chunk_size);
+        let end_x = (start_x + self.chunk_size).min(tiles.width);
+        let end_y = (start_y + self.chunk_size).min(tiles.height);
+
+        let mut meshes = BTreeMap::<usize, VerticesAndIndices>::new();
+
+        (start_y..end_y)
+            .flat_map(|y| (start_x..end_x).map(move |x| (x, y)))
+            .for_each(|(x, y)| {
+                let tile = tiles.tiles[(y * tiles.width + x) as usize];
+                let Some((index, tileset)) = self.tileset_of(tile) else {
+                    return;
+                };
+
+                let region = tileset.region(tile.gid() - tileset.first_gid);
+                self.push_quad(
+                    meshes.entry(index).or_default(),
+                    x,
+                    y,
+                    tile.corner_uvs(region),
+                );
+            });
+
+        meshes
+            .into_iter()
+            .map(|(tileset, mesh)| TileChunk {
+                key: TileChunkKey {
+                    map: self.map,
+                    layer,
+                    chunk,
+                    tileset,
+                },
+                texture: self.tilesets[tileset].texture,
+                generation: self.generation,
+                mesh,
+            })
+            .collect()
+    }
+
+    /// Appends the quad of the tile at column `x` and row `y`
+    fn push_quad(&self, mesh: &mut VerticesAndIndices, x: u32, y: u32, uvs: [Vector2<f32>; 4]) {
+        let left = self.position.x + x as f32 * self.tile_size.x;
+        let top = self.position.y - y as f32 * self.tile_size.y;
+        let (right, bottom) = (left + self.tile_size.x, top - self.tile_size.y);
+        let [top_left, top_right, bottom_right, bottom_left] = uvs;
+
+        let first = mesh.vertices.len() as u16;
+        mesh.vertices.extend(
+            [
+                (left, top, top_left),
+                (right, top, top_right),
+                (right, bottom, bottom_right),
+                (left, bottom, bottom_left),
+            ]
+            .map(|(x, y, uv)| vertex!(Point3::new(x, y, 0.), WHITE, Vector3::z(), uv)),
+        );
+        mesh.indices
+            .extend(SQUARE_INDICES.map(|index| first + index));
+    }
+
+    /// Tileset holding `tile`, with its index
+    fn tileset_of(&self, tile: Tile) -> Option<(usize, &Tileset)> {
+        if tile.is_empty() {
+            return None;
+        }
+
+        let index = self
+            .tilesets
+            .partition_point(|tileset| tileset.first_gid <= tile.gid())
+            .checked_sub(1)?;
+        let tileset = &self.tilesets[index];
+
+        tileset.contains(tile.gid()).then_some((index, tileset))
+    }
+
+    fn dirty_layer(&mut self, layer: usize) {
+        let Some(Layer::Tiles(tiles)) = self.layers.get(layer) else {
+            return;
+        };
+
+        let chunks = |side: u32| side.div_ceil(self.chunk_size);
+        let (columns, rows) = (chunks(tiles.width), chunks(tiles.height));
+
+        self.dirty
+            .extend((0..rows).flat_map(|y| (0..columns).map(move |x| (layer, [x, y]))));
+    }
+
+    fn dirty_all(&mut self) {
+        self.chunks.clear();
+        (0..self.layers.len()).for_each(|layer| self.dirty_layer(layer));
+    }
+}
+
+/// Tilemap added to [`Tilemaps`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TilemapId(usize);
+
+/// Every tilemap drawn, in the order they were added
+#[derive(Clone, Debug, Default)]
+pub struct Tilemaps {
+    maps: Vec<Tilemap>,
+}
+
+impl Tilemaps {
+    pub fn add(&mut self, mut tilemap: Tilemap) -> TilemapId {
+        tilemap.map = self.maps.len();
+        tilemap.dirty_all();
+        self.maps.push(tilemap);
+        TilemapId(self.maps.len() - 1)
+    }
+
+    #[must_use]
+    pub fn get(&self, id: TilemapId) -> &Tilemap {
+        &self.maps[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: TilemapId) -> &mut Tilemap {
+        &mut self.maps[id.0]
+    }
+
+    /// Meshes the changed chunks of every map and returns all chunks in drawing order
+    pub fn chunks(&mut self) -> Vec<&TileChunk> {
+        self.maps.iter_mut().for_each(Tilemap::mesh);
+        self.maps.iter().flat_map(Tilemap::chunks).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wave_window::sprite::Sprites;
+
+    #[test]
+    fn test_only_changed_chunks_are_meshed_again() {
+        let mut sprites = Sprites::default();
+        let texture = sprites.add_texture_bytes(Vec::new());
+
+        let mut map = Tilemap::new(4, 2, vector2!(16., 16.)).chunk_size(2);
+        let first = map.add_tileset(Tileset::new(texture, [16, 16], [64, 32]));
+        let ground = map.add_tile_layer("ground");
+        map.set_tile(ground, 0, 0, Tile(first + 5));
+        map.set_tile(ground, 3, 1, Tile(first));
+        map.mesh();
+
+        let chunks = map.chunks().collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].mesh.vertices.len(), 4);
+        assert_eq!(
+            chunks[0].mesh.vertices[0].tex_coord,
+            vector2!(0.25, 0.5),
+            "the top left of tile 5 is the second column of the second row"
+        );
+        assert_eq!(chunks[1].mesh.vertices[2].pos, Point3::new(64., -32., 0.));
+
+        let untouched = chunks[0].generation;
+        map.set_tile(ground, 2, 0, Tile(first + 1).flipped_horizontally());
+        map.mesh();
+
+        let chunks = map.chunks().collect::<Vec<_>>();
+        assert_eq!(chunks[0].generation, untouched);
+        assert_ne!(chunks[1].generation, untouched);
+        assert_eq!(chunks[1].mesh.indices.len(), 12);
+        assert_eq!(chunks[1].mesh.vertices[0].tex_coord, vector2!(0.5, 0.));
+    }
+}
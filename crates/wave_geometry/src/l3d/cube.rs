@@ -77,6 +77,9 @@ impl Cube {
 
         let indices = calculate_indices(&vertices);
 
-        VerticesAndIndices::new(vertices, indices)
+        let mut vertices_and_indices = VerticesAndIndices::new(vertices, indices);
+        utility::generate_tangents(&mut vertices_and_indices);
+
+        vertices_and_indices
     }
 }
@@ -1,11 +1,15 @@
 pub mod cube;
 pub mod model;
+pub mod particles;
 pub mod sphere;
 
 pub mod prelude {
     pub use super::{
         cube::{Cube, CubeBuilder},
         model::{Model, ModelBuilder},
+        particles::{
+            Burst, Curve, EmitterShape, ParticleEmitter, ParticleEmitterBuilder, ParticleEmitterId,
+        },
         sphere::{Sphere, SphereBuilder},
     };
 }
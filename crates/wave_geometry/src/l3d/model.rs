@@ -46,6 +46,8 @@ impl Model {
                 utility::scale(face, self.radius);
             });
 
+        utility::generate_tangents(&mut vertices_and_indices);
+
         vertices_and_indices
     }
 }
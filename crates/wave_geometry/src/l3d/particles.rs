@@ -0,0 +1,484 @@
+use derive_builder::Builder;
+use nalgebra::{Point3, UnitQuaternion, Vector3, Vector4};
+use std::{
+    f32::consts::{PI, TAU},
+    ops::{Add, Mul, Sub},
+};
+use wave_window::{
+    particles::{ParticleBatches, ParticleBlend, ParticleInstance},
+    sprite::SpriteTextureId,
+};
+
+/// Value over a particle's life, keyed by the fraction of its lifetime from 0 at birth to 1
+/// at death and interpolated linearly between keys
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve<T> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T> Curve<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    #[must_use]
+    pub fn constant(value: T) -> Self {
+        Self {
+            keys: vec![(0., value)],
+        }
+    }
+
+    /// Goes from `start` at birth to `end` at death
+    #[must_use]
+    pub fn linear(start: T, end: T) -> Self {
+        Self {
+            keys: vec![(0., start), (1., end)],
+        }
+    }
+
+    /// Adds a key at the fraction `time` of the lifetime
+    #[must_use]
+    pub fn key(mut self, time: f32, value: T) -> Self {
+        let time = time.clamp(0., 1.);
+        let index = self.keys.partition_point(|(key, _)| *key <= time);
+        self.keys.insert(index, (time, value));
+        self
+    }
+
+    #[must_use]
+    pub fn sample(&self, time: f32) -> T {
+        let index = self.keys.partition_point(|(key, _)| *key <= time);
+
+        match (
+            index.checked_sub(1).map(|index| self.keys[index]),
+            self.keys.get(index),
+        ) {
+            (Some((start, from)), Some(&(end, to))) => {
+                from + (to - from) * ((time - start) / (end - start))
+            }
+            (Some((_, value)), None) | (None, Some(&(_, value))) => value,
+            (None, None) => unreachable!("Curves have at least one key"),
+        }
+    }
+}
+
+impl Default for Curve<f32> {
+    fn default() -> Self {
+        Self::constant(1.)
+    }
+}
+
+impl Default for Curve<Vector4<f32>> {
+    fn default() -> Self {
+        Self::constant(Vector4::repeat(1.))
+    }
+}
+
+/// Particles spawned at once, `time` seconds into the emitter's cycle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Burst {
+    pub time: f32,
+    pub count: u32,
+}
+
+impl Burst {
+    #[must_use]
+    pub fn new(time: f32, count: u32) -> Self {
+        Self { time, count }
+    }
+}
+
+/// Where particles spawn, around the emitter's position and turned by its rotation
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EmitterShape {
+    #[default]
+    Point,
+    /// Anywhere inside the sphere
+    Sphere { radius: f32 },
+    /// Anywhere on the flat ring in the XY plane, like a [`crate::l2d::ring::Ring`]
+    Ring {
+        inner_radius: f32,
+        outer_radius: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Particle {
+    position: Point3<f32>,
+    velocity: Vector3<f32>,
+    age: f32,
+    lifetime: f32,
+}
+
+/// Spawns particles and simulates them on the CPU.
+///
+/// Particles leave within `spread` radians of `direction`, accelerate with `gravity`, lose
+/// the fraction `drag` of their velocity per second and change size and colour over their
+/// life. They are drawn as billboards sampling a sprite texture.
+#[derive(Builder, Debug, Clone)]
+#[builder(default, build_fn(skip))]
+pub struct ParticleEmitter {
+    pub position: Point3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub shape: EmitterShape,
+    /// Particles spawned per second
+    pub rate: f32,
+    pub bursts: Vec<Burst>,
+    /// Length of a cycle, after which the bursts fire again. Without one, each burst fires
+    /// once.
+    #[builder(setter(strip_option))]
+    pub duration: Option<f32>,
+    /// Seconds a particle lives
+    pub lifetime: f32,
+    /// Fraction the lifetime randomly varies by, from 0 to 1
+    pub lifetime_variation: f32,
+    pub direction: Vector3<f32>,
+    /// Half angle of the cone particles leave in, from 0 for a straight line to pi for
+    /// every direction
+    pub spread: f32,
+    pub speed: f32,
+    /// Fraction the speed randomly varies by, from 0 to 1
+    pub speed_variation: f32,
+    pub gravity: Vector3<f32>,
+    pub drag: f32,
+    /// Width and height over the particle's life
+    pub size: Curve<f32>,
+    /// Multiplies the texture's colour over the particle's life
+    pub colour: Curve<Vector4<f32>>,
+    #[builder(setter(strip_option))]
+    pub texture: Option<SpriteTextureId>,
+    pub blend: ParticleBlend,
+    /// Live particles beyond which no more are spawned
+    pub max_particles: usize,
+    pub seed: u64,
+    #[builder(setter(skip))]
+    particles: Vec<Particle>,
+    #[builder(setter(skip))]
+    elapsed: f32,
+    /// Fraction of a particle the rate has accumulated towards the next spawn
+    #[builder(setter(skip))]
+    owed: f32,
+    #[builder(setter(skip))]
+    random: u64,
+    #[builder(setter(skip))]
+    emitting: bool,
+}
+
+impl ParticleEmitterBuilder {
+    pub fn build(&mut self) -> ParticleEmitter {
+        let seed = self.seed.unwrap_or_default();
+
+        ParticleEmitter {
+            position: self.position.unwrap_or_else(Point3::origin),
+            rotation: self.rotation.unwrap_or_default(),
+            shape: self.shape.unwrap_or_default(),
+            rate: self.rate.unwrap_or_default(),
+            bursts: self.bursts.clone().unwrap_or_default(),
+            duration: self.duration.flatten().filter(|duration| *duration > 0.),
+            lifetime: self.lifetime.unwrap_or(1.),
+            lifetime_variation: self.lifetime_variation.unwrap_or_default(),
+            direction: self.direction.unwrap_or_else(Vector3::y),
+            spread: self.spread.unwrap_or_default(),
+            speed: self.speed.unwrap_or_default(),
+            speed_variation: self.speed_variation.unwrap_or_default(),
+            gravity: self.gravity.unwrap_or_default(),
+            drag: self.drag.unwrap_or_default(),
+            size: self.size.clone().unwrap_or_default(),
+            colour: self.colour.clone().unwrap_or_default(),
+            texture: Some(self.texture.flatten().expect("Field `texture` expected")),
+            blend: self.blend.unwrap_or_default(),
+            max_particles: self.max_particles.unwrap_or(1000),
+            seed,
+            particles: Vec::new(),
+            elapsed: 0.,
+            owed: 0.,
+            random: seed.max(1),
+            emitting: true,
+        }
+    }
+}
+
+impl ParticleEmitter {
+    /// Ages, moves and spawns the particles by `delta` seconds
+    pub fn update(&mut self, delta: f32) {
+        let drag = (-self.drag * delta).exp();
+        let gravity = self.gravity * delta;
+
+        self.particles.iter_mut().for_each(|particle| {
+            particle.age += delta;
+            particle.velocity = (particle.velocity + gravity) * drag;
+            particle.position += particle.velocity * delta;
+        });
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        if !self.emitting {
+            return;
+        }
+
+        self.owed += self.rate * delta;
+        let mut count = self.owed.floor() as u32;
+        self.owed -= count as f32;
+
+        count += self.advance(delta);
+
+        (0..count).for_each(|_| self.spawn());
+    }
+
+    /// Moves the cycle on by `delta` and returns the particles of the bursts passed
+    fn advance(&mut self, delta: f32) -> u32 {
+        let mut count = 0;
+        let mut remaining = delta;
+
+        loop {
+            let end = self.duration.map_or(self.elapsed + remaining, |duration| {
+                (self.elapsed + remaining).min(duration)
+            });
+
+            count += self
+                .bursts
+                .iter()
+                .filter(|burst| burst.time >= self.elapsed && burst.time < end)
+                .map(|burst| burst.count)
+                .sum::<u32>();
+
+            remaining -= end - self.elapsed;
+            self.elapsed = end;
+
+            match self.duration {
+                Some(duration) if self.elapsed >= duration => self.elapsed = 0.,
+                _ => return count,
+            }
+        }
+    }
+
+    fn spawn(&mut self) {
+        if self.particles.len() >= self.max_particles {
+            return;
+        }
+
+        let offset = match self.shape {
+            EmitterShape::Point => Vector3::zeros(),
+            EmitterShape::Sphere { radius } => {
+                self.cone(&Vector3::z(), PI) * radius * self.random().cbrt()
+            }
+            EmitterShape::Ring {
+                inner_radius,
+                outer_radius,
+            } => {
+                // Uniform over the ring's area rather than its radius
+                let inner = inner_radius.powi(2);
+                let radius = (inner + self.random() * (outer_radius.powi(2) - inner)).sqrt();
+                let (sin, cos) = (self.random() * TAU).sin_cos();
+                Vector3::new(radius * cos, radius * sin, 0.)
+            }
+        };
+
+        let direction = self.cone(&self.direction.clone(), self.spread);
+        let speed = self.speed * self.vary(self.speed_variation);
+        let lifetime = self.lifetime * self.vary(self.lifetime_variation);
+
+        self.particles.push(Particle {
+            position: self.position + self.rotation * offset,
+            velocity: self.rotation * direction * speed,
+            age: 0.,
+            lifetime,
+        });
+    }
+
+    /// Random unit vector within `spread` radians of `axis`, uniform over the cone's cap
+    fn cone(&mut self, axis: &Vector3<f32>, spread: f32) -> Vector3<f32> {
+        let axis = axis.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y);
+        let cos = 1. - self.random() * (1. - spread.clamp(0., PI).cos());
+        let sin = (1. - cos * cos).max(0.).sqrt();
+        let (around_sin, around_cos) = (self.random() * TAU).sin_cos();
+
+        let to_axis = UnitQuaternion::rotation_between(&Vector3::z(), &axis)
+            .unwrap_or_else(|| UnitQuaternion::from_axis_angle(&Vector3::x_axis(), PI));
+
+        to_axis * Vector3::new(sin * around_cos, sin * around_sin, cos)
+    }
+
+    /// Random factor within `variation` of one
+    fn vary(&mut self, variation: f32) -> f32 {
+        1. + variation.clamp(0., 1.) * (2. * self.random() - 1.)
+    }
+
+    fn random(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        (self.random >> 40) as f32 / (1 << 24) as f32
+    }
+
+    /// Resumes spawning after [`ParticleEmitter::stop`]
+    pub fn play(&mut self) {
+        self.emitting = true;
+    }
+
+    /// Stops spawning, letting the live particles finish their lives
+    pub fn stop(&mut self) {
+        self.emitting = false;
+    }
+
+    #[must_use]
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    /// Removes every live particle and starts the cycle over
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.elapsed = 0.;
+        self.owed = 0.;
+    }
+
+    /// Number of live particles
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Live particles as drawn, alpha blended ones sorted back to front as seen from `eye`
+    #[must_use]
+    pub fn instances(&self, eye: &Point3<f32>) -> Vec<ParticleInstance> {
+        let mut particles = self.particles.iter().collect::<Vec<_>>();
+
+        if self.blend == ParticleBlend::AlphaBlend {
+            particles.sort_by(|a, b| {
+                let distance = |particle: &Particle| (particle.position - eye).norm_squared();
+                distance(b).total_cmp(&distance(a))
+            });
+        }
+
+        particles
+            .into_iter()
+            .map(|particle| {
+                let time = particle.age / particle.lifetime;
+
+                ParticleInstance {
+                    position: particle.position,
+                    size: self.size.sample(time),
+                    colour: self.colour.sample(time),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Emitter added with [`ParticleSystems::add`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ParticleEmitterId(usize);
+
+/// Every particle emitter drawn, in the order they were added
+#[derive(Clone, Debug, Default)]
+pub struct ParticleSystems {
+    emitters: Vec<ParticleEmitter>,
+}
+
+impl ParticleSystems {
+    pub fn add(&mut self, emitter: ParticleEmitter) -> ParticleEmitterId {
+        self.emitters.push(emitter);
+        ParticleEmitterId(self.emitters.len() - 1)
+    }
+
+    #[must_use]
+    pub fn get(&self, id: ParticleEmitterId) -> &ParticleEmitter {
+        &self.emitters[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: ParticleEmitterId) -> &mut ParticleEmitter {
+        &mut self.emitters[id.0]
+    }
+
+    /// Simulates every emitter by `delta` seconds
+    pub fn update(&mut self, delta: f32) {
+        self.emitters
+            .iter_mut()
+            .for_each(|emitter| emitter.update(delta));
+    }
+
+    /// Live particles of every emitter as seen from `eye`, one batch per emitter
+    #[must_use]
+    pub fn batches(&self, eye: &Point3<f32>) -> ParticleBatches {
+        let mut batches = ParticleBatches::default();
+
+        self.emitters.iter().for_each(|emitter| {
+            if let Some(texture) = emitter.texture {
+                batches.push(texture, emitter.blend, emitter.instances(eye));
+            }
+        });
+
+        batches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wave_window::sprite::Sprites;
+
+    #[test]
+    fn test_curves_interpolate_between_keys() {
+        let size = Curve::linear(1., 3.).key(0.5, 5.);
+
+        assert_eq!(size.sample(0.), 1.);
+        assert_eq!(size.sample(0.25), 3.);
+        assert_eq!(size.sample(0.75), 4.);
+        assert_eq!(size.sample(2.), 3.);
+        assert_eq!(Curve::constant(2.).sample(0.5), 2.);
+    }
+
+    #[test]
+    fn test_emitters_spawn_from_rate_and_bursts_and_particles_expire() {
+        let mut sprites = Sprites::default();
+        let texture = sprites.add_texture_bytes(Vec::new());
+
+        let mut emitter = ParticleEmitterBuilder::default()
+            .texture(texture)
+            .rate(10.)
+            .bursts(vec![Burst::new(0., 5)])
+            .duration(1.)
+            .lifetime(0.45)
+            .speed(2.)
+            .gravity(Vector3::new(0., -10., 0.))
+            .build();
+
+        emitter.update(0.25);
+        assert_eq!(emitter.len(), 7);
+
+        // Particles leave straight up and gravity slows them down
+        let eye = Point3::new(0., 0., 10.);
+        let instances = emitter.instances(&eye);
+        assert!(instances
+            .iter()
+            .all(|instance| instance.position == Point3::origin()));
+
+        emitter.update(0.25);
+        assert_eq!(emitter.len(), 10);
+        assert!(emitter
+            .instances(&eye)
+            .iter()
+            .all(|instance| instance.position.x == 0. && instance.position.y < 0.5));
+
+        // Every particle has expired, and the burst fires again once the cycle starts over
+        emitter.update(0.5);
+        assert_eq!(emitter.len(), 5);
+        emitter.update(0.01);
+        assert_eq!(emitter.len(), 10);
+
+        emitter.stop();
+        emitter.update(0.5);
+        assert!(emitter.is_empty());
+
+        let mut systems = ParticleSystems::default();
+        let id = systems.add(emitter);
+        systems.get_mut(id).play();
+        systems.update(0.1);
+        assert_eq!(systems.batches(&eye).instances.len(), 1);
+    }
+}
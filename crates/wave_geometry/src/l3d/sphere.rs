@@ -1,4 +1,7 @@
-use crate::{utility::spherical_indices, VerticesAndIndices, WHITE};
+use crate::{
+    utility::{self, spherical_indices},
+    VerticesAndIndices, WHITE,
+};
 use derive_builder::Builder;
 use wave_space::{
     space::{Pos3, Vertices},
@@ -65,9 +68,12 @@ impl Sphere {
             }
         }
 
-        VerticesAndIndices::new(
+        let mut vertices_and_indices = VerticesAndIndices::new(
             vertices,
             spherical_indices(self.sector_count, self.stack_count),
-        )
+        );
+        utility::generate_tangents(&mut vertices_and_indices);
+
+        vertices_and_indices
     }
 }
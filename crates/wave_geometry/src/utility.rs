@@ -1,6 +1,6 @@
 use super::WHITE;
-use wave_space::space::{Indices, Pos3, Vertex, Vertices};
-use nalgebra::{vector, Matrix4, Point3, Vector2};
+use wave_space::space::{Indices, Pos3, Vertex, Vertices, VerticesAndIndices};
+use nalgebra::{vector, Matrix4, Point3, Vector2, Vector3};
 use std::{
     collections::HashMap,
     ops::{Mul, Sub},
@@ -41,6 +41,87 @@ fn normal(p1: Point3<f32>, p2: Point3<f32>, p3: Point3<f32>) -> Point3<f32> {
     Point3::from(p3.sub(p2).cross(&p1.sub(p2)))
 }
 
+/// Fills in each vertex's tangent and bitangent from its texture coordinates, for
+/// tangent space normal mapping.
+///
+/// Like MikkTSpace, the tangents of every triangle sharing a vertex are accumulated,
+/// then orthogonalised against the vertex normal, and the bitangent keeps the
+/// handedness of the texture mapping so mirrored UVs light correctly. Triangles come
+/// from the indices, or from consecutive vertices when there are none.
+pub fn generate_tangents(vertices_and_indices: &mut VerticesAndIndices) {
+    let VerticesAndIndices { vertices, indices } = vertices_and_indices;
+
+    let triangles = if indices.is_empty() {
+        (0..vertices.len()).collect::<Vec<usize>>()
+    } else {
+        indices.iter().map(|index| *index as usize).collect()
+    };
+
+    let mut tangents = vec![Vector3::zeros(); vertices.len()];
+    let mut bitangents = vec![Vector3::zeros(); vertices.len()];
+
+    triangles.chunks_exact(3).for_each(|triangle| {
+        if triangle.iter().any(|i| *i >= vertices.len()) {
+            return;
+        }
+
+        let [v0, v1, v2] = [triangle[0], triangle[1], triangle[2]].map(|i| &vertices[i]);
+
+        let edge1 = v1.pos - v0.pos;
+        let edge2 = v2.pos - v0.pos;
+        let delta_uv1 = v1.tex_coord - v0.tex_coord;
+        let delta_uv2 = v2.tex_coord - v0.tex_coord;
+
+        let determinant = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+        if determinant.abs() <= f32::EPSILON {
+            return;
+        }
+
+        let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) / determinant;
+        let bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) / determinant;
+
+        triangle.iter().for_each(|i| {
+            tangents[*i] += tangent;
+            bitangents[*i] += bitangent;
+        });
+    });
+
+    vertices
+        .iter_mut()
+        .zip(tangents.into_iter().zip(bitangents))
+        .for_each(|(vertex, (tangent, bitangent))| {
+            let Some(normal) = vertex.normal.try_normalize(f32::EPSILON) else {
+                vertex.tangent = tangent.try_normalize(f32::EPSILON).unwrap_or_default();
+                vertex.bitangent = bitangent.try_normalize(f32::EPSILON).unwrap_or_default();
+                return;
+            };
+
+            let tangent = (tangent - normal * normal.dot(&tangent))
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(|| perpendicular(&normal));
+
+            let handedness = if normal.cross(&tangent).dot(&bitangent) < 0. {
+                -1.
+            } else {
+                1.
+            };
+
+            vertex.tangent = tangent;
+            vertex.bitangent = normal.cross(&tangent) * handedness;
+        });
+}
+
+/// Any unit vector perpendicular to `normal`, for vertices without usable UVs
+fn perpendicular(normal: &Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+
+    (axis - normal * normal.dot(&axis)).normalize()
+}
+
 pub(crate) fn make_point(
     angle: &mut f32,
     radius: f32,
@@ -108,3 +189,54 @@ pub(crate) fn spherical_indices(sector_count: u32, stack_count: u32) -> Indices
 
     indices.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wave_space::space::Vertex;
+
+    #[test]
+    fn test_tangents_follow_texture_coordinates() {
+        let normal = Vector3::z();
+        let mut vertices_and_indices = VerticesAndIndices::new(
+            vec![
+                Vertex::new(
+                    Point3::new(0., 0., 0.),
+                    Vector3::zeros(),
+                    normal,
+                    vector![0., 0.],
+                ),
+                Vertex::new(
+                    Point3::new(1., 0., 0.),
+                    Vector3::zeros(),
+                    normal,
+                    vector![1., 0.],
+                ),
+                Vertex::new(
+                    Point3::new(0., 1., 0.),
+                    Vector3::zeros(),
+                    normal,
+                    vector![0., 1.],
+                ),
+                Vertex::new(
+                    Point3::new(-1., 0., 0.),
+                    Vector3::zeros(),
+                    normal,
+                    vector![1., 0.],
+                ),
+            ]
+            .into(),
+            vec![0, 1, 2, 0, 2, 3].into(),
+        );
+
+        generate_tangents(&mut vertices_and_indices);
+
+        let vertices = &vertices_and_indices.vertices;
+        assert!((vertices[1].tangent - Vector3::x()).norm() < 1e-5);
+        assert!((vertices[1].bitangent - Vector3::y()).norm() < 1e-5);
+
+        // The second triangle's UVs are mirrored, flipping its tangent but not its bitangent
+        assert!((vertices[3].tangent + Vector3::x()).norm() < 1e-5);
+        assert!((vertices[3].bitangent - Vector3::y()).norm() < 1e-5);
+    }
+}
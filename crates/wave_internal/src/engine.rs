@@ -1,21 +1,168 @@
-use crate::time::Time;
+use crate::{
+    profiler::Profiler,
+    recording::{Recorder, Recording},
+    time::Time,
+};
+use nalgebra::{Matrix4, Point3};
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use wave_camera::prelude::Camera;
-use wave_geometry::{Behavior, GeomBuilder};
-use wave_vulkan::{debug::Debugger, GeomProperties, Vulkan};
+use wave_geometry::{
+    l2d::{
+        tiled,
+        tilemap::{Tilemap, TilemapId, Tilemaps},
+    },
+    l3d::particles::{ParticleEmitter, ParticleEmitterId, ParticleSystems},
+    Behavior, GeomBuilder,
+};
+use wave_space::scene_graph::{Attachment, SceneGraph};
+use wave_vulkan::{
+    capture,
+    compute::{ComputeDevice, ComputeStage, Dispatch},
+    debug::Debugger,
+    environment::Skybox,
+    gpu::DeviceSelection,
+    light::Light,
+    material::{Material, MaterialHandle, Materials},
+    post_process::PostProcess,
+    settings::RenderSettings,
+    GeomProperties, Vulkan,
+};
 use wave_window::{
+    debug_draw::DebugDraw,
+    egui,
+    gui::Gui,
     prelude::Resolution,
-    window::{Display, Drawable, Input, RenderBackend},
+    sprite::{SpriteTextureId, Sprites},
+    stats::FrameStats,
+    text::{Font, FontId, Text},
+    window::{DebugView, Display, Drawable, FrameCapture, Input, RenderBackend},
 };
-use winit::window::Window;
+use winit::{event::WindowEvent, window::Window};
+
+/// Called before each frame is rendered
+type FrameCallback = Box<dyn FnMut(&mut Frame)>;
+
+/// Called once when the engine exits
+type ExitCallback = Box<dyn FnOnce(&mut Frame)>;
+
+/// Builds the gui each frame
+type UiCallback = Box<dyn FnMut(&egui::Context)>;
+
+/// Returned by the [`Frame`] methods only the Vulkan renderer supports when drawing through
+/// another [`RenderBackend`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NotVulkan;
+
+impl fmt::Display for NotVulkan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the frame is not drawn by the Vulkan renderer")
+    }
+}
+
+impl std::error::Error for NotVulkan {}
+
+/// What a frame callback can draw into the frame about to be rendered
+pub struct Frame<'a> {
+    pub debug_draw: &'a mut DebugDraw,
+    /// Text for this frame only, cleared once it is rendered
+    pub text: &'a mut Text,
+    /// Sprites for this frame only, cleared once they are rendered. The camera is kept.
+    pub sprites: &'a mut Sprites,
+    /// Tilemaps, meshed again where their tiles changed
+    pub tilemaps: &'a mut Tilemaps,
+    /// Particle emitters, simulated after the callback returns
+    pub particles: &'a mut ParticleSystems,
+    /// Nodes placing the geometry, lights and camera attached to them
+    pub scene: &'a mut SceneGraph,
+    /// Time the previous frame took
+    pub frame_time: Duration,
+    screenshot: &'a mut Option<PathBuf>,
+    renderer: &'a mut dyn RenderBackend,
+}
+
+impl Frame<'_> {
+    /// Shows or hides the geometry at `index`, in the order geometries were added
+    pub fn set_visible(&mut self, index: usize, visible: bool) {
+        self.renderer.set_visible(index, visible);
+    }
+
+    /// The renderer the engine created, for what only Vulkan provides
+    fn vulkan(&mut self) -> Result<&mut Vulkan, NotVulkan> {
+        self.renderer.as_any_mut().downcast_mut().ok_or(NotVulkan)
+    }
+
+    /// Replaces the MSAA sample count, anisotropic filtering and render scale, rebuilding the
+    /// affected resources after this frame
+    pub fn set_render_settings(&mut self, render_settings: &RenderSettings) {
+        self.renderer.set_render_settings(render_settings);
+    }
+
+    /// Replaces the material behind `handle`, changing every geometry drawn with it. Waits for
+    /// the GPU to finish the frames in flight, so it suits occasional changes.
+    pub fn set_material(
+        &mut self,
+        handle: MaterialHandle,
+        material: &Material,
+    ) -> Result<(), NotVulkan> {
+        self.vulkan()?.set_material(handle, material);
+        Ok(())
+    }
+
+    /// Draws the geometry at `index` in `view`, or in the global debug view when `None`
+    pub fn set_debug_view(&mut self, index: usize, view: Option<DebugView>) {
+        self.renderer.set_object_debug_view(index, view);
+    }
+
+    /// Creates compute resources on the rendering device. Everything created must be destroyed
+    /// through it, at the latest from [`Engine::on_exit`].
+    pub fn compute(&mut self) -> Result<ComputeDevice<'_>, NotVulkan> {
+        Ok(self.vulkan()?.compute())
+    }
+
+    /// Queues `dispatch` to run at `stage` of this frame only
+    pub fn dispatch(&mut self, stage: ComputeStage, dispatch: Dispatch) -> Result<(), NotVulkan> {
+        self.vulkan()?.dispatch(stage, dispatch);
+        Ok(())
+    }
+
+    /// Saves this frame to `path` as a PNG once it is rendered
+    pub fn screenshot(&mut self, path: impl Into<PathBuf>) {
+        *self.screenshot = Some(path.into());
+    }
+}
 
 pub struct Engine {
     current_frame: usize,
     is_frame_buffer_resized: bool,
     geometries: Vec<GeomProperties>,
+    materials: Materials,
     time: Time,
     resolution: Resolution,
     camera: Option<Camera>,
     debugger: Option<Debugger>,
+    skybox: Option<Skybox>,
+    lights: Vec<Light>,
+    post_process: PostProcess,
+    render_settings: RenderSettings,
+    device: DeviceSelection,
+    screenshot: Option<PathBuf>,
+    recorder: Option<Recorder>,
+    profiler: Option<Profiler>,
+    debug_view: DebugView,
+    debug_draw: DebugDraw,
+    text: Text,
+    sprites: Sprites,
+    tilemaps: Tilemaps,
+    particles: ParticleSystems,
+    scene: SceneGraph,
+    on_frame: Option<FrameCallback>,
+    on_exit: Option<ExitCallback>,
+    gui: Gui,
+    ui: Option<UiCallback>,
 }
 
 impl Default for Engine {
@@ -24,10 +171,30 @@ impl Default for Engine {
             current_frame: 0,
             is_frame_buffer_resized: false,
             geometries: Vec::new(),
+            materials: Materials::default(),
             time: Time::default(),
             resolution: Resolution::ResFullHD,
             camera: Some(Camera::default()),
             debugger: None,
+            skybox: None,
+            lights: Vec::new(),
+            post_process: PostProcess::default(),
+            render_settings: RenderSettings::default(),
+            device: DeviceSelection::default(),
+            screenshot: None,
+            recorder: None,
+            profiler: None,
+            debug_view: DebugView::default(),
+            debug_draw: DebugDraw::default(),
+            text: Text::default(),
+            sprites: Sprites::default(),
+            tilemaps: Tilemaps::default(),
+            particles: ParticleSystems::default(),
+            scene: SceneGraph::default(),
+            on_frame: None,
+            on_exit: None,
+            gui: Gui::default(),
+            ui: None,
         }
     }
 }
@@ -41,11 +208,252 @@ impl Engine {
         self
     }
 
+    /// Adds a material that geometries can share by passing the returned handle to their
+    /// `material` setter
+    pub fn add_material(&mut self, material: Material) -> MaterialHandle {
+        self.materials.add(material)
+    }
+
     pub fn with_debugger(mut self, debugger: Debugger) -> Self {
         self.debugger = Some(debugger);
         self
     }
 
+    pub fn with_skybox(mut self, skybox: Skybox) -> Self {
+        self.skybox = Some(skybox);
+        self
+    }
+
+    pub fn with_light(mut self, light: impl Into<Light>) -> Self {
+        self.lights.push(light.into());
+        self
+    }
+
+    /// Replaces the post processing applied to the rendered scene, which by default only
+    /// clamps it into the displayable range
+    pub fn with_post_process(mut self, post_process: PostProcess) -> Self {
+        self.post_process = post_process;
+        self
+    }
+
+    /// Sets the MSAA sample count, anisotropic filtering and render scale
+    pub fn with_render_settings(mut self, render_settings: RenderSettings) -> Self {
+        self.render_settings = render_settings;
+        self
+    }
+
+    /// Chooses the device to render with. The `WAVE_DEVICE` environment variable still takes
+    /// precedence, so users can pick another device without rebuilding. If neither matches a
+    /// suitable device, a warning is logged and one is chosen automatically.
+    pub fn with_device(mut self, device: DeviceSelection) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Saves the first rendered frame to `path` as a PNG. Later frames are saved with
+    /// [`Frame::screenshot`].
+    pub fn screenshot(&mut self, path: impl Into<PathBuf>) {
+        self.screenshot = Some(path.into());
+    }
+
+    fn take_screenshot(&mut self, frame: Option<&FrameCapture>) {
+        let Some(path) = self.screenshot.take() else {
+            return;
+        };
+
+        match frame {
+            Some(frame) => match capture::save_png(frame, &path) {
+                Ok(()) => log::info!("Saved screenshot to {}", path.display()),
+                Err(error) => log::error!("Could not save {}: {error}", path.display()),
+            },
+            None => log::warn!("The renderer could not capture the frame"),
+        }
+    }
+
+    /// Records every frame at the recording's fixed frame rate instead of following the wall
+    /// clock. A frame the renderer could not capture is rendered again before the simulation
+    /// moves on, and recording stops with an error when that keeps failing.
+    pub fn with_recording(mut self, recording: Recording) -> Self {
+        self.time = Time::new(recording.fps);
+        self.recorder = Some(Recorder::new(recording));
+        self
+    }
+
+    /// Collects frame timings and counters, optionally showing them in a text overlay or
+    /// exporting them as a Chrome trace on exit. GPU timings are only measured with a
+    /// profiler.
+    pub fn with_profiler(mut self, profiler: Profiler) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Draws every geometry without its own debug view in `view`, which the debug view key
+    /// cycles through in debug builds
+    pub fn with_debug_view(mut self, view: DebugView) -> Self {
+        self.debug_view = view;
+        self
+    }
+
+    /// Lines drawn over the scene. Shapes queued before running need a duration to outlast
+    /// the first frame.
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        &mut self.debug_draw
+    }
+
+    /// Makes `font` available to the text drawn each frame
+    pub fn add_font(&mut self, font: Font) -> FontId {
+        self.text.add_font(font)
+    }
+
+    /// Loads an image, such as a sprite sheet, for the sprites drawn each frame
+    pub fn add_sprite_texture(&mut self, path: &str) -> SpriteTextureId {
+        self.sprites.add_texture(path)
+    }
+
+    /// Draws `tilemap` under the sprites, through the sprites' camera. Its tilesets sample
+    /// textures added with [`Engine::add_sprite_texture`].
+    pub fn add_tilemap(&mut self, tilemap: Tilemap) -> TilemapId {
+        self.tilemaps.add(tilemap)
+    }
+
+    /// Loads a map saved by the Tiled editor as `.tmx` or `.tmj` and draws it like
+    /// [`Engine::add_tilemap`]
+    pub fn load_tilemap(&mut self, path: impl AsRef<Path>) -> io::Result<TilemapId> {
+        let tilemap = tiled::load(path, &mut self.sprites)?;
+        Ok(self.add_tilemap(tilemap))
+    }
+
+    /// Simulates `emitter` every frame and draws its particles in the scene. Its texture is
+    /// one added with [`Engine::add_sprite_texture`].
+    pub fn add_particle_emitter(&mut self, emitter: ParticleEmitter) -> ParticleEmitterId {
+        self.particles.add(emitter)
+    }
+
+    /// Nodes that geometry, lights and the camera can be attached to, so they move with
+    /// their parents. Geometry and lights are attached by the order they were added in.
+    pub fn scene(&mut self) -> &mut SceneGraph {
+        &mut self.scene
+    }
+
+    /// Calls `on_frame` before every frame is rendered, to queue that frame's debug lines,
+    /// text and sprites
+    pub fn on_frame(mut self, on_frame: impl FnMut(&mut Frame) + 'static) -> Self {
+        self.on_frame = Some(Box::new(on_frame));
+        self
+    }
+
+    /// Calls `on_exit` once the renderer is idle, before it is destroyed, to destroy what was
+    /// created with [`Frame::compute`]
+    pub fn on_exit(mut self, on_exit: impl FnOnce(&mut Frame) + 'static) -> Self {
+        self.on_exit = Some(Box::new(on_exit));
+        self
+    }
+
+    /// Calls `ui` every frame to build an egui interface drawn over the final image. The
+    /// camera ignores the pointer while it is over a panel.
+    pub fn ui(mut self, ui: impl FnMut(&egui::Context) + 'static) -> Self {
+        self.ui = Some(Box::new(ui));
+        self
+    }
+
+    /// Stats of the latest frame the GPU has finished, when profiling
+    pub fn frame_stats(&self) -> Option<&FrameStats> {
+        self.profiler.as_ref().and_then(Profiler::latest)
+    }
+
+    /// Places the geometry, lights and camera attached to scene graph nodes, returning where
+    /// the camera is in the world
+    fn place_attachments(&mut self, renderer: &mut Box<dyn RenderBackend>) -> Point3<f32> {
+        if !self.scene.is_empty() {
+            let models = self
+                .geometries
+                .iter()
+                .enumerate()
+                .map(|(index, geometry)| {
+                    self.scene
+                        .world_of(Attachment::Geometry(index))
+                        .map_or(geometry.model(), |world| world * geometry.model())
+                })
+                .collect::<Vec<_>>();
+            renderer.set_models(&models);
+
+            let light_transforms = (0..self.lights.len())
+                .map(|index| {
+                    self.scene
+                        .world_of(Attachment::Light(index))
+                        .unwrap_or_else(Matrix4::identity)
+                })
+                .collect::<Vec<_>>();
+            renderer.set_light_transforms(&light_transforms);
+        }
+
+        let Some(camera) = &self.camera else {
+            return Point3::origin();
+        };
+
+        // The camera moves relative to its node, so the node's world matrix is undone first
+        let world = self
+            .scene
+            .world_of(Attachment::Camera)
+            .unwrap_or_else(Matrix4::identity);
+        let view = world
+            .try_inverse()
+            .map_or_else(|| camera.matrix(), |inverse| camera.matrix() * inverse);
+        renderer.update(view);
+
+        world.transform_point(&camera.position().0.into())
+    }
+
+    /// What the frame callbacks can change, drawn by `renderer`
+    fn frame<'a>(&'a mut self, renderer: &'a mut dyn RenderBackend) -> Frame<'a> {
+        Frame {
+            debug_draw: &mut self.debug_draw,
+            text: &mut self.text,
+            sprites: &mut self.sprites,
+            tilemaps: &mut self.tilemaps,
+            particles: &mut self.particles,
+            scene: &mut self.scene,
+            frame_time: self.time.frame_time,
+            screenshot: &mut self.screenshot,
+            renderer,
+        }
+    }
+
+    fn record(&mut self, frame: Option<&FrameCapture>) {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return;
+        };
+
+        // Frames are missing while the swap chain is recreated, and the simulation holds until
+        // the same moment is rendered and captured
+        let result = match frame {
+            Some(frame) => recorder.record(frame),
+            None => match recorder.miss() {
+                Ok(()) => {
+                    log::warn!(
+                        "Frame {} was not captured, rendering it again",
+                        recorder.recorded()
+                    );
+                    return;
+                }
+                Err(error) => Err(error),
+            },
+        };
+
+        if let Err(error) = &result {
+            log::error!("Stopped recording: {error}");
+        }
+
+        if result.is_err() || recorder.is_finished() {
+            if let Err(error) = recorder.finish() {
+                log::error!("Could not finish recording: {error}");
+            }
+
+            log::info!("Recorded {} frames", recorder.recorded());
+            self.recorder = None;
+        }
+    }
+
     pub fn run(self) {
         Display::new(Box::new(self)).start();
     }
@@ -53,15 +461,62 @@ impl Engine {
 
 impl Drawable for Engine {
     fn draw(&mut self, window: &Window, input: &mut Input, renderer: &mut Box<dyn RenderBackend>) {
-        self.time.tick();
+        match &self.recorder {
+            Some(recorder) if recorder.is_retrying() => self.time.hold(),
+            Some(_) => self.time.advance(),
+            None => self.time.tick(),
+        }
 
         // self.geometries.iter_mut().for_each(Behavior::actions);
 
         // backend.update_objects(&self.geometries);
 
+        if input.next_debug_view {
+            input.next_debug_view = false;
+            self.debug_view = self.debug_view.next();
+            renderer.set_debug_view(self.debug_view);
+            log::info!("Debug view: {:?}", self.debug_view);
+        }
+
+        if let Some(ui) = &mut self.ui {
+            self.gui.run(window, ui);
+            input.pointer_captured = self.gui.wants_pointer();
+            renderer.set_gui(self.gui.frame());
+        }
+
         if let Some(ref mut camera) = self.camera {
-            self.time.step(camera, input, renderer);
+            self.time.step(camera, input);
+        }
+
+        if let Some(mut on_frame) = self.on_frame.take() {
+            on_frame(&mut self.frame(renderer.as_mut()));
+            self.on_frame = Some(on_frame);
+        }
+
+        if input.screenshot {
+            input.screenshot = false;
+            self.screenshot.get_or_insert_with(screenshot_path);
+        }
+
+        let capturing = self.screenshot.is_some() || self.recorder.is_some();
+
+        if capturing {
+            renderer.request_capture();
+        }
+
+        renderer.set_debug_lines(&self.debug_draw.vertices());
+        renderer.set_tile_chunks(&self.tilemaps.chunks());
+        renderer.set_sprites(&self.sprites);
+
+        let eye = self.place_attachments(renderer);
+        self.particles.update(self.time.frame_time.as_secs_f32());
+        renderer.set_particles(&self.particles.batches(&eye));
+
+        renderer.set_profiling(self.profiler.is_some());
+        if let Some(profiler) = &self.profiler {
+            profiler.draw_overlay(&mut self.text);
         }
+        renderer.set_text(&self.text);
 
         renderer.render(
             window,
@@ -69,12 +524,206 @@ impl Drawable for Engine {
             &mut self.is_frame_buffer_resized,
             self.time.delta.as_secs_f32(),
         );
+
+        self.debug_draw.end_frame(self.time.frame_time);
+        self.text.clear();
+        self.sprites.clear();
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.collect(renderer.frame_stats());
+        }
+
+        let frame = capturing.then(|| renderer.capture()).flatten();
+        self.take_screenshot(frame.as_ref());
+        self.record(frame.as_ref());
+    }
+
+    fn exiting(&mut self, renderer: Option<&mut Box<dyn RenderBackend>>) {
+        if let (Some(on_exit), Some(renderer)) = (self.on_exit.take(), renderer) {
+            on_exit(&mut self.frame(renderer.as_mut()));
+        }
+
+        if let Some(Err(error)) = self.profiler.as_ref().map(Profiler::write_trace) {
+            log::error!("Could not write the profiler trace: {error}");
+        }
+    }
+
+    fn window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.ui.is_some() && self.gui.on_window_event(window, event)
     }
 
     fn create_renderer(&self, window: &Window) -> Box<dyn RenderBackend>
     where
         Self: Sized,
     {
-        Box::new(Vulkan::new(window, &self.geometries, self.debugger))
+        let mut vulkan = Vulkan::new(
+            window,
+            &self.materials,
+            &self.geometries,
+            self.debugger.clone(),
+            self.skybox.as_ref(),
+            &self.lights,
+            &self.post_process,
+            &self.render_settings,
+            &self.device,
+        );
+        vulkan.set_debug_view(self.debug_view);
+
+        Box::new(vulkan)
+    }
+}
+
+/// File name of a screenshot taken with the keybind, unique per second
+fn screenshot_path() -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    PathBuf::from(format!("screenshot-{seconds}.png"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wave_vulkan::settings::Msaa;
+    use wave_window::{
+        debug_draw::LineVertex, gui::GuiFrame, particles::ParticleBatches, tilemap::TileChunk,
+    };
+
+    /// Backend that keeps what frames asked of it instead of drawing
+    #[derive(Default)]
+    struct StubBackend {
+        stats: FrameStats,
+        hidden: Vec<usize>,
+        debug_views: Vec<(usize, Option<DebugView>)>,
+        render_settings: RenderSettings,
+    }
+
+    impl RenderBackend for StubBackend {
+        fn create(_window: &Window) -> Self {
+            Self::default()
+        }
+
+        fn render(&mut self, _: &Window, _: &mut usize, _: &mut bool, _: f32) {}
+
+        fn update(&mut self, _view: Matrix4<f32>) {}
+
+        fn set_models(&mut self, _models: &[Matrix4<f32>]) {}
+
+        fn set_light_transforms(&mut self, _transforms: &[Matrix4<f32>]) {}
+
+        fn set_visible(&mut self, index: usize, visible: bool) {
+            self.hidden.retain(|hidden| *hidden != index);
+            if !visible {
+                self.hidden.push(index);
+            }
+        }
+
+        fn request_capture(&mut self) {}
+
+        fn capture(&mut self) -> Option<FrameCapture> {
+            None
+        }
+
+        fn frame_stats(&self) -> &FrameStats {
+            &self.stats
+        }
+
+        fn set_profiling(&mut self, _enabled: bool) {}
+
+        fn set_debug_view(&mut self, _view: DebugView) {}
+
+        fn set_object_debug_view(&mut self, index: usize, view: Option<DebugView>) {
+            self.debug_views.push((index, view));
+        }
+
+        fn set_render_settings(&mut self, render_settings: &RenderSettings) {
+            self.render_settings = *render_settings;
+        }
+
+        fn set_debug_lines(&mut self, _vertices: &[LineVertex]) {}
+
+        fn set_text(&mut self, _text: &Text) {}
+
+        fn set_sprites(&mut self, _sprites: &Sprites) {}
+
+        fn set_tile_chunks(&mut self, _chunks: &[&TileChunk]) {}
+
+        fn set_particles(&mut self, _particles: &ParticleBatches) {}
+
+        fn set_gui(&mut self, _frame: &GuiFrame) {}
+
+        fn destroy(&self) {}
+
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+    }
+
+    /// Calls `on_frame` with a frame of `engine` drawing through `renderer`
+    fn with_frame(
+        engine: &mut Engine,
+        renderer: &mut StubBackend,
+        on_frame: impl FnOnce(&mut Frame),
+    ) {
+        on_frame(&mut engine.frame(renderer));
+    }
+
+    #[test]
+    fn test_frame_hides_and_shows_geometry() {
+        let mut engine = Engine::default();
+        let mut renderer = StubBackend::default();
+
+        with_frame(&mut engine, &mut renderer, |frame| {
+            frame.set_visible(1, false);
+            frame.set_visible(2, false);
+            frame.set_visible(1, true);
+        });
+
+        assert_eq!(renderer.hidden, [2]);
+    }
+
+    #[test]
+    fn test_frame_sets_debug_views_per_geometry() {
+        let mut engine = Engine::default();
+        let mut renderer = StubBackend::default();
+
+        with_frame(&mut engine, &mut renderer, |frame| {
+            frame.set_debug_view(0, Some(DebugView::Wireframe));
+            frame.set_debug_view(0, None);
+        });
+
+        assert_eq!(
+            renderer.debug_views,
+            [(0, Some(DebugView::Wireframe)), (0, None)]
+        );
+    }
+
+    #[test]
+    fn test_frame_changes_render_settings() {
+        let mut engine = Engine::default();
+        let mut renderer = StubBackend::default();
+        let settings = RenderSettings::default().msaa(Msaa::Off).render_scale(0.5);
+
+        with_frame(&mut engine, &mut renderer, |frame| {
+            frame.set_render_settings(&settings);
+        });
+
+        assert_eq!(renderer.render_settings, settings);
+    }
+
+    #[test]
+    fn test_frame_reports_vulkan_only_features() {
+        let mut engine = Engine::default();
+        let mut renderer = StubBackend::default();
+        let handle = engine.add_material(Material::default());
+
+        with_frame(&mut engine, &mut renderer, |frame| {
+            assert_eq!(frame.compute().err(), Some(NotVulkan));
+            assert_eq!(
+                frame.set_material(handle, &Material::default()),
+                Err(NotVulkan)
+            );
+        });
     }
 }
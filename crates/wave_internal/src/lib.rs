@@ -1,4 +1,6 @@
 pub mod engine;
+pub mod profiler;
+pub mod recording;
 mod time;
 
 pub use wave_camera;
@@ -10,7 +12,9 @@ pub use wave_window;
 
 pub mod prelude {
     pub use crate::{
-        engine::Engine,
+        engine::{Engine, Frame},
+        profiler::Profiler,
+        recording::Recording,
         wave_camera::prelude::*,
         wave_geometry::prelude::*,
         wave_proc_macro::{geometry, geometry_system},
@@ -0,0 +1,159 @@
+use nalgebra::point;
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use wave_window::{
+    stats::{self, FrameStats},
+    text::{FontId, Text, TextStyle},
+};
+
+/// How often the overlay is refreshed, so it stays readable
+const OVERLAY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Collects [`FrameStats`] from the renderer.
+///
+/// The overlay draws the latest frame's timings and counters as screen space text in the top
+/// left corner. With a trace path, the most recent frames are written as a Chrome trace when
+/// the engine exits.
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    overlay: Option<FontId>,
+    overlay_text: String,
+    trace: Option<PathBuf>,
+    max_frames: usize,
+    frames: VecDeque<FrameStats>,
+    overlay_updated: Option<Instant>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            overlay: None,
+            overlay_text: String::new(),
+            trace: None,
+            max_frames: 3600,
+            frames: VecDeque::new(),
+            overlay_updated: None,
+        }
+    }
+}
+
+impl Profiler {
+    /// Shows the overlay in `font`, added with [`crate::engine::Engine::add_font`]
+    #[must_use]
+    pub fn overlay(mut self, font: FontId) -> Self {
+        self.overlay = Some(font);
+        self
+    }
+
+    #[must_use]
+    pub fn chrome_trace(mut self, path: impl Into<PathBuf>) -> Self {
+        self.trace = Some(path.into());
+        self
+    }
+
+    /// Frames kept for the trace, dropping the oldest first
+    #[must_use]
+    pub fn max_frames(mut self, max_frames: usize) -> Self {
+        self.max_frames = max_frames;
+        self
+    }
+
+    /// Stats of the latest frame the GPU has finished
+    pub fn latest(&self) -> Option<&FrameStats> {
+        self.frames.back()
+    }
+
+    pub(crate) fn collect(&mut self, stats: &FrameStats) {
+        // Nothing has finished yet while the stats are empty
+        let is_new = !stats.cpu.is_empty()
+            && self
+                .frames
+                .back()
+                .is_none_or(|latest| latest.frame != stats.frame);
+
+        if !is_new {
+            return;
+        }
+
+        if self.frames.len() >= self.max_frames {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(stats.clone());
+
+        let now = Instant::now();
+        let refresh = self
+            .overlay_updated
+            .is_none_or(|updated| now - updated >= OVERLAY_INTERVAL);
+
+        if self.overlay.is_some() && refresh {
+            self.overlay_text = overlay_text(stats);
+            self.overlay_updated = Some(now);
+        }
+    }
+
+    /// Queues the overlay for the next frame, if it is shown
+    pub(crate) fn draw_overlay(&self, text: &mut Text) {
+        if let Some(font) = self.overlay.filter(|_| !self.overlay_text.is_empty()) {
+            text.screen(
+                font,
+                &self.overlay_text,
+                point![8., 8.],
+                &TextStyle::default(),
+            );
+        }
+    }
+
+    /// Writes the kept frames to the trace path, if there is one
+    pub(crate) fn write_trace(&self) -> io::Result<()> {
+        match &self.trace {
+            Some(path) => fs::write(path, stats::chrome_trace(&self.frames)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn overlay_text(stats: &FrameStats) -> String {
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.;
+
+    format!(
+        "CPU {:.2} ms | GPU {:.2} ms | {} draws | {} triangles",
+        millis(stats.cpu_time()),
+        millis(stats.gpu_time()),
+        stats.draw_calls,
+        stats.triangles,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wave_window::stats::Span;
+
+    #[test]
+    fn test_overlay_text() {
+        let stats = FrameStats {
+            cpu: vec![Span::new(
+                "record",
+                Duration::ZERO,
+                Duration::from_micros(1_500),
+            )],
+            gpu: vec![Span::new(
+                "opaque",
+                Duration::ZERO,
+                Duration::from_millis(4),
+            )],
+            draw_calls: 12,
+            triangles: 3_000,
+            ..FrameStats::default()
+        };
+
+        assert_eq!(
+            overlay_text(&stats),
+            "CPU 1.50 ms | GPU 4.00 ms | 12 draws | 3000 triangles"
+        );
+    }
+}
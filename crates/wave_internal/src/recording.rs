@@ -0,0 +1,178 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::PathBuf,
+};
+use wave_vulkan::capture::{self, Y4mWriter};
+use wave_window::window::FrameCapture;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordingOutput {
+    /// Numbered PNG files in a directory, starting at `frame-000000.png`
+    PngSequence(PathBuf),
+    /// A single uncompressed YUV4MPEG2 video file
+    Y4m(PathBuf),
+}
+
+/// Renders at a fixed simulated timestep and writes every frame out.
+///
+/// Each frame advances the simulation by exactly `1 / fps` seconds, so the result plays back
+/// smoothly and is the same on every run however slowly the frames are rendered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub output: RecordingOutput,
+    pub fps: f32,
+    /// Frames to record before stopping, or `None` to record until the window closes
+    pub frames: Option<u32>,
+}
+
+impl Recording {
+    pub fn png_sequence(directory: impl Into<PathBuf>) -> Self {
+        Self::new(RecordingOutput::PngSequence(directory.into()))
+    }
+
+    pub fn y4m(path: impl Into<PathBuf>) -> Self {
+        Self::new(RecordingOutput::Y4m(path.into()))
+    }
+
+    fn new(output: RecordingOutput) -> Self {
+        Self {
+            output,
+            fps: 60.,
+            frames: None,
+        }
+    }
+
+    #[must_use]
+    pub fn fps(mut self, fps: f32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    #[must_use]
+    pub fn frames(mut self, frames: u32) -> Self {
+        self.frames = Some(frames);
+        self
+    }
+}
+
+/// Frames in a row that may go uncaptured, such as while the swap chain is recreated, before
+/// recording stops
+const MAX_MISSED_FRAMES: u32 = 3;
+
+pub(crate) struct Recorder {
+    recording: Recording,
+    recorded: u32,
+    /// Frames in a row that could not be captured, which are rendered again
+    missed: u32,
+    video: Option<Y4mWriter<BufWriter<File>>>,
+}
+
+impl Recorder {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            recorded: 0,
+            missed: 0,
+            video: None,
+        }
+    }
+
+    pub fn recorded(&self) -> u32 {
+        self.recorded
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.recording
+            .frames
+            .is_some_and(|frames| self.recorded >= frames)
+    }
+
+    /// Whether the last frame was not captured, so the simulation should hold until it is
+    pub fn is_retrying(&self) -> bool {
+        self.missed > 0
+    }
+
+    /// Notes a frame that could not be captured, failing once too many were missed in a row
+    pub fn miss(&mut self) -> io::Result<()> {
+        self.missed += 1;
+
+        if self.missed > MAX_MISSED_FRAMES {
+            return Err(io::Error::other(format!(
+                "frame {} could not be captured after {MAX_MISSED_FRAMES} retries",
+                self.recorded
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn record(&mut self, frame: &FrameCapture) -> io::Result<()> {
+        self.missed = 0;
+
+        match &self.recording.output {
+            RecordingOutput::PngSequence(directory) => {
+                if self.recorded == 0 {
+                    fs::create_dir_all(directory)?;
+                }
+
+                let path = directory.join(frame_file_name(self.recorded));
+                capture::save_png(frame, path).map_err(io::Error::other)?;
+            }
+            RecordingOutput::Y4m(path) => {
+                let video = match &mut self.video {
+                    Some(video) => video,
+                    None => self.video.insert(Y4mWriter::new(
+                        BufWriter::new(File::create(path)?),
+                        self.recording.fps,
+                    )),
+                };
+
+                video.write_frame(frame)?;
+            }
+        }
+
+        self.recorded += 1;
+
+        Ok(())
+    }
+
+    /// Flushes buffered video frames to disk
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.video.as_mut().map_or(Ok(()), Y4mWriter::flush)
+    }
+}
+
+fn frame_file_name(frame: u32) -> String {
+    format!("frame-{frame:06}.png")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorder_stops_after_requested_frames() {
+        let mut recorder = Recorder::new(Recording::png_sequence("frames").frames(2));
+        assert!(!recorder.is_finished());
+
+        recorder.recorded = 2;
+        assert!(recorder.is_finished());
+
+        assert_eq!(frame_file_name(42), "frame-000042.png");
+        assert!(!Recorder::new(Recording::y4m("demo.y4m")).is_finished());
+    }
+
+    #[test]
+    fn test_recorder_retries_missed_frames_then_fails() {
+        let mut recorder = Recorder::new(Recording::png_sequence("frames"));
+        assert!(!recorder.is_retrying());
+
+        for _ in 0..MAX_MISSED_FRAMES {
+            assert!(recorder.miss().is_ok());
+            assert!(recorder.is_retrying());
+        }
+
+        assert!(recorder.miss().is_err());
+    }
+}
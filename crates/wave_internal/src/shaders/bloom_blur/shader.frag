@@ -0,0 +1,22 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+// One direction of a separable 9 tap Gaussian blur, using linear filtering to read two
+// texels per sample.
+
+#include "../include/post.glsl"
+
+const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+    vec2 texelStep = post.texelSizeDirection.xy * post.texelSizeDirection.zw;
+
+    vec3 colour = texture(inputImage, inUV).rgb * weights[0];
+    for (int i = 1; i < 3; ++i) {
+        colour += texture(inputImage, inUV + texelStep * offsets[i]).rgb * weights[i];
+        colour += texture(inputImage, inUV - texelStep * offsets[i]).rgb * weights[i];
+    }
+
+    outColor = vec4(colour, 1.0);
+}
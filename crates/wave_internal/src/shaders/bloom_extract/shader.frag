@@ -0,0 +1,31 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+// Downsamples the scene to half resolution, keeping only what is brighter than the threshold.
+
+#include "../include/post.glsl"
+
+// Width of the soft transition below the threshold, relative to the threshold
+const float KNEE = 0.5;
+
+void main() {
+    vec2 texel = post.texelSizeDirection.xy;
+
+    vec3 colour = 0.25 * (
+        texture(inputImage, inUV + texel * vec2(-0.5, -0.5)).rgb +
+        texture(inputImage, inUV + texel * vec2(0.5, -0.5)).rgb +
+        texture(inputImage, inUV + texel * vec2(-0.5, 0.5)).rgb +
+        texture(inputImage, inUV + texel * vec2(0.5, 0.5)).rgb
+    );
+    colour *= post.toneMapping.x;
+
+    float threshold = post.toneMapping.w;
+    float knee = threshold * KNEE + 1e-4;
+    float brightness = max(colour.r, max(colour.g, colour.b));
+
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    float contribution = max(soft, brightness - threshold) / max(brightness, 1e-4);
+
+    outColor = vec4(colour * contribution, 1.0);
+}
@@ -0,0 +1,44 @@
+#version 450
+
+// Debug views, selected when the pipeline is created. Matches the modes in debug_view.rs.
+#define WIREFRAME 0
+#define NORMALS 1
+#define UV_CHECKER 2
+#define VERTEX_COLOURS 3
+#define DEPTH 4
+#define OVERDRAW 5
+
+layout(constant_id = 0) const int DEBUG_VIEW = WIREFRAME;
+
+layout(location = 0) in vec3 inNormal;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+layout(location = 3) in float inViewDepth;
+
+layout(location = 0) out vec4 outColor;
+
+const float CHECKER_SCALE = 8.0;
+
+void main() {
+    vec3 colour;
+
+    if (DEBUG_VIEW == WIREFRAME) {
+        colour = vec3(0.2, 1.0, 0.4);
+    } else if (DEBUG_VIEW == NORMALS) {
+        colour = normalize(inNormal) * 0.5 + 0.5;
+    } else if (DEBUG_VIEW == UV_CHECKER) {
+        ivec2 cell = ivec2(floor(inTexCoord * CHECKER_SCALE));
+        float checker = ((cell.x + cell.y) & 1) == 0 ? 0.9 : 0.2;
+        // Tinting by the coordinates shows which way they run
+        colour = checker * vec3(fract(inTexCoord), 1.0);
+    } else if (DEBUG_VIEW == VERTEX_COLOURS) {
+        colour = inColor;
+    } else if (DEBUG_VIEW == DEPTH) {
+        colour = vec3(1.0 / (1.0 + inViewDepth));
+    } else {
+        // Added on top of what is already drawn, so overlapping fragments heat up
+        colour = vec3(0.1, 0.04, 0.01);
+    }
+
+    outColor = vec4(colour, 1.0);
+}
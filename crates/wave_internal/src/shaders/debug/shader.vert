@@ -0,0 +1,28 @@
+#version 450
+
+layout(binding = 0) uniform ModelViewProjection {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} mvp;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec3 inNormal;
+layout(location = 3) in vec2 inTexCoord;
+
+layout(location = 0) out vec3 outNormal;
+layout(location = 1) out vec3 outColor;
+layout(location = 2) out vec2 outTexCoord;
+layout(location = 3) out float outViewDepth;
+
+void main() {
+    vec4 viewPosition = mvp.view * mvp.model * vec4(inPosition, 1.0);
+
+    outNormal = mat3(mvp.model) * inNormal;
+    outColor = inColor;
+    outTexCoord = inTexCoord;
+    outViewDepth = -viewPosition.z;
+
+    gl_Position = mvp.proj * viewPosition;
+}
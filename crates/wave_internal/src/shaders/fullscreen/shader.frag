@@ -0,0 +1,10 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+// Copies the input, a starting point for user passes.
+
+#include "../include/post.glsl"
+
+void main() {
+    outColor = texture(inputImage, inUV);
+}
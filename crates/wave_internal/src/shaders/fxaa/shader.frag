@@ -0,0 +1,47 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+// Reduced FXAA, blending along edges found from the luminance of the neighbouring texels.
+
+#include "../include/post.glsl"
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float SPAN_MAX = 8.0;
+
+void main() {
+    vec2 texel = post.texelSizeDirection.xy;
+
+    float lumaNW = luminance(texture(inputImage, inUV + vec2(-1.0, -1.0) * texel).rgb);
+    float lumaNE = luminance(texture(inputImage, inUV + vec2(1.0, -1.0) * texel).rgb);
+    float lumaSW = luminance(texture(inputImage, inUV + vec2(-1.0, 1.0) * texel).rgb);
+    float lumaSE = luminance(texture(inputImage, inUV + vec2(1.0, 1.0) * texel).rgb);
+    vec4 centre = texture(inputImage, inUV);
+    float lumaM = luminance(centre.rgb);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 direction = vec2(
+        -((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+        (lumaNW + lumaSW) - (lumaNE + lumaSE)
+    );
+
+    float reduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, -SPAN_MAX, SPAN_MAX) * texel;
+
+    vec3 nearer = 0.5 * (
+        texture(inputImage, inUV + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(inputImage, inUV + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 further = nearer * 0.5 + 0.25 * (
+        texture(inputImage, inUV - direction * 0.5).rgb +
+        texture(inputImage, inUV + direction * 0.5).rgb
+    );
+
+    float lumaFurther = luminance(further);
+    vec3 colour = (lumaFurther < lumaMin || lumaFurther > lumaMax) ? nearer : further;
+
+    outColor = vec4(colour, centre.a);
+}
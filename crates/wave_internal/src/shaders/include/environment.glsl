@@ -0,0 +1,47 @@
+// Environment lighting from the skybox's irradiance and reflections.
+
+#ifndef ENVIRONMENT_GLSL
+#define ENVIRONMENT_GLSL
+
+#include "scene.glsl"
+
+#ifndef PI
+#define PI 3.14159265359
+#endif
+
+bool environmentLighting() {
+    return scene.environment.y > 0.0;
+}
+
+// Irradiance arriving at a surface with normal n, from the projected spherical harmonics
+vec3 irradianceSH(vec3 n) {
+    return scene.irradiance[0].rgb * 0.282095
+        + scene.irradiance[1].rgb * 0.488603 * n.y
+        + scene.irradiance[2].rgb * 0.488603 * n.z
+        + scene.irradiance[3].rgb * 0.488603 * n.x
+        + scene.irradiance[4].rgb * 1.092548 * n.x * n.y
+        + scene.irradiance[5].rgb * 1.092548 * n.y * n.z
+        + scene.irradiance[6].rgb * 0.315392 * (3.0 * n.z * n.z - 1.0)
+        + scene.irradiance[7].rgb * 1.092548 * n.x * n.z
+        + scene.irradiance[8].rgb * 0.546274 * (n.x * n.x - n.y * n.y);
+}
+
+vec3 environmentDiffuse(vec3 n, vec3 albedo) {
+    return max(irradianceSH(n), vec3(0.0)) / PI * albedo * scene.environment.x;
+}
+
+// Environment seen along r, blurred by sampling smaller mips for rougher surfaces
+vec3 environmentRadiance(vec3 r, float roughness) {
+    float lod = roughness * max(scene.environment.z - 1.0, 0.0);
+    return textureLod(environmentMap, r, lod).rgb * scene.environment.x;
+}
+
+// Reflection of the environment off a dielectric surface
+vec3 environmentSpecular(vec3 n, vec3 v, float roughness) {
+    float cosTheta = max(dot(n, v), 0.0);
+    float fresnel = 0.04 + 0.96 * pow(1.0 - cosTheta, 5.0);
+
+    return environmentRadiance(reflect(-v, n), roughness) * fresnel;
+}
+
+#endif
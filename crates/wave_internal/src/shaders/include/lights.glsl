@@ -0,0 +1,77 @@
+// Direct lighting from the scene's lights, with percentage closer filtered shadows.
+
+#ifndef LIGHTS_GLSL
+#define LIGHTS_GLSL
+
+#include "scene.glsl"
+
+layout(constant_id = 1) const bool RECEIVE_SHADOWS = true;
+
+int lightCount() {
+    return int(scene.lightInfo.x);
+}
+
+float shadowFactor(Light light, vec3 worldPosition, vec3 normal) {
+    int layer = int(light.shadow.z);
+    if (!RECEIVE_SHADOWS || layer < 0) {
+        return 1.0;
+    }
+
+    // Directional lights pick the cascade covering the fragment's view depth
+    if (light.position.w == 0.0) {
+        int cascades = int(light.shadow.w);
+        float depth = dot(worldPosition - scene.cameraPosition.xyz, scene.cameraForward.xyz);
+        if (depth > scene.cascadeSplits[cascades - 1]) {
+            return 1.0;
+        }
+
+        int cascade = 0;
+        for (int i = 0; i < cascades - 1; ++i) {
+            if (depth > scene.cascadeSplits[i]) {
+                cascade = i + 1;
+            }
+        }
+        layer += cascade;
+    }
+
+    vec4 shadowCoord = scene.shadowMatrices[layer] * vec4(worldPosition + normal * 0.01, 1.0);
+    shadowCoord.xyz /= shadowCoord.w;
+    if (shadowCoord.z <= 0.0 || shadowCoord.z >= 1.0) {
+        return 1.0;
+    }
+
+    vec2 uv = shadowCoord.xy * 0.5 + 0.5;
+    vec2 texelSize = 1.0 / vec2(textureSize(shadowMap, 0).xy);
+
+    float lit = 0.0;
+    for (int x = -1; x <= 1; ++x) {
+        for (int y = -1; y <= 1; ++y) {
+            lit += texture(shadowMap, vec4(uv + vec2(x, y) * texelSize, float(layer), shadowCoord.z));
+        }
+    }
+
+    return lit / 9.0;
+}
+
+// Radiance arriving at a surface from `light`, writing the direction towards the light to L
+vec3 lightRadiance(Light light, vec3 worldPosition, vec3 normal, out vec3 L) {
+    float attenuation = 1.0;
+
+    if (light.position.w == 0.0) {
+        L = normalize(-light.direction.xyz);
+    } else {
+        vec3 toLight = light.position.xyz - worldPosition;
+        float distance = length(toLight);
+        L = toLight / distance;
+
+        float falloff = clamp(1.0 - pow(distance / light.direction.w, 4.0), 0.0, 1.0);
+        attenuation = falloff * falloff / (distance * distance + 1.0);
+
+        float cosAngle = dot(-L, normalize(light.direction.xyz));
+        attenuation *= smoothstep(light.shadow.y, light.shadow.x, cosAngle);
+    }
+
+    return light.colour.rgb * light.colour.w * attenuation * shadowFactor(light, worldPosition, normal);
+}
+
+#endif
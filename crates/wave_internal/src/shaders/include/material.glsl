@@ -0,0 +1,22 @@
+// Material descriptor set shared by every object drawn with a material.
+// Set 0 holds the object's uniforms and set 2 the scene.
+
+#ifndef MATERIAL_GLSL
+#define MATERIAL_GLSL
+
+#define MATERIAL_SET 1
+
+layout(set = MATERIAL_SET, binding = 0) uniform Material {
+    vec4 baseColourFactor;
+    vec4 emissiveFactor;
+    // x: metallic, y: roughness, z: normal scale, w: occlusion strength
+    vec4 parameters;
+} material;
+
+layout(set = MATERIAL_SET, binding = 1) uniform sampler2D baseColourMap;
+layout(set = MATERIAL_SET, binding = 2) uniform sampler2D metallicRoughnessMap;
+layout(set = MATERIAL_SET, binding = 3) uniform sampler2D normalMap;
+layout(set = MATERIAL_SET, binding = 4) uniform sampler2D occlusionMap;
+layout(set = MATERIAL_SET, binding = 5) uniform sampler2D emissiveMap;
+
+#endif
@@ -0,0 +1,32 @@
+// Tangent space normal mapping.
+
+#ifndef NORMALS_GLSL
+#define NORMALS_GLSL
+
+// Moves a tangent space normal onto the surface, using the interpolated vertex tangent
+// frame, or one built from screen space derivatives when the mesh has no tangents
+vec3 applyNormalMap(vec3 N, vec3 T, vec3 B, vec3 tangentNormal, vec3 position, vec2 uv) {
+    if (dot(T, T) == 0.0 || dot(B, B) == 0.0) {
+        vec3 dp1 = dFdx(position);
+        vec3 dp2 = dFdy(position);
+        vec2 duv1 = dFdx(uv);
+        vec2 duv2 = dFdy(uv);
+
+        vec3 dp2perp = cross(dp2, N);
+        vec3 dp1perp = cross(N, dp1);
+        T = dp2perp * duv1.x + dp1perp * duv2.x;
+        B = dp2perp * duv1.y + dp1perp * duv2.y;
+
+        if (max(dot(T, T), dot(B, B)) == 0.0) {
+            return N;
+        }
+    }
+
+    // Gram-Schmidt keeps the frame orthonormal after interpolation
+    T = normalize(T - N * dot(N, T));
+    B = (dot(cross(N, T), B) < 0.0 ? -1.0 : 1.0) * cross(N, T);
+
+    return normalize(mat3(T, B, N) * tangentNormal);
+}
+
+#endif
@@ -0,0 +1,32 @@
+// Inputs shared by the fullscreen post processing passes, including user passes.
+
+#ifndef POST_GLSL
+#define POST_GLSL
+
+layout(location = 0) in vec2 inUV;
+
+layout(location = 0) out vec4 outColor;
+
+// Output of the previous pass, or the HDR scene for the first pass
+layout(set = 0, binding = 0) uniform sampler2D inputImage;
+// Blurred bloom highlights, only meaningful during tone mapping
+layout(set = 0, binding = 1) uniform sampler2D bloomImage;
+// Colour grading lookup table, N slices of N by N side by side
+layout(set = 0, binding = 2) uniform sampler2D colourLut;
+
+layout(push_constant) uniform PostConstants {
+    // xy: texel size of the input, zw: blur direction
+    vec4 texelSizeDirection;
+    // x: exposure, y: tone mapping operator, z: bloom intensity, w: bloom threshold
+    vec4 toneMapping;
+    // x: vignette strength, y: vignette radius, z: 1 when grading with the lookup table
+    vec4 grading;
+    // Values given to the pass, free for user shaders
+    vec4 parameters;
+} post;
+
+float luminance(vec3 colour) {
+    return dot(colour, vec3(0.2126, 0.7152, 0.0722));
+}
+
+#endif
@@ -0,0 +1,39 @@
+// Scene descriptor set shared by lit shaders and the skybox.
+// Define SCENE_SET before including to select the descriptor set it is bound to.
+
+#ifndef SCENE_GLSL
+#define SCENE_GLSL
+
+#define MAX_LIGHTS 4
+#define MAX_SHADOW_LAYERS 8
+
+struct Light {
+    // w: 0 for directional lights, 1 for spot lights
+    vec4 position;
+    // w: range
+    vec4 direction;
+    // w: intensity
+    vec4 colour;
+    // x: cos inner angle, y: cos outer angle, z: first shadow layer or -1, w: shadow layer count
+    vec4 shadow;
+};
+
+layout(set = SCENE_SET, binding = 0) uniform Scene {
+    vec4 irradiance[9];
+    vec4 cameraPosition;
+    // x: intensity, y: 1 when the environment lights the scene, z: mip level count
+    vec4 environment;
+    vec4 cameraForward;
+    // x: light count
+    vec4 lightInfo;
+    // View space distance at which each cascade ends
+    vec4 cascadeSplits;
+    Light lights[MAX_LIGHTS];
+    mat4 shadowMatrices[MAX_SHADOW_LAYERS];
+} scene;
+
+layout(set = SCENE_SET, binding = 1) uniform samplerCube environmentMap;
+
+layout(set = SCENE_SET, binding = 2) uniform sampler2DArrayShadow shadowMap;
+
+#endif
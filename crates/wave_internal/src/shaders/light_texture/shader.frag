@@ -1,31 +1,54 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
 
-layout(binding = 1) uniform LightModel {
-    vec3 color;
-    vec3 pos;
-} light;
+#define SCENE_SET 2
+#include "../include/environment.glsl"
+#include "../include/lights.glsl"
+#include "../include/material.glsl"
+#include "../include/normals.glsl"
 
-layout(binding = 1) uniform sampler2D texSampler;
+layout(constant_id = 0) const float ALPHA_CUTOFF = 0.0;
 
 layout (location = 0) in vec3 inNormal;
 layout (location = 1) in vec3 inColor;
 layout (location = 2) in vec3 inPosition;
 layout (location = 3) in vec2 inTexCoord;
+layout (location = 4) in vec3 inTangent;
+layout (location = 5) in vec3 inBitangent;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    vec4 tex = texture(texSampler, inTexCoord);
+    vec4 tex = texture(baseColourMap, inTexCoord) * material.baseColourFactor;
 
     vec3 lightPos = vec3(10.0, 10.0, 10.0);
 
     vec3 N = normalize(inNormal);
+	vec3 tangentNormal = normalize(texture(normalMap, inTexCoord).xyz * 2.0 - 1.0);
+	N = applyNormalMap(N, inTangent, inBitangent, tangentNormal, inPosition, inTexCoord);
 	vec3 L = normalize((lightPos.xyz - inPosition.xyz) - inPosition);
-	vec3 V = normalize(-inPosition);
+	vec3 V = normalize(scene.cameraPosition.xyz - inPosition);
 	vec3 R = reflect(-L, N);
 	vec3 ambient = vec3(0.1);
+	vec3 reflection = vec3(0.0);
+	if (environmentLighting()) {
+		ambient = environmentDiffuse(N, vec3(1.0));
+		reflection = environmentSpecular(N, V, 0.5);
+	}
 	vec3 diffuse = max(dot(N, L), 0.0) * vec3(1.0);
+	if (lightCount() > 0) {
+		diffuse = vec3(0.0);
+		for (int i = 0; i < lightCount(); ++i) {
+			vec3 lightDirection;
+			vec3 radiance = lightRadiance(scene.lights[i], inPosition, N, lightDirection);
+			diffuse += max(dot(N, lightDirection), 0.0) * radiance;
+		}
+	}
 	vec3 specular = pow(max(dot(R, V), 0.0), 16.0) * vec3(0.75);
 
-	outColor = vec4((ambient + diffuse) * inColor.rgb , 1.0) * tex;
+	outColor = vec4((ambient + diffuse) * inColor.rgb * tex.rgb + reflection, tex.a);
+
+	if (outColor.a < ALPHA_CUTOFF) {
+		discard;
+	}
 }
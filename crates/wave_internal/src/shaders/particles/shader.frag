@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+#include "../include/material.glsl"
+
+layout(location = 0) in vec4 oColor;
+layout(location = 1) in vec2 oUV;
+
+layout(location = 0) out vec4 finalColor;
+
+void main() {
+    finalColor = oColor * texture(baseColourMap, oUV);
+}
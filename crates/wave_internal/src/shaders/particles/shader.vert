@@ -0,0 +1,33 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform Push {
+    mat4 viewProjection;
+    // World space axes of the view, which the quads are spanned along to face the camera
+    vec4 cameraRight;
+    vec4 cameraUp;
+} push;
+
+// One particle per instance
+layout(location = 0) in vec3 iPosition;
+layout(location = 1) in float iSize;
+layout(location = 2) in vec4 iColor;
+
+layout(location = 0) out vec4 oColor;
+layout(location = 1) out vec2 oUV;
+
+// Two triangles of a quad, counter-clockwise from the bottom left
+const vec2 CORNERS[6] = vec2[](
+    vec2(-0.5, -0.5), vec2(0.5, -0.5), vec2(0.5, 0.5),
+    vec2(-0.5, -0.5), vec2(0.5, 0.5), vec2(-0.5, 0.5)
+);
+
+void main() {
+    vec2 corner = CORNERS[gl_VertexIndex];
+    vec3 offset = push.cameraRight.xyz * corner.x + push.cameraUp.xyz * corner.y;
+
+    oColor = iColor;
+    oUV = vec2(corner.x + 0.5, 0.5 - corner.y);
+
+    gl_Position = push.viewProjection * vec4(iPosition + offset * iSize, 1.0);
+}
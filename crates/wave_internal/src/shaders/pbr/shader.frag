@@ -0,0 +1,120 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+// Metallic-roughness shading with the glTF 2.0 material model and a Cook-Torrance BRDF.
+
+#define SCENE_SET 2
+#include "../include/environment.glsl"
+#include "../include/lights.glsl"
+#include "../include/material.glsl"
+#include "../include/normals.glsl"
+
+layout(constant_id = 0) const float ALPHA_CUTOFF = 0.0;
+
+layout(location = 0) in vec3 inNormal;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec3 inPosition;
+layout(location = 3) in vec2 inTexCoord;
+layout(location = 4) in vec3 inTangent;
+layout(location = 5) in vec3 inBitangent;
+
+layout(location = 0) out vec4 outColor;
+
+const float MIN_ROUGHNESS = 0.045;
+
+// Trowbridge-Reitz GGX normal distribution
+float distributionGGX(float NdotH, float alpha) {
+    float alpha2 = alpha * alpha;
+    float d = NdotH * NdotH * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * d * d);
+}
+
+// Height correlated Smith visibility, the geometry term divided by 4 NdotL NdotV
+float visibilitySmithGGX(float NdotL, float NdotV, float alpha) {
+    float alpha2 = alpha * alpha;
+    float ggxV = NdotL * sqrt(NdotV * NdotV * (1.0 - alpha2) + alpha2);
+    float ggxL = NdotV * sqrt(NdotL * NdotL * (1.0 - alpha2) + alpha2);
+    float ggx = ggxV + ggxL;
+    return ggx > 0.0 ? 0.5 / ggx : 0.0;
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - cosTheta, 5.0);
+}
+
+// Analytic fit of the split sum environment BRDF, avoiding a lookup table
+vec3 environmentBRDF(vec3 f0, float roughness, float NdotV) {
+    const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+    const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+    vec4 r = roughness * c0 + c1;
+    float a004 = min(r.x * r.x, exp2(-9.28 * NdotV)) * r.x + r.y;
+    vec2 scaleBias = vec2(-1.04, 1.04) * a004 + r.zw;
+    return f0 * scaleBias.x + scaleBias.y;
+}
+
+// Light reflected towards the viewer from one light arriving along L
+vec3 cookTorrance(vec3 N, vec3 V, vec3 L, vec3 radiance, vec3 diffuseColour, vec3 f0, float alpha) {
+    vec3 H = normalize(V + L);
+    float NdotL = max(dot(N, L), 0.0);
+    float NdotV = max(dot(N, V), 1e-4);
+    float NdotH = max(dot(N, H), 0.0);
+    float VdotH = max(dot(V, H), 0.0);
+
+    vec3 F = fresnelSchlick(VdotH, f0);
+    vec3 specular = F * distributionGGX(NdotH, alpha) * visibilitySmithGGX(NdotL, NdotV, alpha);
+    vec3 diffuse = (1.0 - F) * diffuseColour / PI;
+
+    return (diffuse + specular) * radiance * NdotL;
+}
+
+void main() {
+    vec4 baseColour = texture(baseColourMap, inTexCoord) * material.baseColourFactor * vec4(inColor, 1.0);
+
+    if (baseColour.a < ALPHA_CUTOFF) {
+        discard;
+    }
+
+    vec4 metallicRoughness = texture(metallicRoughnessMap, inTexCoord);
+    float metallic = clamp(metallicRoughness.b * material.parameters.x, 0.0, 1.0);
+    float roughness = clamp(metallicRoughness.g * material.parameters.y, MIN_ROUGHNESS, 1.0);
+    float alpha = roughness * roughness;
+
+    vec3 N = normalize(inNormal);
+    if (!gl_FrontFacing) {
+        N = -N;
+    }
+    vec3 tangentNormal = texture(normalMap, inTexCoord).xyz * 2.0 - 1.0;
+    tangentNormal.xy *= material.parameters.z;
+    N = applyNormalMap(N, inTangent, inBitangent, normalize(tangentNormal), inPosition, inTexCoord);
+    vec3 V = normalize(scene.cameraPosition.xyz - inPosition);
+
+    vec3 diffuseColour = baseColour.rgb * (1.0 - metallic);
+    vec3 f0 = mix(vec3(0.04), baseColour.rgb, metallic);
+
+    vec3 direct = vec3(0.0);
+    if (lightCount() > 0) {
+        for (int i = 0; i < lightCount(); ++i) {
+            vec3 L;
+            vec3 radiance = lightRadiance(scene.lights[i], inPosition, N, L);
+            direct += cookTorrance(N, V, L, radiance, diffuseColour, f0, alpha);
+        }
+    } else {
+        vec3 L = normalize(vec3(10.0, 10.0, 10.0) - inPosition);
+        direct = cookTorrance(N, V, L, vec3(PI), diffuseColour, f0, alpha);
+    }
+
+    float NdotV = max(dot(N, V), 1e-4);
+    vec3 ambient = 0.03 * baseColour.rgb;
+    if (environmentLighting()) {
+        vec3 specularColour = environmentBRDF(f0, roughness, NdotV);
+        ambient = environmentDiffuse(N, diffuseColour)
+            + environmentRadiance(reflect(-V, N), roughness) * specularColour;
+    }
+
+    float occlusion = texture(occlusionMap, inTexCoord).r;
+    ambient *= 1.0 + material.parameters.w * (occlusion - 1.0);
+
+    vec3 emissive = texture(emissiveMap, inTexCoord).rgb * material.emissiveFactor.rgb;
+
+    outColor = vec4(direct + ambient + emissive, baseColour.a);
+}
@@ -0,0 +1,32 @@
+#version 450
+
+layout(binding = 0) uniform ModelViewProjection {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} mvp;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec3 inNormal;
+layout(location = 3) in vec2 inTexCoord;
+layout(location = 4) in vec3 inTangent;
+layout(location = 5) in vec3 inBitangent;
+
+layout(location = 0) out vec3 outNormal;
+layout(location = 1) out vec3 outColor;
+layout(location = 2) out vec3 outPosition;
+layout(location = 3) out vec2 outTexCoord;
+layout(location = 4) out vec3 outTangent;
+layout(location = 5) out vec3 outBitangent;
+
+void main() {
+    outPosition = vec3(mvp.model * vec4(inPosition, 1.0));
+    outColor = inColor;
+    outNormal = mat3(mvp.model) * inNormal;
+    outTexCoord = inTexCoord;
+    outTangent = mat3(mvp.model) * inTangent;
+    outBitangent = mat3(mvp.model) * inBitangent;
+
+    gl_Position = mvp.proj * mvp.view * vec4(outPosition, 1.0);
+}
@@ -0,0 +1,11 @@
+#version 450
+
+layout(push_constant) uniform Push {
+    mat4 lightModel;
+} push;
+
+layout(location = 0) in vec3 inPosition;
+
+void main() {
+    gl_Position = push.lightModel * vec4(inPosition, 1.0);
+}
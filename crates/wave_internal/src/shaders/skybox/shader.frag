@@ -0,0 +1,13 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#define SCENE_SET 0
+#include "../include/environment.glsl"
+
+layout(location = 0) in vec3 inDirection;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(textureLod(environmentMap, inDirection, 0.0).rgb * scene.environment.x, 1.0);
+}
@@ -0,0 +1,32 @@
+#version 450
+
+layout(push_constant) uniform Push {
+    mat4 viewProjection;
+} push;
+
+layout(location = 0) out vec3 outDirection;
+
+// Corners of a unit cube, bit 0 selecting x, bit 1 y and bit 2 z
+const int indices[36] = int[36](
+    0, 2, 6, 0, 6, 4,
+    1, 5, 7, 1, 7, 3,
+    0, 4, 5, 0, 5, 1,
+    2, 3, 7, 2, 7, 6,
+    0, 1, 3, 0, 3, 2,
+    4, 6, 7, 4, 7, 5
+);
+
+void main() {
+    int corner = indices[gl_VertexIndex];
+    vec3 position = vec3(
+        (corner & 1) == 0 ? -1.0 : 1.0,
+        (corner & 2) == 0 ? -1.0 : 1.0,
+        (corner & 4) == 0 ? -1.0 : 1.0
+    );
+
+    outDirection = position;
+
+    // Force the depth to the far plane so the sky is behind everything
+    vec4 clip = push.viewProjection * vec4(position, 1.0);
+    gl_Position = clip.xyww;
+}
@@ -1,6 +1,9 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
 
-layout(binding = 1) uniform sampler2D texSampler;
+#include "../include/material.glsl"
+
+layout(constant_id = 0) const float ALPHA_CUTOFF = 0.0;
 
 layout(location = 0) in vec3 fragColor;
 layout(location = 1) in vec2 fragTexCoord;
@@ -8,5 +11,9 @@ layout(location = 1) in vec2 fragTexCoord;
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    outColor = texture(texSampler, fragTexCoord);
+    outColor = texture(baseColourMap, fragTexCoord) * material.baseColourFactor;
+
+    if (outColor.a < ALPHA_CUTOFF) {
+        discard;
+    }
 }
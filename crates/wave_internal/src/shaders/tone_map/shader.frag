@@ -0,0 +1,71 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+// Exposes the HDR scene, adds bloom and maps it into the displayable range, then grades
+// and vignettes the result.
+
+#include "../include/post.glsl"
+
+const int TONE_MAPPING_NONE = 0;
+const int TONE_MAPPING_REINHARD = 1;
+const int TONE_MAPPING_ACES = 2;
+
+vec3 reinhard(vec3 colour) {
+    return colour / (1.0 + colour);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 colour) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((colour * (a * colour + b)) / (colour * (c * colour + d) + e), 0.0, 1.0);
+}
+
+vec3 linearToSrgb(vec3 colour) {
+    return mix(colour * 12.92, 1.055 * pow(colour, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, colour));
+}
+
+vec3 srgbToLinear(vec3 colour) {
+    return mix(colour / 12.92, pow((colour + 0.055) / 1.055, vec3(2.4)), step(0.04045, colour));
+}
+
+// Looks the display colour up in the strip of slices, blending between the two nearest
+vec3 grade(vec3 colour) {
+    float size = float(textureSize(colourLut, 0).y);
+    vec3 cell = clamp(colour, 0.0, 1.0) * (size - 1.0);
+
+    float slice = floor(cell.b);
+    float blend = cell.b - slice;
+    vec2 uv = (cell.rg + 0.5) / vec2(size * size, size);
+    uv.x += slice / size;
+
+    vec3 lower = texture(colourLut, uv).rgb;
+    vec3 upper = texture(colourLut, uv + vec2(min(slice + 1.0, size - 1.0) - slice, 0.0) / size).rgb;
+    return mix(lower, upper, blend);
+}
+
+void main() {
+    vec3 colour = texture(inputImage, inUV).rgb * post.toneMapping.x;
+    colour += texture(bloomImage, inUV).rgb * post.toneMapping.z;
+
+    int toneMapper = int(post.toneMapping.y);
+    if (toneMapper == TONE_MAPPING_REINHARD) {
+        colour = reinhard(colour);
+    } else if (toneMapper == TONE_MAPPING_ACES) {
+        colour = aces(colour);
+    } else {
+        colour = clamp(colour, 0.0, 1.0);
+    }
+
+    if (post.grading.z > 0.5) {
+        colour = srgbToLinear(grade(linearToSrgb(colour)));
+    }
+
+    float radius = length(inUV - 0.5) * sqrt(2.0);
+    colour *= 1.0 - post.grading.x * smoothstep(post.grading.y, 1.0, radius);
+
+    outColor = vec4(colour, 1.0);
+}
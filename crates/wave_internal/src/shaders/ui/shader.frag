@@ -1,13 +1,20 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+#include "../include/material.glsl"
+
+layout(constant_id = 0) const float ALPHA_CUTOFF = 0.0;
 
 layout(location = 0) in vec4 oColor;
 layout(location = 1) in vec2 oUV;
 
-layout(binding = 0, set = 0) uniform sampler2D fontsSampler;
-
 layout(location = 0) out vec4 finalColor;
 
 void main() {
-    finalColor = oColor * texture(fontsSampler, oUV);
+    finalColor = oColor * texture(baseColourMap, oUV);
+
+    if (finalColor.a < ALPHA_CUTOFF) {
+        discard;
+    }
 }
@@ -1,9 +1,18 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../include/material.glsl"
+
+layout(constant_id = 0) const float ALPHA_CUTOFF = 0.0;
 
 layout(location = 0) in vec3 fragColor;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    outColor = vec4(fragColor, 1.0);
+    outColor = vec4(fragColor, 1.0) * material.baseColourFactor;
+
+    if (outColor.a < ALPHA_CUTOFF) {
+        discard;
+    }
 }
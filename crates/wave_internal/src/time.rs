@@ -1,6 +1,6 @@
 use std::time::{Duration, Instant};
 use wave_camera::camera::Camera;
-use wave_window::window::{Input, RenderBackend};
+use wave_window::window::Input;
 
 pub trait Fps {
     fn duration(self) -> Duration;
@@ -23,6 +23,8 @@ pub struct Time {
     accumulator: Duration,
     elapsed: Duration,
     pub delta: Duration,
+    /// Time the last frame took, exactly one step when advancing or none when holding
+    pub frame_time: Duration,
     now: Instant,
 }
 
@@ -36,6 +38,7 @@ impl Time {
     pub fn new(fps: impl Fps) -> Self {
         Self {
             delta: fps.duration(),
+            frame_time: Duration::ZERO,
             now: std::time::Instant::now(),
             elapsed: Duration::ZERO,
             accumulator: Duration::ZERO,
@@ -47,23 +50,30 @@ impl Time {
         let frame_time = new_time - self.now;
         self.now = new_time;
         self.accumulator += frame_time;
+        self.frame_time = frame_time;
     }
 
-    pub fn step(
-        &mut self,
-        camera: &mut Camera,
-        input: &mut Input,
-        renderer: &mut Box<dyn RenderBackend>,
-    ) {
+    /// Advances by exactly one step however long the frame took, so every frame simulates the
+    /// same amount of time
+    pub fn advance(&mut self) {
+        self.now = std::time::Instant::now();
+        self.accumulator += self.delta;
+        self.frame_time = self.delta;
+    }
+
+    /// Keeps the simulation where it is for a frame, so the same moment is rendered again
+    pub fn hold(&mut self) {
+        self.now = std::time::Instant::now();
+        self.frame_time = Duration::ZERO;
+    }
+
+    /// Moves the camera in fixed steps for the time accumulated since the last call
+    pub fn step(&mut self, camera: &mut Camera, input: &mut Input) {
         while self.accumulator >= self.delta {
             camera.update(input, self.delta.as_secs_f32());
-            renderer.update(camera.matrix());
 
             self.accumulator -= self.delta;
             self.elapsed += self.delta;
-
-            // println!("{:?}", frame_time);
-            // println!("{:?}", 1. / self.delta.as_secs_f32())
         }
     }
 }
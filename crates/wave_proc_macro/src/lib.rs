@@ -109,6 +109,9 @@ pub fn geometry(args: TokenStream, input: TokenStream) -> TokenStream {
 
     let struct_fields = utility::geometry_fields();
 
+    // Geometries without a label are named after their type for debugging
+    let default_label = struct_name.to_string().to_case(Case::Snake);
+
     quote::quote! {
         #[derive(Default, Debug, Clone)]
         #vis struct #struct_name {
@@ -128,12 +131,37 @@ pub fn geometry(args: TokenStream, input: TokenStream) -> TokenStream {
             }
 
             #vis fn cull_mode(&mut self, cull_mode: wave_internal::wave_vulkan::CullMode) -> &mut Self {
-                self.cull_mode = cull_mode;
+                self.material.cull_mode = cull_mode;
+                self
+            }
+
+            #vis fn blend_mode(&mut self, blend_mode: wave_internal::wave_vulkan::BlendMode) -> &mut Self {
+                self.material.blend_mode = blend_mode;
+                self
+            }
+
+            #vis fn cast_shadows(&mut self, cast_shadows: bool) -> &mut Self {
+                self.material.shadows.cast = cast_shadows;
+                self
+            }
+
+            #vis fn receive_shadows(&mut self, receive_shadows: bool) -> &mut Self {
+                self.material.shadows.receive = receive_shadows;
+                self
+            }
+
+            #vis fn pbr(&mut self, material: wave_internal::wave_vulkan::pbr::PbrMaterial) -> &mut Self {
+                self.material.pbr = material;
                 self
             }
 
             #vis fn shader(&mut self, shader: wave_internal::wave_vulkan::Shader) -> &mut Self {
-                self.shader = shader;
+                self.material.shader = shader;
+                self
+            }
+
+            #vis fn material(&mut self, handle: wave_internal::wave_vulkan::material::MaterialHandle) -> &mut Self {
+                self.material_handle = Some(handle);
                 self
             }
 
@@ -162,6 +190,23 @@ pub fn geometry(args: TokenStream, input: TokenStream) -> TokenStream {
                 self
             }
 
+            #vis fn normal_map(&mut self, path: &str) -> &mut Self {
+                self.normal_map = wave_internal::wave_vulkan::TextureBuffer(
+                    std::fs::read(path).expect("Failed to read contents of normal map file"),
+                );
+                self
+            }
+
+            #vis fn label(&mut self, label: &str) -> &mut Self {
+                self.label = Some(label.to_owned());
+                self
+            }
+
+            #vis fn debug_view(&mut self, view: wave_internal::wave_window::window::DebugView) -> &mut Self {
+                self.debug_view = Some(view);
+                self
+            }
+
             #vis fn no_index(&mut self) -> &mut Self {
                 self.indexed = Indexed(false);
                 self
@@ -174,15 +219,25 @@ pub fn geometry(args: TokenStream, input: TokenStream) -> TokenStream {
 
         impl GeomBuilder for #struct_name {
             #vis fn features(&self) -> wave_internal::wave_vulkan::GeomProperties {
+                // The geometry's own texture and normal map fill in any the material lacks
+                let mut material = self.material.clone();
+                if material.pbr.base_colour_texture.is_empty() {
+                    material.pbr.base_colour_texture = self.texture.clone();
+                }
+                if material.pbr.normal_texture.is_empty() {
+                    material.pbr.normal_texture = self.normal_map.clone();
+                }
+
                 wave_internal::wave_vulkan::GeomProperties::new(
-                    &self.texture,
+                    &material,
+                    self.material_handle,
                     self.vertices_and_indices(),
                     self.topology,
-                    self.cull_mode,
-                    self.shader,
                     *self.indexed,
                     self.properties.model
                 )
+                .label(self.label.clone().unwrap_or_else(|| #default_label.to_owned()))
+                .debug_view(self.debug_view)
             }
 
             #vis fn vertices_and_indices(&self) -> wave_internal::wave_space::space::VerticesAndIndices {
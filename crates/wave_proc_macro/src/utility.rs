@@ -4,10 +4,13 @@ pub fn geometry_fields() -> proc_macro2::TokenStream {
     let mut struct_fields = proc_macro2::TokenStream::default();
     struct_fields.extend(quote! {
         pub texture: wave_internal::wave_vulkan::TextureBuffer,
+        pub normal_map: wave_internal::wave_vulkan::TextureBuffer,
         pub indexed: wave_internal::wave_geometry::Indexed,
         pub topology: wave_internal::wave_vulkan::ModelTopology,
-        pub cull_mode: wave_internal::wave_vulkan::CullMode,
-        pub shader: wave_internal::wave_vulkan::Shader,
+        pub material: wave_internal::wave_vulkan::material::Material,
+        pub material_handle: Option<wave_internal::wave_vulkan::material::MaterialHandle>,
+        pub label: Option<String>,
+        pub debug_view: Option<wave_internal::wave_window::window::DebugView>,
     });
     struct_fields
 }
@@ -1,6 +1,10 @@
 pub mod macros;
+pub mod scene_graph;
 pub mod space;
 
 pub mod prelude {
-    pub use crate::space::{Pos3, VerticesAndIndices};
+    pub use crate::{
+        scene_graph::{Attachment, NodeId, SceneGraph, Transform},
+        space::{Pos3, VerticesAndIndices},
+    };
 }
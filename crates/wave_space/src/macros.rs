@@ -1,11 +1,16 @@
 #[macro_export]
 macro_rules! vertex {
     ($pos:expr, $col:expr, $norm:expr, $tex:expr) => {
+        wave_space::space::Vertex::new($pos, $col, $norm, $tex)
+    };
+    ($pos:expr, $col:expr, $norm:expr, $tex:expr, $tangent:expr, $bitangent:expr) => {
         wave_space::space::Vertex {
             pos: $pos,
             colour: $col,
             normal: $norm,
             tex_coord: $tex,
+            tangent: $tangent,
+            bitangent: $bitangent,
         }
     };
 }
@@ -0,0 +1,354 @@
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+use std::collections::HashMap;
+
+/// Translation, rotation and scale of a node relative to its parent, applied in the order
+/// scale, rotation, translation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::zeros(),
+            rotation: UnitQuaternion::identity(),
+            scale: Vector3::repeat(1.),
+        }
+    }
+}
+
+impl Transform {
+    #[must_use]
+    pub fn from_translation(translation: Vector3<f32>) -> Self {
+        Self {
+            translation,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn from_rotation(rotation: UnitQuaternion<f32>) -> Self {
+        Self {
+            rotation,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn translation(mut self, translation: Vector3<f32>) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    #[must_use]
+    pub fn rotation(mut self, rotation: UnitQuaternion<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    #[must_use]
+    pub fn scale(mut self, scale: Vector3<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Turns by `rotation` after the current rotation, about the node's own origin
+    pub fn rotate(&mut self, rotation: UnitQuaternion<f32>) {
+        self.rotation = rotation * self.rotation;
+    }
+
+    pub fn translate(&mut self, distance: Vector3<f32>) {
+        self.translation += distance;
+    }
+
+    #[must_use]
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+}
+
+/// Node added with [`SceneGraph::add`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Something placed in the world by the node it is attached to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Attachment {
+    /// Geometry at this index, in the order geometries were added to the engine. Its own
+    /// model matrix is applied before the node's.
+    Geometry(usize),
+    /// Light at this index, in the order lights were added to the engine
+    Light(usize),
+    /// The camera, which then moves and looks around relative to the node
+    Camera,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    local: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// Parent's world matrix times the local one, valid unless `dirty`
+    world: Matrix4<f32>,
+    dirty: bool,
+}
+
+/// Hierarchy of nodes whose transforms are relative to their parent, so moving a node
+/// carries everything attached to it and its descendants along.
+///
+/// World matrices are only recomputed when they are read after the node or one of its
+/// ancestors changed.
+#[derive(Clone, Debug, Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    attachments: HashMap<Attachment, NodeId>,
+}
+
+impl SceneGraph {
+    /// Adds a node placed by `local` relative to `parent`, or to the world when `None`
+    pub fn add(&mut self, parent: Option<NodeId>, local: Transform) -> NodeId {
+        let id = NodeId(self.nodes.len());
+
+        self.nodes.push(Node {
+            local,
+            parent,
+            children: Vec::new(),
+            world: Matrix4::identity(),
+            dirty: true,
+        });
+
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+
+        id
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    #[must_use]
+    pub fn local(&self, node: NodeId) -> &Transform {
+        &self.nodes[node.0].local
+    }
+
+    /// Local transform of `node` to change, which moves its descendants along with it
+    pub fn local_mut(&mut self, node: NodeId) -> &mut Transform {
+        self.mark_dirty(node);
+        &mut self.nodes[node.0].local
+    }
+
+    pub fn set_local(&mut self, node: NodeId, local: Transform) {
+        *self.local_mut(node) = local;
+    }
+
+    #[must_use]
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node.0].parent
+    }
+
+    #[must_use]
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        &self.nodes[node.0].children
+    }
+
+    /// Moves `node` and its descendants under `parent`, or to the root when `None`. The local
+    /// transform is kept, so the node is placed relative to its new parent.
+    ///
+    /// # Panics
+    ///
+    /// When `parent` is `node` or one of its descendants.
+    pub fn set_parent(&mut self, node: NodeId, parent: Option<NodeId>) {
+        assert!(
+            !parent.is_some_and(|parent| self.is_ancestor_or_self(node, parent)),
+            "A node cannot be parented to itself or one of its descendants"
+        );
+
+        if let Some(old) = self.nodes[node.0].parent {
+            self.nodes[old.0].children.retain(|child| *child != node);
+        }
+
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(node);
+        }
+
+        self.nodes[node.0].parent = parent;
+        self.mark_dirty(node);
+    }
+
+    /// Places `attachment` with `node`, moving it off any node it was attached to
+    pub fn attach(&mut self, node: NodeId, attachment: Attachment) {
+        self.attachments.insert(attachment, node);
+    }
+
+    /// Leaves `attachment` where its own transform places it in the world
+    pub fn detach(&mut self, attachment: Attachment) {
+        self.attachments.remove(&attachment);
+    }
+
+    #[must_use]
+    pub fn node_of(&self, attachment: Attachment) -> Option<NodeId> {
+        self.attachments.get(&attachment).copied()
+    }
+
+    /// Matrix from the local space of `node` to the world, recomputed only when it or one of
+    /// its ancestors changed since it was last read
+    pub fn world(&mut self, node: NodeId) -> Matrix4<f32> {
+        if !self.nodes[node.0].dirty {
+            return self.nodes[node.0].world;
+        }
+
+        let parent = self.nodes[node.0]
+            .parent
+            .map_or_else(Matrix4::identity, |parent| self.world(parent));
+
+        let node = &mut self.nodes[node.0];
+        node.world = parent * node.local.matrix();
+        node.dirty = false;
+        node.world
+    }
+
+    /// World matrix of the node `attachment` is attached to, or `None` when it is not
+    pub fn world_of(&mut self, attachment: Attachment) -> Option<Matrix4<f32>> {
+        self.node_of(attachment).map(|node| self.world(node))
+    }
+
+    fn mark_dirty(&mut self, node: NodeId) {
+        let mut stack = vec![node];
+
+        while let Some(node) = stack.pop() {
+            let node = &mut self.nodes[node.0];
+
+            // Reading a world matrix cleans its ancestors too, so the descendants of a dirty
+            // node are already dirty
+            if node.dirty {
+                continue;
+            }
+
+            node.dirty = true;
+            stack.extend_from_slice(&node.children);
+        }
+    }
+
+    fn is_ancestor_or_self(&self, ancestor: NodeId, node: NodeId) -> bool {
+        std::iter::successors(Some(node), |node| self.nodes[node.0].parent)
+            .any(|node| node == ancestor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point3;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_near(actual: Point3<f32>, expected: Point3<f32>) {
+        assert!(
+            (actual - expected).norm() < 1e-5,
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn test_children_follow_their_parent() {
+        let mut graph = SceneGraph::default();
+
+        let planet = graph.add(None, Transform::from_translation(Vector3::new(1., 0., 0.)));
+        let moon = graph.add(
+            Some(planet),
+            Transform::from_translation(Vector3::new(0., 0., 2.)).scale(Vector3::repeat(0.5)),
+        );
+
+        assert_near(
+            graph.world(moon).transform_point(&Point3::new(0., 1., 0.)),
+            Point3::new(1., 0.5, 2.),
+        );
+
+        // Turning the planet a quarter about y swings the moon from +z to +x
+        graph
+            .local_mut(planet)
+            .rotate(UnitQuaternion::from_axis_angle(
+                &Vector3::y_axis(),
+                FRAC_PI_2,
+            ));
+
+        assert_near(
+            graph.world(moon).transform_point(&Point3::origin()),
+            Point3::new(3., 0., 0.),
+        );
+    }
+
+    #[test]
+    fn test_reparenting_keeps_the_local_transform() {
+        let mut graph = SceneGraph::default();
+
+        let a = graph.add(None, Transform::from_translation(Vector3::x()));
+        let b = graph.add(None, Transform::from_translation(Vector3::y()));
+        let child = graph.add(Some(a), Transform::from_translation(Vector3::z()));
+
+        assert_near(
+            graph.world(child).transform_point(&Point3::origin()),
+            Point3::new(1., 0., 1.),
+        );
+
+        graph.set_parent(child, Some(b));
+
+        assert!(graph.children(a).is_empty());
+        assert_eq!(graph.children(b), [child]);
+        assert_near(
+            graph.world(child).transform_point(&Point3::origin()),
+            Point3::new(0., 1., 1.),
+        );
+
+        graph.set_parent(child, None);
+        assert_near(
+            graph.world(child).transform_point(&Point3::origin()),
+            Point3::new(0., 0., 1.),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "descendants")]
+    fn test_parenting_to_a_descendant_panics() {
+        let mut graph = SceneGraph::default();
+
+        let root = graph.add(None, Transform::default());
+        let child = graph.add(Some(root), Transform::default());
+
+        graph.set_parent(root, Some(child));
+    }
+
+    #[test]
+    fn test_attachments_move_between_nodes() {
+        let mut graph = SceneGraph::default();
+
+        let a = graph.add(None, Transform::from_translation(Vector3::x()));
+        let b = graph.add(None, Transform::from_translation(Vector3::y()));
+
+        assert_eq!(graph.world_of(Attachment::Camera), None);
+
+        graph.attach(a, Attachment::Geometry(0));
+        graph.attach(b, Attachment::Geometry(0));
+
+        assert_eq!(graph.node_of(Attachment::Geometry(0)), Some(b));
+        assert_eq!(
+            graph.world_of(Attachment::Geometry(0)),
+            Some(Matrix4::new_translation(&Vector3::y()))
+        );
+
+        graph.detach(Attachment::Geometry(0));
+        assert_eq!(graph.node_of(Attachment::Geometry(0)), None);
+    }
+}
@@ -118,6 +118,10 @@ pub struct Vertex {
     pub colour: Vector3<f32>,
     pub normal: Vector3<f32>,
     pub tex_coord: Vector2<f32>,
+    /// Direction of increasing U across the surface, zero until tangents are generated
+    pub tangent: Vector3<f32>,
+    /// Direction of increasing V across the surface, zero until tangents are generated
+    pub bitangent: Vector3<f32>,
 }
 
 impl Vertex {
@@ -133,6 +137,8 @@ impl Vertex {
             colour,
             normal,
             tex_coord,
+            tangent: vector![0., 0., 0.],
+            bitangent: vector![0., 0., 0.],
         }
     }
 }
@@ -140,6 +146,22 @@ impl Vertex {
 #[derive(Clone, Default, Debug, From, Deref, DerefMut)]
 pub struct Vertices(Vec<Vertex>);
 
+impl Vertices {
+    /// Centre of the axis aligned box bounding every vertex position
+    #[must_use]
+    pub fn centre(&self) -> Point3<f32> {
+        let Some(first) = self.first() else {
+            return Point3::origin();
+        };
+
+        let (min, max) = self.iter().fold((first.pos, first.pos), |(min, max), vertex| {
+            (min.inf(&vertex.pos), max.sup(&vertex.pos))
+        });
+
+        nalgebra::center(&min, &max)
+    }
+}
+
 #[derive(Clone, Default, Debug, From, Deref, DerefMut)]
 pub struct Indices(Vec<u16>);
 
@@ -1,3 +1,44 @@
+use wave_vulkan::gpu::{self, DeviceSelection};
+
+const USAGE: &str = "Usage: wave_tooling <command>
+
+Commands:
+    devices    List the Vulkan devices and the one chosen by default";
+
 fn main() {
-    println!("Hello, world!");
+    let command = std::env::args().nth(1);
+
+    match command.as_deref() {
+        Some("devices") => devices(),
+        Some("help" | "--help" | "-h") => println!("{USAGE}"),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    }
+}
+
+fn devices() {
+    let devices = gpu::available_devices();
+
+    if devices.is_empty() {
+        println!("No Vulkan devices found");
+        return;
+    }
+
+    devices.iter().for_each(|device| println!("{device}"));
+
+    // Without a window every device is a candidate, so this may differ from the device
+    // picked for a surface the preferred device cannot present to
+    let selection = DeviceSelection::from_env().unwrap_or_default();
+    match selection.choose(&devices) {
+        Some(position) => println!("Selected ({selection}): {}", devices[position].name),
+        None => {
+            let fallback = DeviceSelection::Auto.choose(&devices).unwrap_or_default();
+            println!(
+                "No device matches {selection}, falling back to: {}",
+                devices[fallback].name
+            );
+        }
+    }
 }
@@ -1,7 +1,13 @@
-use crate::{command_buffer, device::Devices, memory, texture};
-use ash::{vk, Instance};
+use crate::{
+    command_buffer::{self, CommandPool, Handoff},
+    device::Devices,
+    memory,
+    sync_objects::MAX_FRAMES_IN_FLIGHT,
+    texture,
+};
+use ash::{vk, Device, Instance};
 use gpu_allocator::vulkan::{Allocation, Allocator};
-use std::mem::size_of;
+use std::mem::{size_of, size_of_val};
 use wave_space::space::{Vertex, VerticesAndIndices};
 
 #[derive(Default, Debug)]
@@ -17,6 +23,99 @@ impl Buffer {
     }
 }
 
+/// Smallest frame vertex buffer created, in bytes
+const MIN_FRAME_BUFFER_SIZE: vk::DeviceSize = 16 * 1024;
+
+/// Host visible vertex buffer for each frame in flight, rewritten every frame and grown when
+/// the vertices outgrow it
+#[derive(Debug)]
+pub(crate) struct FrameVertexBuffers {
+    name: &'static str,
+    usage: vk::BufferUsageFlags,
+    /// Buffer and its size in bytes
+    buffers: Vec<Option<(Buffer, vk::DeviceSize)>>,
+}
+
+impl FrameVertexBuffers {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            usage: vk::BufferUsageFlags::VERTEX_BUFFER,
+            buffers: (0..MAX_FRAMES_IN_FLIGHT).map(|_| None).collect(),
+        }
+    }
+
+    /// Buffers holding indices instead of vertices
+    pub fn indices(name: &'static str) -> Self {
+        Self {
+            usage: vk::BufferUsageFlags::INDEX_BUFFER,
+            ..Self::new(name)
+        }
+    }
+
+    /// Copies `vertices` into the buffer of `frame`.
+    ///
+    /// # Safety
+    ///
+    /// The last submission of `frame` must have completed.
+    pub unsafe fn write<T: Copy>(
+        &mut self,
+        allocator: &mut Allocator,
+        devices: &Devices,
+        frame: usize,
+        vertices: &[T],
+    ) {
+        let bytes =
+            std::slice::from_raw_parts(vertices.as_ptr().cast::<u8>(), size_of_val(vertices));
+        let size = bytes.len() as vk::DeviceSize;
+
+        let slot = &mut self.buffers[frame];
+
+        if slot.as_ref().is_none_or(|(_, capacity)| *capacity < size) {
+            if let Some((old, _)) = slot.take() {
+                allocator.free(old.allocation).unwrap();
+                devices.logical.device.destroy_buffer(old.buffer, None);
+            }
+
+            let capacity = size.next_power_of_two().max(MIN_FRAME_BUFFER_SIZE);
+            let kind = if self.usage == vk::BufferUsageFlags::INDEX_BUFFER {
+                "index"
+            } else {
+                "vertex"
+            };
+            let name = format!("{}/{kind} buffer {frame}", self.name);
+            let buffer = texture::create_buffer(allocator, capacity, self.usage, devices, &name);
+            devices.debug_names.name(buffer.buffer, &name);
+
+            *slot = Some((buffer, capacity));
+        }
+
+        let (buffer, _) = slot.as_mut().expect("The frame buffer was just created");
+
+        buffer
+            .allocation
+            .mapped_slice_mut()
+            .expect("Frame vertex buffer is not host visible")[..bytes.len()]
+            .copy_from_slice(bytes);
+    }
+
+    pub fn get(&self, frame: usize) -> Option<vk::Buffer> {
+        self.buffers[frame]
+            .as_ref()
+            .map(|(buffer, _)| buffer.buffer)
+    }
+
+    /// # Safety
+    ///
+    /// No frame may be in flight.
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator, device: &Device) {
+        self.buffers.drain(..).flatten().for_each(|(buffer, _)| {
+            allocator.free(buffer.allocation).unwrap();
+            device.destroy_buffer(buffer.buffer, None);
+        });
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct ModelBuffers {
     pub vertex: Buffer,
@@ -27,8 +126,9 @@ impl ModelBuffers {
     pub fn new(
         allocator: &mut Allocator,
         vertices_and_indices: &VerticesAndIndices,
-        command_pool: &vk::CommandPool,
+        command_pool: &CommandPool,
         command_buffer_count: u32,
+        name: &str,
         devices: &Devices,
     ) -> Self {
         let vertex = create_vertex_index_buffer(
@@ -55,8 +155,22 @@ impl ModelBuffers {
             devices,
         );
 
+        let names = &devices.debug_names;
+        names.name(vertex.buffer, &format!("{name}/vertex buffer"));
+        names.name(index.buffer, &format!("{name}/index buffer"));
+
         ModelBuffers { vertex, index }
     }
+
+    /// # Safety
+    ///
+    /// The buffers must no longer be in use by the device.
+    pub unsafe fn destroy(self, allocator: &mut Allocator, device: &Device) {
+        [self.vertex, self.index].into_iter().for_each(|buffer| {
+            allocator.free(buffer.allocation).unwrap();
+            device.destroy_buffer(buffer.buffer, None);
+        });
+    }
 }
 
 pub(crate) fn create_vertex_index_buffer<T: Copy>(
@@ -64,12 +178,10 @@ pub(crate) fn create_vertex_index_buffer<T: Copy>(
     buffer_size: vk::DeviceSize,
     data: &[T],
     usage_flags: vk::BufferUsageFlags,
-    command_pool: &vk::CommandPool,
+    command_pool: &CommandPool,
     command_buffer_count: u32,
     devices: &Devices,
 ) -> Buffer {
-    let device = &devices.logical.device;
-
     let staging = texture::create_buffer(
         allocator,
         buffer_size,
@@ -79,8 +191,8 @@ pub(crate) fn create_vertex_index_buffer<T: Copy>(
     );
 
     unsafe {
-        let mapped_ptr = staging.allocation.mapped_ptr().unwrap().as_ptr() as *mut f32;
-        mapped_ptr.copy_from_nonoverlapping(data.as_ptr() as *const f32, buffer_size as usize);
+        let mapped_ptr = staging.allocation.mapped_ptr().unwrap().as_ptr() as *mut u8;
+        mapped_ptr.copy_from_nonoverlapping(data.as_ptr() as *const u8, buffer_size as usize);
     }
 
     let buffer = texture::create_buffer(
@@ -96,47 +208,46 @@ pub(crate) fn create_vertex_index_buffer<T: Copy>(
         command_pool,
         command_buffer_count,
         buffer_size,
-        staging.buffer,
+        staging,
         buffer.buffer,
     );
 
-    // unsafe {
-    //     device.destroy_buffer(staging.buffer, None);
-    //     device.free_memory(staging.memory, None);
-    // }
-    allocator.free(staging.allocation).unwrap();
-    unsafe { device.destroy_buffer(staging.buffer, None) };
-
     buffer
 }
 
+/// Copies `staging` into `dst_buffer`, freeing it once the copy completes
 fn copy_buffer(
     devices: &Devices,
-    command_pool: &vk::CommandPool,
+    command_pool: &CommandPool,
     _command_buffer_count: u32,
     size: vk::DeviceSize,
-    src_buffer: vk::Buffer,
+    staging: Buffer,
     dst_buffer: vk::Buffer,
 ) {
     let device = &devices.logical.device;
 
-    let command_buffer = command_buffer::begin_single_time_command(device, command_pool);
-
     let copy_region = vk::BufferCopy::default().size(size);
 
-    unsafe {
-        device.cmd_copy_buffer(
-            command_buffer,
-            src_buffer,
-            dst_buffer,
-            std::slice::from_ref(&copy_region),
-        );
-    }
+    let handoff = Handoff::Buffer {
+        buffer: dst_buffer,
+        dst_stage: vk::PipelineStageFlags::VERTEX_INPUT,
+        dst_access: vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::INDEX_READ,
+    };
+
+    let src_buffer = staging.buffer;
 
-    command_buffer::end_single_time_command(
-        device,
-        devices.logical.queues.graphics,
+    command_buffer::upload(
+        devices,
         command_pool,
-        command_buffer,
+        handoff,
+        staging,
+        |command_buffer| unsafe {
+            device.cmd_copy_buffer(
+                command_buffer,
+                src_buffer,
+                dst_buffer,
+                std::slice::from_ref(&copy_region),
+            );
+        },
     );
 }
@@ -0,0 +1,445 @@
+use crate::{buffer::Buffer, device::Devices, swap_chain::SwapChain};
+use ash::{vk, Device};
+use gpu_allocator::{
+    vulkan::{AllocationCreateDesc, AllocationScheme, Allocator},
+    MemoryLocation,
+};
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+use wave_window::window::FrameCapture;
+
+/// Order of the colour channels in a four byte texel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ChannelOrder {
+    Rgba,
+    Bgra,
+}
+
+impl ChannelOrder {
+    fn of(format: vk::Format) -> Option<Self> {
+        match format {
+            vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => Some(Self::Rgba),
+            vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB => Some(Self::Bgra),
+            _ => None,
+        }
+    }
+
+    /// Converts texels to RGBA. Alpha is made opaque, as the window ignores it when
+    /// compositing.
+    fn to_rgba(self, texels: &[u8]) -> Vec<u8> {
+        texels
+            .chunks_exact(4)
+            .flat_map(|texel| match self {
+                Self::Rgba => [texel[0], texel[1], texel[2], u8::MAX],
+                Self::Bgra => [texel[2], texel[1], texel[0], u8::MAX],
+            })
+            .collect()
+    }
+}
+
+/// Encodes a captured frame as a PNG file
+pub fn save_png(frame: &FrameCapture, path: impl AsRef<Path>) -> image::ImageResult<()> {
+    image::save_buffer(
+        path,
+        &frame.pixels,
+        frame.width,
+        frame.height,
+        image::ColorType::Rgba8,
+    )
+}
+
+/// Writes captured frames as an uncompressed YUV4MPEG2 stream, which video encoders such as
+/// ffmpeg read directly.
+///
+/// Frames use full resolution 4:4:4 chroma with BT.601 studio range, and all must have the
+/// size of the first.
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    fps: f32,
+    size: Option<(u32, u32)>,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(writer: W, fps: f32) -> Self {
+        Self {
+            writer,
+            fps,
+            size: None,
+        }
+    }
+
+    pub fn write_frame(&mut self, frame: &FrameCapture) -> io::Result<()> {
+        match self.size {
+            None => {
+                // Frame rates are written as a ratio, kept to a thousandth of a frame
+                writeln!(
+                    self.writer,
+                    "YUV4MPEG2 W{} H{} F{}:1000 Ip A1:1 C444",
+                    frame.width,
+                    frame.height,
+                    (self.fps * 1000.).round() as u32,
+                )?;
+                self.size = Some((frame.width, frame.height));
+            }
+            Some(size) if size != (frame.width, frame.height) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "frame is {}x{} but the stream is {}x{}",
+                        frame.width, frame.height, size.0, size.1
+                    ),
+                ));
+            }
+            Some(_) => {}
+        }
+
+        let texels = frame.pixels.len() / 4;
+        let mut planes = vec![0; texels * 3];
+
+        frame
+            .pixels
+            .chunks_exact(4)
+            .enumerate()
+            .for_each(|(index, texel)| {
+                let [y, u, v] = rgb_to_ycbcr(texel[0], texel[1], texel[2]);
+                planes[index] = y;
+                planes[texels + index] = u;
+                planes[texels * 2 + index] = v;
+            });
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&planes)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// BT.601 conversion to studio range, with luma in 16..=235 and chroma in 16..=240
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> [u8; 3] {
+    let (r, g, b) = (f32::from(r), f32::from(g), f32::from(b));
+
+    let y = 16. + (65.481 * r + 128.553 * g + 24.966 * b) / 255.;
+    let u = 128. + (-37.797 * r - 74.203 * g + 112. * b) / 255.;
+    let v = 128. + (112. * r - 93.786 * g - 18.214 * b) / 255.;
+
+    [y, u, v].map(|channel| channel.round().clamp(0., 255.) as u8)
+}
+
+/// Copy of a frame in host memory. The copy is recorded into the command buffer of the frame
+/// after a capture is requested, following post processing and the gui, so the image is read
+/// before it is handed to the presentation engine.
+///
+/// Swap chains that cannot be transfer sources, or whose format has no RGBA8 equivalent,
+/// cannot be captured.
+#[derive(Default)]
+pub(crate) struct FrameReadback {
+    /// Set until a frame records the copy
+    requested: bool,
+    /// Host visible buffer and its size in bytes, kept for the next capture
+    buffer: Option<(Buffer, vk::DeviceSize)>,
+    /// Frame copying into the buffer, read once its fence is signalled
+    pending: Option<PendingCapture>,
+}
+
+struct PendingCapture {
+    fence: vk::Fence,
+    extent: vk::Extent2D,
+    order: ChannelOrder,
+}
+
+impl FrameReadback {
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// Buffer the frame signalling `fence` should copy its swap chain image into, when a
+    /// capture was requested and the swap chain can be read.
+    ///
+    /// # Safety
+    ///
+    /// `fence` must be signalled by the submission of the frame about to be recorded.
+    pub unsafe fn prepare(
+        &mut self,
+        allocator: &mut Allocator,
+        devices: &Devices,
+        swap_chain: &SwapChain,
+        fence: vk::Fence,
+    ) -> Option<vk::Buffer> {
+        if !std::mem::take(&mut self.requested) {
+            return None;
+        }
+
+        let Some(order) = ChannelOrder::of(swap_chain.image_format).filter(|_| swap_chain.readable)
+        else {
+            log::warn!(
+                "Swap chain images in {:?} cannot be captured",
+                swap_chain.image_format
+            );
+            return None;
+        };
+
+        let device = &devices.logical.device;
+
+        // An unread capture may still be copying into the buffer
+        if let Some(pending) = self.pending.take() {
+            wait(device, pending.fence);
+        }
+
+        let extent = swap_chain.extent;
+        let size = vk::DeviceSize::from(extent.width) * vk::DeviceSize::from(extent.height) * 4;
+
+        if self
+            .buffer
+            .as_ref()
+            .is_none_or(|(_, capacity)| *capacity < size)
+        {
+            if let Some((old, _)) = self.buffer.take() {
+                allocator.free(old.allocation).unwrap();
+                device.destroy_buffer(old.buffer, None);
+            }
+
+            let buffer = create_readback_buffer(allocator, devices, size);
+            self.buffer = Some((buffer, size));
+        }
+
+        self.pending = Some(PendingCapture {
+            fence,
+            extent,
+            order,
+        });
+
+        self.buffer.as_ref().map(|(buffer, _)| buffer.buffer)
+    }
+
+    /// Waits for the frame that recorded the copy and returns its pixels, or `None` when no
+    /// frame has since the last capture was read.
+    ///
+    /// # Safety
+    ///
+    /// The frame's fence must not have been reset without being submitted again.
+    pub unsafe fn read(&mut self, device: &Device) -> Option<FrameCapture> {
+        let pending = self.pending.take()?;
+        let (buffer, _) = self.buffer.as_ref()?;
+
+        wait(device, pending.fence);
+
+        let size = pending.extent.width as usize * pending.extent.height as usize * 4;
+        let texels = buffer
+            .allocation
+            .mapped_slice()
+            .expect("Capture buffer is not host visible");
+
+        Some(FrameCapture {
+            width: pending.extent.width,
+            height: pending.extent.height,
+            pixels: pending.order.to_rgba(&texels[..size]),
+        })
+    }
+
+    /// # Safety
+    ///
+    /// No frame may be in flight.
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator, device: &Device) {
+        self.pending = None;
+
+        if let Some((buffer, _)) = self.buffer.take() {
+            allocator.free(buffer.allocation).unwrap();
+            device.destroy_buffer(buffer.buffer, None);
+        }
+    }
+}
+
+unsafe fn wait(device: &Device, fence: vk::Fence) {
+    device
+        .wait_for_fences(std::slice::from_ref(&fence), true, vk::DeviceSize::MAX)
+        .expect("Failed to wait for the captured frame");
+}
+
+unsafe fn create_readback_buffer(
+    allocator: &mut Allocator,
+    devices: &Devices,
+    size: vk::DeviceSize,
+) -> Buffer {
+    let device = &devices.logical.device;
+
+    let buffer_info = vk::BufferCreateInfo::default()
+        .size(size)
+        .usage(vk::BufferUsageFlags::TRANSFER_DST)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+    let buffer = device
+        .create_buffer(&buffer_info, None)
+        .expect("Failed to create capture buffer");
+
+    let allocation = allocator
+        .allocate(&AllocationCreateDesc {
+            name: "Capture Buffer",
+            requirements: device.get_buffer_memory_requirements(buffer),
+            location: MemoryLocation::GpuToCpu,
+            linear: true,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+        })
+        .expect("Could not allocate capture buffer memory");
+
+    device
+        .bind_buffer_memory(buffer, allocation.memory(), allocation.offset())
+        .expect("Could not bind capture buffer memory");
+
+    devices.debug_names.name(buffer, "capture buffer");
+
+    Buffer::new(buffer, allocation)
+}
+
+/// Records a copy of the frame's swap chain image into `buffer`, made available to the host
+/// once the frame's fence is signalled.
+///
+/// # Safety
+///
+/// `command_buffer` must be recording outside a render pass, after the present pass left
+/// `image` in `PRESENT_SRC_KHR`, which it is returned to. `buffer` must hold the whole image.
+pub(crate) unsafe fn record_copy(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    extent: vk::Extent2D,
+    buffer: vk::Buffer,
+) {
+    let range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1);
+
+    // The image has not been handed to the presentation engine yet, so it can still be read
+    let to_transfer = vk::ImageMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .old_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+        .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(range);
+
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        std::slice::from_ref(&to_transfer),
+    );
+
+    let region = vk::BufferImageCopy::default()
+        .image_subresource(
+            vk::ImageSubresourceLayers::default()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .mip_level(0)
+                .base_array_layer(0)
+                .layer_count(1),
+        )
+        .image_extent(vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        });
+
+    device.cmd_copy_image_to_buffer(
+        command_buffer,
+        image,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        buffer,
+        std::slice::from_ref(&region),
+    );
+
+    let to_present = vk::ImageMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .dst_access_mask(vk::AccessFlags::MEMORY_READ)
+        .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(range);
+
+    let to_host = vk::BufferMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::HOST_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(buffer)
+        .offset(0)
+        .size(vk::WHOLE_SIZE);
+
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::HOST | vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+        vk::DependencyFlags::empty(),
+        &[],
+        std::slice::from_ref(&to_host),
+        std::slice::from_ref(&to_present),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bgra_texels_are_swizzled_and_made_opaque() {
+        let texels = [10, 20, 30, 0, 40, 50, 60, 128];
+
+        assert_eq!(
+            ChannelOrder::of(vk::Format::B8G8R8A8_SRGB)
+                .unwrap()
+                .to_rgba(&texels),
+            [30, 20, 10, 255, 60, 50, 40, 255]
+        );
+        assert_eq!(
+            ChannelOrder::of(vk::Format::R8G8B8A8_UNORM)
+                .unwrap()
+                .to_rgba(&texels),
+            [10, 20, 30, 255, 40, 50, 60, 255]
+        );
+        assert_eq!(ChannelOrder::of(vk::Format::A2B10G10R10_UNORM_PACK32), None);
+    }
+
+    #[test]
+    fn test_y4m_stream_has_header_and_planar_frames() {
+        let frame = FrameCapture {
+            width: 2,
+            height: 1,
+            pixels: vec![0, 0, 0, 255, 255, 255, 255, 255],
+        };
+
+        let mut stream = Vec::new();
+        let mut writer = Y4mWriter::new(&mut stream, 30.);
+        writer.write_frame(&frame).unwrap();
+        writer.write_frame(&frame).unwrap();
+
+        let smaller = FrameCapture {
+            width: 1,
+            height: 1,
+            pixels: vec![0; 4],
+        };
+        assert!(writer.write_frame(&smaller).is_err());
+
+        let header = b"YUV4MPEG2 W2 H1 F30000:1000 Ip A1:1 C444\n";
+        let planes = [16, 235, 128, 128, 128, 128];
+
+        let mut expected = header.to_vec();
+        for _ in 0..2 {
+            expected.extend_from_slice(b"FRAME\n");
+            expected.extend_from_slice(&planes);
+        }
+
+        assert_eq!(stream, expected);
+    }
+}
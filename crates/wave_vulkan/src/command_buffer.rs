@@ -1,62 +1,262 @@
 use crate::{
     any_as_u8_slice,
-    device::{self, Devices},
-    frame_buffer::FrameBuffers,
-    renderer::RenderPass,
-    swap_chain::SwapChain,
-    Shader, VulkanObject,
+    buffer::Buffer,
+    capture, debug_view,
+    device::Devices,
+    material::VulkanMaterial,
+    particles::ParticleView,
+    profiler::{DrawCounts, GpuMarks},
+    sync_objects::MAX_FRAMES_IN_FLIGHT,
+    BlendMode, ModelTopology, Vulkan, VulkanObject,
 };
-use ash::{khr::surface, vk, Device, Instance};
+use ash::{vk, Device};
 use derive_more::{Deref, From};
+use gpu_allocator::vulkan::Allocator;
+use nalgebra::{Matrix4, Point3};
+use std::{cell::RefCell, mem};
+use wave_window::window::DebugView;
 
 #[derive(Debug, From, Deref, Clone)]
 pub struct CommandBuffers(Vec<vk::CommandBuffer>);
 
-#[derive(Debug, From, Deref, Clone)]
-pub struct CommandPool(vk::CommandPool);
+/// Pools for one-off command buffers. Dereferences to the graphics pool, used for anything
+/// that draws, blits or prepares images for shaders, while uploads go through the transfer
+/// pool.
+///
+/// Uploads are submitted without the CPU waiting for them, and are released by
+/// [`CommandPool::release_uploads`] once they complete.
+#[derive(Debug, Deref)]
+pub struct CommandPool {
+    #[deref]
+    pub graphics: vk::CommandPool,
+    pub transfer: vk::CommandPool,
+    uploads: RefCell<Vec<PendingUpload>>,
+}
 
-pub fn create_command_pool(
-    instance: &Instance,
-    devices: &Devices,
-    surface_loader: &surface::Instance,
-    surface: &vk::SurfaceKHR,
-) -> CommandPool {
-    let queue_family_indices =
-        device::find_queue_family(instance, devices.physical.device, surface_loader, surface);
-
-    let pool_info = vk::CommandPoolCreateInfo::default()
-        .queue_family_index(queue_family_indices.graphics_family.unwrap())
-        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
-
-    CommandPool(unsafe {
-        devices
-            .logical
-            .device
-            .create_command_pool(&pool_info, None)
-            .expect("Failed to create command pool!")
-    })
-}
-
-pub(crate) fn create_command_buffers(
-    command_pool: &CommandPool,
-    swap_chain: &SwapChain,
-    device: &Device,
-    render_pass: &RenderPass,
-    frame_buffers: &FrameBuffers,
-    objects: &[VulkanObject],
-) -> CommandBuffers {
-    let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
-        .command_pool(**command_pool)
-        .command_buffer_count(swap_chain.images.len() as u32)
-        .level(vk::CommandBufferLevel::PRIMARY);
+/// Upload submission nothing waited for, with what it uses until it completes
+#[derive(Debug)]
+struct PendingUpload {
+    fence: vk::Fence,
+    pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    /// Semaphore the submission waited for, signalled by the transfer queue
+    semaphore: Option<vk::Semaphore>,
+    staging: Option<Buffer>,
+}
 
-    let command_buffers = unsafe {
+impl CommandPool {
+    /// Ends and submits `command_buffer`, allocated from `pool`, to `queue` without waiting
+    /// for it. Work submitted to the queue afterwards is ordered after it by its barriers.
+    ///
+    /// `wait` is waited for before it runs and destroyed once it completes, along with
+    /// `staging`.
+    unsafe fn submit_upload(
+        &self,
+        device: &Device,
+        queue: vk::Queue,
+        pool: vk::CommandPool,
+        command_buffer: vk::CommandBuffer,
+        (wait, signal): (Option<vk::Semaphore>, Option<vk::Semaphore>),
+        staging: Option<Buffer>,
+    ) {
         device
-            .allocate_command_buffers(&command_buffer_allocate_info)
-            .expect("Failed to allocate command render buffers")
+            .end_command_buffer(command_buffer)
+            .expect("Failed to record upload command buffer!");
+
+        let wait_stage = vk::PipelineStageFlags::ALL_COMMANDS;
+        let submit_info = vk::SubmitInfo::default()
+            .wait_semaphores(wait.as_slice())
+            .wait_dst_stage_mask(if wait.is_some() {
+                std::slice::from_ref(&wait_stage)
+            } else {
+                &[]
+            })
+            .command_buffers(std::slice::from_ref(&command_buffer))
+            .signal_semaphores(signal.as_slice());
+
+        let fence = device
+            .create_fence(&vk::FenceCreateInfo::default(), None)
+            .expect("Failed to create upload fence!");
+
+        device
+            .queue_submit(queue, std::slice::from_ref(&submit_info), fence)
+            .expect("Failed to submit upload!");
+
+        self.uploads.borrow_mut().push(PendingUpload {
+            fence,
+            pool,
+            command_buffer,
+            semaphore: wait,
+            staging,
+        });
+    }
+
+    /// Frees the command buffers, semaphores and staging buffers of the uploads that
+    /// completed, which is every upload once the device is idle.
+    ///
+    /// # Safety
+    ///
+    /// `allocator` must be the one the staging buffers were allocated from.
+    pub unsafe fn release_uploads(&self, allocator: &mut Allocator, device: &Device) {
+        let (completed, pending) = mem::take(&mut *self.uploads.borrow_mut())
+            .into_iter()
+            .partition::<Vec<_>, _>(|upload| device.get_fence_status(upload.fence) == Ok(true));
+
+        *self.uploads.borrow_mut() = pending;
+
+        completed.into_iter().for_each(|upload| {
+            device.destroy_fence(upload.fence, None);
+            device.free_command_buffers(upload.pool, std::slice::from_ref(&upload.command_buffer));
+
+            if let Some(semaphore) = upload.semaphore {
+                device.destroy_semaphore(semaphore, None);
+            }
+
+            if let Some(staging) = upload.staging {
+                allocator.free(staging.allocation).unwrap();
+                device.destroy_buffer(staging.buffer, None);
+            }
+        });
+    }
+
+    /// # Safety
+    ///
+    /// No command buffer allocated from either pool may still be in use by the device, and
+    /// the uploads must have been released.
+    pub unsafe fn destroy(&self, device: &Device) {
+        debug_assert!(
+            self.uploads.borrow().is_empty(),
+            "Uploads must be released before the command pool is destroyed"
+        );
+
+        device.destroy_command_pool(self.graphics, None);
+        device.destroy_command_pool(self.transfer, None);
+    }
+}
+
+pub fn create_command_pool(devices: &Devices) -> CommandPool {
+    let create_pool = |queue_family_index: u32| {
+        let pool_info = vk::CommandPoolCreateInfo::default()
+            .queue_family_index(queue_family_index)
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
+
+        unsafe {
+            devices
+                .logical
+                .device
+                .create_command_pool(&pool_info, None)
+                .expect("Failed to create command pool!")
+        }
     };
 
-    let vk::Extent2D { width, height } = swap_chain.extent;
+    let families = devices.physical.queue_families;
+
+    CommandPool {
+        graphics: create_pool(families.graphics),
+        transfer: create_pool(families.transfer),
+        uploads: RefCell::default(),
+    }
+}
+
+/// Command pools and primary command buffers owned by each frame in flight.
+///
+/// A frame's pool is reset and its command buffer re-recorded from the current
+/// scene state every time the frame is rendered.
+#[derive(Debug, Clone)]
+pub(crate) struct FrameCommands {
+    pub pools: Vec<vk::CommandPool>,
+    pub buffers: CommandBuffers,
+}
+
+impl FrameCommands {
+    pub fn new(devices: &Devices) -> Self {
+        let device = &devices.logical.device;
+
+        let pool_info = vk::CommandPoolCreateInfo::default()
+            .queue_family_index(devices.physical.queue_families.graphics)
+            .flags(vk::CommandPoolCreateFlags::TRANSIENT);
+
+        let mut pools = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT);
+        let mut buffers = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT);
+
+        for frame in 0..MAX_FRAMES_IN_FLIGHT {
+            let pool = unsafe {
+                device
+                    .create_command_pool(&pool_info, None)
+                    .expect("Failed to create frame command pool!")
+            };
+
+            let allocate_info = vk::CommandBufferAllocateInfo::default()
+                .command_pool(pool)
+                .command_buffer_count(1)
+                .level(vk::CommandBufferLevel::PRIMARY);
+
+            let buffer = unsafe {
+                device
+                    .allocate_command_buffers(&allocate_info)
+                    .expect("Failed to allocate frame command buffer!")
+            }[0];
+
+            let names = &devices.debug_names;
+            names.name(pool, &format!("frame {frame}/command pool"));
+            names.name(buffer, &format!("frame {frame}/command buffer"));
+
+            pools.push(pool);
+            buffers.push(buffer);
+        }
+
+        Self {
+            pools,
+            buffers: buffers.into(),
+        }
+    }
+
+    /// Resets the pool of `frame`, returning its command buffer to the initial state.
+    ///
+    /// # Safety
+    ///
+    /// The previous submission of `frame` must have completed.
+    pub unsafe fn reset(&self, device: &Device, frame: usize) -> vk::CommandBuffer {
+        device
+            .reset_command_pool(self.pools[frame], vk::CommandPoolResetFlags::empty())
+            .expect("Failed to reset frame command pool!");
+
+        self.buffers[frame]
+    }
+
+    /// # Safety
+    ///
+    /// The device must be idle or no frame may be in flight.
+    pub unsafe fn destroy(&self, device: &Device) {
+        self.pools
+            .iter()
+            .for_each(|pool| device.destroy_command_pool(*pool, None));
+    }
+}
+
+/// Records the draw commands for a single frame from the current object state, returning
+/// the timestamps written around each pass and draw group and what was drawn.
+///
+/// # Safety
+///
+/// `command_buffer` must be in the initial state, `image_index` must be the
+/// swap chain image acquired for this frame and `frame` its slot among the frames in flight.
+/// `capture`, when given, must hold the whole swap chain image, which is copied into it.
+pub(crate) unsafe fn record_command_buffer(
+    vulkan: &Vulkan,
+    command_buffer: vk::CommandBuffer,
+    image_index: usize,
+    frame: usize,
+    shadow_matrices: &[Matrix4<f32>],
+    capture: Option<vk::Buffer>,
+) -> (GpuMarks, DrawCounts) {
+    let device = &vulkan.devices.logical.device;
+    let extent = vulkan.resources.extent;
+    let objects = &vulkan.objects;
+    let materials = &vulkan.materials;
+    let scene_set = vulkan.scene.sets[image_index];
+
+    let vk::Extent2D { width, height } = extent;
 
     let view_port = vk::Viewport::default()
         .x(0.)
@@ -68,9 +268,10 @@ pub(crate) fn create_command_buffers(
 
     let scissor = vk::Rect2D::default()
         .offset(vk::Offset2D::default())
-        .extent(swap_chain.extent);
+        .extent(extent);
 
-    let begin_info = vk::CommandBufferBeginInfo::default();
+    let begin_info =
+        vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 
     let clear_values = [
         vk::ClearValue {
@@ -83,51 +284,301 @@ pub(crate) fn create_command_buffers(
         },
     ];
 
-    unsafe {
-        for i in 0..swap_chain.images.len() {
-            device
-                .begin_command_buffer(command_buffers[i], &begin_info)
-                .expect("Failed to begin recording command buffer!");
-
-            let render_pass_begin_info = vk::RenderPassBeginInfo::default()
-                .render_pass(render_pass.0)
-                .framebuffer(frame_buffers[i])
-                .render_area(scissor)
-                .clear_values(&clear_values);
-
-            device.cmd_begin_render_pass(
-                command_buffers[i],
-                &render_pass_begin_info,
-                vk::SubpassContents::INLINE,
+    device
+        .begin_command_buffer(command_buffer, &begin_info)
+        .expect("Failed to begin recording command buffer!");
+
+    let mut marks = vulkan.profiler.begin(device, command_buffer, frame);
+    let mut counts = DrawCounts::default();
+    let names = &vulkan.devices.debug_names;
+
+    if !vulkan.dispatches.before_render.is_empty() {
+        names.begin_label(command_buffer, "compute");
+        vulkan
+            .dispatches
+            .record_before_render(device, command_buffer);
+        names.end_label(command_buffer);
+        marks.mark(device, command_buffer, "compute");
+    }
+
+    names.begin_label(command_buffer, "shadows");
+    vulkan.shadow_maps.record(
+        device,
+        command_buffer,
+        objects,
+        materials,
+        shadow_matrices,
+        &mut counts,
+    );
+    names.end_label(command_buffer);
+    marks.mark(device, command_buffer, "shadows");
+
+    let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+        .render_pass(vulkan.render_pass.0)
+        .framebuffer(vulkan.frame_buffer)
+        .render_area(scissor)
+        .clear_values(&clear_values);
+
+    device.cmd_begin_render_pass(
+        command_buffer,
+        &render_pass_begin_info,
+        vk::SubpassContents::INLINE,
+    );
+
+    device.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&view_port));
+
+    device.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&scissor));
+
+    if let Some(skybox) = &vulkan.skybox_pipeline {
+        names.begin_label(command_buffer, "skybox");
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            skybox.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            skybox.layout,
+            0,
+            std::slice::from_ref(&scene_set),
+            &[],
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            skybox.layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            any_as_u8_slice(&vulkan.ubo.skybox_view_projection()),
+        );
+        device.cmd_draw(command_buffer, 36, 1, 0, 0);
+        counts.draw(36, ModelTopology::TriangleList);
+        names.end_label(command_buffer);
+        marks.mark(device, command_buffer, "skybox");
+    }
+
+    let layout = vulkan.pipeline_layouts.layout;
+    let debug_views = &vulkan.debug_views;
+    let mut transparent = false;
+
+    names.begin_label(command_buffer, "opaque");
+    draw_order(objects, materials, &vulkan.ubo.camera_position())
+        .into_iter()
+        .map(|index| &objects[index])
+        .for_each(|object| {
+            let material = &materials[object.material];
+
+            // Transparent objects are drawn after every opaque one
+            if !transparent && material.material.blend_mode.is_transparent() {
+                transparent = true;
+                names.end_label(command_buffer);
+                marks.mark(device, command_buffer, "opaque");
+                names.begin_label(command_buffer, "transparent");
+            }
+
+            names.begin_label(command_buffer, &object.label);
+
+            // Every pipeline shares the layout, so the model can always be pushed
+            device.cmd_push_constants(
+                command_buffer,
+                layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                any_as_u8_slice(&object.model),
             );
+            let view = debug_views.view_of(object);
+
+            // Debug views replace the material's pipeline, but keep its descriptor set bound
+            let pipeline = debug_view::fill_view(view, vulkan.devices.physical.wireframe)
+                .and_then(|view| debug_views.pipeline(view, object.topology.into()))
+                .unwrap_or(material.pipeline(object.topology).features.pipeline);
+
+            let bindings = ObjectBindings {
+                pipeline,
+                layout,
+                sets: [
+                    object.descriptors.sets[image_index],
+                    material.set,
+                    scene_set,
+                ],
+            };
+            let count =
+                bind_index_and_vertex_buffers(device, command_buffer, object, &bindings, &[0_u64]);
+            counts.draw(count, object.topology);
+
+            if view == DebugView::Normals {
+                debug_views.draw_normals(device, command_buffer, object, &mut counts);
+            }
+
+            names.end_label(command_buffer);
+        });
+
+    names.end_label(command_buffer);
+    marks.mark(
+        device,
+        command_buffer,
+        if transparent { "transparent" } else { "opaque" },
+    );
 
-            device.cmd_set_viewport(command_buffers[i], 0, std::slice::from_ref(&view_port));
-
-            device.cmd_set_scissor(command_buffers[i], 0, std::slice::from_ref(&scissor));
-
-            objects.iter().for_each(|object| {
-                if object.shader == Shader::PushConstant {
-                    let push = any_as_u8_slice(&object.model);
-                    device.cmd_push_constants(
-                        command_buffers[i],
-                        object.graphics_pipeline.features.layout,
-                        vk::ShaderStageFlags::VERTEX,
-                        0,
-                        push,
-                    )
-                }
-                bind_index_and_vertex_buffers(object, device, command_buffers[i], &[0_u64], i)
-            });
-
-            device.cmd_end_render_pass(command_buffers[i]);
-
-            device
-                .end_command_buffer(command_buffers[i])
-                .expect("Failed to record command buffer!");
-        }
+    names.begin_label(command_buffer, "particles");
+    vulkan.particles.record(
+        device,
+        command_buffer,
+        frame,
+        &ParticleView::new(
+            vulkan.ubo.view_projection(),
+            vulkan.ubo.camera_right(),
+            vulkan.ubo.camera_up(),
+        ),
+        &vulkan.sprites,
+        &mut counts,
+    );
+    names.end_label(command_buffer);
+
+    names.begin_label(command_buffer, "debug lines");
+    vulkan.debug_lines.record(
+        device,
+        command_buffer,
+        frame,
+        &vulkan.ubo.view_projection(),
+        &mut counts,
+    );
+    names.end_label(command_buffer);
+
+    names.begin_label(command_buffer, "text");
+    vulkan.text.record_world(
+        device,
+        command_buffer,
+        frame,
+        layout,
+        &vulkan.ubo.view_projection(),
+        &mut counts,
+    );
+    names.end_label(command_buffer);
+
+    device.cmd_end_render_pass(command_buffer);
+
+    if !vulkan.dispatches.after_render.is_empty() {
+        names.begin_label(command_buffer, "compute");
+        vulkan
+            .dispatches
+            .record_after_render(device, command_buffer);
+        names.end_label(command_buffer);
+        marks.mark(device, command_buffer, "compute");
     }
 
-    command_buffers.into()
+    names.begin_label(command_buffer, "post process");
+    vulkan.post_processor.record(
+        &vulkan.devices,
+        command_buffer,
+        image_index,
+        &mut counts,
+        |counts| {
+            names.begin_label(command_buffer, "tilemaps");
+            vulkan.tilemaps.record(
+                device,
+                command_buffer,
+                layout,
+                &vulkan.sprites.projection(vulkan.swap_chain.extent),
+                &vulkan.sprites,
+                counts,
+            );
+            names.end_label(command_buffer);
+
+            names.begin_label(command_buffer, "sprites");
+            vulkan.sprites.record(
+                device,
+                command_buffer,
+                frame,
+                layout,
+                vulkan.swap_chain.extent,
+                counts,
+            );
+            names.end_label(command_buffer);
+
+            names.begin_label(command_buffer, "text");
+            vulkan.text.record_screen(
+                device,
+                command_buffer,
+                frame,
+                layout,
+                vulkan.swap_chain.extent,
+                counts,
+            );
+            names.end_label(command_buffer);
+
+            names.begin_label(command_buffer, "gui");
+            vulkan.gui.record(
+                device,
+                command_buffer,
+                frame,
+                layout,
+                vulkan.swap_chain.extent,
+                counts,
+            );
+            names.end_label(command_buffer);
+        },
+    );
+    names.end_label(command_buffer);
+    marks.mark(device, command_buffer, "post process");
+
+    if let Some(buffer) = capture {
+        names.begin_label(command_buffer, "capture");
+        capture::record_copy(
+            device,
+            command_buffer,
+            vulkan.swap_chain.images[image_index],
+            vulkan.swap_chain.extent,
+            buffer,
+        );
+        names.end_label(command_buffer);
+    }
+
+    device
+        .end_command_buffer(command_buffer)
+        .expect("Failed to record command buffer!");
+
+    (marks, counts)
+}
+
+/// Indices of the visible objects in the order they should be drawn.
+///
+/// Opaque objects keep their insertion order and come first. Transparent objects
+/// follow, sorted back to front by the distance from the camera to their centre.
+pub(crate) fn draw_order(
+    objects: &[VulkanObject],
+    materials: &[VulkanMaterial],
+    camera_position: &Point3<f32>,
+) -> Vec<usize> {
+    sort_draws(
+        objects
+            .iter()
+            .enumerate()
+            .filter(|(_, object)| object.visible)
+            .map(|(index, object)| {
+                let centre = object.model.transform_point(&object.centre);
+                (
+                    index,
+                    materials[object.material].material.blend_mode,
+                    nalgebra::distance_squared(&centre, camera_position),
+                )
+            }),
+    )
+}
+
+/// Orders draws given as their index, blend mode and squared distance from the camera, as
+/// [`draw_order`] does. Transparent draws at the same distance keep their order.
+fn sort_draws(draws: impl Iterator<Item = (usize, BlendMode, f32)>) -> Vec<usize> {
+    let (mut transparent, opaque): (Vec<_>, Vec<_>) =
+        draws.partition(|(_, blend_mode, _)| blend_mode.is_transparent());
+
+    transparent.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+
+    opaque
+        .into_iter()
+        .chain(transparent)
+        .map(|(index, ..)| index)
+        .collect()
 }
 
 pub fn begin_single_time_command(
@@ -167,54 +618,282 @@ pub fn end_single_time_command(
 
     let submit_info = vk::SubmitInfo::default().command_buffers(&buffers_to_submit);
 
+    // Waiting on this submission alone leaves other work on the queue running
     unsafe {
         device
             .end_command_buffer(buffer)
             .expect("Failed to record Command Buffer at Ending!");
+
+        let fence = device
+            .create_fence(&vk::FenceCreateInfo::default(), None)
+            .expect("Failed to create single time command fence!");
+
         device
-            .queue_submit(
-                submit_queue,
-                std::slice::from_ref(&submit_info),
-                vk::Fence::null(),
-            )
+            .queue_submit(submit_queue, std::slice::from_ref(&submit_info), fence)
             .expect("Failed to Queue Submit!");
         device
-            .queue_wait_idle(submit_queue)
-            .expect("Failed to wait Queue idle!");
+            .wait_for_fences(std::slice::from_ref(&fence), true, u64::MAX)
+            .expect("Failed to wait for single time command!");
+
+        device.destroy_fence(fence, None);
         device.free_command_buffers(*pool, &buffers_to_submit);
     }
 }
 
+/// Resource written by an upload, and how the graphics queue goes on to use it
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Handoff {
+    Buffer {
+        buffer: vk::Buffer,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    },
+    /// An image written in `TRANSFER_DST_OPTIMAL` and moved to `layout`
+    Image {
+        image: vk::Image,
+        range: vk::ImageSubresourceRange,
+        layout: vk::ImageLayout,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    },
+}
+
+impl Handoff {
+    const fn dst_stage(&self) -> vk::PipelineStageFlags {
+        match self {
+            Self::Buffer { dst_stage, .. } | Self::Image { dst_stage, .. } => *dst_stage,
+        }
+    }
+
+    const fn dst_access(&self) -> vk::AccessFlags {
+        match self {
+            Self::Buffer { dst_access, .. } | Self::Image { dst_access, .. } => *dst_access,
+        }
+    }
+
+    /// Records a barrier for the resource with the given access and family transfer
+    unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        stages: (vk::PipelineStageFlags, vk::PipelineStageFlags),
+        access: (vk::AccessFlags, vk::AccessFlags),
+        families: (u32, u32),
+    ) {
+        let (src_stage, dst_stage) = stages;
+        let (src_access, dst_access) = access;
+        let (src_family, dst_family) = families;
+
+        match *self {
+            Self::Buffer { buffer, .. } => {
+                let barrier = vk::BufferMemoryBarrier::default()
+                    .src_access_mask(src_access)
+                    .dst_access_mask(dst_access)
+                    .src_queue_family_index(src_family)
+                    .dst_queue_family_index(dst_family)
+                    .buffer(buffer)
+                    .offset(0)
+                    .size(vk::WHOLE_SIZE);
+
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    src_stage,
+                    dst_stage,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    std::slice::from_ref(&barrier),
+                    &[],
+                );
+            }
+            Self::Image {
+                image,
+                range,
+                layout,
+                ..
+            } => {
+                let barrier = vk::ImageMemoryBarrier::default()
+                    .src_access_mask(src_access)
+                    .dst_access_mask(dst_access)
+                    .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .new_layout(layout)
+                    .src_queue_family_index(src_family)
+                    .dst_queue_family_index(dst_family)
+                    .image(image)
+                    .subresource_range(range);
+
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    src_stage,
+                    dst_stage,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    std::slice::from_ref(&barrier),
+                );
+            }
+        }
+    }
+}
+
+/// Records `record` on the transfer queue, then makes what it wrote available to the graphics
+/// queue as described by `handoff`. `staging` is freed once the upload completes.
+///
+/// Nothing waits for the upload on the CPU. With a separate transfer family, ownership is
+/// released on the transfer queue and acquired on the graphics queue, which waits for the
+/// transfer through a semaphore. Otherwise both are the same queue, which runs the upload
+/// before any frame submitted after it.
+pub(crate) fn upload(
+    devices: &Devices,
+    command_pool: &CommandPool,
+    handoff: Handoff,
+    staging: Buffer,
+    record: impl FnOnce(vk::CommandBuffer),
+) {
+    let device = &devices.logical.device;
+    let families = devices.physical.queue_families;
+    let queues = &devices.logical.queues;
+
+    let command_buffer = begin_single_time_command(device, &command_pool.transfer);
+
+    record(command_buffer);
+
+    unsafe {
+        if families.separate_transfer() {
+            handoff.record(
+                device,
+                command_buffer,
+                (
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                ),
+                (vk::AccessFlags::TRANSFER_WRITE, vk::AccessFlags::empty()),
+                (families.transfer, families.graphics),
+            );
+        } else {
+            handoff.record(
+                device,
+                command_buffer,
+                (vk::PipelineStageFlags::TRANSFER, handoff.dst_stage()),
+                (vk::AccessFlags::TRANSFER_WRITE, handoff.dst_access()),
+                (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED),
+            );
+        }
+    }
+
+    let transferred = families.separate_transfer().then(|| unsafe {
+        device
+            .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+            .expect("Failed to create upload semaphore!")
+    });
+
+    unsafe {
+        command_pool.submit_upload(
+            device,
+            queues.transfer,
+            command_pool.transfer,
+            command_buffer,
+            (None, transferred),
+            Some(staging),
+        );
+    }
+
+    if families.separate_transfer() {
+        let command_buffer = begin_single_time_command(device, &command_pool.graphics);
+
+        unsafe {
+            handoff.record(
+                device,
+                command_buffer,
+                (vk::PipelineStageFlags::TOP_OF_PIPE, handoff.dst_stage()),
+                (vk::AccessFlags::empty(), handoff.dst_access()),
+                (families.transfer, families.graphics),
+            );
+
+            command_pool.submit_upload(
+                device,
+                queues.graphics,
+                command_pool.graphics,
+                command_buffer,
+                (transferred, None),
+                None,
+            );
+        }
+    }
+}
+
+/// Ends and submits `command_buffer`, allocated from the graphics pool, without waiting for
+/// it, like the uploads it follows
+pub(crate) fn submit_graphics_upload(
+    devices: &Devices,
+    command_pool: &CommandPool,
+    command_buffer: vk::CommandBuffer,
+) {
+    unsafe {
+        command_pool.submit_upload(
+            &devices.logical.device,
+            devices.logical.queues.graphics,
+            command_pool.graphics,
+            command_buffer,
+            (None, None),
+            None,
+        );
+    }
+}
+
+/// Pipeline and descriptor sets an object is drawn with
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ObjectBindings {
+    pub pipeline: vk::Pipeline,
+    pub layout: vk::PipelineLayout,
+    /// The object's set for the swap chain image, the material's set and the scene's set
+    pub sets: [vk::DescriptorSet; 3],
+}
+
+/// Binds the pipeline and descriptor sets, then draws the object, returning the number of
+/// vertices or indices drawn.
+///
 /// # Safety
 ///
-/// Expand on safety of this function
+/// `command_buffer` must be recording inside a render pass compatible with
+/// `bindings.pipeline`, which must have been created with `bindings.layout`. The sets must
+/// match that layout, and, like the object's buffers, outlive the command buffer's execution.
 pub(crate) unsafe fn bind_index_and_vertex_buffers(
-    object: &VulkanObject,
     device: &Device,
     command_buffer: vk::CommandBuffer,
+    object: &VulkanObject,
+    bindings: &ObjectBindings,
     offsets: &[vk::DeviceSize],
-    index: usize,
-) {
+) -> u32 {
     device.cmd_bind_pipeline(
         command_buffer,
         vk::PipelineBindPoint::GRAPHICS,
-        object.graphics_pipeline.features.pipeline,
+        bindings.pipeline,
     );
 
-    let descriptor_sets = if object.shader == Shader::PushConstant {
-        std::slice::from_ref(&object.graphics_pipeline.descriptors.sets[0])
-    } else {
-        std::slice::from_ref(&object.graphics_pipeline.descriptors.sets[index])
-    };
     device.cmd_bind_descriptor_sets(
         command_buffer,
         vk::PipelineBindPoint::GRAPHICS,
-        object.graphics_pipeline.features.layout,
+        bindings.layout,
         0,
-        descriptor_sets,
+        &bindings.sets,
         &[],
     );
 
+    draw_geometry(object, device, command_buffer, offsets)
+}
+
+/// Binds the object's vertex and index buffers and draws it with the bound pipeline, returning
+/// the number of vertices or indices drawn.
+///
+/// # Safety
+///
+/// `command_buffer` must be recording inside a render pass with a compatible pipeline bound.
+pub(crate) unsafe fn draw_geometry(
+    object: &VulkanObject,
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    offsets: &[vk::DeviceSize],
+) -> u32 {
     device.cmd_bind_vertex_buffers(
         command_buffer,
         0,
@@ -230,21 +909,42 @@ pub(crate) unsafe fn bind_index_and_vertex_buffers(
             vk::IndexType::UINT16,
         );
 
-        device.cmd_draw_indexed(
-            command_buffer,
-            object.vertices_and_indices.indices.len() as u32,
-            1,
-            0,
-            0,
-            0,
-        );
+        let count = object.vertices_and_indices.indices.len() as u32;
+        device.cmd_draw_indexed(command_buffer, count, 1, 0, 0, 0);
+        count
     } else {
-        device.cmd_draw(
-            command_buffer,
-            object.vertices_and_indices.vertices.len() as u32,
-            1,
-            0,
-            0,
-        );
+        let count = object.vertices_and_indices.vertices.len() as u32;
+        device.cmd_draw(command_buffer, count, 1, 0, 0);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opaque_draws_come_first_in_insertion_order() {
+        let draws = [
+            (0, BlendMode::AlphaBlend, 1.),
+            (1, BlendMode::Opaque, 9.),
+            (2, BlendMode::Additive, 4.),
+            (3, BlendMode::AlphaTest(0.5), 1.),
+            (4, BlendMode::Premultiplied, 16.),
+        ];
+
+        assert_eq!(sort_draws(draws.into_iter()), [1, 3, 4, 2, 0]);
+    }
+
+    #[test]
+    fn test_transparent_draws_at_equal_depths_keep_their_order() {
+        let draws = [
+            (0, BlendMode::AlphaBlend, 4.),
+            (1, BlendMode::AlphaBlend, 9.),
+            (2, BlendMode::Additive, 4.),
+            (3, BlendMode::AlphaBlend, 4.),
+        ];
+
+        assert_eq!(sort_draws(draws.into_iter()), [1, 0, 2, 3]);
     }
 }
@@ -0,0 +1,1137 @@
+use crate::{
+    buffer::Buffer,
+    command_buffer::{self, CommandPool},
+    device::Devices,
+    gpu::{self, DeviceInfo, DeviceSelection},
+};
+use ash::{vk, Device, Entry, Instance};
+use gpu_allocator::{
+    vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator, AllocatorCreateDesc},
+    MemoryLocation,
+};
+use std::{io, mem, path::Path};
+
+/// Where a [`StorageBuffer`] lives
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BufferLocation {
+    /// Device local memory, the fastest for shaders. It is written and read back through a
+    /// staging buffer.
+    #[default]
+    Device,
+    /// Host visible and cached memory, for results read back after every dispatch
+    Host,
+}
+
+impl From<BufferLocation> for MemoryLocation {
+    fn from(location: BufferLocation) -> Self {
+        match location {
+            BufferLocation::Device => Self::GpuOnly,
+            BufferLocation::Host => Self::GpuToCpu,
+        }
+    }
+}
+
+/// Buffer compute shaders read and write, which can also be bound as a vertex or index buffer
+#[derive(Debug)]
+pub struct StorageBuffer {
+    buffer: Buffer,
+    size: vk::DeviceSize,
+}
+
+impl StorageBuffer {
+    #[must_use]
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer.buffer
+    }
+
+    /// Size in bytes
+    #[must_use]
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+}
+
+/// 2D image compute shaders read and write, kept in the general layout
+#[derive(Debug)]
+pub struct StorageImage {
+    image: vk::Image,
+    view: vk::ImageView,
+    allocation: Allocation,
+    format: vk::Format,
+    extent: vk::Extent2D,
+}
+
+impl StorageImage {
+    #[must_use]
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    #[must_use]
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    #[must_use]
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+}
+
+/// Kind of resource a compute shader declares at a binding of descriptor set 0
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComputeBinding {
+    StorageBuffer,
+    StorageImage,
+}
+
+impl From<ComputeBinding> for vk::DescriptorType {
+    fn from(binding: ComputeBinding) -> Self {
+        match binding {
+            ComputeBinding::StorageBuffer => Self::STORAGE_BUFFER,
+            ComputeBinding::StorageImage => Self::STORAGE_IMAGE,
+        }
+    }
+}
+
+/// Resource bound to a [`ComputeBinding`] of the same kind
+#[derive(Clone, Copy, Debug)]
+pub enum ComputeResource<'a> {
+    Buffer(&'a StorageBuffer),
+    Image(&'a StorageImage),
+}
+
+impl ComputeResource<'_> {
+    const fn binding(&self) -> ComputeBinding {
+        match self {
+            Self::Buffer(_) => ComputeBinding::StorageBuffer,
+            Self::Image(_) => ComputeBinding::StorageImage,
+        }
+    }
+}
+
+/// Compute shader with its layout and a descriptor set holding its bindings, in order from
+/// binding 0 of set 0
+#[derive(Debug)]
+pub struct ComputePipeline {
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    set_layout: vk::DescriptorSetLayout,
+    pool: vk::DescriptorPool,
+    set: vk::DescriptorSet,
+    bindings: Vec<ComputeBinding>,
+    push_constant_size: u32,
+}
+
+/// When a dispatch queued on the renderer runs within its frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComputeStage {
+    /// Before anything is drawn, so the frame can read the results as vertices, indices or
+    /// shader resources
+    BeforeRender,
+    /// After the scene is drawn and before post processing
+    AfterRender,
+}
+
+/// One run of a [`ComputePipeline`] over a grid of workgroups
+#[derive(Clone, Debug)]
+pub struct Dispatch {
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    set: vk::DescriptorSet,
+    push_constant_size: u32,
+    push_constants: Vec<u8>,
+    groups: [u32; 3],
+}
+
+impl Dispatch {
+    #[must_use]
+    pub fn new(pipeline: &ComputePipeline, groups: [u32; 3]) -> Self {
+        Self {
+            pipeline: pipeline.pipeline,
+            layout: pipeline.layout,
+            set: pipeline.set,
+            push_constant_size: pipeline.push_constant_size,
+            push_constants: Vec::new(),
+            groups,
+        }
+    }
+
+    /// Enough workgroups of `local_size` invocations to cover `items` in each dimension
+    #[must_use]
+    pub fn covering(pipeline: &ComputePipeline, items: [u32; 3], local_size: [u32; 3]) -> Self {
+        Self::new(pipeline, group_counts(items, local_size))
+    }
+
+    /// Pushes `constants` before dispatching. They must fill the pipeline's push constant
+    /// range.
+    #[must_use]
+    pub fn push_constants<T: Copy>(mut self, constants: &T) -> Self {
+        let bytes = unsafe { crate::any_as_u8_slice(constants) };
+        assert_eq!(
+            bytes.len(),
+            self.push_constant_size as usize,
+            "Push constants do not match the compute pipeline's push constant size"
+        );
+        self.push_constants = bytes.to_vec();
+        self
+    }
+
+    /// Records the dispatch followed by a barrier making its writes visible to every later
+    /// command.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording outside of a render pass, and the pipeline and the
+    /// resources bound to it must stay alive until it completes.
+    pub unsafe fn record(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.layout,
+            0,
+            std::slice::from_ref(&self.set),
+            &[],
+        );
+
+        if !self.push_constants.is_empty() {
+            device.cmd_push_constants(
+                command_buffer,
+                self.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &self.push_constants,
+            );
+        }
+
+        let [x, y, z] = self.groups;
+        device.cmd_dispatch(command_buffer, x, y, z);
+
+        memory_barrier(
+            device,
+            command_buffer,
+            (
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+            ),
+            (
+                vk::AccessFlags::SHADER_WRITE,
+                vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            ),
+        );
+    }
+}
+
+/// Workgroups of `local_size` needed to cover `items`, rounding up
+fn group_counts(items: [u32; 3], local_size: [u32; 3]) -> [u32; 3] {
+    std::array::from_fn(|axis| items[axis].div_ceil(local_size[axis].max(1)))
+}
+
+/// Dispatches queued on the renderer for the next recorded frame
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FrameDispatches {
+    pub before_render: Vec<Dispatch>,
+    pub after_render: Vec<Dispatch>,
+}
+
+impl FrameDispatches {
+    pub fn push(&mut self, stage: ComputeStage, dispatch: Dispatch) {
+        match stage {
+            ComputeStage::BeforeRender => self.before_render.push(dispatch),
+            ComputeStage::AfterRender => self.after_render.push(dispatch),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.before_render.clear();
+        self.after_render.clear();
+    }
+
+    /// Records the dispatches of the frame that run before anything is drawn, once the
+    /// previous frames no longer read what they write.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording outside of a render pass.
+    pub unsafe fn record_before_render(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        if self.before_render.is_empty() {
+            return;
+        }
+
+        memory_barrier(
+            device,
+            command_buffer,
+            (
+                vk::PipelineStageFlags::ALL_GRAPHICS,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+            ),
+            (vk::AccessFlags::empty(), vk::AccessFlags::empty()),
+        );
+
+        self.before_render
+            .iter()
+            .for_each(|dispatch| dispatch.record(device, command_buffer));
+    }
+
+    /// Records the dispatches of the frame that run after the scene, once its attachments
+    /// were written.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording after the scene render pass ended.
+    pub unsafe fn record_after_render(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        if self.after_render.is_empty() {
+            return;
+        }
+
+        memory_barrier(
+            device,
+            command_buffer,
+            (
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+            ),
+            (
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            ),
+        );
+
+        self.after_render
+            .iter()
+            .for_each(|dispatch| dispatch.record(device, command_buffer));
+    }
+}
+
+unsafe fn memory_barrier(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    stages: (vk::PipelineStageFlags, vk::PipelineStageFlags),
+    access: (vk::AccessFlags, vk::AccessFlags),
+) {
+    let barrier = vk::MemoryBarrier::default()
+        .src_access_mask(access.0)
+        .dst_access_mask(access.1);
+
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        stages.0,
+        stages.1,
+        vk::DependencyFlags::empty(),
+        std::slice::from_ref(&barrier),
+        &[],
+        &[],
+    );
+}
+
+/// Reads a compiled SPIR-V shader, such as one built with `glslc`
+pub fn read_spirv(path: impl AsRef<Path>) -> io::Result<Vec<u32>> {
+    ash::util::read_spv(&mut std::fs::File::open(path)?)
+}
+
+/// Creates compute resources and runs dispatches on a device, either the renderer's from
+/// [`crate::Vulkan::compute`] or a headless one from [`ComputeContext::device`]
+pub struct ComputeDevice<'a> {
+    devices: &'a Devices,
+    allocator: &'a mut Allocator,
+    command_pool: &'a CommandPool,
+}
+
+impl<'a> ComputeDevice<'a> {
+    pub(crate) fn new(
+        devices: &'a Devices,
+        allocator: &'a mut Allocator,
+        command_pool: &'a CommandPool,
+    ) -> Self {
+        Self {
+            devices,
+            allocator,
+            command_pool,
+        }
+    }
+
+    fn device(&self) -> &Device {
+        &self.devices.logical.device
+    }
+
+    /// Buffer of `size` bytes, zeroed only when it is in host memory
+    pub fn create_buffer(
+        &mut self,
+        size: vk::DeviceSize,
+        location: BufferLocation,
+        name: &str,
+    ) -> StorageBuffer {
+        let usage = vk::BufferUsageFlags::STORAGE_BUFFER
+            | vk::BufferUsageFlags::VERTEX_BUFFER
+            | vk::BufferUsageFlags::INDEX_BUFFER
+            | vk::BufferUsageFlags::TRANSFER_SRC
+            | vk::BufferUsageFlags::TRANSFER_DST;
+
+        let mut buffer = self.allocate_buffer(size, usage, location.into(), name);
+
+        if let Some(mapped) = buffer.allocation.mapped_slice_mut() {
+            mapped.fill(0);
+        }
+
+        StorageBuffer { buffer, size }
+    }
+
+    /// Buffer holding `data`
+    pub fn create_buffer_with<T: Copy>(
+        &mut self,
+        data: &[T],
+        location: BufferLocation,
+        name: &str,
+    ) -> StorageBuffer {
+        let buffer = self.create_buffer(mem::size_of_val(data) as vk::DeviceSize, location, name);
+        self.write(&buffer, data);
+        buffer
+    }
+
+    /// Copies `data` to the start of `buffer`, waiting for the device when the buffer is
+    /// device local. The buffer must not be in use.
+    pub fn write<T: Copy>(&mut self, buffer: &StorageBuffer, data: &[T]) {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(data.as_ptr().cast::<u8>(), mem::size_of_val(data))
+        };
+        assert!(
+            bytes.len() as vk::DeviceSize <= buffer.size,
+            "Data does not fit in the storage buffer"
+        );
+
+        if bytes.is_empty() {
+            return;
+        }
+
+        let mut staging = self.allocate_buffer(
+            bytes.len() as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            MemoryLocation::CpuToGpu,
+            "compute/staging",
+        );
+        staging
+            .allocation
+            .mapped_slice_mut()
+            .expect("Staging buffer is not host visible")[..bytes.len()]
+            .copy_from_slice(bytes);
+
+        let region = vk::BufferCopy::default().size(bytes.len() as vk::DeviceSize);
+
+        self.submit(|device, command_buffer| unsafe {
+            device.cmd_copy_buffer(
+                command_buffer,
+                staging.buffer,
+                buffer.buffer.buffer,
+                std::slice::from_ref(&region),
+            );
+            memory_barrier(
+                device,
+                command_buffer,
+                (
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                ),
+                (
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+                ),
+            );
+        });
+
+        self.free_buffer(staging);
+    }
+
+    /// Contents of `buffer` as `T`s, once every submitted dispatch writing it has completed
+    pub fn read<T: Copy + Default>(&mut self, buffer: &StorageBuffer) -> Vec<T> {
+        let mut data = vec![T::default(); buffer.size as usize / mem::size_of::<T>().max(1)];
+        let size = mem::size_of_val(data.as_slice());
+
+        let copy = |source: &[u8], data: &mut [T]| unsafe {
+            std::ptr::copy_nonoverlapping(source.as_ptr(), data.as_mut_ptr().cast::<u8>(), size);
+        };
+
+        if let Some(mapped) = buffer.buffer.allocation.mapped_slice() {
+            self.wait_idle();
+            copy(&mapped[..size], &mut data);
+            return data;
+        }
+
+        let staging = self.allocate_buffer(
+            buffer.size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuToCpu,
+            "compute/readback",
+        );
+
+        let region = vk::BufferCopy::default().size(buffer.size);
+
+        self.submit(|device, command_buffer| unsafe {
+            device.cmd_copy_buffer(
+                command_buffer,
+                buffer.buffer.buffer,
+                staging.buffer,
+                std::slice::from_ref(&region),
+            );
+            host_read_barrier(device, command_buffer, vk::PipelineStageFlags::TRANSFER);
+        });
+
+        copy(
+            &staging
+                .allocation
+                .mapped_slice()
+                .expect("Readback buffer is not host visible")[..size],
+            &mut data,
+        );
+
+        self.free_buffer(staging);
+
+        data
+    }
+
+    /// Device local image of `format` in the general layout, ready for shaders to write
+    pub fn create_image(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        name: &str,
+    ) -> StorageImage {
+        let extent = vk::Extent2D { width, height };
+
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(extent.into())
+            .mip_levels(1)
+            .array_layers(1)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(
+                vk::ImageUsageFlags::STORAGE
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_SRC
+                    | vk::ImageUsageFlags::TRANSFER_DST,
+            )
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let range = colour_range();
+
+        let (image, allocation, view) = unsafe {
+            let device = self.device();
+            let image = device
+                .create_image(&image_info, None)
+                .expect("Failed to create storage image!");
+            let requirements = device.get_image_memory_requirements(image);
+
+            let allocation = self
+                .allocator
+                .allocate(&AllocationCreateDesc {
+                    name,
+                    requirements,
+                    location: MemoryLocation::GpuOnly,
+                    linear: false,
+                    allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+                })
+                .expect("Could not allocate storage image memory");
+
+            let device = self.device();
+            device
+                .bind_image_memory(image, allocation.memory(), allocation.offset())
+                .expect("Could not bind storage image memory");
+
+            let view_info = vk::ImageViewCreateInfo::default()
+                .image(image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(format)
+                .subresource_range(range);
+            let view = device
+                .create_image_view(&view_info, None)
+                .expect("Failed to create storage image view!");
+
+            (image, allocation, view)
+        };
+
+        self.devices.debug_names.name(image, name);
+
+        self.submit(|device, command_buffer| unsafe {
+            let barrier = vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::empty())
+                .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::GENERAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(range);
+
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&barrier),
+            );
+        });
+
+        StorageImage {
+            image,
+            view,
+            allocation,
+            format,
+            extent,
+        }
+    }
+
+    /// Texels of `image`, tightly packed row by row, once every submitted dispatch writing
+    /// it has completed
+    pub fn read_image(&mut self, image: &StorageImage) -> Vec<u8> {
+        let vk::Extent2D { width, height } = image.extent;
+        let size = image
+            .allocation
+            .size()
+            .min(width as vk::DeviceSize * height as vk::DeviceSize * texel_size(image.format));
+
+        let staging = self.allocate_buffer(
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuToCpu,
+            "compute/image readback",
+        );
+
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .layer_count(1),
+            )
+            .image_extent(image.extent.into());
+
+        self.submit(|device, command_buffer| unsafe {
+            device.cmd_copy_image_to_buffer(
+                command_buffer,
+                image.image,
+                vk::ImageLayout::GENERAL,
+                staging.buffer,
+                std::slice::from_ref(&region),
+            );
+            host_read_barrier(device, command_buffer, vk::PipelineStageFlags::TRANSFER);
+        });
+
+        let texels = staging
+            .allocation
+            .mapped_slice()
+            .expect("Readback buffer is not host visible")[..size as usize]
+            .to_vec();
+
+        self.free_buffer(staging);
+
+        texels
+    }
+
+    /// Pipeline running the `main` entry point of the compute shader `spirv`, whose set 0
+    /// declares `bindings` from binding 0 and which takes `push_constant_size` bytes of push
+    /// constants
+    pub fn create_pipeline(
+        &mut self,
+        spirv: &[u32],
+        bindings: &[ComputeBinding],
+        push_constant_size: u32,
+        name: &str,
+    ) -> ComputePipeline {
+        assert!(
+            push_constant_size.is_multiple_of(4),
+            "Push constant size must be a multiple of 4"
+        );
+
+        let device = self.device();
+
+        let layout_bindings = bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| {
+                vk::DescriptorSetLayoutBinding::default()
+                    .binding(index as u32)
+                    .descriptor_type((*binding).into())
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            })
+            .collect::<Vec<_>>();
+
+        let pool_sizes = [ComputeBinding::StorageBuffer, ComputeBinding::StorageImage]
+            .into_iter()
+            .filter_map(|kind| {
+                let count = bindings.iter().filter(|binding| **binding == kind).count();
+                (count > 0).then(|| {
+                    vk::DescriptorPoolSize::default()
+                        .ty(kind.into())
+                        .descriptor_count(count as u32)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(push_constant_size);
+        let push_constant_ranges = if push_constant_size > 0 {
+            std::slice::from_ref(&push_constant_range)
+        } else {
+            &[]
+        };
+
+        unsafe {
+            let set_layout = device
+                .create_descriptor_set_layout(
+                    &vk::DescriptorSetLayoutCreateInfo::default().bindings(&layout_bindings),
+                    None,
+                )
+                .expect("Failed to create compute descriptor set layout!");
+
+            let layout = device
+                .create_pipeline_layout(
+                    &vk::PipelineLayoutCreateInfo::default()
+                        .set_layouts(std::slice::from_ref(&set_layout))
+                        .push_constant_ranges(push_constant_ranges),
+                    None,
+                )
+                .expect("Failed to create compute pipeline layout!");
+
+            let pool = device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo::default()
+                        .max_sets(1)
+                        .pool_sizes(&pool_sizes),
+                    None,
+                )
+                .expect("Failed to create compute descriptor pool!");
+
+            let set = device
+                .allocate_descriptor_sets(
+                    &vk::DescriptorSetAllocateInfo::default()
+                        .descriptor_pool(pool)
+                        .set_layouts(std::slice::from_ref(&set_layout)),
+                )
+                .expect("Failed to allocate compute descriptor set!")[0];
+
+            let module = device
+                .create_shader_module(&vk::ShaderModuleCreateInfo::default().code(spirv), None)
+                .expect("Failed to create compute shader module!");
+
+            let stage = vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::COMPUTE)
+                .module(module)
+                .name(c"main");
+
+            let pipeline = device
+                .create_compute_pipelines(
+                    vk::PipelineCache::null(),
+                    std::slice::from_ref(
+                        &vk::ComputePipelineCreateInfo::default()
+                            .stage(stage)
+                            .layout(layout),
+                    ),
+                    None,
+                )
+                .expect("Failed to create compute pipeline!")[0];
+
+            device.destroy_shader_module(module, None);
+
+            self.devices.debug_names.name(pipeline, name);
+
+            ComputePipeline {
+                pipeline,
+                layout,
+                set_layout,
+                pool,
+                set,
+                bindings: bindings.to_vec(),
+                push_constant_size,
+            }
+        }
+    }
+
+    /// [`ComputeDevice::create_pipeline`] with the shader compiled at `path`
+    pub fn load_pipeline(
+        &mut self,
+        path: impl AsRef<Path>,
+        bindings: &[ComputeBinding],
+        push_constant_size: u32,
+        name: &str,
+    ) -> io::Result<ComputePipeline> {
+        let spirv = read_spirv(path)?;
+        Ok(self.create_pipeline(&spirv, bindings, push_constant_size, name))
+    }
+
+    /// Points the bindings of `pipeline` at `resources`, in binding order. No dispatch of the
+    /// pipeline may be in flight.
+    pub fn bind(&mut self, pipeline: &ComputePipeline, resources: &[ComputeResource]) {
+        assert!(
+            resources
+                .iter()
+                .map(ComputeResource::binding)
+                .eq(pipeline.bindings.iter().copied()),
+            "Resources do not match the compute pipeline's bindings"
+        );
+
+        let infos = resources
+            .iter()
+            .map(|resource| match resource {
+                ComputeResource::Buffer(buffer) => (
+                    Some(
+                        vk::DescriptorBufferInfo::default()
+                            .buffer(buffer.buffer.buffer)
+                            .offset(0)
+                            .range(vk::WHOLE_SIZE),
+                    ),
+                    None,
+                ),
+                ComputeResource::Image(image) => (
+                    None,
+                    Some(
+                        vk::DescriptorImageInfo::default()
+                            .image_view(image.view)
+                            .image_layout(vk::ImageLayout::GENERAL),
+                    ),
+                ),
+            })
+            .collect::<Vec<_>>();
+
+        let writes = infos
+            .iter()
+            .zip(&pipeline.bindings)
+            .enumerate()
+            .map(|(index, ((buffer, image), binding))| {
+                let write = vk::WriteDescriptorSet::default()
+                    .dst_set(pipeline.set)
+                    .dst_binding(index as u32)
+                    .descriptor_type((*binding).into());
+
+                match (buffer, image) {
+                    (Some(buffer), _) => write.buffer_info(std::slice::from_ref(buffer)),
+                    (_, Some(image)) => write.image_info(std::slice::from_ref(image)),
+                    (None, None) => unreachable!("Every resource is a buffer or an image"),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { self.device().update_descriptor_sets(&writes, &[]) };
+    }
+
+    /// Runs `dispatch` and waits for it, after which its results can be read back
+    pub fn run(&mut self, dispatch: &Dispatch) {
+        self.submit(|device, command_buffer| unsafe {
+            dispatch.record(device, command_buffer);
+            host_read_barrier(
+                device,
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+            );
+        });
+    }
+
+    /// # Safety
+    ///
+    /// No dispatch or draw using `buffer` may be in flight.
+    pub unsafe fn destroy_buffer(&mut self, buffer: StorageBuffer) {
+        self.free_buffer(buffer.buffer);
+    }
+
+    /// # Safety
+    ///
+    /// No dispatch or draw using `image` may be in flight.
+    pub unsafe fn destroy_image(&mut self, image: StorageImage) {
+        let device = &self.devices.logical.device;
+        device.destroy_image_view(image.view, None);
+        device.destroy_image(image.image, None);
+        self.allocator.free(image.allocation).unwrap();
+    }
+
+    /// # Safety
+    ///
+    /// No dispatch of `pipeline` may be in flight.
+    pub unsafe fn destroy_pipeline(&mut self, pipeline: ComputePipeline) {
+        let device = self.device();
+        device.destroy_pipeline(pipeline.pipeline, None);
+        device.destroy_pipeline_layout(pipeline.layout, None);
+        device.destroy_descriptor_pool(pipeline.pool, None);
+        device.destroy_descriptor_set_layout(pipeline.set_layout, None);
+    }
+
+    fn allocate_buffer(
+        &mut self,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        location: MemoryLocation,
+        name: &str,
+    ) -> Buffer {
+        let buffer_info = vk::BufferCreateInfo::default()
+            .size(size.max(4))
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        unsafe {
+            let device = &self.devices.logical.device;
+            let buffer = device
+                .create_buffer(&buffer_info, None)
+                .expect("Failed to create buffer");
+
+            let requirements = device.get_buffer_memory_requirements(buffer);
+
+            let allocation = self
+                .allocator
+                .allocate(&AllocationCreateDesc {
+                    name,
+                    requirements,
+                    location,
+                    linear: true,
+                    allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+                })
+                .expect("Could not allocate buffer memory");
+
+            device
+                .bind_buffer_memory(buffer, allocation.memory(), allocation.offset())
+                .expect("Could not bind buffer memory");
+
+            self.devices.debug_names.name(buffer, name);
+
+            Buffer::new(buffer, allocation)
+        }
+    }
+
+    fn free_buffer(&mut self, buffer: Buffer) {
+        self.allocator.free(buffer.allocation).unwrap();
+        unsafe {
+            self.devices
+                .logical
+                .device
+                .destroy_buffer(buffer.buffer, None)
+        };
+    }
+
+    /// Records `record` into a one-off command buffer on the queue compute runs on and
+    /// waits for it to complete
+    fn submit(&self, record: impl FnOnce(&Device, vk::CommandBuffer)) {
+        let device = self.device();
+        let command_buffer =
+            command_buffer::begin_single_time_command(device, &self.command_pool.graphics);
+
+        record(device, command_buffer);
+
+        command_buffer::end_single_time_command(
+            device,
+            self.devices.logical.queues.graphics,
+            &self.command_pool.graphics,
+            command_buffer,
+        );
+    }
+
+    fn wait_idle(&self) {
+        unsafe {
+            self.device()
+                .queue_wait_idle(self.devices.logical.queues.graphics)
+                .expect("Failed to wait for the compute queue");
+        }
+    }
+}
+
+unsafe fn host_read_barrier(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    src_stage: vk::PipelineStageFlags,
+) {
+    memory_barrier(
+        device,
+        command_buffer,
+        (src_stage, vk::PipelineStageFlags::HOST),
+        (
+            vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::HOST_READ,
+        ),
+    );
+}
+
+const fn colour_range() -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+/// Bytes per texel of the formats storage images commonly use
+fn texel_size(format: vk::Format) -> vk::DeviceSize {
+    match format {
+        vk::Format::R8_UNORM | vk::Format::R8_UINT => 1,
+        vk::Format::R16G16B16A16_SFLOAT | vk::Format::R32G32_SFLOAT | vk::Format::R32G32_UINT => 8,
+        vk::Format::R32G32B32A32_SFLOAT | vk::Format::R32G32B32A32_UINT => 16,
+        _ => 4,
+    }
+}
+
+/// Device for compute work without a window or renderer, such as tools and tests. It runs on
+/// any driver with a compute queue, including lavapipe.
+pub struct ComputeContext {
+    /// Dropped before the device its memory belongs to
+    allocator: Option<Allocator>,
+    command_pool: CommandPool,
+    devices: Devices,
+    instance: Instance,
+    _entry: Entry,
+}
+
+impl ComputeContext {
+    /// Opens the device chosen by `selection`, or returns `None` when there is no Vulkan
+    /// driver or no device with a compute queue
+    pub fn new(selection: &DeviceSelection) -> Option<Self> {
+        unsafe {
+            let entry = Entry::load().ok()?;
+            let instance = gpu::create_headless_instance(&entry).ok()?;
+
+            let Some(devices) = Devices::headless(&instance, selection) else {
+                instance.destroy_instance(None);
+                return None;
+            };
+
+            let allocator = Allocator::new(&AllocatorCreateDesc {
+                instance: instance.clone(),
+                device: devices.logical.device.clone(),
+                physical_device: devices.physical.device,
+                debug_settings: Default::default(),
+                buffer_device_address: false,
+                allocation_sizes: Default::default(),
+            })
+            .expect("Failed to create allocator");
+
+            let command_pool = command_buffer::create_command_pool(&devices);
+
+            Some(Self {
+                allocator: Some(allocator),
+                command_pool,
+                devices,
+                instance,
+                _entry: entry,
+            })
+        }
+    }
+
+    /// Creates resources and runs dispatches on the context's device
+    pub fn device(&mut self) -> ComputeDevice<'_> {
+        ComputeDevice::new(
+            &self.devices,
+            self.allocator
+                .as_mut()
+                .expect("The allocator lives as long as the context"),
+            &self.command_pool,
+        )
+    }
+
+    #[must_use]
+    pub fn info(&self) -> &DeviceInfo {
+        &self.devices.physical.info
+    }
+}
+
+impl Drop for ComputeContext {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.devices.logical.device;
+            device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle state");
+
+            drop(self.allocator.take());
+
+            self.command_pool.destroy(device);
+
+            device.destroy_device(None);
+
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compiled from
+    ///
+    /// ```glsl
+    /// #version 450
+    /// layout(local_size_x = 1) in;
+    /// layout(set = 0, binding = 0) buffer Data { uint values[]; };
+    /// void main() { values[gl_GlobalInvocationID.x] *= 2u; }
+    /// ```
+    const DOUBLE: &[u32] = &[
+        0x07230203, 0x00010000, 0x00000000, 0x00000018, 0x00000000, 0x00020011, 0x00000001,
+        0x0003000e, 0x00000000, 0x00000001, 0x0006000f, 0x00000005, 0x00000001, 0x6e69616d,
+        0x00000000, 0x00000002, 0x00060010, 0x00000001, 0x00000011, 0x00000001, 0x00000001,
+        0x00000001, 0x00040047, 0x00000002, 0x0000000b, 0x0000001c, 0x00040047, 0x00000003,
+        0x00000006, 0x00000004, 0x00050048, 0x00000004, 0x00000000, 0x00000023, 0x00000000,
+        0x00030047, 0x00000004, 0x00000003, 0x00040047, 0x00000005, 0x00000022, 0x00000000,
+        0x00040047, 0x00000005, 0x00000021, 0x00000000, 0x00020013, 0x00000006, 0x00030021,
+        0x00000007, 0x00000006, 0x00040015, 0x00000008, 0x00000020, 0x00000000, 0x00040017,
+        0x00000009, 0x00000008, 0x00000003, 0x00040020, 0x0000000a, 0x00000001, 0x00000009,
+        0x0004003b, 0x0000000a, 0x00000002, 0x00000001, 0x0003001d, 0x00000003, 0x00000008,
+        0x0003001e, 0x00000004, 0x00000003, 0x00040020, 0x0000000b, 0x00000002, 0x00000004,
+        0x0004003b, 0x0000000b, 0x00000005, 0x00000002, 0x00040015, 0x0000000c, 0x00000020,
+        0x00000001, 0x0004002b, 0x0000000c, 0x0000000d, 0x00000000, 0x0004002b, 0x00000008,
+        0x0000000e, 0x00000000, 0x0004002b, 0x00000008, 0x0000000f, 0x00000002, 0x00040020,
+        0x00000010, 0x00000001, 0x00000008, 0x00040020, 0x00000011, 0x00000002, 0x00000008,
+        0x00050036, 0x00000006, 0x00000001, 0x00000000, 0x00000007, 0x000200f8, 0x00000012,
+        0x00050041, 0x00000010, 0x00000013, 0x00000002, 0x0000000e, 0x0004003d, 0x00000008,
+        0x00000014, 0x00000013, 0x00060041, 0x00000011, 0x00000015, 0x00000005, 0x0000000d,
+        0x00000014, 0x0004003d, 0x00000008, 0x00000016, 0x00000015, 0x00050084, 0x00000008,
+        0x00000017, 0x00000016, 0x0000000f, 0x0003003e, 0x00000015, 0x00000017, 0x000100fd,
+        0x00010038,
+    ];
+
+    #[test]
+    fn test_dispatches_cover_every_item() {
+        assert_eq!(group_counts([100, 1, 1], [64, 1, 1]), [2, 1, 1]);
+        assert_eq!(group_counts([64, 17, 0], [64, 8, 1]), [1, 3, 0]);
+    }
+
+    /// Needs a Vulkan driver, such as lavapipe on CI, so it only runs with `--ignored`
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn test_compute_doubles_a_buffer_and_reads_it_back() {
+        let mut context =
+            ComputeContext::new(&DeviceSelection::Auto).expect("No Vulkan device to compute on");
+        let mut device = context.device();
+
+        let values = [1u32, 2, 3, 40];
+        let pipeline =
+            device.create_pipeline(DOUBLE, &[ComputeBinding::StorageBuffer], 0, "test/double");
+
+        [BufferLocation::Device, BufferLocation::Host]
+            .into_iter()
+            .for_each(|location| {
+                let buffer = device.create_buffer_with(&values, location, "test/values");
+                device.bind(&pipeline, &[ComputeResource::Buffer(&buffer)]);
+                device.run(&Dispatch::covering(&pipeline, [4, 1, 1], [1, 1, 1]));
+
+                assert_eq!(device.read::<u32>(&buffer), [2, 4, 6, 80]);
+
+                unsafe { device.destroy_buffer(buffer) };
+            });
+
+        unsafe { device.destroy_pipeline(pipeline) };
+    }
+}
@@ -1,14 +1,145 @@
 use crate::utility::EntryInstance;
-use ash::{ext::debug_utils, vk, Entry};
-use std::{borrow::Cow, ffi::CStr};
+use ash::{ext::debug_utils, vk, Device, Entry, Instance};
+use std::{
+    borrow::Cow,
+    ffi::{CStr, CString},
+    fmt,
+    sync::Arc,
+};
 
 pub(crate) const ENABLE_VALIDATION_LAYERS: bool = cfg!(debug_assertions);
 pub(crate) const VALIDATION_LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
 
+/// Target validation messages are logged under
+pub const VALIDATION_TARGET: &str = "wave_vulkan::validation";
+
 #[derive(Clone)]
 pub(crate) struct Debug {
     pub messenger: vk::DebugUtilsMessengerEXT,
     pub utils: debug_utils::Instance,
+    /// Passed to the messenger as user data, so it is only kept alive until the instance is
+    /// destroyed
+    pub _sink: Arc<MessageSink>,
+}
+
+/// Receives validation messages on behalf of the messengers
+#[derive(Default)]
+pub(crate) struct MessageSink {
+    callback: Option<MessageCallback>,
+}
+
+impl MessageSink {
+    pub fn new(debugger: &Debugger) -> Arc<Self> {
+        Arc::new(Self {
+            callback: debugger.callback.clone(),
+        })
+    }
+
+    /// Pointer handed to Vulkan as the messenger's user data
+    pub fn user_data(self: &Arc<Self>) -> *mut std::os::raw::c_void {
+        Arc::as_ptr(self) as *mut _
+    }
+}
+
+/// Message reported by the validation layers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationMessage {
+    pub severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    pub id_name: String,
+    pub id_number: i32,
+    pub message: String,
+}
+
+impl ValidationMessage {
+    pub fn is_error(&self) -> bool {
+        self.severity
+            .contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR)
+    }
+
+    fn level(&self) -> log::Level {
+        match self.severity {
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => log::Level::Error,
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => log::Level::Warn,
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO => log::Level::Info,
+            _ => log::Level::Trace,
+        }
+    }
+}
+
+impl fmt::Display for ValidationMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} [{} ({})]: {}",
+            self.message_type, self.id_name, self.id_number, self.message
+        )
+    }
+}
+
+/// Called with every validation message, from whichever thread made the Vulkan call
+pub type MessageCallback = Arc<dyn Fn(&ValidationMessage) + Send + Sync>;
+
+/// Names objects and labels command buffer regions, so validation messages and graphics
+/// debuggers such as RenderDoc show them. Does nothing without the debug utils extension.
+#[derive(Clone, Default)]
+pub(crate) struct DebugNames {
+    utils: Option<debug_utils::Device>,
+}
+
+impl DebugNames {
+    pub fn new(instance: &Instance, device: &Device) -> Self {
+        Self {
+            utils: ENABLE_VALIDATION_LAYERS.then(|| debug_utils::Device::new(instance, device)),
+        }
+    }
+
+    pub fn name(&self, handle: impl vk::Handle, name: &str) {
+        let Some(utils) = &self.utils else {
+            return;
+        };
+
+        let name = c_name(name);
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(handle)
+            .object_name(&name);
+
+        unsafe {
+            utils
+                .set_debug_utils_object_name(&name_info)
+                .expect("Failed to name object");
+        }
+    }
+
+    /// Opens a labelled region, closed by the next [`DebugNames::end_label`].
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording.
+    pub unsafe fn begin_label(&self, command_buffer: vk::CommandBuffer, name: &str) {
+        let Some(utils) = &self.utils else {
+            return;
+        };
+
+        let name = c_name(name);
+        let label = vk::DebugUtilsLabelEXT::default().label_name(&name);
+
+        utils.cmd_begin_debug_utils_label(command_buffer, &label);
+    }
+
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording, inside a region opened in it.
+    pub unsafe fn end_label(&self, command_buffer: vk::CommandBuffer) {
+        if let Some(utils) = &self.utils {
+            utils.cmd_end_debug_utils_label(command_buffer);
+        }
+    }
+}
+
+/// Names can come from users, so nul bytes are dropped rather than rejected
+fn c_name(name: &str) -> CString {
+    CString::new(name.replace('\0', "")).expect("Nul bytes were removed")
 }
 
 pub(crate) fn check_validation_layer_support(entry: &Entry) -> bool {
@@ -41,14 +172,16 @@ pub(crate) fn check_validation_layer_support(entry: &Entry) -> bool {
     true
 }
 
+/// Logs the message and forwards it to the callback of the [`MessageSink`] in `user_data`.
+///
 /// # Safety
 ///
-/// Expand on the safety of this function
-pub unsafe extern "system" fn vulkan_debug_callback(
+/// `user_data` must be null or point to a live [`MessageSink`].
+pub(crate) unsafe extern "system" fn vulkan_debug_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _user_data: *mut std::os::raw::c_void,
+    user_data: *mut std::os::raw::c_void,
 ) -> vk::Bool32 {
     let callback_data = *p_callback_data;
     let message_id_number: i32 = callback_data.message_id_number;
@@ -65,14 +198,22 @@ pub unsafe extern "system" fn vulkan_debug_callback(
         CStr::from_ptr(callback_data.p_message).to_string_lossy()
     };
 
-    println!(
-        "{:?}:\n{:?} [{} ({})] : {}",
-        message_severity,
+    let message = ValidationMessage {
+        severity: message_severity,
         message_type,
-        message_id_name,
-        &message_id_number.to_string(),
-        message,
-    );
+        id_name: message_id_name.into_owned(),
+        id_number: message_id_number,
+        message: message.into_owned(),
+    };
+
+    log::log!(target: VALIDATION_TARGET, message.level(), "{message}");
+
+    if let Some(callback) = (user_data as *const MessageSink)
+        .as_ref()
+        .and_then(|sink| sink.callback.as_ref())
+    {
+        callback(&message);
+    }
 
     vk::FALSE
 }
@@ -161,10 +302,23 @@ impl Default for MessageType {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+/// Validation layer messages to report, which are logged under [`VALIDATION_TARGET`] and
+/// passed to the callback set with [`Debugger::on_message`]
+#[derive(Default, Clone)]
 pub struct Debugger {
     pub message_level: MessageLevel,
     pub message_type: MessageType,
+    callback: Option<MessageCallback>,
+}
+
+impl fmt::Debug for Debugger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Debugger")
+            .field("message_level", &self.message_level)
+            .field("message_type", &self.message_type)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
 }
 
 impl Debugger {
@@ -172,22 +326,52 @@ impl Debugger {
         Self {
             message_level,
             message_type,
+            callback: None,
         }
     }
 
     pub fn all() -> Self {
         Self::new(MessageLevel::all(), MessageType::all())
     }
+
+    /// Also passes every message to `callback`, for example to collect them and assert a
+    /// scene renders without validation errors.
+    ///
+    /// The callback runs inside a Vulkan call, so panicking in it aborts the process.
+    #[must_use]
+    pub fn on_message(
+        mut self,
+        callback: impl Fn(&ValidationMessage) + Send + Sync + 'static,
+    ) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    pub(crate) fn create_info(
+        &self,
+        sink: &Arc<MessageSink>,
+    ) -> vk::DebugUtilsMessengerCreateInfoEXT<'static> {
+        vk::DebugUtilsMessengerCreateInfoEXT::default()
+            .message_severity(self.message_level.flags)
+            .message_type(self.message_type.flags)
+            .pfn_user_callback(Some(vulkan_debug_callback))
+            .user_data(sink.user_data())
+    }
 }
 
 pub(crate) fn debugger(
-    EntryInstance { entry, instance }: &EntryInstance,
-    debug_properties: Debugger,
+    EntryInstance {
+        entry,
+        instance,
+        sink,
+    }: &EntryInstance,
+    debug_properties: &Debugger,
 ) -> Debug {
-    let create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
-        .message_severity(debug_properties.message_level.flags)
-        .message_type(debug_properties.message_type.flags)
-        .pfn_user_callback(Some(vulkan_debug_callback));
+    let sink = sink
+        .clone()
+        .unwrap_or_else(|| MessageSink::new(debug_properties));
+
+    let create_info = debug_properties.create_info(&sink);
 
     let utils = debug_utils::Instance::new(entry, instance);
 
@@ -197,5 +381,54 @@ pub(crate) fn debugger(
             .expect("Failed to create debug messenger")
     };
 
-    Debug { messenger, utils }
+    Debug {
+        messenger,
+        utils,
+        _sink: sink,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_names_drop_nul_bytes() {
+        assert_eq!(c_name("ring/pipeline").as_bytes(), b"ring/pipeline");
+        assert_eq!(c_name("sat\0urn").as_bytes(), b"saturn");
+    }
+
+    #[test]
+    fn test_messages_reach_the_callback() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let collected = received.clone();
+
+        let debugger = Debugger::all().on_message(move |message| {
+            collected.lock().unwrap().push(message.clone());
+        });
+        let sink = MessageSink::new(&debugger);
+
+        let id_name = CString::new("VUID-test").unwrap();
+        let text = CString::new("Something went wrong").unwrap();
+        let data = vk::DebugUtilsMessengerCallbackDataEXT::default()
+            .message_id_name(&id_name)
+            .message_id_number(7)
+            .message(&text);
+
+        unsafe {
+            vulkan_debug_callback(
+                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+                vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+                &data,
+                sink.user_data(),
+            );
+        }
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert!(received[0].is_error());
+        assert_eq!(received[0].id_name, "VUID-test");
+        assert_eq!(received[0].message, "Something went wrong");
+    }
 }
@@ -0,0 +1,407 @@
+use crate::{
+    any_as_u8_slice, buffer,
+    command_buffer::CommandPool,
+    device::Devices,
+    graphics_pipeline::{self, ShaderModules},
+    profiler::DrawCounts,
+    BlendMode, ModelTopology, VulkanObject,
+};
+use ash::{vk, Device};
+use gpu_allocator::vulkan::Allocator;
+use memoffset::offset_of;
+use nalgebra::Point3;
+use std::mem;
+use wave_space::space::Vertex;
+use wave_window::window::DebugView;
+
+/// Specialization constant selecting the view in the debug fragment shader
+const DEBUG_VIEW_CONSTANT_ID: u32 = 0;
+
+/// Length of the normal lines as a fraction of the diagonal of the object's bounds
+const NORMAL_LENGTH: f32 = 0.05;
+
+/// Pipelines for the debug views, created the first time an object is drawn in a view
+#[derive(Debug, Default)]
+pub(crate) struct DebugViews {
+    /// View of every object without one of its own
+    pub global: DebugView,
+    pipelines: Vec<(DebugView, vk::PrimitiveTopology, vk::Pipeline)>,
+}
+
+impl DebugViews {
+    pub fn view_of(&self, object: &VulkanObject) -> DebugView {
+        object.debug_view.unwrap_or(self.global)
+    }
+
+    pub fn pipeline(
+        &self,
+        view: DebugView,
+        topology: vk::PrimitiveTopology,
+    ) -> Option<vk::Pipeline> {
+        self.pipelines
+            .iter()
+            .find(|(pipeline_view, pipeline_topology, _)| {
+                *pipeline_view == view && *pipeline_topology == topology
+            })
+            .map(|(.., pipeline)| *pipeline)
+    }
+
+    /// Creates the pipelines and normal lines the objects' views need
+    pub fn prepare(
+        &mut self,
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        devices: &Devices,
+        render_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+        objects: &mut [VulkanObject],
+    ) {
+        for object in objects.iter_mut() {
+            let view = self.view_of(object);
+            let topology = object.topology;
+
+            if view == DebugView::Wireframe && !devices.physical.wireframe {
+                log::warn!(
+                    "The device cannot rasterise lines, so {} is shaded instead of wireframed",
+                    object.label
+                );
+            }
+
+            if let Some(view) = fill_view(view, devices.physical.wireframe) {
+                self.add_pipeline(devices, render_pass, layout, view, topology.into());
+            }
+
+            if view == DebugView::Normals {
+                self.add_pipeline(
+                    devices,
+                    render_pass,
+                    layout,
+                    DebugView::Normals,
+                    vk::PrimitiveTopology::LINE_LIST,
+                );
+
+                if object.normal_lines.is_none() {
+                    let lines = normal_lines(&object.vertices_and_indices.vertices);
+                    let buffer = buffer::create_vertex_index_buffer(
+                        allocator,
+                        (mem::size_of::<Vertex>() * lines.len()) as vk::DeviceSize,
+                        &lines,
+                        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
+                        command_pool,
+                        1,
+                        devices,
+                    );
+                    devices
+                        .debug_names
+                        .name(buffer.buffer, &format!("{}/normal lines", object.label));
+                    object.normal_lines = Some(buffer);
+                }
+            }
+        }
+    }
+
+    fn add_pipeline(
+        &mut self,
+        devices: &Devices,
+        render_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+        view: DebugView,
+        topology: vk::PrimitiveTopology,
+    ) {
+        if self.pipeline(view, topology).is_none() {
+            let pipeline = create_pipeline(devices, render_pass, layout, view, topology);
+            self.pipelines.push((view, topology, pipeline));
+        }
+    }
+
+    /// Rebuilds every pipeline after the render pass was recreated
+    pub fn recreate(
+        &mut self,
+        devices: &Devices,
+        render_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+    ) {
+        self.pipelines
+            .iter_mut()
+            .for_each(|(view, topology, pipeline)| {
+                *pipeline = create_pipeline(devices, render_pass, layout, *view, *topology);
+            });
+    }
+
+    /// Draws the normals of an object in the normals view as lines, with the object's
+    /// descriptor sets still bound.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording inside the scene render pass.
+    pub unsafe fn draw_normals(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        object: &VulkanObject,
+        counts: &mut DrawCounts,
+    ) {
+        let pipeline = self.pipeline(DebugView::Normals, vk::PrimitiveTopology::LINE_LIST);
+
+        let (Some(lines), Some(pipeline)) = (&object.normal_lines, pipeline) else {
+            return;
+        };
+
+        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        device.cmd_bind_vertex_buffers(
+            command_buffer,
+            0,
+            std::slice::from_ref(&lines.buffer),
+            &[0],
+        );
+
+        let count = object.vertices_and_indices.vertices.len() as u32 * 2;
+        device.cmd_draw(command_buffer, count, 1, 0, 0);
+        counts.draw(count, ModelTopology::LineList);
+    }
+
+    /// # Safety
+    ///
+    /// The pipelines must no longer be in use by the device.
+    pub unsafe fn destroy_pipelines(&self, device: &Device) {
+        self.pipelines
+            .iter()
+            .for_each(|(.., pipeline)| device.destroy_pipeline(*pipeline, None));
+    }
+}
+
+/// View an object in `view` is filled with, or `None` when it keeps its material. Normals
+/// are drawn over the shaded object, as are wireframes on devices that cannot draw them.
+pub(crate) fn fill_view(view: DebugView, wireframe: bool) -> Option<DebugView> {
+    match view {
+        DebugView::Shaded | DebugView::Normals => None,
+        DebugView::Wireframe if !wireframe => None,
+        view => Some(view),
+    }
+}
+
+/// Mode of the debug fragment shader, matching the defines in debug/shader.frag
+fn shader_mode(view: DebugView) -> i32 {
+    match view {
+        DebugView::Shaded | DebugView::Wireframe => 0,
+        DebugView::Normals => 1,
+        DebugView::UvChecker => 2,
+        DebugView::VertexColours => 3,
+        DebugView::Depth => 4,
+        DebugView::Overdraw => 5,
+    }
+}
+
+/// Line list from each vertex along its normal, coloured by the shader from the normal
+fn normal_lines(vertices: &[Vertex]) -> Vec<Vertex> {
+    let Some(first) = vertices.first() else {
+        return Vec::new();
+    };
+
+    let (min, max) = vertices
+        .iter()
+        .fold((first.pos, first.pos), |(min, max), vertex| {
+            (min.inf(&vertex.pos), max.sup(&vertex.pos))
+        });
+
+    let diagonal = nalgebra::distance(&min, &max);
+    let length = if diagonal > 0. {
+        diagonal * NORMAL_LENGTH
+    } else {
+        NORMAL_LENGTH
+    };
+
+    vertices
+        .iter()
+        .flat_map(|vertex| {
+            let direction = vertex
+                .normal
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_default();
+            let tip: Point3<f32> = vertex.pos + direction * length;
+            [
+                *vertex,
+                Vertex {
+                    pos: tip,
+                    ..*vertex
+                },
+            ]
+        })
+        .collect()
+}
+
+fn create_pipeline(
+    devices: &Devices,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+    view: DebugView,
+    topology: vk::PrimitiveTopology,
+) -> vk::Pipeline {
+    let device = &devices.logical.device;
+
+    let ShaderModules { vert, frag } = graphics_pipeline::load_shader_stages("debug", device);
+
+    let binding_description = vk::VertexInputBindingDescription::default()
+        .binding(0)
+        .stride(mem::size_of::<Vertex>() as u32)
+        .input_rate(vk::VertexInputRate::VERTEX);
+
+    let attribute = |location: u32, format: vk::Format, offset: usize| {
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(location)
+            .format(format)
+            .offset(offset as u32)
+    };
+
+    let attribute_descriptions = [
+        attribute(0, vk::Format::R32G32B32_SFLOAT, offset_of!(Vertex, pos)),
+        attribute(1, vk::Format::R32G32B32_SFLOAT, offset_of!(Vertex, colour)),
+        attribute(2, vk::Format::R32G32B32_SFLOAT, offset_of!(Vertex, normal)),
+        attribute(3, vk::Format::R32G32_SFLOAT, offset_of!(Vertex, tex_coord)),
+    ];
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(std::slice::from_ref(&binding_description))
+        .vertex_attribute_descriptions(&attribute_descriptions);
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default().topology(topology);
+
+    let view_port_state = vk::PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let polygon_mode = if view == DebugView::Wireframe {
+        vk::PolygonMode::LINE
+    } else {
+        vk::PolygonMode::FILL
+    };
+
+    // Back faces are kept so every edge and overlapping surface shows
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
+        .polygon_mode(polygon_mode)
+        .line_width(1.)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+
+    let multi_sampling = vk::PipelineMultisampleStateCreateInfo::default()
+        .rasterization_samples(devices.physical.samples);
+
+    // Overdraw counts every fragment, including the hidden ones
+    let overdraw = view == DebugView::Overdraw;
+
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+        .depth_test_enable(!overdraw)
+        .depth_write_enable(!overdraw)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .max_depth_bounds(1.);
+
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::from(if overdraw {
+        BlendMode::Additive
+    } else {
+        BlendMode::Opaque
+    });
+
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
+        .attachments(std::slice::from_ref(&color_blend_attachment));
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+    let dynamic_state_create_info =
+        vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    unsafe {
+        let mode = shader_mode(view);
+        let specialization_entry = vk::SpecializationMapEntry::default()
+            .constant_id(DEBUG_VIEW_CONSTANT_ID)
+            .size(mem::size_of::<i32>());
+        let specialization_info = vk::SpecializationInfo::default()
+            .map_entries(std::slice::from_ref(&specialization_entry))
+            .data(any_as_u8_slice(&mode));
+
+        let entry_point = c"main";
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert)
+                .name(entry_point),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag)
+                .name(entry_point)
+                .specialization_info(&specialization_info),
+        ];
+
+        let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_info)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&view_port_state)
+            .rasterization_state(&rasterizer)
+            .multisample_state(&multi_sampling)
+            .depth_stencil_state(&depth_stencil)
+            .color_blend_state(&color_blending)
+            .dynamic_state(&dynamic_state_create_info)
+            .layout(layout)
+            .render_pass(render_pass)
+            .subpass(0);
+
+        let pipeline = device
+            .create_graphics_pipelines(
+                vk::PipelineCache::null(),
+                std::slice::from_ref(&pipeline_info),
+                None,
+            )
+            .expect("Failed to create debug view pipeline!")[0];
+
+        graphics_pipeline::destroy_shader_modules(device, vert, frag);
+
+        devices.debug_names.name(
+            pipeline,
+            &format!("debug view/{view:?} {topology:?} pipeline"),
+        );
+
+        pipeline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{vector, Point3};
+
+    #[test]
+    fn test_normals_and_unsupported_wireframes_keep_the_material() {
+        assert_eq!(fill_view(DebugView::Shaded, true), None);
+        assert_eq!(fill_view(DebugView::Normals, true), None);
+        assert_eq!(fill_view(DebugView::Wireframe, false), None);
+        assert_eq!(
+            fill_view(DebugView::Wireframe, true),
+            Some(DebugView::Wireframe)
+        );
+        assert_eq!(
+            fill_view(DebugView::Overdraw, false),
+            Some(DebugView::Overdraw)
+        );
+    }
+
+    #[test]
+    fn test_normal_lines_scale_with_the_object() {
+        let vertex = |x: f32| {
+            Vertex::new(
+                Point3::new(x, 0., 0.),
+                vector![1., 1., 1.],
+                vector![0., 2., 0.],
+                vector![0., 0.],
+            )
+        };
+
+        let lines = normal_lines(&[vertex(0.), vertex(10.)]);
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].pos, Point3::new(0., 0., 0.));
+        assert_eq!(lines[1].pos, Point3::new(0., 0.5, 0.));
+        assert_eq!(lines[3].pos, Point3::new(10., 0.5, 0.));
+        assert_eq!(lines[3].normal, vector![0., 2., 0.]);
+    }
+}
@@ -1,5 +1,9 @@
 use crate::{
-    graphics_pipeline::GraphicsPipeline, sync_objects::MAX_FRAMES_IN_FLIGHT, VulkanObject,
+    debug::DebugNames,
+    gpu::{DeviceInfo, DeviceSelection},
+    settings::RenderSettings,
+    sync_objects::MAX_FRAMES_IN_FLIGHT,
+    VulkanObject,
 };
 use ash::{khr::surface, vk, Device, Instance};
 use gpu_allocator::vulkan::Allocator;
@@ -7,33 +11,88 @@ use gpu_allocator::vulkan::Allocator;
 #[derive(Debug, Clone)]
 pub struct PhysicalDeviceProperties {
     pub device: vk::PhysicalDevice,
-    pub queue_family_index: u32,
+    pub queue_families: QueueFamilies,
     pub samples: vk::SampleCountFlags,
+    /// Anisotropic filtering level of texture samplers, 1 when it is off
+    pub anisotropy: f32,
+    pub info: DeviceInfo,
+    /// Whether polygons can be rasterised as lines, which the wireframe view needs
+    pub wireframe: bool,
 }
 
 impl PhysicalDeviceProperties {
     pub fn new(
         device: vk::PhysicalDevice,
-        queue_family_index: u32,
+        queue_families: QueueFamilies,
         samples: vk::SampleCountFlags,
+        anisotropy: f32,
+        info: DeviceInfo,
+        wireframe: bool,
     ) -> Self {
         Self {
             device,
-            queue_family_index,
+            queue_families,
             samples,
+            anisotropy,
+            info,
+            wireframe,
         }
     }
+
+    /// Picks the sample count and anisotropy closest to `settings` that the device supports.
+    ///
+    /// Returns whether the anisotropy changed, as existing samplers then need recreating.
+    pub(crate) fn apply_settings(
+        &mut self,
+        instance: &Instance,
+        settings: &RenderSettings,
+    ) -> bool {
+        let limits = unsafe { instance.get_physical_device_properties(self.device).limits };
+
+        let supported_samples =
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+        self.samples = settings.msaa.sample_count(supported_samples);
+
+        let anisotropy = settings.anisotropy_level(limits.max_sampler_anisotropy);
+        let changed = anisotropy != self.anisotropy;
+        self.anisotropy = anisotropy;
+
+        changed
+    }
+}
+
+/// Queue family indices of the device. Rendering and presenting share one family when the
+/// device has one that does both, otherwise the swap chain images are shared by the two
+/// families. Uploads use a separate transfer family when the device has one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFamilies {
+    pub graphics: u32,
+    pub present: u32,
+    pub transfer: u32,
+}
+
+impl QueueFamilies {
+    /// Whether uploads run on another family, and so have to hand resources over to the
+    /// graphics family
+    pub const fn separate_transfer(&self) -> bool {
+        self.transfer != self.graphics
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Queues {
     pub present: vk::Queue,
     pub graphics: vk::Queue,
+    pub transfer: vk::Queue,
 }
 
 impl Queues {
-    pub fn new(present: vk::Queue, graphics: vk::Queue) -> Self {
-        Self { present, graphics }
+    pub fn new(present: vk::Queue, graphics: vk::Queue, transfer: vk::Queue) -> Self {
+        Self {
+            present,
+            graphics,
+            transfer,
+        }
     }
 }
 
@@ -49,22 +108,11 @@ impl LogicalDeviceFeatures {
     }
 }
 
-#[derive(Default, Debug)]
-pub(crate) struct QueueFamilyIndices {
-    pub graphics_family: Option<u32>,
-    pub present_family: Option<u32>,
-}
-
-impl QueueFamilyIndices {
-    pub const fn is_complete(&self) -> bool {
-        self.graphics_family.is_some() && self.present_family.is_some()
-    }
-}
-
 #[derive(Clone)]
 pub struct Devices {
     pub physical: PhysicalDeviceProperties,
     pub logical: LogicalDeviceFeatures,
+    pub(crate) debug_names: DebugNames,
 }
 
 impl Devices {
@@ -72,63 +120,156 @@ impl Devices {
         instance: &Instance,
         surface: &vk::SurfaceKHR,
         surface_loader: &surface::Instance,
+        settings: &RenderSettings,
+        selection: &DeviceSelection,
     ) -> Self {
-        let physical = pick_physical_device(instance, surface, surface_loader);
-
-        let logical = create_logical_device(instance, &physical, surface, surface_loader);
-
-        Self { physical, logical }
-    }
-}
-
-pub(crate) fn find_queue_family(
-    instance: &Instance,
-    physical_device: vk::PhysicalDevice,
-    surface_loader: &surface::Instance,
-    surface: &vk::SurfaceKHR,
-) -> QueueFamilyIndices {
-    let queue_families =
-        unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let mut physical = pick_physical_device(instance, surface, surface_loader, selection);
+        physical.apply_settings(instance, settings);
 
-    let mut queue_family_indices = QueueFamilyIndices::default();
+        let logical = create_logical_device(instance, &physical);
 
-    for (index, queue_family) in queue_families.iter().enumerate() {
-        if queue_family.queue_count > 0
-            && queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-        {
-            queue_family_indices.graphics_family = Some(index.try_into().unwrap());
-        }
+        let debug_names = DebugNames::new(instance, &logical.device);
 
-        let is_present_support = unsafe {
-            surface_loader.get_physical_device_surface_support(
-                physical_device,
-                index as u32,
-                *surface,
-            )
-        }
-        .unwrap();
-
-        if queue_family.queue_count > 0 && is_present_support {
-            queue_family_indices.present_family = Some(index.try_into().unwrap());
+        Self {
+            physical,
+            logical,
+            debug_names,
         }
+    }
 
-        if queue_family_indices.is_complete() {
-            break;
+    /// Device for compute work that never presents, using one family with a compute queue
+    /// for everything. Returns `None` when no device has one or matches `selection`.
+    ///
+    /// [`crate::gpu::DEVICE_ENV_VAR`] takes precedence over `selection`.
+    pub(crate) fn headless(instance: &Instance, selection: &DeviceSelection) -> Option<Self> {
+        unsafe {
+            let suitable = instance
+                .enumerate_physical_devices()
+                .ok()?
+                .into_iter()
+                .enumerate()
+                .filter_map(|(device_index, device)| {
+                    instance
+                        .get_physical_device_queue_family_properties(device)
+                        .iter()
+                        .position(|family| {
+                            family.queue_count > 0
+                                && family.queue_flags.contains(vk::QueueFlags::COMPUTE)
+                        })
+                        .map(|family| {
+                            (
+                                (device, family as u32),
+                                DeviceInfo::new(instance, device, device_index),
+                            )
+                        })
+                })
+                .collect::<Vec<_>>();
+
+            let selection = DeviceSelection::resolve(selection);
+
+            let infos = suitable
+                .iter()
+                .map(|(_, info)| info.clone())
+                .collect::<Vec<DeviceInfo>>();
+
+            let ((device, family), info) = suitable[selection.choose_or_auto(&infos)?].clone();
+
+            let priorities = 1.0;
+            let queue_info = vk::DeviceQueueCreateInfo::default()
+                .queue_family_index(family)
+                .queue_priorities(std::slice::from_ref(&priorities));
+
+            let device_extension_names_raw = [
+                #[cfg(any(target_os = "macos", target_os = "ios"))]
+                vk::KHR_PORTABILITY_SUBSET_NAME.as_ptr(),
+            ];
+
+            let device_create_info = vk::DeviceCreateInfo::default()
+                .queue_create_infos(std::slice::from_ref(&queue_info))
+                .enabled_extension_names(&device_extension_names_raw);
+
+            let logical = instance
+                .create_device(device, &device_create_info, None)
+                .ok()?;
+            let queue = logical.get_device_queue(family, 0);
+
+            Some(Self {
+                physical: PhysicalDeviceProperties::new(
+                    device,
+                    QueueFamilies {
+                        graphics: family,
+                        present: family,
+                        transfer: family,
+                    },
+                    vk::SampleCountFlags::TYPE_1,
+                    1.,
+                    info,
+                    false,
+                ),
+                logical: LogicalDeviceFeatures::new(logical, Queues::new(queue, queue, queue)),
+                // Headless instances are created without the debug utils extension
+                debug_names: DebugNames::default(),
+            })
         }
     }
+}
+
+/// Families to draw and to present with, preferring one that does both so swap chain images
+/// are never shared, and otherwise the first of each
+fn find_graphics_and_present_families(
+    families: &[vk::QueueFamilyProperties],
+    supports_present: impl Fn(u32) -> bool,
+) -> Option<(u32, u32)> {
+    let graphics = |index: &u32| {
+        let family = &families[*index as usize];
+        family.queue_count > 0 && family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+    };
+    let present =
+        |index: &u32| families[*index as usize].queue_count > 0 && supports_present(*index);
+    let indices = 0..families.len() as u32;
+
+    indices
+        .clone()
+        .find(|index| graphics(index) && present(index))
+        .map(|index| (index, index))
+        .or_else(|| {
+            Some((
+                indices.clone().find(graphics)?,
+                indices.clone().find(present)?,
+            ))
+        })
+}
 
-    queue_family_indices
+/// Family for uploads, preferring one dedicated to transfers, then any without graphics,
+/// and otherwise the graphics family itself
+fn find_transfer_family(families: &[vk::QueueFamilyProperties], graphics: u32) -> u32 {
+    let transfer = |flags: vk::QueueFlags, excluded: vk::QueueFlags| {
+        families
+            .iter()
+            .position(|family| {
+                family.queue_count > 0
+                    && family.queue_flags.contains(flags)
+                    && !family.queue_flags.intersects(excluded)
+            })
+            .map(|index| index as u32)
+    };
+
+    transfer(
+        vk::QueueFlags::TRANSFER,
+        vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE,
+    )
+    .or_else(|| transfer(vk::QueueFlags::TRANSFER, vk::QueueFlags::GRAPHICS))
+    .unwrap_or(graphics)
 }
 
 fn create_logical_device(
     instance: &Instance,
     physical_device_properties: &PhysicalDeviceProperties,
-    surface: &vk::SurfaceKHR,
-    surface_loader: &surface::Instance,
 ) -> LogicalDeviceFeatures {
     let PhysicalDeviceProperties {
         device,
-        queue_family_index,
+        queue_families,
+        wireframe,
         ..
     } = physical_device_properties;
 
@@ -141,14 +282,27 @@ fn create_logical_device(
     let features = vk::PhysicalDeviceFeatures::default()
         .sampler_anisotropy(true)
         .sample_rate_shading(true)
-        .fill_mode_non_solid(true)
+        .fill_mode_non_solid(*wireframe)
         .shader_clip_distance(true);
 
     let priorities = 1.0;
 
-    let queue_info = vk::DeviceQueueCreateInfo::default()
-        .queue_family_index(*queue_family_index)
-        .queue_priorities(std::slice::from_ref(&priorities));
+    let mut unique_families = vec![
+        queue_families.graphics,
+        queue_families.present,
+        queue_families.transfer,
+    ];
+    unique_families.sort_unstable();
+    unique_families.dedup();
+
+    let queue_infos = unique_families
+        .iter()
+        .map(|family| {
+            vk::DeviceQueueCreateInfo::default()
+                .queue_family_index(*family)
+                .queue_priorities(std::slice::from_ref(&priorities))
+        })
+        .collect::<Vec<vk::DeviceQueueCreateInfo>>();
 
     let device_extension_names_raw = [
         vk::KHR_SWAPCHAIN_NAME.as_ptr(),
@@ -157,100 +311,87 @@ fn create_logical_device(
     ];
 
     let device_create_info = vk::DeviceCreateInfo::default()
-        .queue_create_infos(std::slice::from_ref(&queue_info))
+        .queue_create_infos(&queue_infos)
         .enabled_extension_names(&device_extension_names_raw)
         .enabled_features(&features);
 
-    let queue_family = find_queue_family(instance, *device, surface_loader, surface);
-
     unsafe {
         let device = instance
             .create_device(*device, &device_create_info, None)
             .expect("Could not create device");
 
-        let graphics_queue = device.get_device_queue(queue_family.graphics_family.unwrap(), 0);
-        let present_queue = device.get_device_queue(queue_family.present_family.unwrap(), 0);
+        let graphics_queue = device.get_device_queue(queue_families.graphics, 0);
+        let present_queue = device.get_device_queue(queue_families.present, 0);
+        let transfer_queue = device.get_device_queue(queue_families.transfer, 0);
 
-        LogicalDeviceFeatures::new(device, Queues::new(present_queue, graphics_queue))
+        LogicalDeviceFeatures::new(
+            device,
+            Queues::new(present_queue, graphics_queue, transfer_queue),
+        )
     }
 }
 
+/// Picks the device to render with among those with queues that can draw and present.
+///
+/// [`crate::gpu::DEVICE_ENV_VAR`] takes precedence over `selection`.
 fn pick_physical_device(
     instance: &Instance,
     surface: &vk::SurfaceKHR,
     surface_loader: &surface::Instance,
+    selection: &DeviceSelection,
 ) -> PhysicalDeviceProperties {
     unsafe {
         let devices = instance
             .enumerate_physical_devices()
             .expect("Failed to find GPUs with Vulkan support!");
 
-        let (physical_device, queue_family_index) = devices
+        let suitable = devices
             .iter()
-            .find_map(|p_device| {
-                instance
-                    .get_physical_device_queue_family_properties(*p_device)
-                    .iter()
-                    .enumerate()
-                    .find_map(|(index, info)| {
-                        let supports_graphic_and_surface =
-                            info.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-                                && surface_loader
-                                    .get_physical_device_surface_support(
-                                        *p_device,
-                                        index as u32,
-                                        *surface,
-                                    )
-                                    .unwrap();
-                        if supports_graphic_and_surface {
-                            Some((*p_device, index))
-                        } else {
-                            None
-                        }
-                    })
+            .enumerate()
+            .filter_map(|(device_index, p_device)| {
+                let families = instance.get_physical_device_queue_family_properties(*p_device);
+
+                find_graphics_and_present_families(&families, |index| {
+                    surface_loader
+                        .get_physical_device_surface_support(*p_device, index, *surface)
+                        .unwrap_or(false)
+                })
+                .map(|families| {
+                    (
+                        (*p_device, families),
+                        DeviceInfo::new(instance, *p_device, device_index),
+                    )
+                })
             })
-            .expect("Couldn't find suitable device.");
-
-        let samples = get_max_usable_sample_count(instance, physical_device);
+            .collect::<Vec<_>>();
 
-        PhysicalDeviceProperties::new(physical_device, queue_family_index as u32, samples)
-    }
-}
+        let selection = DeviceSelection::resolve(selection);
 
-fn get_max_usable_sample_count(
-    instance: &Instance,
-    physical_device: vk::PhysicalDevice,
-) -> vk::SampleCountFlags {
-    unsafe {
-        let physical_device_properties = instance.get_physical_device_properties(physical_device);
-
-        let counts = physical_device_properties
-            .limits
-            .framebuffer_color_sample_counts
-            & physical_device_properties
-                .limits
-                .framebuffer_depth_sample_counts;
-
-        if (counts & vk::SampleCountFlags::TYPE_64) == vk::SampleCountFlags::TYPE_64 {
-            return vk::SampleCountFlags::TYPE_64;
-        }
-        if (counts & vk::SampleCountFlags::TYPE_32) == vk::SampleCountFlags::TYPE_32 {
-            return vk::SampleCountFlags::TYPE_32;
-        }
-        if (counts & vk::SampleCountFlags::TYPE_16) == vk::SampleCountFlags::TYPE_16 {
-            return vk::SampleCountFlags::TYPE_16;
-        }
-        if (counts & vk::SampleCountFlags::TYPE_8) == vk::SampleCountFlags::TYPE_8 {
-            return vk::SampleCountFlags::TYPE_8;
-        }
-        if (counts & vk::SampleCountFlags::TYPE_4) == vk::SampleCountFlags::TYPE_4 {
-            return vk::SampleCountFlags::TYPE_4;
-        }
-        if (counts & vk::SampleCountFlags::TYPE_2) == vk::SampleCountFlags::TYPE_2 {
-            return vk::SampleCountFlags::TYPE_2;
-        }
-
-        vk::SampleCountFlags::TYPE_1
+        let infos = suitable
+            .iter()
+            .map(|(_, info)| info.clone())
+            .collect::<Vec<DeviceInfo>>();
+
+        let ((physical_device, (graphics, present)), info) = selection
+            .choose_or_auto(&infos)
+            .map(|position| suitable[position].clone())
+            .expect("Couldn't find a suitable device.");
+
+        let families = instance.get_physical_device_queue_family_properties(physical_device);
+        let features = instance.get_physical_device_features(physical_device);
+
+        PhysicalDeviceProperties::new(
+            physical_device,
+            QueueFamilies {
+                graphics,
+                present,
+                transfer: find_transfer_family(&families, graphics),
+            },
+            vk::SampleCountFlags::TYPE_1,
+            1.,
+            info,
+            features.fill_mode_non_solid == vk::TRUE,
+        )
     }
 }
 
@@ -263,39 +404,14 @@ pub unsafe fn recreate_drop(allocator: &mut Allocator, object: VulkanObject, dev
         // device.free_memory(graphics_pipeline.uniform_buffers[i].memory, None);
     }
 
-    for buffer in object.graphics_pipeline.uniform_buffers {
-        allocator.free(buffer.allocation).unwrap();
-        unsafe { device.destroy_buffer(buffer.buffer, None) };
-    }
-
-    device.destroy_descriptor_pool(object.graphics_pipeline.descriptors.pool, None);
+    object.descriptors.destroy(allocator, device);
 }
 
 /// # Safety
 ///
 ///
 pub(crate) unsafe fn destroy(allocator: &mut Allocator, object: VulkanObject, device: &Device) {
-    for i in 0..MAX_FRAMES_IN_FLIGHT {
-        // device.destroy_buffer(graphics_pipeline.uniform_buffers[i].buffer, None);
-        // device.free_memory(graphics_pipeline.uniform_buffers[i].memory, None);
-    }
-
-    for buffer in object.graphics_pipeline.uniform_buffers {
-        allocator.free(buffer.allocation).unwrap();
-        unsafe { device.destroy_buffer(buffer.buffer, None) };
-    }
-
-    device.destroy_descriptor_pool(object.graphics_pipeline.descriptors.pool, None);
-
-    if let Some(object_texture) = &object.texture {
-        device.destroy_sampler(object_texture.sampler, None);
-        device.destroy_image_view(object_texture.view, None);
-
-        device.destroy_image(object_texture.image.image, None);
-        device.free_memory(object_texture.image.memory, None);
-    }
-
-    device.destroy_descriptor_set_layout(object.graphics_pipeline.descriptors.set_layout, None);
+    object.descriptors.destroy(allocator, device);
 
     // device.destroy_buffer(object.buffers.index.buffer, None);
     // device.free_memory(object.buffers.index.memory, None);
@@ -308,4 +424,63 @@ pub(crate) unsafe fn destroy(allocator: &mut Allocator, object: VulkanObject, de
 
     allocator.free(object.buffers.vertex.allocation).unwrap();
     unsafe { device.destroy_buffer(object.buffers.vertex.buffer, None) };
+
+    if let Some(normal_lines) = object.normal_lines {
+        allocator.free(normal_lines.allocation).unwrap();
+        unsafe { device.destroy_buffer(normal_lines.buffer, None) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family(queue_flags: vk::QueueFlags) -> vk::QueueFamilyProperties {
+        vk::QueueFamilyProperties {
+            queue_flags,
+            queue_count: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_transfer_family_prefers_dedicated_queues() {
+        let graphics =
+            family(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER);
+        let compute = family(vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER);
+        let transfer = family(vk::QueueFlags::TRANSFER);
+
+        assert_eq!(find_transfer_family(&[graphics, compute, transfer], 0), 2);
+        assert_eq!(find_transfer_family(&[graphics, compute], 0), 1);
+        assert_eq!(find_transfer_family(&[graphics], 0), 0);
+    }
+
+    #[test]
+    fn test_present_family_prefers_the_graphics_family() {
+        let graphics = family(vk::QueueFlags::GRAPHICS | vk::QueueFlags::TRANSFER);
+        let compute = family(vk::QueueFlags::COMPUTE);
+        let families = [compute, graphics, graphics];
+
+        assert_eq!(
+            find_graphics_and_present_families(&families, |_| true),
+            Some((1, 1))
+        );
+        assert_eq!(
+            find_graphics_and_present_families(&families, |index| index == 2),
+            Some((2, 2))
+        );
+        // Only the compute family presents, so images are shared between the two
+        assert_eq!(
+            find_graphics_and_present_families(&families, |index| index == 0),
+            Some((1, 0))
+        );
+        assert_eq!(
+            find_graphics_and_present_families(&families, |_| false),
+            None
+        );
+        assert_eq!(
+            find_graphics_and_present_families(&[compute], |_| true),
+            None
+        );
+    }
 }
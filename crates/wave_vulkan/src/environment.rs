@@ -0,0 +1,653 @@
+use crate::{
+    command_buffer::CommandPool,
+    device::Devices,
+    graphics_pipeline::{self, ShaderModules},
+    texture::{self, MipChain, Texture},
+    utility::{self, ImageInfo},
+};
+use ash::{vk, Device, Instance};
+use gpu_allocator::vulkan::Allocator;
+use half::f16;
+use nalgebra::{Point2, Vector3};
+use std::{f32::consts::PI, mem};
+
+const ENVIRONMENT_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Real spherical harmonics basis constants for bands 0 to 2
+const SH_BASIS: [f32; 9] = [
+    0.282_095, 0.488_603, 0.488_603, 0.488_603, 1.092_548, 1.092_548, 0.315_392, 1.092_548,
+    0.546_274,
+];
+
+/// Convolution of each spherical harmonics band with the clamped cosine lobe
+const SH_COSINE_LOBE: [f32; 9] = [
+    PI,
+    2. * PI / 3.,
+    2. * PI / 3.,
+    2. * PI / 3.,
+    PI / 4.,
+    PI / 4.,
+    PI / 4.,
+    PI / 4.,
+    PI / 4.,
+];
+
+/// Linear RGBA texels of the six faces of a cube map.
+///
+/// Faces are stored in Vulkan layer order: +X, -X, +Y, -Y, +Z, -Z.
+#[derive(Clone, Debug)]
+pub struct CubeFaces {
+    pub size: u32,
+    pub faces: [Vec<[f32; 4]>; 6],
+}
+
+impl CubeFaces {
+    /// Cube map where every texel has the same colour
+    #[must_use]
+    pub fn solid(size: u32, colour: [f32; 4]) -> Self {
+        let texels = vec![colour; (size * size) as usize];
+        Self {
+            size,
+            faces: std::array::from_fn(|_| texels.clone()),
+        }
+    }
+
+    /// Loads six LDR images, in +X, -X, +Y, -Y, +Z, -Z order, converting them from sRGB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an image cannot be read or the faces are not all the same square size.
+    #[must_use]
+    pub fn from_images(paths: [&str; 6]) -> Self {
+        let images = paths.map(|path| {
+            image::open(path)
+                .expect("Failed to open skybox face")
+                .to_rgba8()
+        });
+
+        let size = images[0].width();
+        assert!(
+            images
+                .iter()
+                .all(|image| image.width() == size && image.height() == size),
+            "Skybox faces must be square and share the same size"
+        );
+
+        Self {
+            size,
+            faces: images.map(|image| {
+                image
+                    .pixels()
+                    .map(|pixel| {
+                        let [r, g, b, a] = pixel.0;
+                        [
+                            srgb_to_linear(r),
+                            srgb_to_linear(g),
+                            srgb_to_linear(b),
+                            f32::from(a) / 255.,
+                        ]
+                    })
+                    .collect()
+            }),
+        }
+    }
+
+    /// Resamples an equirectangular (latitude/longitude) image into a cube map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image cannot be read.
+    #[must_use]
+    pub fn from_equirectangular(path: &str, size: u32) -> Self {
+        let image = image::open(path)
+            .expect("Failed to open equirectangular environment")
+            .to_rgba32f();
+
+        let (width, height) = image.dimensions();
+        let texels = image
+            .pixels()
+            .map(|pixel| pixel.0)
+            .collect::<Vec<[f32; 4]>>();
+
+        Self::from_fn(size, |direction| {
+            sample_equirectangular(&texels, width, height, direction)
+        })
+    }
+
+    /// Builds a cube map by evaluating `texel` for the direction through each texel centre.
+    pub fn from_fn(size: u32, mut texel: impl FnMut(Vector3<f32>) -> [f32; 4]) -> Self {
+        Self {
+            size,
+            faces: std::array::from_fn(|face| {
+                (0..size * size)
+                    .map(|index| texel(texel_direction(face, index % size, index / size, size)))
+                    .collect()
+            }),
+        }
+    }
+
+    /// Spherical harmonics coefficients of the irradiance reaching a surface from this
+    /// environment, already convolved with the cosine lobe.
+    #[must_use]
+    pub fn irradiance(&self) -> [[f32; 4]; 9] {
+        let mut coefficients = [[0.; 4]; 9];
+        let mut total_weight = 0.;
+
+        self.faces.iter().enumerate().for_each(|(face, texels)| {
+            texels.iter().enumerate().for_each(|(index, colour)| {
+                let index = index as u32;
+                let (s, t) = texel_coordinates(index % self.size, index / self.size, self.size);
+                let weight = 4. / ((1. + s * s + t * t).powf(1.5) * (self.size * self.size) as f32);
+                let direction =
+                    texel_direction(face, index % self.size, index / self.size, self.size);
+
+                sh_basis(&direction)
+                    .iter()
+                    .zip(coefficients.iter_mut())
+                    .for_each(|(basis, coefficient)| {
+                        (0..3).for_each(|channel| {
+                            coefficient[channel] += colour[channel] * basis * weight;
+                        });
+                    });
+
+                total_weight += weight;
+            });
+        });
+
+        let normalisation = 4. * PI / total_weight;
+
+        coefficients
+            .iter_mut()
+            .zip(SH_COSINE_LOBE)
+            .for_each(|(coefficient, lobe)| {
+                (0..3).for_each(|channel| coefficient[channel] *= normalisation * lobe);
+            });
+
+        coefficients
+    }
+}
+
+/// Evaluates irradiance coefficients returned by [`CubeFaces::irradiance`] for a normal.
+#[must_use]
+pub fn evaluate_irradiance(coefficients: &[[f32; 4]; 9], normal: &Vector3<f32>) -> Vector3<f32> {
+    sh_basis(normal).iter().zip(coefficients).fold(
+        Vector3::zeros(),
+        |irradiance, (basis, coefficient)| {
+            irradiance + Vector3::new(coefficient[0], coefficient[1], coefficient[2]) * *basis
+        },
+    )
+}
+
+fn sh_basis(direction: &Vector3<f32>) -> [f32; 9] {
+    let direction = direction.normalize();
+    let (x, y, z) = (direction.x, direction.y, direction.z);
+    let [c0, c1, c2, c3, c4, c5, c6, c7, c8] = SH_BASIS;
+    [
+        c0,
+        c1 * y,
+        c2 * z,
+        c3 * x,
+        c4 * x * y,
+        c5 * y * z,
+        c6 * (3. * z * z - 1.),
+        c7 * x * z,
+        c8 * (x * x - y * y),
+    ]
+}
+
+fn texel_coordinates(x: u32, y: u32, size: u32) -> (f32, f32) {
+    let s = 2. * (x as f32 + 0.5) / size as f32 - 1.;
+    let t = 2. * (y as f32 + 0.5) / size as f32 - 1.;
+    (s, t)
+}
+
+/// Direction through the centre of a cube map texel, following the Vulkan face orientation.
+fn texel_direction(face: usize, x: u32, y: u32, size: u32) -> Vector3<f32> {
+    let (s, t) = texel_coordinates(x, y, size);
+    match face {
+        0 => Vector3::new(1., -t, -s),
+        1 => Vector3::new(-1., -t, s),
+        2 => Vector3::new(s, 1., t),
+        3 => Vector3::new(s, -1., -t),
+        4 => Vector3::new(s, -t, 1.),
+        _ => Vector3::new(-s, -t, -1.),
+    }
+    .normalize()
+}
+
+fn sample_equirectangular(
+    texels: &[[f32; 4]],
+    width: u32,
+    height: u32,
+    direction: Vector3<f32>,
+) -> [f32; 4] {
+    let u = 0.5 + direction.z.atan2(direction.x) / (2. * PI);
+    let v = direction.y.clamp(-1., 1.).acos() / PI;
+
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0., (height - 1) as f32);
+
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let (fx, fy) = (x - x0, y - y0);
+
+    let texel = |x: f32, y: f32| {
+        let x = (x as i64).rem_euclid(i64::from(width)) as usize;
+        let y = (y as usize).min(height as usize - 1);
+        texels[y * width as usize + x]
+    };
+
+    let (a, b, c, d) = (
+        texel(x0, y0),
+        texel(x0 + 1., y0),
+        texel(x0, y0 + 1.),
+        texel(x0 + 1., y0 + 1.),
+    );
+
+    std::array::from_fn(|channel| {
+        let top = a[channel] + (b[channel] - a[channel]) * fx;
+        let bottom = c[channel] + (d[channel] - c[channel]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = f32::from(value) / 255.;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Environment drawn behind the scene, optionally lighting lit shaders with its
+/// diffuse irradiance and specular reflections.
+#[derive(Clone, Debug)]
+pub struct Skybox {
+    pub(crate) faces: CubeFaces,
+    pub(crate) intensity: f32,
+    pub(crate) lighting: bool,
+}
+
+impl Skybox {
+    #[must_use]
+    pub fn new(faces: CubeFaces) -> Self {
+        Self {
+            faces,
+            intensity: 1.,
+            lighting: false,
+        }
+    }
+
+    /// Skybox from six images in +X, -X, +Y, -Y, +Z, -Z order
+    #[must_use]
+    pub fn from_faces(paths: [&str; 6]) -> Self {
+        Self::new(CubeFaces::from_images(paths))
+    }
+
+    /// Skybox from an equirectangular image such as a `.hdr` panorama
+    #[must_use]
+    pub fn from_equirectangular(path: &str, face_size: u32) -> Self {
+        Self::new(CubeFaces::from_equirectangular(path, face_size))
+    }
+
+    /// Procedural field of stars on a black background.
+    ///
+    /// `density` is the fraction of texels, between 0 and 1, that hold a star.
+    #[must_use]
+    pub fn starfield(face_size: u32, density: f32, seed: u64) -> Self {
+        let mut state = seed.max(1);
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 40) as f32 / (1 << 24) as f32
+        };
+
+        Self::new(CubeFaces::from_fn(face_size, |_| {
+            if random() >= density {
+                return [0., 0., 0., 1.];
+            }
+
+            let brightness = random().powi(3) * 4.;
+            let warmth = random() * 0.3;
+            [
+                brightness * (1. - warmth * 0.5),
+                brightness * (1. - warmth * 0.3),
+                brightness * (1. - warmth * 0.5 + warmth),
+                1.,
+            ]
+        }))
+    }
+
+    /// Scales the environment's radiance
+    #[must_use]
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Uses the environment for the ambient and specular lighting of lit shaders
+    #[must_use]
+    pub fn with_lighting(mut self) -> Self {
+        self.lighting = true;
+        self
+    }
+}
+
+/// The environment cube map on the GPU and the lighting derived from it
+#[derive(Debug)]
+pub(crate) struct EnvironmentMap {
+    pub texture: Texture,
+    pub irradiance: [[f32; 4]; 9],
+    pub intensity: f32,
+    pub lighting: bool,
+}
+
+impl EnvironmentMap {
+    /// Uploads the skybox's cube map, or a black placeholder without lighting when there is none.
+    pub fn new(
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        devices: &Devices,
+        skybox: Option<&Skybox>,
+    ) -> Self {
+        let placeholder;
+        let (faces, intensity, lighting) = match skybox {
+            Some(skybox) => (&skybox.faces, skybox.intensity, skybox.lighting),
+            None => {
+                placeholder = CubeFaces::solid(1, [0., 0., 0., 1.]);
+                (&placeholder, 0., false)
+            }
+        };
+
+        let texture = create_cube_texture(allocator, command_pool, instance, devices, faces);
+        texture.set_name(devices, "skybox/environment");
+
+        Self {
+            texture,
+            irradiance: if lighting {
+                faces.irradiance()
+            } else {
+                [[0.; 4]; 9]
+            },
+            intensity,
+            lighting,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The cube map must no longer be in use by the device.
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.destroy_sampler(self.texture.sampler, None);
+        device.destroy_image_view(self.texture.view, None);
+        device.destroy_image(self.texture.image.image, None);
+        device.free_memory(self.texture.image.memory, None);
+    }
+}
+
+fn create_cube_texture(
+    allocator: &mut Allocator,
+    command_pool: &CommandPool,
+    instance: &Instance,
+    devices: &Devices,
+    faces: &CubeFaces,
+) -> Texture {
+    let device = &devices.logical.device;
+    let size = faces.size;
+    let mip_levels = (size as f32).log2().floor() as u32 + 1;
+
+    let data = faces
+        .faces
+        .iter()
+        .flatten()
+        .flatten()
+        .flat_map(|channel| f16::from_f32(*channel).to_bits().to_ne_bytes())
+        .collect::<Vec<u8>>();
+
+    let mut staging = texture::create_buffer(
+        allocator,
+        data.len() as vk::DeviceSize,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        devices,
+        "Environment Staging Buffer",
+    );
+
+    staging
+        .allocation
+        .mapped_slice_mut()
+        .expect("Environment staging buffer is not host visible")[..data.len()]
+        .copy_from_slice(&data);
+
+    let image_info = ImageInfo::new(
+        (size, size),
+        mip_levels,
+        vk::SampleCountFlags::TYPE_1,
+        ENVIRONMENT_FORMAT,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::TRANSFER_DST
+            | vk::ImageUsageFlags::SAMPLED,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )
+    .cube();
+
+    let image = utility::create_image(image_info, instance, devices).mip_levels(mip_levels);
+
+    texture::upload_image(
+        devices,
+        command_pool,
+        (size, size),
+        staging,
+        image.image,
+        mip_levels,
+        6,
+    );
+
+    texture::generate_mip_maps(
+        MipChain {
+            image: image.image,
+            format: ENVIRONMENT_FORMAT,
+            extent: Point2::new(size as i32, size as i32),
+            mip_levels,
+            layer_count: 6,
+        },
+        command_pool,
+        instance,
+        devices,
+    );
+
+    let view_info = vk::ImageViewCreateInfo::default()
+        .image(image.image)
+        .view_type(vk::ImageViewType::CUBE)
+        .format(ENVIRONMENT_FORMAT)
+        .subresource_range(
+            vk::ImageSubresourceRange::default()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(mip_levels)
+                .base_array_layer(0)
+                .layer_count(6),
+        );
+
+    let sampler_info = vk::SamplerCreateInfo::default()
+        .mag_filter(vk::Filter::LINEAR)
+        .min_filter(vk::Filter::LINEAR)
+        .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .min_lod(0.)
+        .max_lod(mip_levels as f32);
+
+    unsafe {
+        let view = device
+            .create_image_view(&view_info, None)
+            .expect("Failed to create environment image view!");
+        let sampler = device
+            .create_sampler(&sampler_info, None)
+            .expect("Failed to create environment sampler!");
+
+        Texture {
+            image,
+            view,
+            sampler,
+        }
+    }
+}
+
+/// Pipeline drawing the environment cube map behind everything else
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SkyboxPipeline {
+    pub pipeline: vk::Pipeline,
+    pub layout: vk::PipelineLayout,
+}
+
+impl SkyboxPipeline {
+    pub fn new(
+        devices: &Devices,
+        render_pass: vk::RenderPass,
+        samples: vk::SampleCountFlags,
+        scene_set_layout: vk::DescriptorSetLayout,
+    ) -> Self {
+        let device = &devices.logical.device;
+        let ShaderModules { vert, frag } = graphics_pipeline::load_shader_stages("skybox", device);
+
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .size(mem::size_of::<nalgebra::Matrix4<f32>>() as u32)
+            .offset(0);
+
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(std::slice::from_ref(&scene_set_layout))
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+
+        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default();
+
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+        let view_port_state = vk::PipelineViewportStateCreateInfo::default()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+
+        let multi_sampling =
+            vk::PipelineMultisampleStateCreateInfo::default().rasterization_samples(samples);
+
+        let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .max_depth_bounds(1.);
+
+        let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .blend_enable(false);
+
+        let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
+            .attachments(std::slice::from_ref(&color_blend_attachment));
+
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+        let dynamic_state_create_info =
+            vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+        unsafe {
+            let layout = device
+                .create_pipeline_layout(&layout_info, None)
+                .expect("Failed to create skybox pipeline layout!");
+
+            let entry_point = c"main";
+            let stages = [
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::VERTEX)
+                    .module(vert)
+                    .name(entry_point),
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::FRAGMENT)
+                    .module(frag)
+                    .name(entry_point),
+            ];
+
+            let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+                .stages(&stages)
+                .vertex_input_state(&vertex_input_info)
+                .input_assembly_state(&input_assembly)
+                .viewport_state(&view_port_state)
+                .rasterization_state(&rasterizer)
+                .multisample_state(&multi_sampling)
+                .depth_stencil_state(&depth_stencil)
+                .color_blend_state(&color_blending)
+                .dynamic_state(&dynamic_state_create_info)
+                .layout(layout)
+                .render_pass(render_pass)
+                .subpass(0);
+
+            let pipeline = device
+                .create_graphics_pipelines(
+                    vk::PipelineCache::null(),
+                    std::slice::from_ref(&pipeline_info),
+                    None,
+                )
+                .expect("Failed to create skybox pipeline!")[0];
+
+            graphics_pipeline::destroy_shader_modules(device, vert, frag);
+
+            devices.debug_names.name(pipeline, "skybox/pipeline");
+
+            Self { pipeline, layout }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The pipeline must no longer be in use by the device.
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.destroy_pipeline(self.pipeline, None);
+        device.destroy_pipeline_layout(self.layout, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniform_environment_irradiance() {
+        let faces = CubeFaces::solid(8, [0.5, 0.25, 1., 1.]);
+        let coefficients = faces.irradiance();
+
+        [Vector3::x(), -Vector3::y(), Vector3::new(1., 1., -1.)]
+            .iter()
+            .for_each(|normal| {
+                let irradiance = evaluate_irradiance(&coefficients, normal);
+                let expected = Vector3::new(0.5, 0.25, 1.) * PI;
+                assert!((irradiance - expected).norm() < 1e-3);
+            });
+    }
+
+    #[test]
+    fn test_texel_directions_face_outwards() {
+        let axes = [
+            Vector3::x(),
+            -Vector3::x(),
+            Vector3::y(),
+            -Vector3::y(),
+            Vector3::z(),
+            -Vector3::z(),
+        ];
+
+        axes.iter().enumerate().for_each(|(face, axis)| {
+            let centre = texel_direction(face, 1, 1, 3);
+            assert!((centre - axis).norm() < 1e-6);
+        });
+    }
+}
@@ -2,32 +2,50 @@ use crate::{renderer::RenderPass, resource::Resources, swap_chain::SwapChain};
 use ash::{vk, Device};
 use derive_more::{Deref, From};
 
-#[derive(Debug, From, Deref, Clone)]
+#[derive(Debug, Default, From, Deref, Clone)]
 pub struct FrameBuffers(pub(crate) Vec<vk::Framebuffer>);
 
-pub(crate) fn create_frame_buffers(
-    swap_chain: &SwapChain,
+/// Frame buffer the scene is drawn into, resolving into the HDR scene image when
+/// multisampled
+pub(crate) fn create_scene_frame_buffer(
     render_pass: &RenderPass,
     device: &Device,
     resources: &Resources,
-) -> FrameBuffers {
-    let mut frame_buffers = Vec::new();
-
-    for i in 0..swap_chain.images.len() {
-        let attachments = &[
-            resources.colour.view,
-            resources.depth.view,
-            swap_chain.image_views[i],
-        ];
-
-        let vk::Extent2D { width, height } = swap_chain.extent;
-
-        let frame_buffer = create_frame_buffer(render_pass, attachments, device, width, height);
+) -> vk::Framebuffer {
+    let vk::Extent2D { width, height } = resources.extent;
 
-        frame_buffers.push(frame_buffer);
+    match &resources.colour {
+        Some(colour) => create_frame_buffer(
+            render_pass,
+            &[colour.view, resources.depth.view, resources.scene.view],
+            device,
+            width,
+            height,
+        ),
+        None => create_frame_buffer(
+            render_pass,
+            &[resources.scene.view, resources.depth.view],
+            device,
+            width,
+            height,
+        ),
     }
+}
+
+/// One frame buffer for each swap chain image, written by the last post processing pass
+pub(crate) fn create_frame_buffers(
+    swap_chain: &SwapChain,
+    render_pass: &RenderPass,
+    device: &Device,
+) -> FrameBuffers {
+    let vk::Extent2D { width, height } = swap_chain.extent;
 
-    frame_buffers.into()
+    swap_chain
+        .image_views
+        .iter()
+        .map(|view| create_frame_buffer(render_pass, &[*view], device, width, height))
+        .collect::<Vec<vk::Framebuffer>>()
+        .into()
 }
 
 pub(crate) fn create_frame_buffer<const N: usize>(
@@ -0,0 +1,408 @@
+use ash::{prelude::VkResult, vk, Entry, Instance};
+use std::{ffi::CStr, fmt};
+
+/// Environment variable overriding the device chosen by the application, holding either
+/// the index reported by [`available_devices`] or part of the device name
+pub const DEVICE_ENV_VAR: &str = "WAVE_DEVICE";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceType {
+    Discrete,
+    Integrated,
+    Virtual,
+    Cpu,
+    Other,
+}
+
+impl From<vk::PhysicalDeviceType> for DeviceType {
+    fn from(device_type: vk::PhysicalDeviceType) -> Self {
+        match device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => Self::Discrete,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => Self::Integrated,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => Self::Virtual,
+            vk::PhysicalDeviceType::CPU => Self::Cpu,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Discrete => "discrete GPU",
+            Self::Integrated => "integrated GPU",
+            Self::Virtual => "virtual GPU",
+            Self::Cpu => "CPU",
+            Self::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl From<u32> for Version {
+    fn from(version: u32) -> Self {
+        Self {
+            major: vk::api_version_major(version),
+            minor: vk::api_version_minor(version),
+            patch: vk::api_version_patch(version),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Limits of a device that affect what the renderer can do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeviceLimits {
+    pub max_image_dimension_2d: u32,
+    pub max_sampler_anisotropy: f32,
+    /// Highest MSAA sample count usable for both colour and depth
+    pub max_msaa_samples: u32,
+    pub max_push_constants_size: u32,
+    pub max_bound_descriptor_sets: u32,
+    pub max_uniform_buffer_range: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryHeap {
+    /// Size in bytes
+    pub size: u64,
+    /// Whether the heap is memory local to the device, rather than shared with the host
+    pub device_local: bool,
+}
+
+/// Description of a physical device, as reported by the driver
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceInfo {
+    /// Position in the driver's list of devices, usable with [`DeviceSelection::Index`]
+    pub index: usize,
+    pub name: String,
+    pub device_type: DeviceType,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    /// Driver name and version, in the driver's own format
+    pub driver: String,
+    /// Highest Vulkan version the device supports
+    pub api_version: Version,
+    pub limits: DeviceLimits,
+    pub memory_heaps: Vec<MemoryHeap>,
+}
+
+impl DeviceInfo {
+    pub(crate) fn new(instance: &Instance, device: vk::PhysicalDevice, index: usize) -> Self {
+        let properties = unsafe { instance.get_physical_device_properties(device) };
+        let memory = unsafe { instance.get_physical_device_memory_properties(device) };
+        let limits = properties.limits;
+
+        let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+
+        let api_version = Version::from(properties.api_version);
+
+        // Driver properties are core from Vulkan 1.2, older devices only report a number
+        let driver = if properties.api_version >= vk::API_VERSION_1_2 {
+            let mut driver_properties = vk::PhysicalDeviceDriverProperties::default();
+            let mut properties2 =
+                vk::PhysicalDeviceProperties2::default().push_next(&mut driver_properties);
+            unsafe { instance.get_physical_device_properties2(device, &mut properties2) };
+
+            let driver_name = driver_properties
+                .driver_name_as_c_str()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let driver_info = driver_properties
+                .driver_info_as_c_str()
+                .map(|info| info.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            format!("{driver_name} {driver_info}").trim().to_owned()
+        } else {
+            properties.driver_version.to_string()
+        };
+
+        let sample_counts =
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+        let max_msaa_samples = [64, 32, 16, 8, 4, 2]
+            .into_iter()
+            .find(|count| sample_counts.contains(vk::SampleCountFlags::from_raw(*count)))
+            .unwrap_or(1);
+
+        let memory_heaps = memory.memory_heaps[..memory.memory_heap_count as usize]
+            .iter()
+            .map(|heap| MemoryHeap {
+                size: heap.size,
+                device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+            })
+            .collect();
+
+        Self {
+            index,
+            name,
+            device_type: properties.device_type.into(),
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            driver,
+            api_version,
+            limits: DeviceLimits {
+                max_image_dimension_2d: limits.max_image_dimension2_d,
+                max_sampler_anisotropy: limits.max_sampler_anisotropy,
+                max_msaa_samples,
+                max_push_constants_size: limits.max_push_constants_size,
+                max_bound_descriptor_sets: limits.max_bound_descriptor_sets,
+                max_uniform_buffer_range: limits.max_uniform_buffer_range,
+            },
+            memory_heaps,
+        }
+    }
+
+    /// Total size in bytes of the heaps local to the device
+    pub fn device_local_memory(&self) -> u64 {
+        self.memory_heaps
+            .iter()
+            .filter(|heap| heap.device_local)
+            .map(|heap| heap.size)
+            .sum()
+    }
+
+    /// Preference used when no device is requested: discrete GPUs first, then integrated,
+    /// with more local memory breaking ties
+    fn score(&self) -> u64 {
+        let type_score = match self.device_type {
+            DeviceType::Discrete => 4,
+            DeviceType::Integrated => 3,
+            DeviceType::Virtual => 2,
+            DeviceType::Cpu => 1,
+            DeviceType::Other => 0,
+        };
+
+        (type_score << 48) + (self.device_local_memory() >> 20)
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MIB: u64 = 1024 * 1024;
+
+        writeln!(f, "[{}] {} ({})", self.index, self.name, self.device_type)?;
+        writeln!(
+            f,
+            "    vendor {:#06x}, device {:#06x}",
+            self.vendor_id, self.device_id
+        )?;
+        writeln!(f, "    driver: {}", self.driver)?;
+        writeln!(f, "    vulkan: {}", self.api_version)?;
+        writeln!(
+            f,
+            "    max image size {}, anisotropy {}x, MSAA {}x, push constants {} bytes",
+            self.limits.max_image_dimension_2d,
+            self.limits.max_sampler_anisotropy,
+            self.limits.max_msaa_samples,
+            self.limits.max_push_constants_size,
+        )?;
+        self.memory_heaps.iter().try_for_each(|heap| {
+            let location = if heap.device_local { "device" } else { "host" };
+            writeln!(f, "    {location} heap: {} MiB", heap.size / MIB)
+        })
+    }
+}
+
+/// Which device to render with.
+///
+/// [`DEVICE_ENV_VAR`] takes precedence over the selection made in code. If neither matches a
+/// suitable device, a warning is logged and the device is chosen as with [`Self::Auto`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DeviceSelection {
+    /// The highest scoring suitable device, preferring discrete GPUs
+    #[default]
+    Auto,
+    /// The first suitable device whose name contains this, ignoring case
+    Name(String),
+    /// The device at this index in [`available_devices`]
+    Index(usize),
+}
+
+impl DeviceSelection {
+    /// Reads [`DEVICE_ENV_VAR`], where a number is an index and anything else a name
+    pub fn from_env() -> Option<Self> {
+        std::env::var(DEVICE_ENV_VAR)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| Self::parse(value.trim()))
+    }
+
+    fn parse(value: &str) -> Self {
+        value
+            .parse()
+            .map_or_else(|_| Self::Name(value.to_owned()), Self::Index)
+    }
+
+    /// [`Self::from_env`] if set, otherwise `configured`
+    pub fn resolve(configured: &Self) -> Self {
+        Self::from_env().unwrap_or_else(|| configured.clone())
+    }
+
+    /// Position in `candidates` of the selected device, falling back to [`Self::Auto`] when
+    /// none matches. Only `None` if there are no candidates.
+    pub fn choose_or_auto(&self, candidates: &[DeviceInfo]) -> Option<usize> {
+        self.choose(candidates).or_else(|| {
+            log::warn!("Couldn't find a suitable device matching {self}, choosing automatically");
+            Self::Auto.choose(candidates)
+        })
+    }
+
+    /// Position in `candidates` of the selected device, if any matches
+    pub fn choose(&self, candidates: &[DeviceInfo]) -> Option<usize> {
+        match self {
+            Self::Auto => candidates
+                .iter()
+                .enumerate()
+                .max_by_key(|(position, info)| (info.score(), std::cmp::Reverse(*position)))
+                .map(|(position, _)| position),
+            Self::Name(name) => {
+                let name = name.to_lowercase();
+                candidates
+                    .iter()
+                    .position(|info| info.name.to_lowercase().contains(&name))
+            }
+            Self::Index(index) => candidates.iter().position(|info| info.index == *index),
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("automatic"),
+            Self::Name(name) => write!(f, "name containing \"{name}\""),
+            Self::Index(index) => write!(f, "index {index}"),
+        }
+    }
+}
+
+/// Describes every device the Vulkan driver exposes, without needing a window.
+///
+/// Devices that cannot present to a particular window are still listed.
+pub fn available_devices() -> Vec<DeviceInfo> {
+    unsafe {
+        let entry = Entry::load().expect("Failed to load the Vulkan library");
+
+        let instance = create_headless_instance(&entry).expect("Instance creation error");
+
+        let devices = instance
+            .enumerate_physical_devices()
+            .expect("Failed to find GPUs with Vulkan support!")
+            .into_iter()
+            .enumerate()
+            .map(|(index, device)| DeviceInfo::new(&instance, device, index))
+            .collect();
+
+        instance.destroy_instance(None);
+
+        devices
+    }
+}
+
+/// Instance without the window system extensions, for listing devices and for compute work
+/// that never presents.
+///
+/// # Safety
+///
+/// `entry` must outlive the instance.
+pub(crate) unsafe fn create_headless_instance(entry: &Entry) -> VkResult<Instance> {
+    let app_info = vk::ApplicationInfo::default().api_version(vk::API_VERSION_1_3);
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    let extension_names_raw = [
+        vk::KHR_PORTABILITY_ENUMERATION_NAME.as_ptr(),
+        vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_NAME.as_ptr(),
+    ];
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    let extension_names_raw = [];
+
+    let create_flags = if cfg!(any(target_os = "macos", target_os = "ios")) {
+        vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
+    } else {
+        vk::InstanceCreateFlags::default()
+    };
+
+    let create_info = vk::InstanceCreateInfo::default()
+        .application_info(&app_info)
+        .flags(create_flags)
+        .enabled_extension_names(&extension_names_raw);
+
+    entry.create_instance(&create_info, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(index: usize, name: &str, device_type: DeviceType, memory_mib: u64) -> DeviceInfo {
+        DeviceInfo {
+            index,
+            name: name.to_owned(),
+            device_type,
+            vendor_id: 0,
+            device_id: 0,
+            driver: String::new(),
+            api_version: Version::from(vk::API_VERSION_1_3),
+            limits: DeviceLimits {
+                max_image_dimension_2d: 0,
+                max_sampler_anisotropy: 1.,
+                max_msaa_samples: 1,
+                max_push_constants_size: 0,
+                max_bound_descriptor_sets: 0,
+                max_uniform_buffer_range: 0,
+            },
+            memory_heaps: vec![MemoryHeap {
+                size: memory_mib << 20,
+                device_local: true,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_selection_prefers_discrete_and_honours_overrides() {
+        let candidates = [
+            device(0, "Intel(R) UHD Graphics", DeviceType::Integrated, 8192),
+            device(1, "llvmpipe", DeviceType::Cpu, 16384),
+            device(
+                2,
+                "NVIDIA GeForce RTX 3060 Laptop GPU",
+                DeviceType::Discrete,
+                6144,
+            ),
+        ];
+
+        assert_eq!(DeviceSelection::Auto.choose(&candidates), Some(2));
+        assert_eq!(
+            DeviceSelection::Name("intel".to_owned()).choose(&candidates),
+            Some(0)
+        );
+        assert_eq!(DeviceSelection::Index(1).choose(&candidates), Some(1));
+        assert_eq!(DeviceSelection::Index(5).choose(&candidates), None);
+        assert_eq!(
+            DeviceSelection::Index(5).choose_or_auto(&candidates),
+            Some(2)
+        );
+        assert_eq!(DeviceSelection::Auto.choose_or_auto(&[]), None);
+
+        assert_eq!(DeviceSelection::parse("1"), DeviceSelection::Index(1));
+        assert_eq!(
+            DeviceSelection::parse("RTX"),
+            DeviceSelection::Name("RTX".to_owned())
+        );
+    }
+}
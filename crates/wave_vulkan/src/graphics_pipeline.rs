@@ -1,10 +1,6 @@
 use crate::{
-    buffer::Buffer,
-    device::Devices,
-    swap_chain::SwapChain,
-    texture::{self, Texture},
-    uniform_buffer::UniformBufferObject,
-    CullMode, ModelTopology, Shader,
+    any_as_u8_slice, buffer::Buffer, device::Devices, swap_chain::SwapChain, texture,
+    uniform_buffer::UniformBufferObject, BlendMode, CullMode, ModelTopology, Shader,
 };
 use ash::{vk, Device};
 use gpu_allocator::vulkan::Allocator;
@@ -13,11 +9,81 @@ use smallvec::{smallvec, SmallVec};
 use std::{ffi::CStr, mem};
 use wave_space::space::Vertex;
 
-#[derive(Default, Debug, Clone)]
-pub struct Descriptor {
+/// Specialization constant holding the fragment shaders' alpha test cutoff.
+const ALPHA_CUTOFF_CONSTANT_ID: u32 = 0;
+/// Specialization constant telling lit fragment shaders to sample the shadow maps.
+const RECEIVE_SHADOWS_CONSTANT_ID: u32 = 1;
+
+/// Fragment specialization constants, laid out as the map entries read them
+#[repr(C)]
+struct FragmentConstants {
+    alpha_cutoff: f32,
+    receive_shadows: vk::Bool32,
+}
+
+/// Material maps bound after the factors in the material set
+pub(crate) const MATERIAL_MAP_COUNT: u32 = 5;
+
+/// Bytes of vertex push constants available to every pipeline, enough for one matrix
+const PUSH_CONSTANT_SIZE: u32 = 64;
+
+/// Per object descriptor sets, one for each swap chain image, holding the object's uniforms
+#[derive(Default, Debug)]
+pub struct ObjectDescriptors {
     pub sets: Vec<vk::DescriptorSet>,
     pub pool: vk::DescriptorPool,
-    pub set_layout: vk::DescriptorSetLayout,
+    pub uniform_buffers: Vec<Buffer>,
+}
+
+impl ObjectDescriptors {
+    pub fn new(
+        allocator: &mut Allocator,
+        swap_chain_image_count: u32,
+        set_layout: vk::DescriptorSetLayout,
+        name: &str,
+        devices: &Devices,
+    ) -> Self {
+        let device = &devices.logical.device;
+
+        let pool = create_descriptor_pool(device, swap_chain_image_count);
+
+        let uniform_buffers = create_uniform_buffers(allocator, swap_chain_image_count, devices);
+
+        let sets = create_descriptor_sets(
+            device,
+            set_layout,
+            pool,
+            swap_chain_image_count as usize,
+            &uniform_buffers,
+        );
+
+        sets.iter()
+            .zip(&uniform_buffers)
+            .enumerate()
+            .for_each(|(image, (set, buffer))| {
+                let names = &devices.debug_names;
+                names.name(*set, &format!("{name}/descriptor set {image}"));
+                names.name(buffer.buffer, &format!("{name}/uniform buffer {image}"));
+            });
+
+        Self {
+            sets,
+            pool,
+            uniform_buffers,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The descriptor sets must no longer be in use by the device.
+    pub unsafe fn destroy(self, allocator: &mut Allocator, device: &Device) {
+        for buffer in self.uniform_buffers {
+            allocator.free(buffer.allocation).unwrap();
+            device.destroy_buffer(buffer.buffer, None);
+        }
+
+        device.destroy_descriptor_pool(self.pool, None);
+    }
 }
 
 #[derive(Default, Debug, Clone)]
@@ -41,174 +107,183 @@ impl GraphicsPipelineFeatures {
     }
 }
 
+/// Descriptor set layouts and the pipeline layout shared by every material's pipeline.
+///
+/// Set 0 holds the object's uniforms, set 1 the material and set 2 the scene.
 #[derive(Default, Debug)]
-pub struct GraphicsPipeline {
-    pub features: GraphicsPipelineFeatures,
-    pub descriptors: Descriptor,
+pub(crate) struct PipelineLayouts {
+    pub object_set_layout: vk::DescriptorSetLayout,
+    pub material_set_layout: vk::DescriptorSetLayout,
+    pub layout: vk::PipelineLayout,
+}
+
+impl PipelineLayouts {
+    pub fn new(device: &Device, scene_set_layout: vk::DescriptorSetLayout) -> Self {
+        let object_set_layout = create_object_set_layout(device);
+        let material_set_layout = create_material_set_layout(device);
+
+        let set_layouts = [object_set_layout, material_set_layout, scene_set_layout];
+
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .size(PUSH_CONSTANT_SIZE)
+            .offset(0);
+
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+
+        let layout = unsafe {
+            device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Failed to create pipeline layout!")
+        };
+
+        Self {
+            object_set_layout,
+            material_set_layout,
+            layout,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// No pipeline created with these layouts may still be in use by the device.
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.destroy_pipeline_layout(self.layout, None);
+        device.destroy_descriptor_set_layout(self.material_set_layout, None);
+        device.destroy_descriptor_set_layout(self.object_set_layout, None);
+    }
+}
+
+/// Shader and render state a [`GraphicsPipeline`] draws with
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct PipelineState {
     pub topology: ModelTopology,
     pub cull_mode: CullMode,
+    pub blend_mode: BlendMode,
+    pub receive_shadows: bool,
     pub shader_type: Shader,
-    pub uniform_buffers: Vec<Buffer>,
+}
+
+/// Pipeline drawing every object that uses one material
+#[derive(Default, Debug)]
+pub struct GraphicsPipeline {
+    pub features: GraphicsPipelineFeatures,
+    pub state: PipelineState,
+    /// Name of the material, which the pipeline is named after for debugging
+    pub name: String,
 }
 
 impl GraphicsPipeline {
     pub fn new(
-        allocator: &mut Allocator,
         swap_chain: &SwapChain,
         render_pass: vk::RenderPass,
-        texture: &Option<Texture>,
-        topology: ModelTopology,
-        cull_mode: CullMode,
-        shader_type: Shader,
+        layout: vk::PipelineLayout,
+        state: PipelineState,
+        name: &str,
         devices: &Devices,
     ) -> Self {
-        let descriptor_set_layout =
-            create_descriptor_set_layout(&devices.logical.device, shader_type);
-
-        let features = create_pipeline_and_layout(
-            devices,
-            swap_chain,
-            &descriptor_set_layout,
-            render_pass,
-            topology,
-            cull_mode,
-            shader_type,
-        );
-
-        let descriptor_pool =
-            create_descriptor_pool(&devices.logical.device, swap_chain.images.len() as u32);
-
-        let uniform_buffers =
-            create_uniform_buffers(allocator, swap_chain.images.len() as u32, devices);
-
-        let descriptor_sets = if shader_type == Shader::PushConstant {
-            create_descriptor_set(
-                &devices.logical.device,
-                descriptor_pool,
-                texture.as_ref().unwrap(),
-            )
-        } else {
-            create_descriptor_sets(
-                &devices.logical.device,
-                descriptor_set_layout,
-                descriptor_pool,
-                swap_chain.images.len(),
-                texture,
-                &uniform_buffers,
-            )
-        };
-
-        let descriptors = Descriptor {
-            sets: descriptor_sets,
-            pool: descriptor_pool,
-            set_layout: descriptor_set_layout,
-        };
+        let features = create_pipeline(devices, swap_chain, layout, render_pass, state);
 
-        Self {
+        let pipeline = Self {
             features,
-            descriptors,
-            topology,
-            cull_mode,
-            shader_type,
-            uniform_buffers,
-        }
+            state,
+            name: name.to_owned(),
+        };
+        pipeline.set_name(devices);
+        pipeline
     }
 
+    /// Rebuilds the pipeline after the render pass was recreated.
+    ///
+    /// The shared layout does not depend on the swap chain and is kept.
     pub fn recreate(
-        &self,
-        allocator: &mut Allocator,
+        &mut self,
         swap_chain: &SwapChain,
         render_pass: vk::RenderPass,
-        texture: &Option<Texture>,
         devices: &Devices,
-    ) -> Self {
-        let descriptor_set_layout =
-            create_descriptor_set_layout(&devices.logical.device, self.shader_type);
-
-        let features = create_pipeline_and_layout(
+    ) {
+        self.features = create_pipeline(
             devices,
             swap_chain,
-            &descriptor_set_layout,
+            self.features.layout,
             render_pass,
-            self.topology,
-            self.cull_mode,
-            self.shader_type,
+            self.state,
         );
+        self.set_name(devices);
+    }
 
-        let descriptor_pool =
-            create_descriptor_pool(&devices.logical.device, swap_chain.images.len() as u32);
+    fn set_name(&self, devices: &Devices) {
+        devices
+            .debug_names
+            .name(self.features.pipeline, &format!("{}/pipeline", self.name));
+    }
 
-        let uniform_buffers =
-            create_uniform_buffers(allocator, swap_chain.images.len() as u32, devices);
+    /// # Safety
+    ///
+    /// The pipeline must no longer be in use by the device.
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.destroy_pipeline(self.features.pipeline, None);
 
-        let descriptor_sets = create_descriptor_sets(
-            &devices.logical.device,
-            descriptor_set_layout,
-            descriptor_pool,
-            swap_chain.images.len(),
-            texture,
-            &uniform_buffers,
-        );
+        if let Some(cache) = self.features.cache {
+            device.destroy_pipeline_cache(cache, None);
+        }
+    }
+}
 
-        let descriptor_set = Descriptor {
-            sets: descriptor_sets,
-            pool: descriptor_pool,
-            set_layout: descriptor_set_layout,
-        };
+fn create_object_set_layout(device: &Device) -> vk::DescriptorSetLayout {
+    let binding = vk::DescriptorSetLayoutBinding::default()
+        .binding(0)
+        .descriptor_count(1)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+        .stage_flags(vk::ShaderStageFlags::VERTEX);
 
-        Self {
-            features,
-            uniform_buffers,
-            descriptors: descriptor_set,
-            topology: self.topology,
-            cull_mode: self.cull_mode,
-            shader_type: self.shader_type,
-        }
+    let layout_info =
+        vk::DescriptorSetLayoutCreateInfo::default().bindings(std::slice::from_ref(&binding));
+
+    unsafe {
+        device
+            .create_descriptor_set_layout(&layout_info, None)
+            .expect("Failed to create descriptor set layout")
     }
 }
 
-fn create_descriptor_set_layout(device: &Device, shader_type: Shader) -> vk::DescriptorSetLayout {
-    let mut bindings: SmallVec<[vk::DescriptorSetLayoutBinding; 2]> = smallvec![
+/// Material factors at binding 0, followed by the base colour, metallic-roughness,
+/// normal, occlusion and emissive maps
+fn create_material_set_layout(device: &Device) -> vk::DescriptorSetLayout {
+    let bindings = std::iter::once(
         vk::DescriptorSetLayoutBinding::default()
             .binding(0)
             .descriptor_count(1)
             .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-            .stage_flags(vk::ShaderStageFlags::VERTEX),
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+    )
+    .chain((1..=MATERIAL_MAP_COUNT).map(|binding| {
         vk::DescriptorSetLayoutBinding::default()
-            .binding(1)
+            .binding(binding)
             .descriptor_count(1)
-            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .stage_flags(vk::ShaderStageFlags::FRAGMENT),
-    ];
-    if shader_type == Shader::PushConstant {
-        bindings = smallvec![vk::DescriptorSetLayoutBinding::default()
             .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
             .stage_flags(vk::ShaderStageFlags::FRAGMENT)
-            .binding(0)
-            .descriptor_count(1)];
-    };
+    }))
+    .collect::<SmallVec<[vk::DescriptorSetLayoutBinding; 6]>>();
 
     let layout_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
 
     unsafe {
         device
             .create_descriptor_set_layout(&layout_info, None)
-            .expect("Failed to create descriptor set layout")
+            .expect("Failed to create material descriptor set layout")
     }
 }
 
 fn create_descriptor_pool(device: &Device, swap_chain_image_count: u32) -> vk::DescriptorPool {
-    let pool_sizes = &[
-        vk::DescriptorPoolSize::default()
-            .ty(vk::DescriptorType::UNIFORM_BUFFER)
-            .descriptor_count(swap_chain_image_count),
-        vk::DescriptorPoolSize::default()
-            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .descriptor_count(swap_chain_image_count),
-    ];
+    let pool_size = vk::DescriptorPoolSize::default()
+        .ty(vk::DescriptorType::UNIFORM_BUFFER)
+        .descriptor_count(swap_chain_image_count);
 
     let pool_info = vk::DescriptorPoolCreateInfo::default()
-        .pool_sizes(pool_sizes)
+        .pool_sizes(std::slice::from_ref(&pool_size))
         .max_sets(swap_chain_image_count);
 
     unsafe {
@@ -239,7 +314,7 @@ fn create_uniform_buffers(
     buffers
 }
 
-fn create_shader_module(device: &Device, path: &str) -> vk::ShaderModule {
+pub(crate) fn create_shader_module(device: &Device, path: &str) -> vk::ShaderModule {
     let mut file = std::fs::File::open(path).unwrap();
     let spv = ash::util::read_spv(&mut file).unwrap();
 
@@ -252,16 +327,21 @@ fn create_shader_module(device: &Device, path: &str) -> vk::ShaderModule {
     }
 }
 
-fn create_pipeline_and_layout(
+fn create_pipeline(
     devices: &Devices,
     swap_chain: &SwapChain,
-    descriptor_set_layout: &vk::DescriptorSetLayout,
+    layout: vk::PipelineLayout,
     render_pass: vk::RenderPass,
-    topology: ModelTopology,
-    cull_mode: CullMode,
-    shader_type: Shader,
+    state: PipelineState,
 ) -> GraphicsPipelineFeatures {
     let device = &devices.logical.device;
+    let PipelineState {
+        topology,
+        cull_mode,
+        blend_mode,
+        receive_shadows,
+        shader_type,
+    } = state;
 
     let shader_modules = create_shader_stages(shader_type, device);
 
@@ -270,7 +350,7 @@ fn create_pipeline_and_layout(
         .stride(mem::size_of::<Vertex>().try_into().unwrap())
         .input_rate(vk::VertexInputRate::VERTEX);
 
-    let mut attribute_descriptions: SmallVec<[vk::VertexInputAttributeDescription; 2]> = smallvec![
+    let mut attribute_descriptions: SmallVec<[vk::VertexInputAttributeDescription; 6]> = smallvec![
         vk::VertexInputAttributeDescription::default()
             .binding(0)
             .location(0)
@@ -298,6 +378,22 @@ fn create_pipeline_and_layout(
         ]);
     }
 
+    // Normal mapped shaders also read the tangent frame
+    if matches!(shader_type, Shader::LightTexture | Shader::Pbr) {
+        attribute_descriptions.extend([
+            vk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(4)
+                .format(vk::Format::R32G32B32_SFLOAT)
+                .offset(offset_of!(Vertex, tangent) as u32),
+            vk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(5)
+                .format(vk::Format::R32G32B32_SFLOAT)
+                .offset(offset_of!(Vertex, bitangent) as u32),
+        ]);
+    }
+
     let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
         .vertex_binding_descriptions(std::slice::from_ref(&binding_description))
         .vertex_attribute_descriptions(&attribute_descriptions);
@@ -352,7 +448,7 @@ fn create_pipeline_and_layout(
 
     let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
         .depth_test_enable(true)
-        .depth_write_enable(true)
+        .depth_write_enable(!blend_mode.is_transparent())
         .depth_compare_op(vk::CompareOp::LESS)
         .depth_bounds_test_enable(false)
         .stencil_test_enable(false)
@@ -361,15 +457,7 @@ fn create_pipeline_and_layout(
         .min_depth_bounds(0.)
         .max_depth_bounds(1.);
 
-    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
-        .color_write_mask(vk::ColorComponentFlags::RGBA)
-        .blend_enable(true)
-        .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-        .color_blend_op(vk::BlendOp::ADD)
-        .src_alpha_blend_factor(vk::BlendFactor::ONE)
-        .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
-        .alpha_blend_op(vk::BlendOp::ADD);
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::from(blend_mode);
 
     let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
         .logic_op_enable(false)
@@ -377,30 +465,32 @@ fn create_pipeline_and_layout(
         .attachments(std::slice::from_ref(&color_blend_attachment))
         .blend_constants([0., 0., 0., 0.]);
 
-    let mut pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
-        .set_layouts(std::slice::from_ref(descriptor_set_layout));
-
-    let push_constant_range = vk::PushConstantRange::default()
-        .stage_flags(vk::ShaderStageFlags::VERTEX)
-        // .size(std::mem::size_of::<Push>().try_into().unwrap())
-        .size(64)
-        .offset(0);
-    if shader_type == Shader::PushConstant {
-        pipeline_layout_info =
-            pipeline_layout_info.push_constant_ranges(std::slice::from_ref(&push_constant_range));
-    }
-
     let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
 
     let dynamic_state_create_info =
         vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
 
     unsafe {
-        let layout = device
-            .create_pipeline_layout(&pipeline_layout_info, None)
-            .expect("Failed to create pipeline layout!");
-
         let entry_point = &CStr::from_bytes_with_nul(b"main\0").unwrap();
+
+        let fragment_constants = FragmentConstants {
+            alpha_cutoff: blend_mode.alpha_cutoff(),
+            receive_shadows: receive_shadows.into(),
+        };
+        let specialization_entries = [
+            vk::SpecializationMapEntry::default()
+                .constant_id(ALPHA_CUTOFF_CONSTANT_ID)
+                .offset(offset_of!(FragmentConstants, alpha_cutoff) as u32)
+                .size(mem::size_of::<f32>()),
+            vk::SpecializationMapEntry::default()
+                .constant_id(RECEIVE_SHADOWS_CONSTANT_ID)
+                .offset(offset_of!(FragmentConstants, receive_shadows) as u32)
+                .size(mem::size_of::<vk::Bool32>()),
+        ];
+        let specialization_info = vk::SpecializationInfo::default()
+            .map_entries(&specialization_entries)
+            .data(any_as_u8_slice(&fragment_constants));
+
         let stages = [
             vk::PipelineShaderStageCreateInfo::default()
                 .stage(vk::ShaderStageFlags::VERTEX)
@@ -409,7 +499,8 @@ fn create_pipeline_and_layout(
             vk::PipelineShaderStageCreateInfo::default()
                 .stage(vk::ShaderStageFlags::FRAGMENT)
                 .module(shader_modules.frag)
-                .name(entry_point),
+                .name(entry_point)
+                .specialization_info(&specialization_info),
         ];
 
         let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
@@ -473,8 +564,11 @@ pub(crate) struct ShaderModules {
 }
 
 pub(crate) fn create_shader_stages(shader_type: Shader, device: &Device) -> ShaderModules {
-    let shader_folder: &str = shader_type.into();
+    load_shader_stages(shader_type.into(), device)
+}
 
+/// Loads the compiled vertex and fragment stages from a folder under `shaders`.
+pub(crate) fn load_shader_stages(shader_folder: &str, device: &Device) -> ShaderModules {
     let vert = create_shader_module(
         device,
         &format!("./crates/wave_internal/src/shaders/{shader_folder}/vert.spv"),
@@ -487,54 +581,11 @@ pub(crate) fn create_shader_stages(shader_type: Shader, device: &Device) -> Shad
     ShaderModules { vert, frag }
 }
 
-fn create_descriptor_set(
-    device: &Device,
-    descriptor_pool: vk::DescriptorPool,
-    texture: &Texture,
-) -> Vec<vk::DescriptorSet> {
-    // Descriptor Set Layout
-    let set_layout_bindings = vk::DescriptorSetLayoutBinding::default()
-        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
-        .binding(0)
-        .descriptor_count(1);
-    let descriptor_layout = vk::DescriptorSetLayoutCreateInfo::default()
-        .bindings(std::slice::from_ref(&set_layout_bindings));
-    let descriptor_set_layout = unsafe {
-        device
-            .create_descriptor_set_layout(&descriptor_layout, None)
-            .expect("Could not create descriptor set layout!")
-    };
-
-    // Descriptor Set
-    let descriptor_set_alloc_info = vk::DescriptorSetAllocateInfo::default()
-        .descriptor_pool(descriptor_pool)
-        .set_layouts(std::slice::from_ref(&descriptor_set_layout));
-    let descriptor_set = unsafe {
-        device
-            .allocate_descriptor_sets(&descriptor_set_alloc_info)
-            .expect("Could not allocate descriptor set!")[0]
-    };
-    let font_descriptor = vk::DescriptorImageInfo::default()
-        .sampler(texture.sampler)
-        .image_view(texture.view)
-        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
-    let write_descriptor_set = vk::WriteDescriptorSet::default()
-        .dst_set(descriptor_set)
-        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-        .dst_binding(0)
-        .image_info(std::slice::from_ref(&font_descriptor));
-    unsafe { device.update_descriptor_sets(std::slice::from_ref(&write_descriptor_set), &[]) };
-
-    std::slice::from_ref(&descriptor_set).to_vec()
-}
-
 fn create_descriptor_sets(
     device: &Device,
     descriptor_layout: vk::DescriptorSetLayout,
     descriptor_pool: vk::DescriptorPool,
     swap_chain_image_count: usize,
-    texture: &Option<Texture>,
     uniform_buffers: &[Buffer],
 ) -> Vec<vk::DescriptorSet> {
     let layouts = vec![descriptor_layout; swap_chain_image_count];
@@ -543,13 +594,6 @@ fn create_descriptor_sets(
         .descriptor_pool(descriptor_pool)
         .set_layouts(&layouts);
 
-    let image_info = texture.as_ref().map(|texture| {
-        vk::DescriptorImageInfo::default()
-            .sampler(texture.sampler)
-            .image_view(texture.view)
-            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-    });
-
     let descriptor_sets = unsafe {
         device
             .allocate_descriptor_sets(&alloc_info)
@@ -562,25 +606,14 @@ fn create_descriptor_sets(
             .offset(0)
             .range(mem::size_of::<UniformBufferObject>().try_into().unwrap());
 
-        let mut descriptor_writes: SmallVec<[vk::WriteDescriptorSet; 2]> =
-            smallvec![vk::WriteDescriptorSet::default()
-                .dst_set(descriptor_sets[i])
-                .dst_binding(0)
-                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                .buffer_info(std::slice::from_ref(&buffer_info))];
-
-        if let Some(ref image_info) = image_info {
-            descriptor_writes.push(
-                vk::WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[i])
-                    .dst_binding(1)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(std::slice::from_ref(image_info)),
-            )
-        }
+        let descriptor_write = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_sets[i])
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(std::slice::from_ref(&buffer_info));
 
         unsafe {
-            device.update_descriptor_sets(&descriptor_writes, &[]);
+            device.update_descriptor_sets(std::slice::from_ref(&descriptor_write), &[]);
         }
     }
 
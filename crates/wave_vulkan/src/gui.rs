@@ -0,0 +1,321 @@
+use crate::{
+    any_as_u8_slice,
+    buffer::FrameVertexBuffers,
+    command_buffer::CommandPool,
+    device::Devices,
+    graphics_pipeline::PipelineLayouts,
+    profiler::DrawCounts,
+    text::{self, UiPipelineState, UiTexture, UiVertex},
+    texture::{self, ImageProperties, Texture},
+    BlendMode, ModelTopology,
+};
+use ash::{vk, Device, Instance};
+use gpu_allocator::vulkan::Allocator;
+use nalgebra::Matrix4;
+use std::collections::HashMap;
+use wave_window::{
+    gui::{GuiDraw, GuiFrame, GuiTexture, TextureId},
+    text::TextVertex,
+};
+
+/// Texture uploaded from a [`GuiTexture`], with the generation it was uploaded from
+struct UploadedTexture {
+    texture: UiTexture,
+    generation: u64,
+}
+
+/// Meshes from [`GuiFrame`], drawn with the ui shaders over the final image after post
+/// processing so the gui keeps its colours.
+pub(crate) struct GuiRenderer {
+    pipeline: vk::Pipeline,
+    /// Whether the swap chain encodes sRGB when written, so colours are given linear
+    srgb: bool,
+    textures: HashMap<TextureId, UploadedTexture>,
+    vertex_buffers: FrameVertexBuffers,
+    index_buffers: FrameVertexBuffers,
+    vertices: Vec<TextVertex>,
+    indices: Vec<u32>,
+    draws: Vec<GuiDraw>,
+}
+
+impl GuiRenderer {
+    pub fn new(
+        devices: &Devices,
+        present_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+        format: vk::Format,
+    ) -> Self {
+        Self {
+            pipeline: create_pipeline(devices, present_pass, layout),
+            srgb: is_srgb(format),
+            textures: HashMap::new(),
+            vertex_buffers: FrameVertexBuffers::new("gui"),
+            index_buffers: FrameVertexBuffers::indices("gui"),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            draws: Vec::new(),
+        }
+    }
+
+    /// Whether textures were added, changed or freed since they were last uploaded
+    pub fn needs_textures(&self, frame: &GuiFrame) -> bool {
+        let textures = frame.textures();
+
+        textures.len() != self.textures.len()
+            || textures.iter().any(|(id, texture)| {
+                self.textures
+                    .get(id)
+                    .is_none_or(|uploaded| uploaded.generation != texture.generation)
+            })
+    }
+
+    /// Uploads the textures that changed, returning the ones replaced or freed by the gui,
+    /// which frames in flight may still use.
+    ///
+    /// # Safety
+    ///
+    /// `command_pool` must allocate from `devices`.
+    pub unsafe fn upload_textures(
+        &mut self,
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        devices: &Devices,
+        layouts: &PipelineLayouts,
+        frame: &GuiFrame,
+    ) -> Vec<UiTexture> {
+        let textures = frame.textures();
+
+        let replaced = self
+            .textures
+            .extract_if(|id, uploaded| {
+                textures
+                    .get(id)
+                    .is_none_or(|texture| texture.generation != uploaded.generation)
+            })
+            .map(|(_, uploaded)| uploaded.texture)
+            .collect();
+
+        textures.iter().for_each(|(id, texture)| {
+            if !self.textures.contains_key(id) {
+                let gpu_texture =
+                    self.upload_texture(allocator, command_pool, instance, devices, texture);
+                let uploaded = UploadedTexture {
+                    texture: UiTexture::new(
+                        devices,
+                        layouts,
+                        gpu_texture,
+                        &format!("gui/texture {id:?}"),
+                    ),
+                    generation: texture.generation,
+                };
+                self.textures.insert(*id, uploaded);
+            }
+        });
+
+        replaced
+    }
+
+    /// Replaces the meshes drawn from the next recorded frame onwards
+    pub fn set(&mut self, frame: &GuiFrame) {
+        let srgb = self.srgb;
+
+        self.vertices.clear();
+        self.vertices
+            .extend(frame.vertices().iter().map(|vertex| TextVertex {
+                pos: vertex.pos,
+                uv: vertex.uv,
+                colour: if srgb { vertex.linear() } else { vertex.srgb() },
+            }));
+        self.indices.clear();
+        self.indices.extend_from_slice(frame.indices());
+        self.draws.clear();
+        self.draws.extend_from_slice(frame.draws());
+    }
+
+    /// Copies the meshes into the buffers of `frame`.
+    ///
+    /// # Safety
+    ///
+    /// The last submission of `frame` must have completed.
+    pub unsafe fn upload(&mut self, allocator: &mut Allocator, devices: &Devices, frame: usize) {
+        if !self.indices.is_empty() {
+            self.vertex_buffers
+                .write(allocator, devices, frame, &self.vertices);
+            self.index_buffers
+                .write(allocator, devices, frame, &self.indices);
+        }
+    }
+
+    /// Draws the meshes over a window `extent` pixels in size.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording inside the post processor's present pass, with the
+    /// meshes of `frame` uploaded.
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        layout: vk::PipelineLayout,
+        extent: vk::Extent2D,
+        counts: &mut DrawCounts,
+    ) {
+        let (Some(vertex_buffer), Some(index_buffer)) = (
+            self.vertex_buffers.get(frame),
+            self.index_buffers.get(frame),
+        ) else {
+            return;
+        };
+
+        if self.draws.is_empty() {
+            return;
+        }
+
+        let screen =
+            Matrix4::new_orthographic(0., extent.width as f32, 0., extent.height as f32, -1., 1.);
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            any_as_u8_slice(&screen),
+        );
+        device.cmd_bind_vertex_buffers(
+            command_buffer,
+            0,
+            std::slice::from_ref(&vertex_buffer),
+            &[0],
+        );
+        device.cmd_bind_index_buffer(command_buffer, index_buffer, 0, vk::IndexType::UINT32);
+
+        self.draws.iter().for_each(|draw| {
+            let Some(uploaded) = self.textures.get(&draw.texture) else {
+                return;
+            };
+
+            uploaded.texture.bind(device, command_buffer, layout);
+
+            let scissor = vk::Rect2D::default()
+                .offset(vk::Offset2D {
+                    x: draw.clip.x as i32,
+                    y: draw.clip.y as i32,
+                })
+                .extent(vk::Extent2D {
+                    width: draw.clip.width,
+                    height: draw.clip.height,
+                });
+            device.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&scissor));
+
+            device.cmd_draw_indexed(
+                command_buffer,
+                draw.index_count,
+                1,
+                draw.first_index,
+                draw.vertex_offset,
+                0,
+            );
+            counts.draw(draw.index_count, ModelTopology::TriangleList);
+        });
+    }
+
+    /// Rebuilds the pipeline after the swap chain was recreated, uploading the textures
+    /// again when the swap chain's encoding changed
+    pub fn recreate(
+        &mut self,
+        devices: &Devices,
+        present_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+        format: vk::Format,
+    ) {
+        self.pipeline = create_pipeline(devices, present_pass, layout);
+
+        if self.srgb != is_srgb(format) {
+            self.srgb = is_srgb(format);
+            self.textures
+                .values_mut()
+                .for_each(|uploaded| uploaded.generation = 0);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The pipeline must no longer be in use by the device.
+    pub unsafe fn destroy_pipeline(&self, device: &Device) {
+        device.destroy_pipeline(self.pipeline, None);
+    }
+
+    /// Frees the textures and buffers, after the pipeline was destroyed with the swap chain.
+    ///
+    /// # Safety
+    ///
+    /// No frame may be in flight.
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator, device: &Device) {
+        self.vertex_buffers.destroy(allocator, device);
+        self.index_buffers.destroy(allocator, device);
+
+        self.textures
+            .drain()
+            .for_each(|(_, uploaded)| uploaded.texture.destroy(device));
+    }
+
+    fn upload_texture(
+        &self,
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        devices: &Devices,
+        texture: &GuiTexture,
+    ) -> Texture {
+        let [width, height] = texture.size.map(|side| side as u32);
+
+        // sRGB textures are read back linear, matching the vertex colours
+        let format = if self.srgb {
+            vk::Format::R8G8B8A8_SRGB
+        } else {
+            vk::Format::R8G8B8A8_UNORM
+        };
+
+        texture::create_sampled_texture(
+            allocator,
+            command_pool,
+            instance,
+            devices,
+            ImageProperties::new(
+                (width, height),
+                &texture.pixels,
+                1,
+                texture.pixels.len() as vk::DeviceSize,
+            ),
+            format,
+        )
+    }
+}
+
+fn create_pipeline(
+    devices: &Devices,
+    present_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+) -> vk::Pipeline {
+    text::create_ui_pipeline(
+        devices,
+        present_pass,
+        layout,
+        UiPipelineState::present(UiVertex::Text, BlendMode::Premultiplied),
+        "gui/pipeline",
+    )
+}
+
+fn is_srgb(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8G8B8A8_SRGB | vk::Format::B8G8R8A8_SRGB | vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
@@ -1,45 +1,102 @@
 mod buffer;
+pub mod capture;
 mod command_buffer;
+pub mod compute;
 pub mod debug;
+mod debug_view;
 mod device;
+pub mod environment;
 mod frame_buffer;
+pub mod gpu;
 mod graphics_pipeline;
+mod gui;
+pub mod light;
+mod lines;
+pub mod material;
 mod memory;
+mod particles;
+pub mod pbr;
+pub mod post_process;
+mod profiler;
 pub mod renderer;
 mod resource;
+mod scene;
+pub mod settings;
+mod shadow;
+mod sprite;
 mod swap_chain;
 mod sync_objects;
+mod text;
 mod texture;
+mod tilemap;
 mod uniform_buffer;
 mod utility;
 
 use crate::{debug::ENABLE_VALIDATION_LAYERS, sync_objects::MAX_FRAMES_IN_FLIGHT};
 use ash::{khr::surface, vk, Instance};
-use buffer::ModelBuffers;
-use command_buffer::{CommandBuffers, CommandPool};
+use buffer::{Buffer, ModelBuffers};
+use capture::FrameReadback;
+use command_buffer::{CommandPool, FrameCommands};
+use compute::{ComputeDevice, ComputeStage, Dispatch, FrameDispatches};
 use debug::{Debug, Debugger};
+use debug_view::DebugViews;
 use derive_more::{Deref, DerefMut};
 use device::Devices;
-use frame_buffer::FrameBuffers;
+use environment::{EnvironmentMap, Skybox, SkyboxPipeline};
+use gpu::{DeviceInfo, DeviceSelection};
 use gpu_allocator::vulkan::*;
-use graphics_pipeline::GraphicsPipeline;
-use nalgebra::{matrix, Matrix4};
+use graphics_pipeline::{ObjectDescriptors, PipelineLayouts};
+use gui::GuiRenderer;
+use light::{FrameLights, Light};
+use lines::DebugLines;
+use material::{Material, MaterialCreateInfo, MaterialHandle, Materials, VulkanMaterial};
+use nalgebra::{matrix, Matrix4, Point3};
+use particles::ParticleRenderer;
+use post_process::{PostProcess, PostProcessCreateInfo, PostProcessor};
+use profiler::FrameProfiler;
 use renderer::RenderPass;
 use resource::Resources;
+use scene::SceneDescriptors;
+use settings::RenderSettings;
+use shadow::ShadowMaps;
+use sprite::SpriteRenderer;
+use std::{any::Any, time::Instant};
 use swap_chain::{recreate_swap_chain, SwapChain};
-use sync_objects::SyncObjects;
-use texture::{ImageProperties, Texture};
+use sync_objects::{RetiredResources, SyncObjects};
+use text::TextRenderer;
+use tilemap::TilemapRenderer;
 use uniform_buffer::{update_uniform_buffers, UniformBufferObject};
-use utility::{EntryInstance, ImageInfo};
+use utility::EntryInstance;
 use wave_space::space::VerticesAndIndices;
-use wave_window::window::RenderBackend;
+use wave_window::{
+    debug_draw::LineVertex,
+    gui::GuiFrame,
+    particles::ParticleBatches,
+    sprite::Sprites,
+    stats::{FrameStats, Span},
+    text::Text,
+    tilemap::TileChunk,
+    window::{DebugView, FrameCapture, RenderBackend},
+};
 use winit::window::Window;
 
 pub mod prelude {
     pub use crate::{
-        debug::{Debugger, MessageLevel, MessageType},
-        CullMode, ModelTopology, Shader, TextureBuffer,
+        compute::{
+            BufferLocation, ComputeBinding, ComputeContext, ComputePipeline, ComputeResource,
+            ComputeStage, Dispatch, StorageBuffer,
+        },
+        debug::{Debugger, MessageLevel, MessageType, ValidationMessage},
+        environment::{CubeFaces, Skybox},
+        gpu::{DeviceInfo, DeviceSelection, DeviceType},
+        light::{DirectionalLight, Light, SpotLight},
+        material::{Material, MaterialHandle, Materials},
+        pbr::PbrMaterial,
+        post_process::{Bloom, PostPass, PostProcess, ToneMapping, Vignette},
+        settings::{Msaa, RenderSettings},
+        BlendMode, CullMode, ModelTopology, Shader, Shadows, TextureBuffer,
     };
+    pub use nalgebra::{Point2, Point3, UnitQuaternion, Vector2, Vector3, Vector4};
 }
 
 pub fn orthographic_vk(
@@ -69,7 +126,7 @@ pub(crate) unsafe fn any_as_u8_slice<T: Sized>(any: &T) -> &[u8] {
 }
 
 pub struct Vulkan {
-    pub(crate) command_buffers: CommandBuffers,
+    pub(crate) frame_commands: FrameCommands,
     pub(crate) command_pool: CommandPool,
     pub(crate) resources: Resources,
     pub(crate) render_pass: RenderPass,
@@ -79,8 +136,33 @@ pub struct Vulkan {
     pub(crate) sync_objects: SyncObjects,
     pub ubo: UniformBufferObject,
     pub(crate) debugger: Option<Debug>,
-    pub(crate) frame_buffers: FrameBuffers,
+    pub(crate) frame_buffer: vk::Framebuffer,
+    pub(crate) post_processor: PostProcessor,
     pub(crate) objects: Vec<VulkanObject>,
+    pub(crate) materials: Vec<VulkanMaterial>,
+    pub(crate) pipeline_layouts: PipelineLayouts,
+    pub(crate) environment: EnvironmentMap,
+    pub(crate) scene: SceneDescriptors,
+    pub(crate) skybox_pipeline: Option<SkyboxPipeline>,
+    pub(crate) shadow_maps: ShadowMaps,
+    pub(crate) lights: Vec<Light>,
+    /// World matrix placing each light, from the scene graph node it is attached to
+    pub(crate) light_transforms: Vec<Matrix4<f32>>,
+    pub(crate) render_settings: RenderSettings,
+    /// Set when the settings changed, so the swap chain is recreated after the next frame
+    pub(crate) render_settings_changed: bool,
+    pub(crate) readback: FrameReadback,
+    /// Resources replaced while frames in flight may still use them
+    pub(crate) retired: RetiredResources,
+    pub(crate) profiler: FrameProfiler,
+    pub(crate) debug_views: DebugViews,
+    pub(crate) debug_lines: DebugLines,
+    pub(crate) particles: ParticleRenderer,
+    pub(crate) sprites: SpriteRenderer,
+    pub(crate) tilemaps: TilemapRenderer,
+    pub(crate) text: TextRenderer,
+    pub(crate) gui: GuiRenderer,
+    pub(crate) dispatches: FrameDispatches,
     pub(crate) allocator: Allocator,
     pub(crate) devices: Devices,
     pub(crate) instance: Instance,
@@ -91,19 +173,160 @@ impl RenderBackend for Vulkan {
     where
         Self: Sized,
     {
-        Self::new(window, &[], None)
+        Self::new(
+            window,
+            &Materials::default(),
+            &[],
+            None,
+            None,
+            &[],
+            &PostProcess::default(),
+            &RenderSettings::default(),
+            &DeviceSelection::default(),
+        )
     }
 
     fn destroy(&self) {
         self.wait_device_idle();
     }
 
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
     fn update(&mut self, view: Matrix4<f32>) {
         self.ubo.update(&self.swap_chain.extent, view);
     }
 
+    fn set_models(&mut self, models: &[Matrix4<f32>]) {
+        self.objects
+            .iter_mut()
+            .zip(models)
+            .for_each(|(object, model)| object.model = *model);
+    }
+
+    fn set_light_transforms(&mut self, transforms: &[Matrix4<f32>]) {
+        self.light_transforms = transforms.to_vec();
+    }
+
+    fn set_visible(&mut self, index: usize, visible: bool) {
+        if let Some(object) = self.objects.get_mut(index) {
+            object.visible = visible;
+        }
+    }
+
+    fn frame_stats(&self) -> &FrameStats {
+        self.profiler.latest()
+    }
+
+    fn set_profiling(&mut self, enabled: bool) {
+        self.profiler.timestamps = enabled;
+    }
+
+    fn set_debug_view(&mut self, view: DebugView) {
+        self.debug_views.global = view;
+        self.prepare_debug_views();
+    }
+
+    fn set_render_settings(&mut self, render_settings: &RenderSettings) {
+        self.render_settings_changed |= *render_settings != self.render_settings;
+        self.render_settings = *render_settings;
+    }
+
+    fn set_object_debug_view(&mut self, index: usize, view: Option<DebugView>) {
+        if let Some(object) = self.objects.get_mut(index) {
+            object.debug_view = view;
+            self.prepare_debug_views();
+        }
+    }
+
+    fn set_debug_lines(&mut self, vertices: &[LineVertex]) {
+        self.debug_lines.set(vertices);
+    }
+
+    fn set_sprites(&mut self, sprites: &Sprites) {
+        if self.sprites.needs_textures(sprites) {
+            unsafe {
+                self.sprites.upload_textures(
+                    &mut self.allocator,
+                    &self.command_pool,
+                    &self.instance,
+                    &self.devices,
+                    &self.pipeline_layouts,
+                    sprites,
+                );
+            }
+        }
+
+        self.sprites.set(sprites);
+    }
+
+    fn set_tile_chunks(&mut self, chunks: &[&TileChunk]) {
+        if self.tilemaps.needs_upload(chunks) {
+            let replaced = unsafe {
+                self.tilemaps.upload(
+                    &mut self.allocator,
+                    &self.command_pool,
+                    &self.devices,
+                    chunks,
+                )
+            };
+            self.retired.retire(replaced);
+        }
+
+        self.tilemaps.set(chunks);
+    }
+
+    fn set_particles(&mut self, particles: &ParticleBatches) {
+        self.particles.set(particles);
+    }
+
+    fn set_text(&mut self, text: &Text) {
+        if self.text.needs_atlas(text) {
+            let replaced = unsafe {
+                self.text.upload_atlas(
+                    &mut self.allocator,
+                    &self.command_pool,
+                    &self.instance,
+                    &self.devices,
+                    &self.pipeline_layouts,
+                    text.atlas(),
+                )
+            };
+            self.retired.retire(replaced);
+        }
+
+        self.text.set(text);
+    }
+
+    fn set_gui(&mut self, frame: &GuiFrame) {
+        if self.gui.needs_textures(frame) {
+            let replaced = unsafe {
+                self.gui.upload_textures(
+                    &mut self.allocator,
+                    &self.command_pool,
+                    &self.instance,
+                    &self.devices,
+                    &self.pipeline_layouts,
+                    frame,
+                )
+            };
+            self.retired.retire(replaced);
+        }
+
+        self.gui.set(frame);
+    }
+
+    fn request_capture(&mut self) {
+        self.readback.request();
+    }
+
+    fn capture(&mut self) -> Option<FrameCapture> {
+        unsafe { self.readback.read(&self.devices.logical.device) }
+    }
+
     fn render(&mut self, window: &Window, current_frame: &mut usize, resized: &mut bool, dt: f32) {
-        let device = &mut self.devices.logical.device;
+        let device = &self.devices.logical.device;
 
         let image_available_semaphore =
             self.sync_objects.image_available_semaphores[*current_frame];
@@ -114,12 +337,36 @@ impl RenderBackend for Vulkan {
         unsafe {
             device
                 .wait_for_fences(
-                    &self.sync_objects.in_flight_fences,
+                    std::slice::from_ref(&in_flight_fence),
                     true,
                     vk::DeviceSize::MAX,
                 )
                 .expect("Failed to wait for Fence!");
 
+            self.profiler.resolve(device, *current_frame);
+
+            self.command_pool
+                .release_uploads(&mut self.allocator, device);
+
+            self.retired.release(&mut self.allocator, device);
+
+            self.debug_lines
+                .upload(&mut self.allocator, &self.devices, *current_frame);
+
+            self.particles
+                .upload(&mut self.allocator, &self.devices, *current_frame);
+
+            self.sprites
+                .upload(&mut self.allocator, &self.devices, *current_frame);
+
+            self.text
+                .upload(&mut self.allocator, &self.devices, *current_frame);
+
+            self.gui
+                .upload(&mut self.allocator, &self.devices, *current_frame);
+
+            let frame_start = Instant::now();
+
             let (image_index, _is_sub_optimal) = {
                 let result = self.swap_chain.swap_chain.acquire_next_image(
                     self.swap_chain.swap_chain_khr,
@@ -139,6 +386,8 @@ impl RenderBackend for Vulkan {
                 }
             };
 
+            let update_start = Instant::now();
+
             update_uniform_buffers(
                 &mut self.objects,
                 &self.ubo,
@@ -160,14 +409,52 @@ impl RenderBackend for Vulkan {
 
             self.sync_objects.images_in_flight[image_index as usize] = in_flight_fence;
 
+            let aspect = self.swap_chain.extent.width as f32 / self.swap_chain.extent.height as f32;
+            let lights = self
+                .lights
+                .iter()
+                .enumerate()
+                .map(|(index, light)| {
+                    self.light_transforms
+                        .get(index)
+                        .map_or(*light, |world| light.transformed(world))
+                })
+                .collect::<Vec<Light>>();
+            let frame_lights = FrameLights::new(&lights, &self.ubo, aspect);
+
+            self.scene
+                .update(image_index as usize, &self.ubo, &frame_lights);
+
+            let record_start = Instant::now();
+
+            let capture = self.readback.prepare(
+                &mut self.allocator,
+                &self.devices,
+                &self.swap_chain,
+                in_flight_fence,
+            );
+
+            let command_buffer = self.frame_commands.reset(device, *current_frame);
+
+            let (marks, counts) = command_buffer::record_command_buffer(
+                self,
+                command_buffer,
+                image_index as usize,
+                *current_frame,
+                &frame_lights.shadow_matrices,
+                capture,
+            );
+
+            self.dispatches.clear();
+
+            let submit_start = Instant::now();
+
             let submit_infos = vk::SubmitInfo::default()
                 .wait_semaphores(std::slice::from_ref(&image_available_semaphore))
                 .wait_dst_stage_mask(std::slice::from_ref(
                     &vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
                 ))
-                .command_buffers(std::slice::from_ref(
-                    &self.command_buffers[image_index as usize],
-                ))
+                .command_buffers(std::slice::from_ref(&command_buffer))
                 .signal_semaphores(std::slice::from_ref(&render_finished_semaphore));
 
             device
@@ -176,12 +463,16 @@ impl RenderBackend for Vulkan {
 
             device
                 .queue_submit(
-                    self.devices.logical.queues.present,
+                    self.devices.logical.queues.graphics,
                     std::slice::from_ref(&submit_infos),
                     in_flight_fence,
                 )
                 .expect("Failed to execute queue submit.");
 
+            self.retired.frame_submitted();
+
+            let present_start = Instant::now();
+
             let present_info = vk::PresentInfoKHR::default()
                 .wait_semaphores(std::slice::from_ref(&render_finished_semaphore))
                 .swapchains(std::slice::from_ref(&self.swap_chain.swap_chain_khr))
@@ -192,6 +483,27 @@ impl RenderBackend for Vulkan {
                 .swap_chain
                 .queue_present(self.devices.logical.queues.present, &present_info);
 
+            let present_end = Instant::now();
+
+            let span = |name, start: Instant, end: Instant| {
+                Span::new(name, start - frame_start, end - start)
+            };
+
+            let stats = FrameStats {
+                started: self.profiler.since_start(frame_start),
+                cpu: vec![
+                    span("update", update_start, record_start),
+                    span("record", record_start, submit_start),
+                    span("submit", submit_start, present_start),
+                    span("present", present_start, present_end),
+                ],
+                draw_calls: counts.draw_calls,
+                triangles: counts.triangles,
+                ..FrameStats::default()
+            };
+
+            self.profiler.submit(*current_frame, stats, marks);
+
             let is_resized = match result {
                 Ok(_) => *resized,
                 Err(vk_result) => match vk_result {
@@ -200,7 +512,7 @@ impl RenderBackend for Vulkan {
                 },
             };
 
-            if is_resized {
+            if is_resized || self.render_settings_changed {
                 *resized = false;
                 recreate_swap_chain(self, window);
             }
@@ -223,13 +535,19 @@ impl Vulkan {
 
     pub fn new(
         window: &Window,
+        materials: &Materials,
         geom_properties: &[GeomProperties],
         debugging: Option<Debugger>,
+        skybox: Option<&Skybox>,
+        lights: &[Light],
+        post_process: &PostProcess,
+        render_settings: &RenderSettings,
+        device_selection: &DeviceSelection,
     ) -> Self {
-        let entry_instance = EntryInstance::new(window, debugging);
+        let entry_instance = EntryInstance::new(window, debugging.as_ref());
 
         let debugger = if cfg!(debug_assertions) {
-            debugging.map(|debugging| debug::debugger(&entry_instance, debugging))
+            debugging.map(|debugging| debug::debugger(&entry_instance, &debugging))
         } else {
             None
         };
@@ -239,7 +557,19 @@ impl Vulkan {
 
         let surface_loader = create_surface(&entry_instance);
 
-        let devices = Devices::new(&entry_instance.instance, &surface, &surface_loader);
+        let devices = Devices::new(
+            &entry_instance.instance,
+            &surface,
+            &surface_loader,
+            render_settings,
+            device_selection,
+        );
+
+        log::info!(
+            "Rendering with {} ({})",
+            devices.physical.info.name,
+            devices.physical.info.device_type
+        );
 
         let swap_chain = SwapChain::new(
             &entry_instance.instance,
@@ -249,26 +579,25 @@ impl Vulkan {
             window,
         );
 
-        let render_pass =
-            renderer::create_render_pass(&devices, &swap_chain, &entry_instance.instance);
+        let render_pass = renderer::create_render_pass(&devices, &entry_instance.instance);
 
-        let resources = Resources::new(&swap_chain, &entry_instance.instance, &devices);
+        let resources = Resources::new(
+            render_settings.render_extent(swap_chain.extent),
+            &entry_instance.instance,
+            &devices,
+        );
 
-        let frame_buffers = frame_buffer::create_frame_buffers(
-            &swap_chain,
+        let frame_buffer = frame_buffer::create_scene_frame_buffer(
             &render_pass,
             &devices.logical.device,
             &resources,
         );
 
-        let command_pool = command_buffer::create_command_pool(
-            &entry_instance.instance,
-            &devices,
-            &surface_loader,
-            &surface,
-        );
+        let command_pool = command_buffer::create_command_pool(&devices);
 
-        let sync_objects = SyncObjects::new(&devices.logical.device);
+        let frame_commands = FrameCommands::new(&devices);
+
+        let sync_objects = SyncObjects::new(&devices.logical.device, swap_chain.images.len());
 
         let swap_chain_len = swap_chain.images.len() as u32;
 
@@ -282,42 +611,158 @@ impl Vulkan {
         })
         .expect("Failed to create allocator");
 
-        let objects = geom_properties
+        let environment = EnvironmentMap::new(
+            &mut allocator,
+            &command_pool,
+            &entry_instance.instance,
+            &devices,
+            skybox,
+        );
+
+        let post_processor = PostProcessor::new(
+            &mut allocator,
+            &command_pool,
+            &entry_instance.instance,
+            &devices,
+            &PostProcessCreateInfo {
+                settings: post_process,
+                swap_chain: &swap_chain,
+                resources: &resources,
+            },
+        );
+
+        let shadow_maps = ShadowMaps::new(&entry_instance.instance, &devices, &command_pool);
+
+        let profiler = FrameProfiler::new(&entry_instance.instance, &devices);
+
+        let scene = SceneDescriptors::new(
+            &mut allocator,
+            &devices,
+            swap_chain_len,
+            &environment,
+            &shadow_maps,
+        );
+
+        let skybox_pipeline = skybox.map(|_| {
+            SkyboxPipeline::new(
+                &devices,
+                render_pass.0,
+                devices.physical.samples,
+                scene.set_layout,
+            )
+        });
+
+        let debug_lines = DebugLines::new(&devices, render_pass.0);
+
+        let pipeline_layouts = PipelineLayouts::new(&devices.logical.device, scene.set_layout);
+
+        let particles = ParticleRenderer::new(&devices, render_pass.0, &pipeline_layouts);
+
+        let sprites = SpriteRenderer::new(
+            &devices,
+            post_processor.present_pass(),
+            pipeline_layouts.layout,
+        );
+
+        let tilemaps = TilemapRenderer::new(
+            &devices,
+            post_processor.present_pass(),
+            pipeline_layouts.layout,
+        );
+
+        let text = TextRenderer::new(
+            &devices,
+            render_pass.0,
+            post_processor.present_pass(),
+            pipeline_layouts.layout,
+        );
+
+        let gui = GuiRenderer::new(
+            &devices,
+            post_processor.present_pass(),
+            pipeline_layouts.layout,
+            swap_chain.image_format,
+        );
+
+        let shared_materials = materials
+            .iter()
+            .enumerate()
+            .map(|(index, material)| (material, material.name_or(|| format!("material {index}"))));
+
+        // Geometries without a shared material get their own, after the shared ones
+        let owned_materials = geom_properties
             .iter()
+            .filter(|property| property.material_handle.is_none())
             .map(|property| {
-                let tex = property.create_texture(
+                let material = &property.material;
+                (material, material.name_or(|| property.label.clone()))
+            });
+
+        let object_materials = material::material_indices(
+            geom_properties
+                .iter()
+                .map(|property| property.material_handle),
+            materials.len(),
+        );
+
+        let vulkan_materials = shared_materials
+            .chain(owned_materials)
+            .enumerate()
+            .map(|(index, (material, name))| {
+                let topologies = object_materials
+                    .iter()
+                    .zip(geom_properties)
+                    .filter(|(material, _)| **material == index)
+                    .map(|(_, property)| property.topology)
+                    .collect::<Vec<ModelTopology>>();
+
+                VulkanMaterial::new(
                     &mut allocator,
                     &command_pool,
                     &entry_instance.instance,
-                    &devices.physical.device,
                     &devices,
-                );
+                    &MaterialCreateInfo {
+                        material,
+                        topologies: &topologies,
+                        name: &name,
+                        swap_chain: &swap_chain,
+                        render_pass: render_pass.0,
+                        layouts: &pipeline_layouts,
+                    },
+                )
+            })
+            .collect::<Vec<VulkanMaterial>>();
+
+        let mut objects = geom_properties
+            .iter()
+            .zip(object_materials)
+            .map(|(property, material)| {
                 VulkanObject::new(
                     &mut allocator,
                     &command_pool,
                     swap_chain_len,
-                    &swap_chain,
-                    &render_pass,
                     property,
-                    tex,
+                    material,
+                    pipeline_layouts.object_set_layout,
                     &devices,
                 )
             })
             .collect::<Vec<VulkanObject>>();
 
-        let ubo = UniformBufferObject::default();
-
-        let command_buffers = command_buffer::create_command_buffers(
+        let mut debug_views = DebugViews::default();
+        debug_views.prepare(
+            &mut allocator,
             &command_pool,
-            &swap_chain,
-            &devices.logical.device,
-            &render_pass,
-            &frame_buffers,
-            &objects,
+            &devices,
+            render_pass.0,
+            pipeline_layouts.layout,
+            &mut objects,
         );
 
+        let ubo = UniformBufferObject::default();
+
         Self {
-            command_buffers,
+            frame_commands,
             command_pool,
             render_pass,
             resources,
@@ -327,8 +772,30 @@ impl Vulkan {
             sync_objects,
             ubo,
             debugger,
-            frame_buffers,
+            frame_buffer,
+            post_processor,
             objects,
+            materials: vulkan_materials,
+            pipeline_layouts,
+            environment,
+            scene,
+            skybox_pipeline,
+            shadow_maps,
+            lights: lights.to_vec(),
+            light_transforms: Vec::new(),
+            render_settings: *render_settings,
+            render_settings_changed: false,
+            readback: FrameReadback::default(),
+            retired: RetiredResources::default(),
+            profiler,
+            debug_views,
+            debug_lines,
+            particles,
+            sprites,
+            tilemaps,
+            text,
+            gui,
+            dispatches: FrameDispatches::default(),
             allocator,
             devices,
             instance: entry_instance.instance,
@@ -342,19 +809,172 @@ impl Vulkan {
             .zip(properties)
             .for_each(|(object, properties)| object.model = properties.model);
     }
+
+    /// Replaces the lights used from the next rendered frame onwards.
+    ///
+    /// Only the first few lights are evaluated, and lights stop casting shadows once
+    /// the shadow map layers have all been used.
+    pub fn set_lights(&mut self, lights: &[Light]) {
+        self.lights = lights.to_vec();
+    }
+
+    /// Replaces the material behind `handle`, changing every geometry that references it.
+    ///
+    /// Waits for the device to be idle so the old textures and pipelines can be destroyed.
+    pub fn set_material(&mut self, handle: MaterialHandle, material: &Material) {
+        self.wait_device_idle();
+
+        let topologies = self
+            .materials
+            .get(handle.0)
+            .expect("Unknown material handle")
+            .topologies();
+
+        let replacement = VulkanMaterial::new(
+            &mut self.allocator,
+            &self.command_pool,
+            &self.instance,
+            &self.devices,
+            &MaterialCreateInfo {
+                material,
+                topologies: &topologies,
+                name: &material.name_or(|| format!("material {}", handle.0)),
+                swap_chain: &self.swap_chain,
+                render_pass: self.render_pass.0,
+                layouts: &self.pipeline_layouts,
+            },
+        );
+
+        let old = std::mem::replace(
+            self.materials
+                .get_mut(handle.0)
+                .expect("Unknown material handle"),
+            replacement,
+        );
+
+        let device = &self.devices.logical.device;
+        unsafe {
+            old.destroy_pipelines(device);
+            old.destroy(&mut self.allocator, device);
+        }
+    }
+
+    /// Description of the device being rendered with
+    #[inline]
+    #[must_use]
+    pub fn device_info(&self) -> &DeviceInfo {
+        &self.devices.physical.info
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn render_settings(&self) -> &RenderSettings {
+        &self.render_settings
+    }
+
+    /// Replaces the post processing passes from the next rendered frame onwards.
+    ///
+    /// Waits for the device to be idle so the old passes can be destroyed.
+    pub fn set_post_process(&mut self, post_process: &PostProcess) {
+        self.wait_device_idle();
+
+        unsafe {
+            self.post_processor.set_settings(
+                &mut self.allocator,
+                &self.command_pool,
+                &self.instance,
+                &self.devices,
+                &PostProcessCreateInfo {
+                    settings: post_process,
+                    swap_chain: &self.swap_chain,
+                    resources: &self.resources,
+                },
+            );
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn material(&self, handle: MaterialHandle) -> Option<&Material> {
+        self.materials
+            .get(handle.0)
+            .map(|material| &material.material)
+    }
+
+    /// Creates compute resources and runs dispatches on the rendering device.
+    ///
+    /// Everything created through it must be destroyed through it before the renderer is
+    /// dropped.
+    pub fn compute(&mut self) -> ComputeDevice<'_> {
+        ComputeDevice::new(&self.devices, &mut self.allocator, &self.command_pool)
+    }
+
+    /// Queues `dispatch` to run at `stage` of the next recorded frame only, so dispatches
+    /// that should run every frame are queued every frame.
+    pub fn dispatch(&mut self, stage: ComputeStage, dispatch: Dispatch) {
+        self.dispatches.push(stage, dispatch);
+    }
+
+    /// Waits for the device to be idle, as normal lines are uploaded through the command pool
+    fn prepare_debug_views(&mut self) {
+        self.wait_device_idle();
+
+        self.debug_views.prepare(
+            &mut self.allocator,
+            &self.command_pool,
+            &self.devices,
+            self.render_pass.0,
+            self.pipeline_layouts.layout,
+            &mut self.objects,
+        );
+    }
 }
 
 impl Drop for Vulkan {
     fn drop(&mut self) {
+        // Also destroys everything built on the render pass, such as the skybox pipeline with
+        // its layout and the debug view pipelines
         swap_chain::cleanup_swap_chain(self);
 
         let device = &self.devices.logical.device;
 
         unsafe {
-            // self.objects.iter().for_each(|object| {
-            //     // device::recreate_drop(&mut self.allocator, object, device);
-            //     device::destroy(&mut self.allocator, object, device);
-            // });
+            // Along with the normal lines of those drawn in the normals view
+            self.objects
+                .drain(..)
+                .for_each(|object| device::destroy(&mut self.allocator, object, device));
+
+            self.materials
+                .drain(..)
+                .for_each(|material| material.destroy(&mut self.allocator, device));
+
+            self.pipeline_layouts.destroy(device);
+
+            self.post_processor.destroy(device);
+
+            self.profiler.destroy(device);
+
+            self.readback.destroy(&mut self.allocator, device);
+
+            self.retired.destroy(&mut self.allocator, device);
+
+            self.debug_lines.destroy(&mut self.allocator, device);
+
+            self.particles.destroy(&mut self.allocator, device);
+
+            self.sprites.destroy(&mut self.allocator, device);
+
+            self.tilemaps.destroy(&mut self.allocator, device);
+
+            self.text.destroy(&mut self.allocator, device);
+
+            self.gui.destroy(&mut self.allocator, device);
+
+            std::mem::take(&mut self.scene).destroy(&mut self.allocator, device);
+
+            self.environment.destroy(device);
+
+            self.shadow_maps.destroy(device);
 
             for i in 0..MAX_FRAMES_IN_FLIGHT {
                 device.destroy_semaphore(self.sync_objects.render_finished_semaphores[i], None);
@@ -362,14 +982,17 @@ impl Drop for Vulkan {
                 device.destroy_fence(self.sync_objects.in_flight_fences[i], None);
             }
 
-            device.destroy_command_pool(*self.command_pool, None);
+            self.frame_commands.destroy(device);
 
-            dbg!("2");
+            self.command_pool
+                .release_uploads(&mut self.allocator, device);
+            self.command_pool.destroy(device);
 
-            println!("here");
+            // Still receives messages until the instance is destroyed
+            let debugger = self.debugger.take();
 
             if ENABLE_VALIDATION_LAYERS {
-                if let Some(debugger) = self.debugger.take() {
+                if let Some(debugger) = &debugger {
                     debugger
                         .utils
                         .destroy_debug_utils_messenger(debugger.messenger, None);
@@ -381,6 +1004,8 @@ impl Drop for Vulkan {
             device.destroy_device(None);
 
             self.instance.destroy_instance(None);
+
+            drop(debugger);
         }
     }
 }
@@ -390,83 +1015,79 @@ pub struct TextureBuffer(pub Vec<u8>);
 
 #[derive(Debug, Clone)]
 pub struct GeomProperties {
-    texture_buffer: Vec<u8>,
+    material: Material,
+    material_handle: Option<MaterialHandle>,
     vertices_and_indices: VerticesAndIndices,
     topology: ModelTopology,
-    cull_mode: CullMode,
-    shader: Shader,
     indexed: bool,
     model: Matrix4<f32>,
+    label: String,
+    debug_view: Option<DebugView>,
 }
 
 impl GeomProperties {
+    /// Geometry drawn with the shared material behind `material_handle`, or with its own
+    /// `material` when there is no handle. The material gets a pipeline for each topology
+    /// drawn with it.
     pub fn new(
-        texture_buffer: &[u8],
+        material: &Material,
+        material_handle: Option<MaterialHandle>,
         vertices_and_indices: VerticesAndIndices,
         topology: ModelTopology,
-        cull_mode: CullMode,
-        shader: Shader,
         indexed: bool,
         model: Matrix4<f32>,
     ) -> Self {
         Self {
-            texture_buffer: texture_buffer.to_vec(),
+            material: material.clone(),
+            material_handle,
             vertices_and_indices,
             topology,
-            cull_mode,
-            shader,
             indexed,
             model,
+            label: String::from("geometry"),
+            debug_view: None,
         }
     }
 
-    fn create_texture(
-        &self,
-        allocator: &mut Allocator,
-        command_pool: &CommandPool,
-        instance: &Instance,
-        physical_device: &vk::PhysicalDevice,
-        devices: &Devices,
-    ) -> Option<Texture> {
-        let mut texture = None;
-        if !self.texture_buffer.is_empty() {
-            let image_properties =
-                ImageProperties::get_image_properties_from_buffer(&self.texture_buffer);
-            let image_info = ImageInfo::new(
-                image_properties.image_dimensions,
-                image_properties.mip_levels,
-                vk::SampleCountFlags::TYPE_1,
-                vk::Format::R8G8B8A8_SRGB,
-                vk::ImageTiling::OPTIMAL,
-                vk::ImageUsageFlags::TRANSFER_SRC
-                    | vk::ImageUsageFlags::TRANSFER_DST
-                    | vk::ImageUsageFlags::SAMPLED,
-                vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            );
-            texture = Some(Texture::new(
-                allocator,
-                image_properties,
-                command_pool,
-                instance,
-                physical_device,
-                devices,
-                vk::Format::R8G8B8A8_SRGB,
-                image_info,
-            ));
-        }
-        texture
+    /// Model matrix the geometry was created with, placing it in the world or relative to the
+    /// scene graph node it is attached to
+    #[must_use]
+    pub fn model(&self) -> Matrix4<f32> {
+        self.model
+    }
+
+    /// Name the geometry's buffers and draws are given in graphics debuggers and validation
+    /// messages
+    #[must_use]
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    /// View the geometry is drawn in regardless of the global one, which it follows when `None`
+    #[must_use]
+    pub fn debug_view(mut self, debug_view: Option<DebugView>) -> Self {
+        self.debug_view = debug_view;
+        self
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct VulkanObject {
     vertices_and_indices: VerticesAndIndices,
-    texture: Option<Texture>,
-    graphics_pipeline: GraphicsPipeline,
+    descriptors: ObjectDescriptors,
     buffers: ModelBuffers,
     indexed: bool,
+    topology: ModelTopology,
     model: Matrix4<f32>,
-    shader: Shader,
+    /// Index into [`Vulkan::materials`]
+    material: usize,
+    centre: Point3<f32>,
+    visible: bool,
+    label: String,
+    debug_view: Option<DebugView>,
+    /// Line list along the normals, created once the object is drawn in the normals view
+    normal_lines: Option<Buffer>,
 }
 
 impl VulkanObject {
@@ -474,10 +1095,9 @@ impl VulkanObject {
         allocator: &mut Allocator,
         command_pool: &CommandPool,
         command_buffer_count: u32,
-        swap_chain: &SwapChain,
-        render_pass: &RenderPass,
         properties: &GeomProperties,
-        texture: Option<Texture>,
+        material: usize,
+        object_set_layout: vk::DescriptorSetLayout,
         devices: &Devices,
     ) -> Self {
         let buffers = ModelBuffers::new(
@@ -485,28 +1105,33 @@ impl VulkanObject {
             &properties.vertices_and_indices,
             command_pool,
             command_buffer_count,
+            &properties.label,
             devices,
         );
 
-        let graphics_pipeline = GraphicsPipeline::new(
+        let descriptors = ObjectDescriptors::new(
             allocator,
-            swap_chain,
-            render_pass.0,
-            &texture,
-            properties.topology,
-            properties.cull_mode,
-            properties.shader,
+            command_buffer_count,
+            object_set_layout,
+            &properties.label,
             devices,
         );
 
+        let centre = properties.vertices_and_indices.vertices.centre();
+
         Self {
             vertices_and_indices: properties.vertices_and_indices.clone(),
-            texture,
-            graphics_pipeline,
+            descriptors,
             buffers,
             indexed: properties.indexed,
+            topology: properties.topology,
             model: properties.model,
-            shader: properties.shader,
+            material,
+            centre,
+            visible: true,
+            label: properties.label.clone(),
+            debug_view: properties.debug_view,
+            normal_lines: None,
         }
     }
 }
@@ -516,7 +1141,7 @@ pub(crate) fn create_surface(entry_instance: &EntryInstance) -> surface::Instanc
     surface::Instance::new(&entry_instance.entry, &entry_instance.instance)
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ModelTopology {
     LineList,
     LineListWithAdjacency,
@@ -576,11 +1201,98 @@ impl From<CullMode> for vk::CullModeFlags {
     }
 }
 
+/// How an object's fragments are combined with what is already in the colour target.
+///
+/// Transparent modes are drawn after every opaque object, sorted back to front, and
+/// do not write depth.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum BlendMode {
+    #[default]
+    Opaque,
+    AlphaBlend,
+    Additive,
+    Premultiplied,
+    /// Opaque, but fragments with an alpha below the cutoff are discarded
+    AlphaTest(f32),
+}
+
+impl BlendMode {
+    #[must_use]
+    pub const fn is_transparent(self) -> bool {
+        matches!(
+            self,
+            Self::AlphaBlend | Self::Additive | Self::Premultiplied
+        )
+    }
+
+    pub(crate) const fn alpha_cutoff(self) -> f32 {
+        match self {
+            Self::AlphaTest(cutoff) => cutoff,
+            _ => 0.,
+        }
+    }
+}
+
+impl From<BlendMode> for vk::PipelineColorBlendAttachmentState {
+    fn from(blend_mode: BlendMode) -> Self {
+        let attachment = vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .alpha_blend_op(vk::BlendOp::ADD);
+
+        let (src_colour, dst_colour, src_alpha, dst_alpha) = match blend_mode {
+            BlendMode::Opaque | BlendMode::AlphaTest(_) => return attachment.blend_enable(false),
+            BlendMode::AlphaBlend => (
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            BlendMode::Additive => (
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ZERO,
+                vk::BlendFactor::ONE,
+            ),
+            BlendMode::Premultiplied => (
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+        };
+
+        attachment
+            .blend_enable(true)
+            .src_color_blend_factor(src_colour)
+            .dst_color_blend_factor(dst_colour)
+            .src_alpha_blend_factor(src_alpha)
+            .dst_alpha_blend_factor(dst_alpha)
+    }
+}
+
+/// Whether a geometry casts shadows onto, and receives shadows from, other geometry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shadows {
+    pub cast: bool,
+    pub receive: bool,
+}
+
+impl Default for Shadows {
+    fn default() -> Self {
+        Self {
+            cast: true,
+            receive: true,
+        }
+    }
+}
+
 const LIGHT: &str = "light";
 const LIGHT_TEXTURE: &str = "light_texture";
 const TEXTURE: &str = "texture";
 const VERTEX: &str = "vertex";
 const UI: &str = "ui";
+const PBR: &str = "pbr";
 // const PUSH_CONSTANT: &str = "push_constant";
 /// For now ui and push constant will be the same
 const PUSH_CONSTANT: &str = UI;
@@ -594,6 +1306,16 @@ pub enum Shader {
     Vertex,
     PushConstant,
     Ui,
+    /// Metallic-roughness shading configured by the material's [`pbr::PbrMaterial`]
+    Pbr,
+}
+
+impl Shader {
+    /// Whether the shader is lit and reads the scene descriptor set
+    #[must_use]
+    pub const fn is_lit(self) -> bool {
+        matches!(self, Self::Light | Self::LightTexture | Self::Pbr)
+    }
 }
 
 impl From<Shader> for &str {
@@ -605,6 +1327,7 @@ impl From<Shader> for &str {
             Shader::Vertex => VERTEX,
             Shader::PushConstant => PUSH_CONSTANT,
             Shader::Ui => UI,
+            Shader::Pbr => PBR,
         }
     }
 }
@@ -0,0 +1,453 @@
+use crate::uniform_buffer::{UniformBufferObject, FIELD_OF_VIEW};
+use nalgebra::{Matrix4, Point3, Vector3};
+
+/// Most lights the lit shaders evaluate, extra lights are ignored
+pub(crate) const MAX_LIGHTS: usize = 4;
+/// Shadow map layers shared between every shadow casting light
+pub(crate) const MAX_SHADOW_LAYERS: usize = 8;
+/// Cascades rendered for each shadow casting directional light
+pub(crate) const SHADOW_CASCADES: usize = 3;
+/// Width and height of each shadow map layer
+pub(crate) const SHADOW_MAP_SIZE: u32 = 2048;
+
+/// Distance from the camera, along its view direction, covered by cascaded shadows
+const SHADOW_DISTANCE: f32 = 40.;
+const CASCADE_NEAR: f32 = 0.1;
+/// Blend between uniform (0) and logarithmic (1) cascade splits
+const CASCADE_SPLIT_LAMBDA: f32 = 0.75;
+/// Extra depth behind each cascade so casters outside the view still shadow it
+const CASCADE_CASTER_MARGIN: f32 = 20.;
+const SPOT_NEAR: f32 = 0.05;
+
+/// Light illuminating objects drawn with [`crate::Shader::Light`],
+/// [`crate::Shader::LightTexture`] and [`crate::Shader::Pbr`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    Directional(DirectionalLight),
+    Spot(SpotLight),
+}
+
+impl Light {
+    /// The light placed by `world`, such as the world matrix of the scene graph node it is
+    /// attached to
+    #[must_use]
+    pub fn transformed(&self, world: &Matrix4<f32>) -> Self {
+        match *self {
+            Self::Directional(light) => Self::Directional(DirectionalLight {
+                direction: world.transform_vector(&light.direction),
+                ..light
+            }),
+            Self::Spot(light) => Self::Spot(SpotLight {
+                position: world.transform_point(&light.position),
+                direction: world.transform_vector(&light.direction),
+                ..light
+            }),
+        }
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Self {
+        Self::Directional(light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Self::Spot(light)
+    }
+}
+
+/// Light arriving from a single direction everywhere in the scene, such as the sun
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight {
+    pub direction: Vector3<f32>,
+    pub colour: Vector3<f32>,
+    pub intensity: f32,
+    pub cast_shadows: bool,
+}
+
+impl DirectionalLight {
+    #[must_use]
+    pub fn new(direction: Vector3<f32>) -> Self {
+        Self {
+            direction,
+            colour: Vector3::new(1., 1., 1.),
+            intensity: 1.,
+            cast_shadows: true,
+        }
+    }
+
+    #[must_use]
+    pub fn colour(mut self, colour: Vector3<f32>) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    #[must_use]
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    #[must_use]
+    pub fn cast_shadows(mut self, cast_shadows: bool) -> Self {
+        self.cast_shadows = cast_shadows;
+        self
+    }
+}
+
+/// Cone of light from a point, fading out between its inner and outer angles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpotLight {
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub colour: Vector3<f32>,
+    pub intensity: f32,
+    pub range: f32,
+    /// Half angle of the fully lit part of the cone, in radians
+    pub inner_angle: f32,
+    /// Half angle of the whole cone, in radians
+    pub outer_angle: f32,
+    pub cast_shadows: bool,
+}
+
+impl SpotLight {
+    #[must_use]
+    pub fn new(position: Point3<f32>, direction: Vector3<f32>) -> Self {
+        Self {
+            position,
+            direction,
+            colour: Vector3::new(1., 1., 1.),
+            intensity: 10.,
+            range: 20.,
+            inner_angle: 20_f32.to_radians(),
+            outer_angle: 30_f32.to_radians(),
+            cast_shadows: true,
+        }
+    }
+
+    #[must_use]
+    pub fn colour(mut self, colour: Vector3<f32>) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    #[must_use]
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    #[must_use]
+    pub fn range(mut self, range: f32) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Sets the inner and outer half angles of the cone, in radians
+    #[must_use]
+    pub fn cone(mut self, inner_angle: f32, outer_angle: f32) -> Self {
+        self.inner_angle = inner_angle.min(outer_angle);
+        self.outer_angle = outer_angle;
+        self
+    }
+
+    #[must_use]
+    pub fn cast_shadows(mut self, cast_shadows: bool) -> Self {
+        self.cast_shadows = cast_shadows;
+        self
+    }
+}
+
+/// A light as read by the lit shaders, matching `Light` in `scene.glsl`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct LightUniform {
+    /// w: 0 for directional lights, 1 for spot lights
+    pub position: [f32; 4],
+    /// w: range
+    pub direction: [f32; 4],
+    /// w: intensity
+    pub colour: [f32; 4],
+    /// x: cosine of the inner angle, y: cosine of the outer angle,
+    /// z: first shadow map layer or -1, w: shadow map layer count
+    pub shadow: [f32; 4],
+}
+
+/// Lights and shadow views for a single frame
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FrameLights {
+    pub lights: [LightUniform; MAX_LIGHTS],
+    pub light_count: usize,
+    /// View projection of each used shadow map layer
+    pub shadow_matrices: Vec<Matrix4<f32>>,
+    /// View space distance at which each cascade ends
+    pub cascade_splits: [f32; 4],
+}
+
+impl FrameLights {
+    pub fn new(lights: &[Light], ubo: &UniformBufferObject, aspect: f32) -> Self {
+        let inverse_view = ubo.view().try_inverse().unwrap_or_else(Matrix4::identity);
+        let cascade_splits = cascade_splits();
+
+        let mut frame = Self {
+            cascade_splits,
+            ..Default::default()
+        };
+
+        lights.iter().take(MAX_LIGHTS).for_each(|light| {
+            let uniform = match light {
+                Light::Directional(light) => {
+                    let direction = light.direction.normalize();
+
+                    let shadow = if light.cast_shadows
+                        && frame.shadow_matrices.len() + SHADOW_CASCADES <= MAX_SHADOW_LAYERS
+                    {
+                        let first_layer = frame.shadow_matrices.len();
+                        let mut near = CASCADE_NEAR;
+                        cascade_splits
+                            .iter()
+                            .take(SHADOW_CASCADES)
+                            .for_each(|&far| {
+                                frame.shadow_matrices.push(cascade_matrix(
+                                    &direction,
+                                    &inverse_view,
+                                    aspect,
+                                    near,
+                                    far,
+                                ));
+                                near = far;
+                            });
+                        [0., 0., first_layer as f32, SHADOW_CASCADES as f32]
+                    } else {
+                        [0., 0., -1., 0.]
+                    };
+
+                    LightUniform {
+                        position: [0., 0., 0., 0.],
+                        direction: [direction.x, direction.y, direction.z, 0.],
+                        colour: [
+                            light.colour.x,
+                            light.colour.y,
+                            light.colour.z,
+                            light.intensity,
+                        ],
+                        shadow,
+                    }
+                }
+                Light::Spot(light) => {
+                    let direction = light.direction.normalize();
+
+                    let shadow_layer =
+                        if light.cast_shadows && frame.shadow_matrices.len() < MAX_SHADOW_LAYERS {
+                            frame.shadow_matrices.push(spot_matrix(light));
+                            (frame.shadow_matrices.len() - 1) as f32
+                        } else {
+                            -1.
+                        };
+
+                    LightUniform {
+                        position: [light.position.x, light.position.y, light.position.z, 1.],
+                        direction: [direction.x, direction.y, direction.z, light.range],
+                        colour: [
+                            light.colour.x,
+                            light.colour.y,
+                            light.colour.z,
+                            light.intensity,
+                        ],
+                        shadow: [
+                            light.inner_angle.cos(),
+                            light.outer_angle.cos(),
+                            shadow_layer,
+                            f32::from(u8::from(shadow_layer >= 0.)),
+                        ],
+                    }
+                }
+            };
+
+            frame.lights[frame.light_count] = uniform;
+            frame.light_count += 1;
+        });
+
+        frame
+    }
+}
+
+/// Far distance of each cascade, mixing uniform and logarithmic splits
+fn cascade_splits() -> [f32; 4] {
+    let mut splits = [SHADOW_DISTANCE; 4];
+    splits
+        .iter_mut()
+        .take(SHADOW_CASCADES)
+        .enumerate()
+        .for_each(|(i, split)| {
+            let p = (i + 1) as f32 / SHADOW_CASCADES as f32;
+            let logarithmic = CASCADE_NEAR * (SHADOW_DISTANCE / CASCADE_NEAR).powf(p);
+            let uniform = CASCADE_NEAR + (SHADOW_DISTANCE - CASCADE_NEAR) * p;
+            *split = CASCADE_SPLIT_LAMBDA * logarithmic + (1. - CASCADE_SPLIT_LAMBDA) * uniform;
+        });
+    splits
+}
+
+/// Orthographic light view enclosing the slice of the camera frustum between `near` and `far`.
+///
+/// The slice is bounded by a sphere and the result snapped to whole shadow map
+/// texels, so the shadows don't shimmer as the camera moves or turns.
+fn cascade_matrix(
+    direction: &Vector3<f32>,
+    inverse_view: &Matrix4<f32>,
+    aspect: f32,
+    near: f32,
+    far: f32,
+) -> Matrix4<f32> {
+    let tan_y = (FIELD_OF_VIEW / 2.).tan();
+    let tan_x = tan_y * aspect;
+
+    let corners = [near, far].into_iter().flat_map(|distance| {
+        [(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)].map(|(x, y)| {
+            inverse_view.transform_point(&Point3::new(
+                x * distance * tan_x,
+                y * distance * tan_y,
+                -distance,
+            ))
+        })
+    });
+    let corners = corners.collect::<Vec<_>>();
+
+    let centre = Point3::from(
+        corners
+            .iter()
+            .fold(Vector3::zeros(), |sum, corner| sum + corner.coords)
+            / corners.len() as f32,
+    );
+
+    let radius = corners
+        .iter()
+        .map(|corner| nalgebra::distance(corner, &centre))
+        .fold(0., f32::max);
+    let radius = (radius * 16.).ceil() / 16.;
+
+    let eye = centre - direction * (radius + CASCADE_CASTER_MARGIN);
+    let view = Matrix4::look_at_rh(&eye, &centre, &up_for(direction));
+    let mut projection = orthographic_zo(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        0.,
+        2. * radius + CASCADE_CASTER_MARGIN,
+    );
+
+    let half_size = SHADOW_MAP_SIZE as f32 / 2.;
+    let origin = (projection * view).transform_point(&Point3::origin()) * half_size;
+    projection[(0, 3)] += (origin.x.round() - origin.x) / half_size;
+    projection[(1, 3)] += (origin.y.round() - origin.y) / half_size;
+
+    projection * view
+}
+
+fn spot_matrix(light: &SpotLight) -> Matrix4<f32> {
+    let direction = light.direction.normalize();
+    let view = Matrix4::look_at_rh(
+        &light.position,
+        &(light.position + direction),
+        &up_for(&direction),
+    );
+    perspective_zo(2. * light.outer_angle, 1., SPOT_NEAR, light.range) * view
+}
+
+fn up_for(direction: &Vector3<f32>) -> Vector3<f32> {
+    if direction.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    }
+}
+
+/// Right handed orthographic projection onto Vulkan's 0 to 1 depth range
+fn orthographic_zo(
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near: f32,
+    far: f32,
+) -> Matrix4<f32> {
+    let mut projection = Matrix4::identity();
+    projection[(0, 0)] = 2. / (right - left);
+    projection[(1, 1)] = 2. / (top - bottom);
+    projection[(2, 2)] = -1. / (far - near);
+    projection[(0, 3)] = -(right + left) / (right - left);
+    projection[(1, 3)] = -(top + bottom) / (top - bottom);
+    projection[(2, 3)] = -near / (far - near);
+    projection
+}
+
+/// Right handed perspective projection onto Vulkan's 0 to 1 depth range
+fn perspective_zo(fov_y: f32, aspect: f32, near: f32, far: f32) -> Matrix4<f32> {
+    let focal_length = 1. / (fov_y / 2.).tan();
+    let mut projection = Matrix4::zeros();
+    projection[(0, 0)] = focal_length / aspect;
+    projection[(1, 1)] = focal_length;
+    projection[(2, 2)] = far / (near - far);
+    projection[(2, 3)] = near * far / (near - far);
+    projection[(3, 2)] = -1.;
+    projection
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cascade_splits_increase_to_shadow_distance() {
+        let splits = cascade_splits();
+
+        assert!(splits.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!((splits[SHADOW_CASCADES - 1] - SHADOW_DISTANCE).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_spot_matrix_depth_range() {
+        let light = SpotLight::new(Point3::origin(), -Vector3::z()).range(10.);
+        let matrix = spot_matrix(&light);
+
+        let near = matrix.transform_point(&Point3::new(0., 0., -SPOT_NEAR));
+        let far = matrix.transform_point(&Point3::new(0., 0., -10.));
+
+        assert!(near.z.abs() < 1e-4);
+        assert!((far.z - 1.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_transformed_lights_follow_their_node() {
+        let world = Matrix4::new_translation(&Vector3::new(1., 2., 3.))
+            * Matrix4::from_scaled_axis(Vector3::y() * std::f32::consts::FRAC_PI_2);
+
+        let Light::Spot(spot) =
+            Light::from(SpotLight::new(Point3::origin(), -Vector3::z())).transformed(&world)
+        else {
+            unreachable!()
+        };
+        assert!((spot.position - Point3::new(1., 2., 3.)).norm() < 1e-5);
+        assert!((spot.direction - -Vector3::x()).norm() < 1e-5);
+
+        let Light::Directional(directional) =
+            Light::from(DirectionalLight::new(-Vector3::y())).transformed(&world)
+        else {
+            unreachable!()
+        };
+        assert!((directional.direction - -Vector3::y()).norm() < 1e-5);
+    }
+
+    #[test]
+    fn test_shadow_layers_are_limited() {
+        let lights = vec![Light::from(DirectionalLight::new(-Vector3::y())); MAX_LIGHTS];
+        let frame = FrameLights::new(&lights, &UniformBufferObject::default(), 1.);
+
+        assert_eq!(frame.light_count, MAX_LIGHTS);
+        assert!(frame.shadow_matrices.len() <= MAX_SHADOW_LAYERS);
+        assert_eq!(frame.lights[MAX_LIGHTS - 1].shadow[2], -1.);
+    }
+}
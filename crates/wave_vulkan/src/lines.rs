@@ -0,0 +1,237 @@
+use crate::{
+    any_as_u8_slice,
+    buffer::FrameVertexBuffers,
+    device::Devices,
+    graphics_pipeline::{self, ShaderModules},
+    profiler::DrawCounts,
+    ModelTopology,
+};
+use ash::{vk, Device};
+use gpu_allocator::vulkan::Allocator;
+use memoffset::offset_of;
+use nalgebra::Matrix4;
+use std::mem;
+use wave_window::debug_draw::LineVertex;
+
+/// Debug lines from [`wave_window::debug_draw::DebugDraw`], drawn in one batch over the scene
+pub(crate) struct DebugLines {
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    buffers: FrameVertexBuffers,
+    vertices: Vec<LineVertex>,
+}
+
+impl DebugLines {
+    pub fn new(devices: &Devices, render_pass: vk::RenderPass) -> Self {
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .size(mem::size_of::<Matrix4<f32>>() as u32)
+            .offset(0);
+
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+
+        let layout = unsafe {
+            devices
+                .logical
+                .device
+                .create_pipeline_layout(&layout_info, None)
+                .expect("Failed to create debug line pipeline layout!")
+        };
+
+        Self {
+            pipeline: create_pipeline(devices, render_pass, layout),
+            layout,
+            buffers: FrameVertexBuffers::new("debug lines"),
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Replaces the lines drawn from the next recorded frame onwards
+    pub fn set(&mut self, vertices: &[LineVertex]) {
+        self.vertices.clear();
+        self.vertices.extend_from_slice(vertices);
+    }
+
+    /// Copies the lines into the vertex buffer of `frame`.
+    ///
+    /// # Safety
+    ///
+    /// The last submission of `frame` must have completed.
+    pub unsafe fn upload(&mut self, allocator: &mut Allocator, devices: &Devices, frame: usize) {
+        if !self.vertices.is_empty() {
+            self.buffers
+                .write(allocator, devices, frame, &self.vertices);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording inside the scene render pass, with the lines of
+    /// `frame` uploaded.
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        view_projection: &Matrix4<f32>,
+        counts: &mut DrawCounts,
+    ) {
+        let Some(buffer) = self.buffers.get(frame) else {
+            return;
+        };
+
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            self.layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            any_as_u8_slice(view_projection),
+        );
+        device.cmd_bind_vertex_buffers(command_buffer, 0, std::slice::from_ref(&buffer), &[0]);
+
+        let count = self.vertices.len() as u32;
+        device.cmd_draw(command_buffer, count, 1, 0, 0);
+        counts.draw(count, ModelTopology::LineList);
+    }
+
+    /// Rebuilds the pipeline after the render pass was recreated
+    pub fn recreate(&mut self, devices: &Devices, render_pass: vk::RenderPass) {
+        self.pipeline = create_pipeline(devices, render_pass, self.layout);
+    }
+
+    /// # Safety
+    ///
+    /// The pipeline must no longer be in use by the device.
+    pub unsafe fn destroy_pipeline(&self, device: &Device) {
+        device.destroy_pipeline(self.pipeline, None);
+    }
+
+    /// Frees the buffers and layout, after the pipeline was destroyed with the swap chain.
+    ///
+    /// # Safety
+    ///
+    /// No frame may be in flight.
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator, device: &Device) {
+        self.buffers.destroy(allocator, device);
+
+        device.destroy_pipeline_layout(self.layout, None);
+    }
+}
+
+fn create_pipeline(
+    devices: &Devices,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+) -> vk::Pipeline {
+    let device = &devices.logical.device;
+    let ShaderModules { vert, frag } = graphics_pipeline::load_shader_stages("lines", device);
+
+    let binding_description = vk::VertexInputBindingDescription::default()
+        .binding(0)
+        .stride(mem::size_of::<LineVertex>() as u32)
+        .input_rate(vk::VertexInputRate::VERTEX);
+
+    let attribute_descriptions = [
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(0)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(LineVertex, pos) as u32),
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(1)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(LineVertex, colour) as u32),
+    ];
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(std::slice::from_ref(&binding_description))
+        .vertex_attribute_descriptions(&attribute_descriptions);
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+        .topology(ModelTopology::LineList.into());
+
+    let view_port_state = vk::PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+
+    let multi_sampling = vk::PipelineMultisampleStateCreateInfo::default()
+        .rasterization_samples(devices.physical.samples);
+
+    // Lines are hidden behind the scene but do not hide each other
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+        .depth_test_enable(true)
+        .depth_write_enable(false)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .max_depth_bounds(1.);
+
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
+        .color_write_mask(vk::ColorComponentFlags::RGBA)
+        .blend_enable(false);
+
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
+        .attachments(std::slice::from_ref(&color_blend_attachment));
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+    let dynamic_state_create_info =
+        vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    unsafe {
+        let entry_point = c"main";
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert)
+                .name(entry_point),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag)
+                .name(entry_point),
+        ];
+
+        let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_info)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&view_port_state)
+            .rasterization_state(&rasterizer)
+            .multisample_state(&multi_sampling)
+            .depth_stencil_state(&depth_stencil)
+            .color_blend_state(&color_blending)
+            .dynamic_state(&dynamic_state_create_info)
+            .layout(layout)
+            .render_pass(render_pass)
+            .subpass(0);
+
+        let pipeline = device
+            .create_graphics_pipelines(
+                vk::PipelineCache::null(),
+                std::slice::from_ref(&pipeline_info),
+                None,
+            )
+            .expect("Failed to create debug line pipeline!")[0];
+
+        graphics_pipeline::destroy_shader_modules(device, vert, frag);
+
+        devices.debug_names.name(pipeline, "debug lines/pipeline");
+
+        pipeline
+    }
+}
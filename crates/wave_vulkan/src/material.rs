@@ -0,0 +1,462 @@
+use crate::{
+    command_buffer::CommandPool,
+    device::Devices,
+    graphics_pipeline::{GraphicsPipeline, PipelineLayouts, PipelineState, MATERIAL_MAP_COUNT},
+    pbr::{PbrMaterial, PbrTextures},
+    swap_chain::SwapChain,
+    BlendMode, CullMode, ModelTopology, Shader, Shadows,
+};
+use ash::{vk, Device, Instance};
+use gpu_allocator::vulkan::Allocator;
+
+/// Shader, textures, colour and render state shared by every geometry drawn with it.
+///
+/// Materials are added to [`Materials`] once and referenced by [`MaterialHandle`], so many
+/// geometries can share one pipeline and one set of uploaded textures.
+#[derive(Clone, Debug, Default)]
+pub struct Material {
+    pub shader: Shader,
+    /// Colour, scalar parameters and maps. Shaders other than [`Shader::Pbr`] only read the
+    /// base colour and, for [`Shader::LightTexture`], the normal map.
+    pub pbr: PbrMaterial,
+    pub cull_mode: CullMode,
+    pub blend_mode: BlendMode,
+    pub shadows: Shadows,
+    /// Name the pipeline and textures are given in graphics debuggers and validation
+    /// messages. Shared materials are otherwise numbered, and a geometry's own material
+    /// takes the geometry's label.
+    pub label: Option<String>,
+}
+
+impl Material {
+    #[must_use]
+    pub fn new(shader: Shader) -> Self {
+        Self {
+            shader,
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn colour(mut self, colour: [f32; 4]) -> Self {
+        self.pbr.base_colour_factor = colour;
+        self
+    }
+
+    #[must_use]
+    pub fn texture(mut self, path: &str) -> Self {
+        self.pbr = self.pbr.base_colour_texture(path);
+        self
+    }
+
+    #[must_use]
+    pub fn normal_map(mut self, path: &str) -> Self {
+        self.pbr = self.pbr.normal_texture(path);
+        self
+    }
+
+    /// Replaces the colour, parameters and maps, keeping the shader and render state
+    #[must_use]
+    pub fn pbr(mut self, pbr: PbrMaterial) -> Self {
+        self.pbr = pbr;
+        self
+    }
+
+    #[must_use]
+    pub fn cull_mode(mut self, cull_mode: CullMode) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    #[must_use]
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    #[must_use]
+    pub fn cast_shadows(mut self, cast: bool) -> Self {
+        self.shadows.cast = cast;
+        self
+    }
+
+    #[must_use]
+    pub fn receive_shadows(mut self, receive: bool) -> Self {
+        self.shadows.receive = receive;
+        self
+    }
+
+    #[must_use]
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// The label, or `fallback` for materials without one
+    pub(crate) fn name_or(&self, fallback: impl FnOnce() -> String) -> String {
+        self.label.clone().unwrap_or_else(fallback)
+    }
+}
+
+/// Reference to a material added to [`Materials`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialHandle(pub(crate) usize);
+
+/// Materials shared between geometries, uploaded once when the renderer is created
+#[derive(Clone, Debug, Default)]
+pub struct Materials(Vec<Material>);
+
+impl Materials {
+    pub fn add(&mut self, material: Material) -> MaterialHandle {
+        self.0.push(material);
+        MaterialHandle(self.0.len() - 1)
+    }
+
+    #[must_use]
+    pub fn get(&self, handle: MaterialHandle) -> Option<&Material> {
+        self.0.get(handle.0)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Material> {
+        self.0.iter()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Index into the renderer's materials of each geometry's material, given its handle if it
+/// shares one. Geometries without a handle get their own material, numbered after the
+/// `shared` ones in the order of the geometries.
+///
+/// # Panics
+///
+/// When a handle is not one of the shared materials.
+pub(crate) fn material_indices(
+    handles: impl IntoIterator<Item = Option<MaterialHandle>>,
+    shared: usize,
+) -> Vec<usize> {
+    let mut owned = shared;
+
+    handles
+        .into_iter()
+        .map(|handle| match handle {
+            Some(MaterialHandle(index)) => {
+                assert!(index < shared, "Unknown material handle {index}");
+                index
+            }
+            None => {
+                owned += 1;
+                owned - 1
+            }
+        })
+        .collect()
+}
+
+/// What a [`VulkanMaterial`] is created from, and the render pass it draws in
+pub(crate) struct MaterialCreateInfo<'a> {
+    pub material: &'a Material,
+    /// Topologies of the geometries drawn with the material, each getting a pipeline
+    pub topologies: &'a [ModelTopology],
+    pub name: &'a str,
+    pub swap_chain: &'a SwapChain,
+    pub render_pass: vk::RenderPass,
+    pub layouts: &'a PipelineLayouts,
+}
+
+/// A material's uploaded textures, its descriptor set and the pipelines drawing it, one for
+/// each topology of the geometry using it
+#[derive(Debug)]
+pub(crate) struct VulkanMaterial {
+    pub material: Material,
+    pub textures: PbrTextures,
+    pub pool: vk::DescriptorPool,
+    pub set: vk::DescriptorSet,
+    pub pipelines: Vec<GraphicsPipeline>,
+    /// Name the material's objects are given for debugging
+    pub name: String,
+}
+
+impl VulkanMaterial {
+    pub fn new(
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        devices: &Devices,
+        info: &MaterialCreateInfo,
+    ) -> Self {
+        let device = &devices.logical.device;
+        let MaterialCreateInfo {
+            material,
+            topologies,
+            name,
+            swap_chain,
+            render_pass,
+            layouts,
+        } = *info;
+
+        let textures = PbrTextures::new(
+            allocator,
+            command_pool,
+            instance,
+            devices,
+            &material.pbr,
+            name,
+        );
+
+        let pool = create_descriptor_pool(device);
+
+        let set = create_descriptor_set(device, pool, layouts.material_set_layout, &textures);
+        devices
+            .debug_names
+            .name(set, &format!("{name}/descriptor set"));
+
+        let mut pipelines: Vec<GraphicsPipeline> = Vec::new();
+
+        topologies.iter().for_each(|topology| {
+            if pipelines
+                .iter()
+                .all(|pipeline| pipeline.state.topology != *topology)
+            {
+                pipelines.push(GraphicsPipeline::new(
+                    swap_chain,
+                    render_pass,
+                    layouts.layout,
+                    PipelineState {
+                        topology: *topology,
+                        cull_mode: material.cull_mode,
+                        blend_mode: material.blend_mode,
+                        receive_shadows: material.shadows.receive,
+                        shader_type: material.shader,
+                    },
+                    &format!("{name}/{topology:?}"),
+                    devices,
+                ));
+            }
+        });
+
+        Self {
+            material: material.clone(),
+            textures,
+            pool,
+            set,
+            pipelines,
+            name: name.to_owned(),
+        }
+    }
+
+    /// Pipeline drawing the material's geometry with `topology`
+    ///
+    /// # Panics
+    ///
+    /// When no geometry with `topology` used the material as it was created.
+    pub fn pipeline(&self, topology: ModelTopology) -> &GraphicsPipeline {
+        self.pipelines
+            .iter()
+            .find(|pipeline| pipeline.state.topology == topology)
+            .expect("The material has no pipeline for the topology")
+    }
+
+    pub fn topologies(&self) -> Vec<ModelTopology> {
+        self.pipelines
+            .iter()
+            .map(|pipeline| pipeline.state.topology)
+            .collect()
+    }
+
+    /// # Safety
+    ///
+    /// The pipelines must no longer be in use by the device.
+    pub unsafe fn destroy_pipelines(&self, device: &Device) {
+        self.pipelines
+            .iter()
+            .for_each(|pipeline| pipeline.destroy(device));
+    }
+
+    /// Uploads the textures again, so their samplers pick up a changed anisotropy. The
+    /// pipeline is kept.
+    ///
+    /// # Safety
+    ///
+    /// The material must no longer be in use by the device.
+    pub unsafe fn reload_textures(
+        &mut self,
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        layouts: &PipelineLayouts,
+        devices: &Devices,
+    ) {
+        let device = &devices.logical.device;
+
+        let textures = PbrTextures::new(
+            allocator,
+            command_pool,
+            instance,
+            devices,
+            &self.material.pbr,
+            &self.name,
+        );
+        let pool = create_descriptor_pool(device);
+        let set = create_descriptor_set(device, pool, layouts.material_set_layout, &textures);
+        devices
+            .debug_names
+            .name(set, &format!("{}/descriptor set", self.name));
+
+        device.destroy_descriptor_pool(std::mem::replace(&mut self.pool, pool), None);
+        std::mem::replace(&mut self.textures, textures).destroy(allocator, device);
+        self.set = set;
+    }
+
+    /// Destroys the textures and descriptor set. The pipelines are destroyed separately, as
+    /// they are with every swap chain cleanup.
+    ///
+    /// # Safety
+    ///
+    /// The material must no longer be in use by the device.
+    pub unsafe fn destroy(self, allocator: &mut Allocator, device: &Device) {
+        device.destroy_descriptor_pool(self.pool, None);
+        self.textures.destroy(allocator, device);
+    }
+}
+
+pub(crate) fn create_descriptor_pool(device: &Device) -> vk::DescriptorPool {
+    let pool_sizes = [
+        vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1),
+        vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(MATERIAL_MAP_COUNT),
+    ];
+
+    let pool_info = vk::DescriptorPoolCreateInfo::default()
+        .pool_sizes(&pool_sizes)
+        .max_sets(1);
+
+    unsafe {
+        device
+            .create_descriptor_pool(&pool_info, None)
+            .expect("Failed to create material descriptor pool!")
+    }
+}
+
+fn create_descriptor_set(
+    device: &Device,
+    pool: vk::DescriptorPool,
+    set_layout: vk::DescriptorSetLayout,
+    textures: &PbrTextures,
+) -> vk::DescriptorSet {
+    let alloc_info = vk::DescriptorSetAllocateInfo::default()
+        .descriptor_pool(pool)
+        .set_layouts(std::slice::from_ref(&set_layout));
+
+    let set = unsafe {
+        device
+            .allocate_descriptor_sets(&alloc_info)
+            .expect("Failed to allocate material descriptor set!")[0]
+    };
+
+    let factors_info = textures.factors_info();
+    let image_infos = textures.image_infos();
+
+    // The maps occupy consecutive bindings, so one write fills all of them
+    let descriptor_writes = [
+        vk::WriteDescriptorSet::default()
+            .dst_set(set)
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(std::slice::from_ref(&factors_info)),
+        vk::WriteDescriptorSet::default()
+            .dst_set(set)
+            .dst_binding(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_infos),
+    ];
+
+    unsafe { device.update_descriptor_sets(&descriptor_writes, &[]) };
+
+    set
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_material_defaults() {
+        let material = Material::new(Shader::Pbr);
+
+        assert_eq!(material.shader, Shader::Pbr);
+        assert_eq!(material.cull_mode, CullMode::default());
+        assert_eq!(material.blend_mode, BlendMode::Opaque);
+        assert_eq!(material.shadows, Shadows::default());
+        assert_eq!(material.pbr.base_colour_factor, [1., 1., 1., 1.]);
+        assert!(material.pbr.base_colour_texture.is_empty());
+        assert_eq!(material.name_or(|| "material 0".to_owned()), "material 0");
+    }
+
+    #[test]
+    fn test_material_builder() {
+        let material = Material::default()
+            .colour([1., 0., 0., 0.5])
+            .blend_mode(BlendMode::AlphaBlend)
+            .cast_shadows(false)
+            .label("glass");
+
+        assert_eq!(material.pbr.base_colour_factor, [1., 0., 0., 0.5]);
+        assert_eq!(material.blend_mode, BlendMode::AlphaBlend);
+        assert_eq!(
+            material.shadows,
+            Shadows {
+                cast: false,
+                receive: true
+            }
+        );
+        assert_eq!(material.name_or(|| "material 0".to_owned()), "glass");
+    }
+
+    #[test]
+    fn test_materials_hand_out_handles_in_order() {
+        let mut materials = Materials::default();
+
+        let red = materials.add(Material::default().colour([1., 0., 0., 1.]));
+        let blue = materials.add(Material::default().colour([0., 0., 1., 1.]));
+
+        assert_eq!((red, blue), (MaterialHandle(0), MaterialHandle(1)));
+        assert_eq!(materials.len(), 2);
+        assert_eq!(
+            materials
+                .get(blue)
+                .map(|material| material.pbr.base_colour_factor),
+            Some([0., 0., 1., 1.])
+        );
+        assert!(materials.get(MaterialHandle(2)).is_none());
+    }
+
+    #[test]
+    fn test_owned_materials_follow_the_shared_ones() {
+        let handles = [
+            Some(MaterialHandle(1)),
+            None,
+            Some(MaterialHandle(0)),
+            None,
+            Some(MaterialHandle(1)),
+        ];
+
+        assert_eq!(material_indices(handles, 2), [1, 2, 0, 3, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown material handle")]
+    fn test_unknown_material_handle() {
+        material_indices([Some(MaterialHandle(2))], 2);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{
+    any_as_u8_slice,
+    buffer::FrameVertexBuffers,
+    device::Devices,
+    graphics_pipeline::{self, PipelineLayouts, ShaderModules},
+    profiler::DrawCounts,
+    sprite::SpriteRenderer,
+    BlendMode, ModelTopology,
+};
+use ash::{vk, Device};
+use gpu_allocator::vulkan::Allocator;
+use memoffset::offset_of;
+use nalgebra::{Matrix4, Vector3, Vector4};
+use std::mem;
+use wave_window::particles::{ParticleBatches, ParticleBlend, ParticleInstance};
+
+/// Corners of the two triangles drawn for each particle
+const QUAD_VERTICES: u32 = 6;
+
+/// What the particle vertex shader needs to turn each quad towards the camera
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ParticleView {
+    view_projection: Matrix4<f32>,
+    right: Vector4<f32>,
+    up: Vector4<f32>,
+}
+
+impl ParticleView {
+    pub fn new(view_projection: Matrix4<f32>, right: Vector3<f32>, up: Vector3<f32>) -> Self {
+        Self {
+            view_projection,
+            right: right.push(0.),
+            up: up.push(0.),
+        }
+    }
+}
+
+/// Particles from [`ParticleBatches`], drawn in the scene pass after the transparent objects
+/// as one instanced quad per particle, sampling the sprite textures
+pub(crate) struct ParticleRenderer {
+    alpha_blend: vk::Pipeline,
+    additive: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    buffers: FrameVertexBuffers,
+    particles: ParticleBatches,
+}
+
+impl ParticleRenderer {
+    pub fn new(devices: &Devices, render_pass: vk::RenderPass, layouts: &PipelineLayouts) -> Self {
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .size(mem::size_of::<ParticleView>() as u32)
+            .offset(0);
+
+        // Textures are bound to the material set, so the sets up to it match the other
+        // pipelines
+        let set_layouts = [layouts.object_set_layout, layouts.material_set_layout];
+
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+
+        let layout = unsafe {
+            devices
+                .logical
+                .device
+                .create_pipeline_layout(&layout_info, None)
+                .expect("Failed to create particle pipeline layout!")
+        };
+
+        let (alpha_blend, additive) = create_pipelines(devices, render_pass, layout);
+
+        Self {
+            alpha_blend,
+            additive,
+            layout,
+            buffers: FrameVertexBuffers::new("particles"),
+            particles: ParticleBatches::default(),
+        }
+    }
+
+    /// Replaces the particles drawn from the next recorded frame onwards
+    pub fn set(&mut self, particles: &ParticleBatches) {
+        self.particles.clone_from(particles);
+    }
+
+    /// Copies the particles into the instance buffer of `frame`.
+    ///
+    /// # Safety
+    ///
+    /// The last submission of `frame` must have completed.
+    pub unsafe fn upload(&mut self, allocator: &mut Allocator, devices: &Devices, frame: usize) {
+        if !self.particles.instances.is_empty() {
+            self.buffers
+                .write(allocator, devices, frame, &self.particles.instances);
+        }
+    }
+
+    /// Draws the particles facing the camera of `view`, with the sprites' textures.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording inside the scene render pass, with the particles
+    /// of `frame` uploaded.
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        view: &ParticleView,
+        sprites: &SpriteRenderer,
+        counts: &mut DrawCounts,
+    ) {
+        let Some(buffer) = self.buffers.get(frame) else {
+            return;
+        };
+
+        if self.particles.is_empty() {
+            return;
+        }
+
+        device.cmd_push_constants(
+            command_buffer,
+            self.layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            any_as_u8_slice(view),
+        );
+        device.cmd_bind_vertex_buffers(command_buffer, 0, std::slice::from_ref(&buffer), &[0]);
+
+        let mut bound = None;
+
+        self.particles.batches.iter().for_each(|batch| {
+            let Some(texture) = sprites.texture(batch.texture) else {
+                return;
+            };
+
+            if bound != Some(batch.blend) {
+                let pipeline = match batch.blend {
+                    ParticleBlend::AlphaBlend => self.alpha_blend,
+                    ParticleBlend::Additive => self.additive,
+                };
+
+                device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                bound = Some(batch.blend);
+            }
+
+            texture.bind(device, command_buffer, self.layout);
+            device.cmd_draw(command_buffer, QUAD_VERTICES, batch.count, 0, batch.first);
+            counts.draw(QUAD_VERTICES * batch.count, ModelTopology::TriangleList);
+        });
+    }
+
+    /// Rebuilds the pipelines after the render pass was recreated
+    pub fn recreate(&mut self, devices: &Devices, render_pass: vk::RenderPass) {
+        (self.alpha_blend, self.additive) = create_pipelines(devices, render_pass, self.layout);
+    }
+
+    /// # Safety
+    ///
+    /// The pipelines must no longer be in use by the device.
+    pub unsafe fn destroy_pipeline(&self, device: &Device) {
+        device.destroy_pipeline(self.alpha_blend, None);
+        device.destroy_pipeline(self.additive, None);
+    }
+
+    /// Frees the buffers and layout, after the pipelines were destroyed with the swap chain.
+    ///
+    /// # Safety
+    ///
+    /// No frame may be in flight.
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator, device: &Device) {
+        self.buffers.destroy(allocator, device);
+
+        device.destroy_pipeline_layout(self.layout, None);
+    }
+}
+
+fn create_pipelines(
+    devices: &Devices,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+) -> (vk::Pipeline, vk::Pipeline) {
+    (
+        create_pipeline(
+            devices,
+            render_pass,
+            layout,
+            BlendMode::AlphaBlend,
+            "particles/alpha blend pipeline",
+        ),
+        create_pipeline(
+            devices,
+            render_pass,
+            layout,
+            BlendMode::Additive,
+            "particles/additive pipeline",
+        ),
+    )
+}
+
+fn create_pipeline(
+    devices: &Devices,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+    blend: BlendMode,
+    name: &str,
+) -> vk::Pipeline {
+    let device = &devices.logical.device;
+    let ShaderModules { vert, frag } = graphics_pipeline::load_shader_stages("particles", device);
+
+    // The quad corners come from the vertex index, so the only input is per particle
+    let binding_description = vk::VertexInputBindingDescription::default()
+        .binding(0)
+        .stride(mem::size_of::<ParticleInstance>() as u32)
+        .input_rate(vk::VertexInputRate::INSTANCE);
+
+    let attribute_descriptions = [
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(0)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(ParticleInstance, position) as u32),
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(1)
+            .format(vk::Format::R32_SFLOAT)
+            .offset(offset_of!(ParticleInstance, size) as u32),
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(2)
+            .format(vk::Format::R32G32B32A32_SFLOAT)
+            .offset(offset_of!(ParticleInstance, colour) as u32),
+    ];
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(std::slice::from_ref(&binding_description))
+        .vertex_attribute_descriptions(&attribute_descriptions);
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+        .topology(ModelTopology::TriangleList.into());
+
+    let view_port_state = vk::PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+
+    let multi_sampling = vk::PipelineMultisampleStateCreateInfo::default()
+        .rasterization_samples(devices.physical.samples);
+
+    // Particles are hidden behind the scene but do not hide each other
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+        .depth_test_enable(true)
+        .depth_write_enable(false)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .max_depth_bounds(1.);
+
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::from(blend);
+
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
+        .attachments(std::slice::from_ref(&color_blend_attachment));
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+    let dynamic_state_create_info =
+        vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    unsafe {
+        let entry_point = c"main";
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert)
+                .name(entry_point),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag)
+                .name(entry_point),
+        ];
+
+        let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_info)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&view_port_state)
+            .rasterization_state(&rasterizer)
+            .multisample_state(&multi_sampling)
+            .depth_stencil_state(&depth_stencil)
+            .color_blend_state(&color_blending)
+            .dynamic_state(&dynamic_state_create_info)
+            .layout(layout)
+            .render_pass(render_pass)
+            .subpass(0);
+
+        let pipeline = device
+            .create_graphics_pipelines(
+                vk::PipelineCache::null(),
+                std::slice::from_ref(&pipeline_info),
+                None,
+            )
+            .expect("Failed to create particle pipeline!")[0];
+
+        graphics_pipeline::destroy_shader_modules(device, vert, frag);
+
+        devices.debug_names.name(pipeline, name);
+
+        pipeline
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    buffer::Buffer,
+    command_buffer::CommandPool,
+    device::Devices,
+    texture::{self, Texture, FLAT_NORMAL, WHITE},
+    TextureBuffer,
+};
+use ash::{vk, Device, Instance};
+use gpu_allocator::vulkan::Allocator;
+use std::mem;
+
+/// Metallic-roughness material read by [`crate::Shader::Pbr`], following the glTF 2.0
+/// material model so imported assets keep the factors and maps they were authored with.
+///
+/// Every map is optional. Missing maps sample as white, or as a flat normal, so only the
+/// factors apply.
+#[derive(Clone, Debug)]
+pub struct PbrMaterial {
+    /// Linear RGBA multiplied with the base colour map and the vertex colour
+    pub base_colour_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Scale applied to the X and Y of the tangent space normal map
+    pub normal_scale: f32,
+    /// How much of the occlusion map is applied to indirect lighting
+    pub occlusion_strength: f32,
+    /// Linear RGB emitted light, multiplied with the emissive map
+    pub emissive_factor: [f32; 3],
+    /// sRGB colour and linear alpha
+    pub base_colour_texture: TextureBuffer,
+    /// Roughness in the green channel and metalness in the blue channel
+    pub metallic_roughness_texture: TextureBuffer,
+    /// Tangent space normals
+    pub normal_texture: TextureBuffer,
+    /// Ambient occlusion in the red channel
+    pub occlusion_texture: TextureBuffer,
+    /// sRGB emitted colour
+    pub emissive_texture: TextureBuffer,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            base_colour_factor: [1., 1., 1., 1.],
+            metallic_factor: 1.,
+            roughness_factor: 1.,
+            normal_scale: 1.,
+            occlusion_strength: 1.,
+            emissive_factor: [0., 0., 0.],
+            base_colour_texture: TextureBuffer::default(),
+            metallic_roughness_texture: TextureBuffer::default(),
+            normal_texture: TextureBuffer::default(),
+            occlusion_texture: TextureBuffer::default(),
+            emissive_texture: TextureBuffer::default(),
+        }
+    }
+}
+
+impl PbrMaterial {
+    #[must_use]
+    pub fn base_colour(mut self, base_colour: [f32; 4]) -> Self {
+        self.base_colour_factor = base_colour;
+        self
+    }
+
+    #[must_use]
+    pub fn metallic(mut self, metallic: f32) -> Self {
+        self.metallic_factor = metallic;
+        self
+    }
+
+    #[must_use]
+    pub fn roughness(mut self, roughness: f32) -> Self {
+        self.roughness_factor = roughness;
+        self
+    }
+
+    #[must_use]
+    pub fn normal_scale(mut self, normal_scale: f32) -> Self {
+        self.normal_scale = normal_scale;
+        self
+    }
+
+    #[must_use]
+    pub fn occlusion_strength(mut self, occlusion_strength: f32) -> Self {
+        self.occlusion_strength = occlusion_strength;
+        self
+    }
+
+    #[must_use]
+    pub fn emissive(mut self, emissive: [f32; 3]) -> Self {
+        self.emissive_factor = emissive;
+        self
+    }
+
+    #[must_use]
+    pub fn base_colour_texture(mut self, path: &str) -> Self {
+        self.base_colour_texture = read_texture(path);
+        self
+    }
+
+    #[must_use]
+    pub fn metallic_roughness_texture(mut self, path: &str) -> Self {
+        self.metallic_roughness_texture = read_texture(path);
+        self
+    }
+
+    #[must_use]
+    pub fn normal_texture(mut self, path: &str) -> Self {
+        self.normal_texture = read_texture(path);
+        self
+    }
+
+    #[must_use]
+    pub fn occlusion_texture(mut self, path: &str) -> Self {
+        self.occlusion_texture = read_texture(path);
+        self
+    }
+
+    #[must_use]
+    pub fn emissive_texture(mut self, path: &str) -> Self {
+        self.emissive_texture = read_texture(path);
+        self
+    }
+
+    /// Maps in binding order, empty where missing
+    fn maps(&self) -> [&TextureBuffer; 5] {
+        [
+            &self.base_colour_texture,
+            &self.metallic_roughness_texture,
+            &self.normal_texture,
+            &self.occlusion_texture,
+            &self.emissive_texture,
+        ]
+    }
+
+    fn factors(&self) -> PbrFactors {
+        let [r, g, b] = self.emissive_factor;
+        PbrFactors {
+            base_colour: self.base_colour_factor,
+            emissive: [r, g, b, 0.],
+            parameters: [
+                self.metallic_factor,
+                self.roughness_factor,
+                self.normal_scale,
+                self.occlusion_strength,
+            ],
+        }
+    }
+}
+
+fn read_texture(path: &str) -> TextureBuffer {
+    TextureBuffer(std::fs::read(path).expect("Failed to read contents of texture file"))
+}
+
+/// Name, texel sampled in place of a missing map and format of each map, in binding order
+const MAPS: [(&str, [u8; 4], vk::Format); 5] = [
+    ("base colour", WHITE, vk::Format::R8G8B8A8_SRGB),
+    ("metallic roughness", WHITE, vk::Format::R8G8B8A8_UNORM),
+    ("normal", FLAT_NORMAL, vk::Format::R8G8B8A8_UNORM),
+    ("occlusion", WHITE, vk::Format::R8G8B8A8_UNORM),
+    ("emissive", WHITE, vk::Format::R8G8B8A8_SRGB),
+];
+
+/// Material factors, matching `Material` in the shaders' material.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct PbrFactors {
+    base_colour: [f32; 4],
+    /// xyz: emissive colour
+    emissive: [f32; 4],
+    /// x: metallic, y: roughness, z: normal scale, w: occlusion strength
+    parameters: [f32; 4],
+}
+
+/// Maps and factors of a [`PbrMaterial`] uploaded for one material
+#[derive(Debug)]
+pub(crate) struct PbrTextures {
+    /// Base colour, metallic-roughness, normal, occlusion and emissive, in binding order
+    pub maps: [Texture; 5],
+    pub factors: Buffer,
+}
+
+impl PbrTextures {
+    pub fn new(
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        devices: &Devices,
+        material: &PbrMaterial,
+        name: &str,
+    ) -> Self {
+        let textures = material.maps();
+        let maps = std::array::from_fn(|index| {
+            let (map_name, fallback, format) = MAPS[index];
+            let map = texture::create_map_texture(
+                allocator,
+                command_pool,
+                instance,
+                devices,
+                textures[index],
+                fallback,
+                format,
+            );
+            map.set_name(devices, &format!("{name}/{map_name}"));
+            map
+        });
+
+        let mut factors = texture::create_buffer(
+            allocator,
+            mem::size_of::<PbrFactors>() as vk::DeviceSize,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            devices,
+            "Material Factors",
+        );
+        devices
+            .debug_names
+            .name(factors.buffer, &format!("{name}/factors"));
+
+        let material_factors = material.factors();
+        let bytes = unsafe { crate::any_as_u8_slice(&material_factors) };
+        factors
+            .allocation
+            .mapped_slice_mut()
+            .expect("PBR factor buffer is not host visible")[..bytes.len()]
+            .copy_from_slice(bytes);
+
+        Self { maps, factors }
+    }
+
+    pub fn image_infos(&self) -> [vk::DescriptorImageInfo; 5] {
+        self.maps.each_ref().map(|map| {
+            vk::DescriptorImageInfo::default()
+                .sampler(map.sampler)
+                .image_view(map.view)
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        })
+    }
+
+    pub fn factors_info(&self) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo::default()
+            .buffer(self.factors.buffer)
+            .offset(0)
+            .range(mem::size_of::<PbrFactors>() as vk::DeviceSize)
+    }
+
+    /// # Safety
+    ///
+    /// The maps and factors must no longer be in use by the device.
+    pub unsafe fn destroy(self, allocator: &mut Allocator, device: &Device) {
+        self.maps.iter().for_each(|map| map.destroy(device));
+
+        allocator.free(self.factors.allocation).unwrap();
+        device.destroy_buffer(self.factors.buffer, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factors_match_the_std140_block() {
+        // Three vec4s, each aligned to 16 bytes
+        assert_eq!(mem::size_of::<PbrFactors>(), 48);
+        assert_eq!(mem::offset_of!(PbrFactors, base_colour), 0);
+        assert_eq!(mem::offset_of!(PbrFactors, emissive), 16);
+        assert_eq!(mem::offset_of!(PbrFactors, parameters), 32);
+    }
+
+    #[test]
+    fn test_factors_packing() {
+        let material = PbrMaterial::default()
+            .base_colour([0.5, 0.25, 1., 0.75])
+            .metallic(0.2)
+            .roughness(0.7)
+            .normal_scale(0.5)
+            .occlusion_strength(0.8)
+            .emissive([1., 0.5, 0.25]);
+
+        assert_eq!(
+            material.factors(),
+            PbrFactors {
+                base_colour: [0.5, 0.25, 1., 0.75],
+                emissive: [1., 0.5, 0.25, 0.],
+                parameters: [0.2, 0.7, 0.5, 0.8],
+            }
+        );
+    }
+
+    #[test]
+    fn test_defaults_follow_gltf() {
+        let material = PbrMaterial::default();
+
+        assert_eq!(
+            material.factors(),
+            PbrFactors {
+                base_colour: [1., 1., 1., 1.],
+                emissive: [0., 0., 0., 0.],
+                parameters: [1., 1., 1., 1.],
+            }
+        );
+        assert!(material.maps().iter().all(|map| map.is_empty()));
+    }
+
+    #[test]
+    fn test_missing_maps_fall_back_to_neutral_texels() {
+        let fallbacks = MAPS.map(|(_, fallback, _)| fallback);
+        assert_eq!(fallbacks, [WHITE, WHITE, FLAT_NORMAL, WHITE, WHITE]);
+
+        // Unpacked from [0, 1] to [-1, 1], the flat normal points along +Z
+        let normal = FLAT_NORMAL.map(|channel| f32::from(channel) / 255. * 2. - 1.);
+        assert!(normal[0].abs() < 0.01 && normal[1].abs() < 0.01);
+        assert_eq!(normal[2], 1.);
+
+        // Colours are stored in sRGB, the data maps linearly
+        let formats = MAPS.map(|(.., format)| format);
+        assert_eq!(formats[0], vk::Format::R8G8B8A8_SRGB);
+        assert_eq!(formats[4], vk::Format::R8G8B8A8_SRGB);
+        assert!(formats[1..4]
+            .iter()
+            .all(|format| *format == vk::Format::R8G8B8A8_UNORM));
+    }
+}
@@ -0,0 +1,1085 @@
+use crate::{
+    any_as_u8_slice,
+    command_buffer::CommandPool,
+    device::Devices,
+    frame_buffer::{self, FrameBuffers},
+    graphics_pipeline,
+    profiler::DrawCounts,
+    renderer::RenderPass,
+    resource::Resources,
+    swap_chain::SwapChain,
+    texture::{self, Texture, WHITE},
+    utility::{self, Image, ImageInfo},
+    ModelTopology, TextureBuffer,
+};
+use ash::{vk, Device, Instance};
+use gpu_allocator::vulkan::Allocator;
+use std::mem;
+
+/// Format of the scene colour target and the bloom targets
+pub(crate) const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Format of the targets between tone mapping and the swap chain
+const LDR_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// Shaders under `shaders` run by the built in passes
+const FULLSCREEN: &str = "fullscreen";
+const BLOOM_EXTRACT: &str = "bloom_extract";
+const BLOOM_BLUR: &str = "bloom_blur";
+const TONE_MAP: &str = "tone_map";
+const FXAA: &str = "fxaa";
+
+/// Operator mapping HDR scene colour into the displayable range
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ToneMapping {
+    /// Clamps, for scenes that are already in range, leaving their colours as they are
+    #[default]
+    None,
+    Reinhard,
+    /// Filmic curve fitted to the ACES reference rendering transform
+    Aces,
+}
+
+impl ToneMapping {
+    /// Operator index read by the tone map shader
+    const fn index(self) -> f32 {
+        match self {
+            Self::None => 0.,
+            Self::Reinhard => 1.,
+            Self::Aces => 2.,
+        }
+    }
+}
+
+/// Glow around colours brighter than the threshold, blurred at half resolution
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bloom {
+    /// Exposed brightness above which colours start to bloom
+    pub threshold: f32,
+    /// Amount of the blurred highlights added back to the scene
+    pub intensity: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.,
+            intensity: 0.3,
+        }
+    }
+}
+
+/// Darkening towards the corners of the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vignette {
+    /// How dark the corners become, from 0 to 1
+    pub strength: f32,
+    /// Distance from the centre, with 1 at the corners, where the darkening starts
+    pub radius: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            strength: 0.4,
+            radius: 0.6,
+        }
+    }
+}
+
+/// Fullscreen pass running a user fragment shader after tone mapping.
+///
+/// The shader is compiled SPIR-V and includes `include/post.glsl`, which declares the
+/// previous pass's output as `inputImage`, the screen coordinates as `inUV` and
+/// [`PostPass::parameters`] as `post.parameters`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostPass {
+    /// Path to the compiled fragment shader
+    pub shader: String,
+    pub parameters: [f32; 4],
+}
+
+impl PostPass {
+    pub fn new(shader: &str) -> Self {
+        Self {
+            shader: shader.to_owned(),
+            parameters: [0.; 4],
+        }
+    }
+
+    #[must_use]
+    pub fn parameters(mut self, parameters: [f32; 4]) -> Self {
+        self.parameters = parameters;
+        self
+    }
+}
+
+/// Screen space effects applied to the HDR scene before it is presented.
+///
+/// The default only clamps the scene into the displayable range, without tone mapping or
+/// effects.
+///
+/// Passes run in a fixed order: bloom, exposure and tone mapping with colour grading and
+/// vignette, the user passes in the order they were added, then FXAA.
+#[derive(Clone, Debug)]
+pub struct PostProcess {
+    /// Multiplier applied to the scene colour before tone mapping
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+    pub bloom: Option<Bloom>,
+    pub fxaa: bool,
+    pub vignette: Option<Vignette>,
+    /// Colour grading lookup table, as an image of N slices of N by N laid side by side,
+    /// mapping display colours to graded ones. Empty when there is no grading.
+    pub colour_lut: TextureBuffer,
+    pub passes: Vec<PostPass>,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            exposure: 1.,
+            tone_mapping: ToneMapping::default(),
+            bloom: None,
+            fxaa: false,
+            vignette: None,
+            colour_lut: TextureBuffer::default(),
+            passes: Vec::new(),
+        }
+    }
+}
+
+impl PostProcess {
+    #[must_use]
+    pub fn exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    #[must_use]
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    #[must_use]
+    pub fn bloom(mut self, bloom: Bloom) -> Self {
+        self.bloom = Some(bloom);
+        self
+    }
+
+    #[must_use]
+    pub fn fxaa(mut self, fxaa: bool) -> Self {
+        self.fxaa = fxaa;
+        self
+    }
+
+    #[must_use]
+    pub fn vignette(mut self, vignette: Vignette) -> Self {
+        self.vignette = Some(vignette);
+        self
+    }
+
+    #[must_use]
+    pub fn colour_lut(mut self, path: &str) -> Self {
+        self.colour_lut = TextureBuffer(
+            std::fs::read(path).expect("Failed to read contents of colour lookup table file"),
+        );
+        self
+    }
+
+    #[must_use]
+    pub fn pass(mut self, pass: PostPass) -> Self {
+        self.passes.push(pass);
+        self
+    }
+}
+
+/// Push constants shared by every pass, matching `PostConstants` in post.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct PassConstants {
+    /// xy: texel size of the input, zw: blur direction
+    texel_size_direction: [f32; 4],
+    /// x: exposure, y: tone mapping operator, z: bloom intensity, w: bloom threshold
+    tone_mapping: [f32; 4],
+    /// x: vignette strength, y: vignette radius, z: 1 when grading with the lookup table
+    grading: [f32; 4],
+    parameters: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StageShader {
+    BloomExtract,
+    BloomBlur {
+        horizontal: bool,
+    },
+    ToneMap,
+    /// Index into [`PostProcess::passes`]
+    Custom(usize),
+    Fxaa,
+}
+
+impl StageShader {
+    /// Name the pass is labelled with for debugging
+    fn label(self) -> String {
+        match self {
+            Self::BloomExtract => String::from("bloom extract"),
+            Self::BloomBlur { horizontal: true } => String::from("bloom blur horizontal"),
+            Self::BloomBlur { horizontal: false } => String::from("bloom blur vertical"),
+            Self::ToneMap => String::from("tone map"),
+            Self::Custom(index) => format!("post pass {index}"),
+            Self::Fxaa => String::from("fxaa"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    Scene,
+    Bloom(usize),
+    Ldr(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Destination {
+    Bloom(usize),
+    Ldr(usize),
+    Present,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stage {
+    shader: StageShader,
+    input: Source,
+    output: Destination,
+}
+
+/// Orders the passes the settings need, ping-ponging between two targets of each kind.
+///
+/// Bloom renders into its own half resolution targets and is read again by tone mapping,
+/// which is always run. The last pass writes the swap chain image.
+fn plan(settings: &PostProcess) -> Vec<Stage> {
+    let mut stages = Vec::new();
+
+    if settings.bloom.is_some() {
+        stages.extend([
+            Stage {
+                shader: StageShader::BloomExtract,
+                input: Source::Scene,
+                output: Destination::Bloom(0),
+            },
+            Stage {
+                shader: StageShader::BloomBlur { horizontal: true },
+                input: Source::Bloom(0),
+                output: Destination::Bloom(1),
+            },
+            Stage {
+                shader: StageShader::BloomBlur { horizontal: false },
+                input: Source::Bloom(1),
+                output: Destination::Bloom(0),
+            },
+        ]);
+    }
+
+    let display_shaders = std::iter::once(StageShader::ToneMap)
+        .chain((0..settings.passes.len()).map(StageShader::Custom))
+        .chain(settings.fxaa.then_some(StageShader::Fxaa))
+        .collect::<Vec<StageShader>>();
+
+    let last = display_shaders.len() - 1;
+    stages.extend(
+        display_shaders
+            .into_iter()
+            .enumerate()
+            .map(|(index, shader)| Stage {
+                shader,
+                input: if index == 0 {
+                    Source::Scene
+                } else {
+                    Source::Ldr((index - 1) % 2)
+                },
+                output: if index == last {
+                    Destination::Present
+                } else {
+                    Destination::Ldr(index % 2)
+                },
+            }),
+    );
+
+    stages
+}
+
+/// Offscreen colour image one pass renders into and a later pass samples
+#[derive(Debug, Default)]
+struct Target {
+    image: Image,
+    view: vk::ImageView,
+    frame_buffer: vk::Framebuffer,
+}
+
+impl Target {
+    fn new(
+        instance: &Instance,
+        devices: &Devices,
+        render_pass: vk::RenderPass,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Self {
+        let image_info = ImageInfo::new(
+            (extent.width, extent.height),
+            1,
+            vk::SampleCountFlags::TYPE_1,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+
+        let image = utility::create_image(image_info, instance, devices);
+
+        let view = utility::create_image_view(
+            &image,
+            format,
+            vk::ImageAspectFlags::COLOR,
+            &devices.logical.device,
+        );
+
+        let frame_buffer = frame_buffer::create_frame_buffer(
+            &RenderPass(render_pass),
+            &[view],
+            &devices.logical.device,
+            extent.width,
+            extent.height,
+        );
+
+        Self {
+            image,
+            view,
+            frame_buffer,
+        }
+    }
+
+    fn set_name(&self, devices: &Devices, name: &str) {
+        let names = &devices.debug_names;
+        names.name(self.image.image, &format!("{name}/image"));
+        names.name(self.view, &format!("{name}/view"));
+        names.name(self.frame_buffer, &format!("{name}/frame buffer"));
+    }
+
+    unsafe fn destroy(&self, device: &Device) {
+        device.destroy_framebuffer(self.frame_buffer, None);
+        device.destroy_image_view(self.view, None);
+        device.destroy_image(self.image.image, None);
+        device.free_memory(self.image.memory, None);
+    }
+}
+
+#[derive(Debug)]
+struct Pass {
+    label: String,
+    output: Destination,
+    render_pass: vk::RenderPass,
+    pipeline: vk::Pipeline,
+    set: vk::DescriptorSet,
+    extent: vk::Extent2D,
+    constants: PassConstants,
+}
+
+/// Targets, frame buffers and pipelines that depend on the swap chain
+#[derive(Debug, Default)]
+struct Chain {
+    bloom: Vec<Target>,
+    ldr: Vec<Target>,
+    present_pass: vk::RenderPass,
+    present_frame_buffers: FrameBuffers,
+    pool: vk::DescriptorPool,
+    passes: Vec<Pass>,
+}
+
+/// Settings the passes are built from, and the images they read and write
+pub(crate) struct PostProcessCreateInfo<'a> {
+    pub settings: &'a PostProcess,
+    pub swap_chain: &'a SwapChain,
+    pub resources: &'a Resources,
+}
+
+/// Runs the [`PostProcess`] passes over the HDR scene image, ending in the swap chain image
+#[derive(Debug)]
+pub(crate) struct PostProcessor {
+    settings: PostProcess,
+    sampler: vk::Sampler,
+    set_layout: vk::DescriptorSetLayout,
+    layout: vk::PipelineLayout,
+    hdr_pass: vk::RenderPass,
+    ldr_pass: vk::RenderPass,
+    lut: Texture,
+    chain: Chain,
+}
+
+impl PostProcessor {
+    pub fn new(
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        devices: &Devices,
+        info: &PostProcessCreateInfo,
+    ) -> Self {
+        let device = &devices.logical.device;
+        let PostProcessCreateInfo {
+            settings,
+            swap_chain,
+            resources,
+        } = *info;
+
+        let sampler_info = vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .max_lod(0.);
+
+        let sampler = unsafe {
+            device
+                .create_sampler(&sampler_info, None)
+                .expect("Failed to create post process sampler!")
+        };
+
+        let set_layout = create_set_layout(device);
+
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .size(mem::size_of::<PassConstants>() as u32)
+            .offset(0);
+
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(std::slice::from_ref(&set_layout))
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+
+        let layout = unsafe {
+            device
+                .create_pipeline_layout(&layout_info, None)
+                .expect("Failed to create post process pipeline layout!")
+        };
+
+        let mut post_processor = Self {
+            settings: settings.clone(),
+            sampler,
+            set_layout,
+            layout,
+            hdr_pass: create_render_pass(device, HDR_FORMAT, false),
+            ldr_pass: create_render_pass(device, LDR_FORMAT, false),
+            lut: create_lut(allocator, command_pool, instance, devices, settings),
+            chain: Chain::default(),
+        };
+
+        post_processor.recreate(instance, devices, swap_chain, resources);
+
+        post_processor
+    }
+
+    /// Rebuilds the targets and passes for a new swap chain, after
+    /// [`PostProcessor::destroy_chain`].
+    pub fn recreate(
+        &mut self,
+        instance: &Instance,
+        devices: &Devices,
+        swap_chain: &SwapChain,
+        resources: &Resources,
+    ) {
+        let device = &devices.logical.device;
+        let stages = plan(&self.settings);
+
+        let extent = swap_chain.extent;
+        let scene = resources.scene.view;
+        let scene_extent = resources.extent;
+        let half_extent = vk::Extent2D {
+            width: (scene_extent.width / 2).max(1),
+            height: (scene_extent.height / 2).max(1),
+        };
+
+        let count = |matches: fn(&Destination) -> bool| {
+            stages.iter().filter(|stage| matches(&stage.output)).count()
+        };
+        let bloom_count = count(|output| matches!(output, Destination::Bloom(_))).min(2);
+        let ldr_count = count(|output| matches!(output, Destination::Ldr(_))).min(2);
+
+        let bloom = (0..bloom_count)
+            .map(|index| {
+                let target = Target::new(instance, devices, self.hdr_pass, HDR_FORMAT, half_extent);
+                target.set_name(devices, &format!("bloom target {index}"));
+                target
+            })
+            .collect::<Vec<Target>>();
+        let ldr = (0..ldr_count)
+            .map(|index| {
+                let target = Target::new(instance, devices, self.ldr_pass, LDR_FORMAT, extent);
+                target.set_name(devices, &format!("ldr target {index}"));
+                target
+            })
+            .collect::<Vec<Target>>();
+
+        let present_pass = create_render_pass(device, swap_chain.image_format, true);
+        let present_frame_buffers =
+            frame_buffer::create_frame_buffers(swap_chain, &RenderPass(present_pass), device);
+
+        let pool = create_descriptor_pool(device, stages.len() as u32);
+
+        let view_of = |source: Source| match source {
+            Source::Scene => scene,
+            Source::Bloom(index) => bloom[index].view,
+            Source::Ldr(index) => ldr[index].view,
+        };
+        let bloom_view = bloom.first().map_or(scene, |target| target.view);
+
+        let vertex = graphics_pipeline::load_shader_stages(FULLSCREEN, device);
+
+        let passes = stages
+            .iter()
+            .map(|stage| {
+                let (render_pass, pass_extent) = match stage.output {
+                    Destination::Bloom(_) => (self.hdr_pass, half_extent),
+                    Destination::Ldr(_) => (self.ldr_pass, extent),
+                    Destination::Present => (present_pass, extent),
+                };
+                let input_extent = match stage.input {
+                    Source::Scene => scene_extent,
+                    Source::Bloom(_) => half_extent,
+                    Source::Ldr(_) => extent,
+                };
+
+                let set = create_descriptor_set(
+                    device,
+                    pool,
+                    self.set_layout,
+                    self.sampler,
+                    [view_of(stage.input), bloom_view, self.lut.view],
+                );
+
+                let fragment = match stage.shader {
+                    StageShader::BloomExtract => shader_path(BLOOM_EXTRACT),
+                    StageShader::BloomBlur { .. } => shader_path(BLOOM_BLUR),
+                    StageShader::ToneMap => shader_path(TONE_MAP),
+                    StageShader::Custom(index) => self.settings.passes[index].shader.clone(),
+                    StageShader::Fxaa => shader_path(FXAA),
+                };
+
+                let pipeline =
+                    create_pipeline(device, self.layout, render_pass, vertex.vert, &fragment);
+
+                let label = stage.shader.label();
+                devices
+                    .debug_names
+                    .name(pipeline, &format!("{label}/pipeline"));
+
+                Pass {
+                    label,
+                    output: stage.output,
+                    render_pass,
+                    pipeline,
+                    set,
+                    extent: pass_extent,
+                    constants: self.constants(stage.shader, input_extent),
+                }
+            })
+            .collect::<Vec<Pass>>();
+
+        unsafe { graphics_pipeline::destroy_shader_modules(device, vertex.vert, vertex.frag) };
+
+        self.chain = Chain {
+            bloom,
+            ldr,
+            present_pass,
+            present_frame_buffers,
+            pool,
+            passes,
+        };
+    }
+
+    /// Replaces the settings, rebuilding every pass.
+    ///
+    /// # Safety
+    ///
+    /// The device must be idle.
+    pub unsafe fn set_settings(
+        &mut self,
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        devices: &Devices,
+        info: &PostProcessCreateInfo,
+    ) {
+        let device = &devices.logical.device;
+        let PostProcessCreateInfo {
+            settings,
+            swap_chain,
+            resources,
+        } = *info;
+
+        self.destroy_chain(device);
+        self.lut.destroy(device);
+
+        self.settings = settings.clone();
+        self.lut = create_lut(allocator, command_pool, instance, devices, settings);
+
+        self.recreate(instance, devices, swap_chain, resources);
+    }
+
+    fn constants(&self, shader: StageShader, input_extent: vk::Extent2D) -> PassConstants {
+        let settings = &self.settings;
+        let bloom = settings.bloom.unwrap_or_default();
+        let vignette = settings.vignette.unwrap_or(Vignette {
+            strength: 0.,
+            ..Default::default()
+        });
+
+        let direction = match shader {
+            StageShader::BloomBlur { horizontal: true } => [1., 0.],
+            StageShader::BloomBlur { horizontal: false } => [0., 1.],
+            _ => [0., 0.],
+        };
+
+        PassConstants {
+            texel_size_direction: [
+                1. / input_extent.width as f32,
+                1. / input_extent.height as f32,
+                direction[0],
+                direction[1],
+            ],
+            tone_mapping: [
+                settings.exposure,
+                settings.tone_mapping.index(),
+                if settings.bloom.is_some() {
+                    bloom.intensity
+                } else {
+                    0.
+                },
+                bloom.threshold,
+            ],
+            grading: [
+                vignette.strength,
+                vignette.radius,
+                if settings.colour_lut.is_empty() {
+                    0.
+                } else {
+                    1.
+                },
+                0.,
+            ],
+            parameters: match shader {
+                StageShader::Custom(index) => settings.passes[index].parameters,
+                _ => [0.; 4],
+            },
+        }
+    }
+
+    /// Pass writing the swap chain image, which overlays drawn by [`PostProcessor::record`]
+    /// must be compatible with
+    pub fn present_pass(&self) -> vk::RenderPass {
+        self.chain.present_pass
+    }
+
+    /// Records every pass, leaving `image_index` of the swap chain ready to present.
+    /// `overlay` records into the present pass after the final image is drawn.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording outside of a render pass, after the scene pass.
+    pub unsafe fn record(
+        &self,
+        devices: &Devices,
+        command_buffer: vk::CommandBuffer,
+        image_index: usize,
+        counts: &mut DrawCounts,
+        mut overlay: impl FnMut(&mut DrawCounts),
+    ) {
+        let device = &devices.logical.device;
+
+        self.chain.passes.iter().for_each(|pass| {
+            devices.debug_names.begin_label(command_buffer, &pass.label);
+
+            let frame_buffer = match pass.output {
+                Destination::Bloom(index) => self.chain.bloom[index].frame_buffer,
+                Destination::Ldr(index) => self.chain.ldr[index].frame_buffer,
+                Destination::Present => self.chain.present_frame_buffers[image_index],
+            };
+
+            let view_port = vk::Viewport::default()
+                .width(pass.extent.width as f32)
+                .height(pass.extent.height as f32)
+                .max_depth(1.);
+
+            let scissor = vk::Rect2D::default().extent(pass.extent);
+
+            let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+                .render_pass(pass.render_pass)
+                .framebuffer(frame_buffer)
+                .render_area(scissor);
+
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+
+            device.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&view_port));
+            device.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&scissor));
+
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pass.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.layout,
+                0,
+                std::slice::from_ref(&pass.set),
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                any_as_u8_slice(&pass.constants),
+            );
+
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            counts.draw(3, ModelTopology::TriangleList);
+
+            if pass.output == Destination::Present {
+                overlay(counts);
+            }
+
+            device.cmd_end_render_pass(command_buffer);
+            devices.debug_names.end_label(command_buffer);
+        });
+    }
+
+    /// Destroys everything created by [`PostProcessor::recreate`].
+    ///
+    /// # Safety
+    ///
+    /// The passes must no longer be in use by the device.
+    pub unsafe fn destroy_chain(&self, device: &Device) {
+        let chain = &self.chain;
+
+        chain
+            .passes
+            .iter()
+            .for_each(|pass| device.destroy_pipeline(pass.pipeline, None));
+        device.destroy_descriptor_pool(chain.pool, None);
+
+        chain
+            .present_frame_buffers
+            .iter()
+            .for_each(|frame_buffer| device.destroy_framebuffer(*frame_buffer, None));
+        device.destroy_render_pass(chain.present_pass, None);
+
+        chain
+            .bloom
+            .iter()
+            .chain(&chain.ldr)
+            .for_each(|target| target.destroy(device));
+    }
+
+    /// Destroys what outlives the swap chain. The chain itself goes with every swap chain
+    /// cleanup.
+    ///
+    /// # Safety
+    ///
+    /// The passes must no longer be in use by the device.
+    pub unsafe fn destroy(&self, device: &Device) {
+        self.lut.destroy(device);
+        device.destroy_render_pass(self.ldr_pass, None);
+        device.destroy_render_pass(self.hdr_pass, None);
+        device.destroy_pipeline_layout(self.layout, None);
+        device.destroy_descriptor_set_layout(self.set_layout, None);
+        device.destroy_sampler(self.sampler, None);
+    }
+}
+
+fn shader_path(folder: &str) -> String {
+    format!("./crates/wave_internal/src/shaders/{folder}/frag.spv")
+}
+
+fn create_lut(
+    allocator: &mut Allocator,
+    command_pool: &CommandPool,
+    instance: &Instance,
+    devices: &Devices,
+    settings: &PostProcess,
+) -> Texture {
+    // The table holds display values, so it is read without sRGB decoding
+    let lut = texture::create_map_texture(
+        allocator,
+        command_pool,
+        instance,
+        devices,
+        &settings.colour_lut,
+        WHITE,
+        vk::Format::R8G8B8A8_UNORM,
+    );
+    lut.set_name(devices, "post process/colour lut");
+    lut
+}
+
+/// Input image at binding 0, bloom at binding 1 and the colour lookup table at binding 2
+fn create_set_layout(device: &Device) -> vk::DescriptorSetLayout {
+    let bindings = [0, 1, 2].map(|binding| {
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(binding)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+    });
+
+    let layout_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+
+    unsafe {
+        device
+            .create_descriptor_set_layout(&layout_info, None)
+            .expect("Failed to create post process descriptor set layout!")
+    }
+}
+
+fn create_descriptor_pool(device: &Device, pass_count: u32) -> vk::DescriptorPool {
+    let pool_size = vk::DescriptorPoolSize::default()
+        .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(3 * pass_count);
+
+    let pool_info = vk::DescriptorPoolCreateInfo::default()
+        .pool_sizes(std::slice::from_ref(&pool_size))
+        .max_sets(pass_count);
+
+    unsafe {
+        device
+            .create_descriptor_pool(&pool_info, None)
+            .expect("Failed to create post process descriptor pool!")
+    }
+}
+
+fn create_descriptor_set(
+    device: &Device,
+    pool: vk::DescriptorPool,
+    set_layout: vk::DescriptorSetLayout,
+    sampler: vk::Sampler,
+    views: [vk::ImageView; 3],
+) -> vk::DescriptorSet {
+    let alloc_info = vk::DescriptorSetAllocateInfo::default()
+        .descriptor_pool(pool)
+        .set_layouts(std::slice::from_ref(&set_layout));
+
+    let set = unsafe {
+        device
+            .allocate_descriptor_sets(&alloc_info)
+            .expect("Failed to allocate post process descriptor set!")[0]
+    };
+
+    let image_infos = views.map(|view| {
+        vk::DescriptorImageInfo::default()
+            .sampler(sampler)
+            .image_view(view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+    });
+
+    let descriptor_write = vk::WriteDescriptorSet::default()
+        .dst_set(set)
+        .dst_binding(0)
+        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .image_info(&image_infos);
+
+    unsafe { device.update_descriptor_sets(std::slice::from_ref(&descriptor_write), &[]) };
+
+    set
+}
+
+/// Single colour attachment pass. Offscreen targets end ready to be sampled, the swap chain
+/// image ready to be presented.
+fn create_render_pass(device: &Device, format: vk::Format, present: bool) -> vk::RenderPass {
+    let final_layout = if present {
+        vk::ImageLayout::PRESENT_SRC_KHR
+    } else {
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+    };
+
+    // Every pass covers the whole target, so the previous contents are never loaded
+    let attachment = vk::AttachmentDescription::default()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(final_layout);
+
+    let colour_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    let sub_pass = vk::SubpassDescription::default()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(std::slice::from_ref(&colour_attachment_ref));
+
+    // Earlier reads of the target must finish before it is overwritten, and the writes
+    // must land before a later pass samples them
+    let dependencies = [
+        vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            )
+            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE),
+        vk::SubpassDependency::default()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ),
+    ];
+
+    let render_pass_create_info = vk::RenderPassCreateInfo::default()
+        .attachments(std::slice::from_ref(&attachment))
+        .subpasses(std::slice::from_ref(&sub_pass))
+        .dependencies(&dependencies);
+
+    unsafe {
+        device
+            .create_render_pass(&render_pass_create_info, None)
+            .expect("Failed to create post process render pass!")
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    vert: vk::ShaderModule,
+    fragment_path: &str,
+) -> vk::Pipeline {
+    let frag = graphics_pipeline::create_shader_module(device, fragment_path);
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default();
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+    let view_port_state = vk::PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+
+    let multi_sampling = vk::PipelineMultisampleStateCreateInfo::default()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
+        .color_write_mask(vk::ColorComponentFlags::RGBA)
+        .blend_enable(false);
+
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
+        .attachments(std::slice::from_ref(&color_blend_attachment));
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+    let dynamic_state_create_info =
+        vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    let entry_point = c"main";
+    let stages = [
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vert)
+            .name(entry_point),
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(frag)
+            .name(entry_point),
+    ];
+
+    let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input_info)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&view_port_state)
+        .rasterization_state(&rasterizer)
+        .multisample_state(&multi_sampling)
+        .color_blend_state(&color_blending)
+        .dynamic_state(&dynamic_state_create_info)
+        .layout(layout)
+        .render_pass(render_pass)
+        .subpass(0);
+
+    unsafe {
+        let pipeline = device
+            .create_graphics_pipelines(
+                vk::PipelineCache::null(),
+                std::slice::from_ref(&pipeline_info),
+                None,
+            )
+            .expect("Failed to create post process pipeline!")[0];
+
+        device.destroy_shader_module(frag, None);
+
+        pipeline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_ends_in_the_swap_chain() {
+        assert_eq!(PostProcess::default().tone_mapping, ToneMapping::None);
+
+        let tone_map_only = plan(&PostProcess::default());
+        assert_eq!(
+            tone_map_only,
+            [Stage {
+                shader: StageShader::ToneMap,
+                input: Source::Scene,
+                output: Destination::Present,
+            }]
+        );
+
+        let settings = PostProcess::default()
+            .bloom(Bloom::default())
+            .pass(PostPass::new("custom.spv"))
+            .fxaa(true);
+        let stages = plan(&settings);
+
+        let shaders = stages.iter().map(|stage| stage.shader).collect::<Vec<_>>();
+        assert_eq!(
+            shaders,
+            [
+                StageShader::BloomExtract,
+                StageShader::BloomBlur { horizontal: true },
+                StageShader::BloomBlur { horizontal: false },
+                StageShader::ToneMap,
+                StageShader::Custom(0),
+                StageShader::Fxaa,
+            ]
+        );
+
+        // Each display pass reads what the one before it wrote
+        assert_eq!(stages[3].output, Destination::Ldr(0));
+        assert_eq!(stages[4].input, Source::Ldr(0));
+        assert_eq!(stages[4].output, Destination::Ldr(1));
+        assert_eq!(stages[5].input, Source::Ldr(1));
+        assert_eq!(stages[5].output, Destination::Present);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{device::Devices, sync_objects::MAX_FRAMES_IN_FLIGHT, ModelTopology};
+use ash::{vk, Device, Instance};
+use std::time::{Duration, Instant};
+use wave_window::stats::{FrameStats, Span};
+
+/// Most timestamps a frame can write
+const MAX_TIMESTAMPS: u32 = 16;
+
+/// Draw calls and triangles recorded into a frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct DrawCounts {
+    pub draw_calls: u32,
+    pub triangles: u64,
+}
+
+impl DrawCounts {
+    /// Counts a draw of `count` vertices or indices. Triangles are only counted for triangle
+    /// topologies.
+    pub fn draw(&mut self, count: u32, topology: ModelTopology) {
+        let count = u64::from(count);
+
+        self.draw_calls += 1;
+        self.triangles += match topology {
+            ModelTopology::TriangleList => count / 3,
+            ModelTopology::TriangleStrip | ModelTopology::TriangleFan => count.saturating_sub(2),
+            ModelTopology::TriangleListWithAdjacency => count / 6,
+            ModelTopology::TriangleStripWithAdjacency => count.saturating_sub(4) / 2,
+            _ => 0,
+        };
+    }
+}
+
+/// Timestamps written while recording a frame, each closing the span named after it
+#[derive(Debug)]
+pub(crate) struct GpuMarks {
+    pool: Option<vk::QueryPool>,
+    names: Vec<&'static str>,
+}
+
+impl GpuMarks {
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording the frame the marks were begun for.
+    pub unsafe fn mark(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        name: &'static str,
+    ) {
+        let Some(pool) = self.pool else {
+            return;
+        };
+
+        if self.names.len() < MAX_TIMESTAMPS as usize {
+            device.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                pool,
+                self.names.len() as u32,
+            );
+            self.names.push(name);
+        }
+    }
+}
+
+/// Frame recorded and submitted, waiting for the GPU to finish it
+#[derive(Debug)]
+struct PendingFrame {
+    stats: FrameStats,
+    marks: GpuMarks,
+}
+
+/// Collects CPU and GPU timings of each frame in flight into [`FrameStats`]
+pub(crate) struct FrameProfiler {
+    /// One pool per frame in flight, empty when the graphics queue cannot write timestamps
+    query_pools: Vec<vk::QueryPool>,
+    /// Whether frames write timestamps, only while someone reads their GPU timings
+    pub timestamps: bool,
+    /// Nanoseconds per timestamp tick
+    timestamp_period: f64,
+    timestamp_mask: u64,
+    pending: Vec<Option<PendingFrame>>,
+    latest: FrameStats,
+    frames: u64,
+    created: Instant,
+}
+
+impl FrameProfiler {
+    pub fn new(instance: &Instance, devices: &Devices) -> Self {
+        let properties =
+            unsafe { instance.get_physical_device_properties(devices.physical.device) };
+        let valid_bits = unsafe {
+            instance.get_physical_device_queue_family_properties(devices.physical.device)
+        }[devices.physical.queue_families.graphics as usize]
+            .timestamp_valid_bits;
+
+        let query_pools =
+            if properties.limits.timestamp_compute_and_graphics == vk::TRUE && valid_bits > 0 {
+                let pool_info = vk::QueryPoolCreateInfo::default()
+                    .query_type(vk::QueryType::TIMESTAMP)
+                    .query_count(MAX_TIMESTAMPS);
+
+                (0..MAX_FRAMES_IN_FLIGHT)
+                    .map(|_| unsafe {
+                        devices
+                            .logical
+                            .device
+                            .create_query_pool(&pool_info, None)
+                            .expect("Failed to create timestamp query pool!")
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+        Self {
+            query_pools,
+            timestamps: false,
+            timestamp_period: f64::from(properties.limits.timestamp_period),
+            timestamp_mask: u64::MAX >> (64 - valid_bits.clamp(1, 64)),
+            pending: (0..MAX_FRAMES_IN_FLIGHT).map(|_| None).collect(),
+            latest: FrameStats::default(),
+            frames: 0,
+            created: Instant::now(),
+        }
+    }
+
+    pub fn latest(&self) -> &FrameStats {
+        &self.latest
+    }
+
+    /// Offset of `instant` from when the profiler was created
+    pub fn since_start(&self, instant: Instant) -> Duration {
+        instant.duration_since(self.created)
+    }
+
+    /// Resets the timestamps of `frame` and writes the one the first span starts from.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording outside a render pass, and the previous
+    /// submission of `frame` must have completed.
+    pub unsafe fn begin(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+    ) -> GpuMarks {
+        let pool = self
+            .query_pools
+            .get(frame)
+            .copied()
+            .filter(|_| self.timestamps);
+
+        if let Some(pool) = pool {
+            device.cmd_reset_query_pool(command_buffer, pool, 0, MAX_TIMESTAMPS);
+            device.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                pool,
+                0,
+            );
+        }
+
+        GpuMarks {
+            pool,
+            names: vec!["frame"],
+        }
+    }
+
+    /// Keeps a submitted frame until its GPU timings can be read.
+    pub fn submit(&mut self, frame: usize, mut stats: FrameStats, marks: GpuMarks) {
+        stats.frame = self.frames;
+        self.frames += 1;
+        self.pending[frame] = Some(PendingFrame { stats, marks });
+    }
+
+    /// Reads the GPU timings of the last submission of `frame`, which becomes the latest
+    /// stats.
+    ///
+    /// # Safety
+    ///
+    /// The last submission of `frame` must have completed.
+    pub unsafe fn resolve(&mut self, device: &Device, frame: usize) {
+        let Some(PendingFrame { mut stats, marks }) = self.pending[frame].take() else {
+            return;
+        };
+
+        if let Some(pool) = marks.pool {
+            let mut timestamps = vec![0_u64; marks.names.len()];
+
+            let read = device.get_query_pool_results(
+                pool,
+                0,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64,
+            );
+
+            if read.is_ok() {
+                stats.gpu = gpu_spans(
+                    &marks.names,
+                    &timestamps,
+                    self.timestamp_period,
+                    self.timestamp_mask,
+                );
+            }
+        }
+
+        self.latest = stats;
+    }
+
+    /// # Safety
+    ///
+    /// No frame may be in flight.
+    pub unsafe fn destroy(&self, device: &Device) {
+        self.query_pools
+            .iter()
+            .for_each(|pool| device.destroy_query_pool(*pool, None));
+    }
+}
+
+/// Spans between consecutive timestamps, each named after the timestamp closing it
+fn gpu_spans(names: &[&'static str], timestamps: &[u64], period: f64, mask: u64) -> Vec<Span> {
+    let first = timestamps.first().copied().unwrap_or_default();
+    let offset = |timestamp: u64| {
+        let ticks = timestamp.wrapping_sub(first) & mask;
+        Duration::from_nanos((ticks as f64 * period) as u64)
+    };
+
+    names
+        .iter()
+        .skip(1)
+        .zip(timestamps.windows(2))
+        .map(|(name, pair)| {
+            let start = offset(pair[0]);
+            Span::new(name, start, offset(pair[1]).saturating_sub(start))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_counts_only_count_triangles() {
+        let mut counts = DrawCounts::default();
+
+        counts.draw(36, ModelTopology::TriangleList);
+        counts.draw(6, ModelTopology::TriangleStrip);
+        counts.draw(10, ModelTopology::LineList);
+
+        assert_eq!(
+            counts,
+            DrawCounts {
+                draw_calls: 3,
+                triangles: 16
+            }
+        );
+    }
+
+    #[test]
+    fn test_gpu_spans_are_named_after_their_closing_timestamp() {
+        let spans = gpu_spans(
+            &["frame", "shadows", "opaque"],
+            &[100, 300, 700],
+            2.,
+            u64::MAX,
+        );
+
+        assert_eq!(
+            spans,
+            [
+                Span::new("shadows", Duration::ZERO, Duration::from_nanos(400)),
+                Span::new(
+                    "opaque",
+                    Duration::from_nanos(400),
+                    Duration::from_nanos(800)
+                ),
+            ]
+        );
+    }
+}
@@ -1,24 +1,28 @@
-use crate::{device::Devices, resource, swap_chain::SwapChain};
+use crate::{device::Devices, post_process::HDR_FORMAT, resource};
 use ash::{vk, Instance};
 
 #[derive(Default, Debug, Clone)]
 pub(crate) struct RenderPass(pub vk::RenderPass);
 
-pub(crate) fn create_render_pass(
-    devices: &Devices,
-    swap_chain: &SwapChain,
-    instance: &Instance,
-) -> RenderPass {
-    let render_pass_attachments = [
+/// Scene render pass, drawing into an HDR target read by post processing. With MSAA the
+/// target is multisampled and resolved, otherwise the scene image is drawn into directly.
+pub(crate) fn create_render_pass(devices: &Devices, instance: &Instance) -> RenderPass {
+    let multisampled = devices.physical.samples != vk::SampleCountFlags::TYPE_1;
+
+    let mut render_pass_attachments = vec![
         vk::AttachmentDescription {
-            format: swap_chain.image_format,
+            format: HDR_FORMAT,
             samples: devices.physical.samples,
             load_op: vk::AttachmentLoadOp::CLEAR,
             store_op: vk::AttachmentStoreOp::STORE,
             stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
             stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
             initial_layout: vk::ImageLayout::UNDEFINED,
-            final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            final_layout: if multisampled {
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            },
             ..Default::default()
         },
         vk::AttachmentDescription {
@@ -32,18 +36,21 @@ pub(crate) fn create_render_pass(
             final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
             ..Default::default()
         },
-        vk::AttachmentDescription {
-            format: swap_chain.image_format,
+    ];
+
+    if multisampled {
+        render_pass_attachments.push(vk::AttachmentDescription {
+            format: HDR_FORMAT,
             samples: vk::SampleCountFlags::TYPE_1,
             load_op: vk::AttachmentLoadOp::DONT_CARE,
             store_op: vk::AttachmentStoreOp::STORE,
             stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
             stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
             initial_layout: vk::ImageLayout::UNDEFINED,
-            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
             ..Default::default()
-        },
-    ];
+        });
+    }
 
     let color_attachment_refs = vk::AttachmentReference {
         attachment: 0,
@@ -58,39 +65,58 @@ pub(crate) fn create_render_pass(
         layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
     };
 
-    let sub_passes = vk::SubpassDescription::default()
+    let mut sub_passes = vk::SubpassDescription::default()
         .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
         .color_attachments(std::slice::from_ref(&color_attachment_refs))
-        .depth_stencil_attachment(&depth_attachment_ref)
-        .resolve_attachments(std::slice::from_ref(&color_attachment_resolver_ref));
+        .depth_stencil_attachment(&depth_attachment_ref);
+
+    if multisampled {
+        sub_passes =
+            sub_passes.resolve_attachments(std::slice::from_ref(&color_attachment_resolver_ref));
+    }
 
-    let dependencies = vk::SubpassDependency::default()
-        .src_subpass(vk::SUBPASS_EXTERNAL)
-        .dst_subpass(0)
-        .src_access_mask(vk::AccessFlags::NONE_KHR)
-        .src_stage_mask(
-            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-        )
-        .dst_stage_mask(
-            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-        )
-        .dst_access_mask(
-            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
-                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-        );
+    // The previous frame's post processing must have read the scene image before it is
+    // resolved into again, and this frame's resolve must land before it is sampled
+    let dependencies = [
+        vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_access_mask(vk::AccessFlags::NONE_KHR)
+            .src_stage_mask(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            )
+            .dst_stage_mask(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+            )
+            .dst_access_mask(
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ),
+        vk::SubpassDependency::default()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ),
+    ];
 
     let render_pass_create_info = vk::RenderPassCreateInfo::default()
         .attachments(&render_pass_attachments)
         .subpasses(std::slice::from_ref(&sub_passes))
-        .dependencies(std::slice::from_ref(&dependencies));
+        .dependencies(&dependencies);
 
-    RenderPass(unsafe {
+    let render_pass = unsafe {
         devices
             .logical
             .device
             .create_render_pass(&render_pass_create_info, None)
             .expect("Failed to create render pass!")
-    })
+    };
+    devices.debug_names.name(render_pass, "scene/render pass");
+
+    RenderPass(render_pass)
 }
@@ -1,6 +1,6 @@
 use crate::{
     device::{Devices, PhysicalDeviceProperties},
-    swap_chain::SwapChain,
+    post_process::HDR_FORMAT,
     utility::{self, Image, ImageInfo},
 };
 use ash::{vk, Device, Instance};
@@ -9,6 +9,18 @@ use ash::{vk, Device, Instance};
 pub enum ResourceType {
     Colour,
     Depth,
+    /// Single sampled HDR image the colour is resolved into and post processing reads
+    Scene,
+}
+
+impl ResourceType {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Colour => "colour target",
+            Self::Depth => "depth target",
+            Self::Scene => "scene target",
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -19,32 +31,46 @@ pub struct Resource {
 
 impl Resource {
     fn new(
-        swap_chain: &SwapChain,
+        extent: vk::Extent2D,
         image_type: ResourceType,
         instance: &Instance,
         devices: &Devices,
     ) -> Self {
-        let (format, usage_flags, aspect_flags) = match image_type {
+        let transient = vk::ImageUsageFlags::TRANSIENT_ATTACHMENT;
+        let lazily_allocated = vk::MemoryPropertyFlags::LAZILY_ALLOCATED;
+
+        let (format, samples, usage_flags, memory_properties, aspect_flags) = match image_type {
             ResourceType::Colour => (
-                swap_chain.image_format,
-                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                HDR_FORMAT,
+                devices.physical.samples,
+                transient | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                lazily_allocated,
                 vk::ImageAspectFlags::COLOR,
             ),
             ResourceType::Depth => (
                 find_depth_format(instance, &devices.physical.device),
-                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                devices.physical.samples,
+                transient | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                lazily_allocated,
                 vk::ImageAspectFlags::DEPTH,
             ),
+            ResourceType::Scene => (
+                HDR_FORMAT,
+                vk::SampleCountFlags::TYPE_1,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                vk::ImageAspectFlags::COLOR,
+            ),
         };
 
         let image_info = ImageInfo::new(
-            (swap_chain.extent.width, swap_chain.extent.height),
+            (extent.width, extent.height),
             1,
-            devices.physical.samples,
+            samples,
             format,
             vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | usage_flags,
-            vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+            usage_flags,
+            memory_properties,
         );
 
         let image = utility::create_image(image_info, instance, devices);
@@ -52,21 +78,35 @@ impl Resource {
         let view =
             utility::create_image_view(&image, format, aspect_flags, &devices.logical.device);
 
+        let name = image_type.name();
+        let names = &devices.debug_names;
+        names.name(image.image, &format!("{name}/image"));
+        names.name(view, &format!("{name}/view"));
+
         Self { image, view }
     }
 }
 
 #[derive(Clone)]
 pub struct Resources {
-    pub colour: Resource,
+    /// Multisampled colour resolved into the scene, absent without MSAA
+    pub colour: Option<Resource>,
     pub depth: Resource,
+    pub scene: Resource,
+    /// Size of every resource, the swap chain extent scaled by the render scale
+    pub extent: vk::Extent2D,
 }
 
 impl Resources {
-    pub fn new(swap_chain: &SwapChain, instance: &Instance, devices: &Devices) -> Self {
+    pub fn new(extent: vk::Extent2D, instance: &Instance, devices: &Devices) -> Self {
+        let multisampled = devices.physical.samples != vk::SampleCountFlags::TYPE_1;
+
         Self {
-            depth: Resource::new(swap_chain, ResourceType::Depth, instance, devices),
-            colour: Resource::new(swap_chain, ResourceType::Colour, instance, devices),
+            depth: Resource::new(extent, ResourceType::Depth, instance, devices),
+            colour: multisampled
+                .then(|| Resource::new(extent, ResourceType::Colour, instance, devices)),
+            scene: Resource::new(extent, ResourceType::Scene, instance, devices),
+            extent,
         }
     }
 }
@@ -89,6 +129,22 @@ pub(crate) fn find_depth_format(
     )
 }
 
+/// Depth format shadow maps are rendered into and then sampled from, without a stencil
+/// aspect. D16 is always supported for both.
+pub(crate) fn find_shadow_format(
+    instance: &Instance,
+    physical_device: &vk::PhysicalDevice,
+) -> vk::Format {
+    let candidates = [vk::Format::D32_SFLOAT, vk::Format::D16_UNORM];
+    find_supported_format(
+        instance,
+        physical_device,
+        &candidates,
+        vk::ImageTiling::OPTIMAL,
+        vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+    )
+}
+
 fn find_supported_format(
     instance: &Instance,
     physical_device: &vk::PhysicalDevice,
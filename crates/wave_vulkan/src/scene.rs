@@ -0,0 +1,226 @@
+use crate::{
+    buffer::Buffer,
+    device::Devices,
+    environment::EnvironmentMap,
+    light::{FrameLights, LightUniform, MAX_LIGHTS, MAX_SHADOW_LAYERS},
+    shadow::ShadowMaps,
+    texture,
+    uniform_buffer::UniformBufferObject,
+};
+use ash::{vk, Device};
+use gpu_allocator::vulkan::Allocator;
+use nalgebra::Matrix4;
+use std::mem;
+
+/// Scene wide values shared by every lit shader, matching `Scene` in `environment.glsl`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct SceneUniforms {
+    pub irradiance: [[f32; 4]; 9],
+    pub camera_position: [f32; 4],
+    /// x: intensity, y: 1 when the environment lights the scene, z: mip level count
+    pub environment: [f32; 4],
+    pub camera_forward: [f32; 4],
+    /// x: light count
+    pub light_info: [f32; 4],
+    pub cascade_splits: [f32; 4],
+    pub lights: [LightUniform; MAX_LIGHTS],
+    pub shadow_matrices: [Matrix4<f32>; MAX_SHADOW_LAYERS],
+}
+
+/// Descriptor set bound next to each lit object's own set, holding the scene
+/// uniforms, the environment cube map and the shadow maps.
+#[derive(Debug, Default)]
+pub(crate) struct SceneDescriptors {
+    pub set_layout: vk::DescriptorSetLayout,
+    pub pool: vk::DescriptorPool,
+    pub sets: Vec<vk::DescriptorSet>,
+    pub buffers: Vec<Buffer>,
+    uniforms: SceneUniforms,
+}
+
+impl SceneDescriptors {
+    pub fn new(
+        allocator: &mut Allocator,
+        devices: &Devices,
+        swap_chain_image_count: u32,
+        environment: &EnvironmentMap,
+        shadow_maps: &ShadowMaps,
+    ) -> Self {
+        let device = &devices.logical.device;
+
+        let set_layout = create_scene_set_layout(device);
+        let pool = create_scene_descriptor_pool(device, swap_chain_image_count);
+
+        let buffers = (0..swap_chain_image_count)
+            .map(|i| {
+                texture::create_buffer(
+                    allocator,
+                    mem::size_of::<SceneUniforms>() as vk::DeviceSize,
+                    vk::BufferUsageFlags::UNIFORM_BUFFER,
+                    devices,
+                    &format!("Scene Uniform Buffer {i}"),
+                )
+            })
+            .collect::<Vec<Buffer>>();
+
+        let layouts = vec![set_layout; swap_chain_image_count as usize];
+        let alloc_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(&layouts);
+
+        let sets = unsafe {
+            device
+                .allocate_descriptor_sets(&alloc_info)
+                .expect("Failed to allocate scene descriptor sets!")
+        };
+
+        let image_info = vk::DescriptorImageInfo::default()
+            .sampler(environment.texture.sampler)
+            .image_view(environment.texture.view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+        let shadow_info = vk::DescriptorImageInfo::default()
+            .sampler(shadow_maps.sampler)
+            .image_view(shadow_maps.array_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+        sets.iter().zip(&buffers).for_each(|(set, buffer)| {
+            let buffer_info = vk::DescriptorBufferInfo::default()
+                .buffer(buffer.buffer)
+                .offset(0)
+                .range(mem::size_of::<SceneUniforms>() as vk::DeviceSize);
+
+            let descriptor_writes = [
+                vk::WriteDescriptorSet::default()
+                    .dst_set(*set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(std::slice::from_ref(&buffer_info)),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(*set)
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(std::slice::from_ref(&image_info)),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(*set)
+                    .dst_binding(2)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(std::slice::from_ref(&shadow_info)),
+            ];
+
+            unsafe { device.update_descriptor_sets(&descriptor_writes, &[]) };
+        });
+
+        let uniforms = SceneUniforms {
+            irradiance: environment.irradiance,
+            camera_position: [0., 0., 0., 1.],
+            environment: [
+                environment.intensity,
+                if environment.lighting { 1. } else { 0. },
+                environment.texture.image.mip_levels as f32,
+                0.,
+            ],
+            ..Default::default()
+        };
+
+        Self {
+            set_layout,
+            pool,
+            sets,
+            buffers,
+            uniforms,
+        }
+    }
+
+    /// Writes the scene uniforms used while rendering the swap chain image `image_index`
+    pub fn update(&mut self, image_index: usize, ubo: &UniformBufferObject, lights: &FrameLights) {
+        let camera_position = ubo.camera_position();
+        let camera_forward = ubo.camera_forward();
+
+        self.uniforms.camera_position =
+            [camera_position.x, camera_position.y, camera_position.z, 1.];
+        self.uniforms.camera_forward = [camera_forward.x, camera_forward.y, camera_forward.z, 0.];
+        self.uniforms.light_info = [lights.light_count as f32, 0., 0., 0.];
+        self.uniforms.cascade_splits = lights.cascade_splits;
+        self.uniforms.lights = lights.lights;
+        self.uniforms
+            .shadow_matrices
+            .iter_mut()
+            .zip(&lights.shadow_matrices)
+            .for_each(|(uniform, matrix)| *uniform = *matrix);
+
+        let bytes = unsafe { crate::any_as_u8_slice(&self.uniforms) };
+
+        self.buffers[image_index]
+            .allocation
+            .mapped_slice_mut()
+            .expect("Scene uniform buffer is not host visible")[..bytes.len()]
+            .copy_from_slice(bytes);
+    }
+
+    /// # Safety
+    ///
+    /// The descriptor sets must no longer be in use by the device.
+    pub unsafe fn destroy(self, allocator: &mut Allocator, device: &Device) {
+        self.buffers.into_iter().for_each(|buffer| {
+            allocator.free(buffer.allocation).unwrap();
+            device.destroy_buffer(buffer.buffer, None);
+        });
+
+        device.destroy_descriptor_pool(self.pool, None);
+        device.destroy_descriptor_set_layout(self.set_layout, None);
+    }
+}
+
+fn create_scene_set_layout(device: &Device) -> vk::DescriptorSetLayout {
+    let bindings = [
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(0)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT),
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(1)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(2)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+    ];
+
+    let layout_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+
+    unsafe {
+        device
+            .create_descriptor_set_layout(&layout_info, None)
+            .expect("Failed to create scene descriptor set layout")
+    }
+}
+
+fn create_scene_descriptor_pool(
+    device: &Device,
+    swap_chain_image_count: u32,
+) -> vk::DescriptorPool {
+    let pool_sizes = [
+        vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(swap_chain_image_count),
+        vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(2 * swap_chain_image_count),
+    ];
+
+    let pool_info = vk::DescriptorPoolCreateInfo::default()
+        .pool_sizes(&pool_sizes)
+        .max_sets(swap_chain_image_count);
+
+    unsafe {
+        device
+            .create_descriptor_pool(&pool_info, None)
+            .expect("Failed to create scene descriptor pool!")
+    }
+}
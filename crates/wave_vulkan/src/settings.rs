@@ -0,0 +1 @@
+pub use wave_window::settings::{Msaa, RenderSettings};
@@ -0,0 +1,500 @@
+use crate::{
+    any_as_u8_slice, command_buffer,
+    device::Devices,
+    graphics_pipeline::{self, ShaderModules},
+    light::{MAX_SHADOW_LAYERS, SHADOW_MAP_SIZE},
+    material::VulkanMaterial,
+    profiler::DrawCounts,
+    resource,
+    utility::{self, Image, ImageInfo},
+    Shader, VulkanObject,
+};
+use ash::{vk, Device, Instance};
+use memoffset::offset_of;
+use nalgebra::Matrix4;
+use std::mem;
+use wave_space::space::Vertex;
+
+/// Topologies that produce filled triangles and so can cast shadows
+const SHADOW_TOPOLOGIES: [vk::PrimitiveTopology; 3] = [
+    vk::PrimitiveTopology::TRIANGLE_LIST,
+    vk::PrimitiveTopology::TRIANGLE_STRIP,
+    vk::PrimitiveTopology::TRIANGLE_FAN,
+];
+
+/// Depth-only render pass drawing shadow casters from each shadow casting light.
+///
+/// Every light view renders into its own layer of a single depth array image,
+/// which the lit shaders sample through `array_view` with depth comparison.
+#[derive(Debug, Default)]
+pub(crate) struct ShadowMaps {
+    pub render_pass: vk::RenderPass,
+    pub image: Image,
+    pub array_view: vk::ImageView,
+    pub layer_views: Vec<vk::ImageView>,
+    pub frame_buffers: Vec<vk::Framebuffer>,
+    pub sampler: vk::Sampler,
+    pub layout: vk::PipelineLayout,
+    pub pipelines: Vec<(vk::PrimitiveTopology, vk::Pipeline)>,
+}
+
+impl ShadowMaps {
+    pub fn new(instance: &Instance, devices: &Devices, command_pool: &vk::CommandPool) -> Self {
+        let device = &devices.logical.device;
+        let format = resource::find_shadow_format(instance, &devices.physical.device);
+
+        let image_info = ImageInfo::new(
+            (SHADOW_MAP_SIZE, SHADOW_MAP_SIZE),
+            1,
+            vk::SampleCountFlags::TYPE_1,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+        .array_layers(MAX_SHADOW_LAYERS as u32);
+
+        let image = utility::create_image(image_info, instance, devices);
+
+        transition_to_shader_read(
+            device,
+            command_pool,
+            devices.logical.queues.graphics,
+            &image,
+        );
+
+        let array_view = create_view(
+            device,
+            image.image,
+            format,
+            vk::ImageViewType::TYPE_2D_ARRAY,
+            0,
+            MAX_SHADOW_LAYERS as u32,
+        );
+
+        let layer_views = (0..MAX_SHADOW_LAYERS as u32)
+            .map(|layer| {
+                create_view(
+                    device,
+                    image.image,
+                    format,
+                    vk::ImageViewType::TYPE_2D,
+                    layer,
+                    1,
+                )
+            })
+            .collect::<Vec<vk::ImageView>>();
+
+        let render_pass = create_shadow_render_pass(device, format);
+
+        let frame_buffers = layer_views
+            .iter()
+            .map(|view| {
+                let frame_buffer_info = vk::FramebufferCreateInfo::default()
+                    .render_pass(render_pass)
+                    .attachments(std::slice::from_ref(view))
+                    .width(SHADOW_MAP_SIZE)
+                    .height(SHADOW_MAP_SIZE)
+                    .layers(1);
+                unsafe {
+                    device
+                        .create_framebuffer(&frame_buffer_info, None)
+                        .expect("Failed to create shadow frame buffer!")
+                }
+            })
+            .collect::<Vec<vk::Framebuffer>>();
+
+        let sampler_info = vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+            .compare_enable(true)
+            .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .max_lod(1.);
+
+        let sampler = unsafe {
+            device
+                .create_sampler(&sampler_info, None)
+                .expect("Failed to create shadow sampler!")
+        };
+
+        let (layout, pipelines) = create_shadow_pipelines(device, render_pass);
+
+        let names = &devices.debug_names;
+        names.name(image.image, "shadows/image");
+        names.name(array_view, "shadows/array view");
+        names.name(sampler, "shadows/sampler");
+        layer_views.iter().zip(&frame_buffers).enumerate().for_each(
+            |(layer, (view, frame_buffer))| {
+                names.name(*view, &format!("shadows/layer view {layer}"));
+                names.name(*frame_buffer, &format!("shadows/frame buffer {layer}"));
+            },
+        );
+        pipelines.iter().for_each(|(topology, pipeline)| {
+            names.name(*pipeline, &format!("shadows/{topology:?} pipeline"));
+        });
+
+        Self {
+            render_pass,
+            image,
+            array_view,
+            layer_views,
+            frame_buffers,
+            sampler,
+            layout,
+            pipelines,
+        }
+    }
+
+    /// Records one depth pass per shadow map layer, drawing every visible shadow caster.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording and outside of a render pass.
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        objects: &[VulkanObject],
+        materials: &[VulkanMaterial],
+        shadow_matrices: &[Matrix4<f32>],
+        counts: &mut DrawCounts,
+    ) {
+        let extent = vk::Extent2D {
+            width: SHADOW_MAP_SIZE,
+            height: SHADOW_MAP_SIZE,
+        };
+
+        let view_port = vk::Viewport::default()
+            .width(SHADOW_MAP_SIZE as f32)
+            .height(SHADOW_MAP_SIZE as f32)
+            .max_depth(1.);
+
+        let scissor = vk::Rect2D::default().extent(extent);
+
+        let clear_value = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue::default().depth(1.),
+        };
+
+        let casters = objects
+            .iter()
+            .filter(|object| object.visible)
+            .map(|object| (object, &materials[object.material].material))
+            .filter(|(_, material)| {
+                material.shadows.cast
+                    && !matches!(material.shader, Shader::PushConstant | Shader::Ui)
+            })
+            .filter_map(|(object, _)| {
+                let topology = vk::PrimitiveTopology::from(object.topology);
+                self.pipelines
+                    .iter()
+                    .find(|(shadow_topology, _)| *shadow_topology == topology)
+                    .map(|(_, pipeline)| (object, *pipeline, object.topology))
+            })
+            .collect::<Vec<_>>();
+
+        shadow_matrices
+            .iter()
+            .zip(&self.frame_buffers)
+            .for_each(|(matrix, frame_buffer)| {
+                let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+                    .render_pass(self.render_pass)
+                    .framebuffer(*frame_buffer)
+                    .render_area(scissor)
+                    .clear_values(std::slice::from_ref(&clear_value));
+
+                device.cmd_begin_render_pass(
+                    command_buffer,
+                    &render_pass_begin_info,
+                    vk::SubpassContents::INLINE,
+                );
+
+                device.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&view_port));
+                device.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&scissor));
+
+                casters.iter().for_each(|(object, pipeline, topology)| {
+                    device.cmd_bind_pipeline(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        *pipeline,
+                    );
+
+                    let light_model = matrix * object.model;
+                    device.cmd_push_constants(
+                        command_buffer,
+                        self.layout,
+                        vk::ShaderStageFlags::VERTEX,
+                        0,
+                        any_as_u8_slice(&light_model),
+                    );
+
+                    let count =
+                        command_buffer::draw_geometry(object, device, command_buffer, &[0_u64]);
+                    counts.draw(count, *topology);
+                });
+
+                device.cmd_end_render_pass(command_buffer);
+            });
+    }
+
+    /// # Safety
+    ///
+    /// The shadow maps must no longer be in use by the device.
+    pub unsafe fn destroy(&self, device: &Device) {
+        self.pipelines
+            .iter()
+            .for_each(|(_, pipeline)| device.destroy_pipeline(*pipeline, None));
+        device.destroy_pipeline_layout(self.layout, None);
+
+        self.frame_buffers
+            .iter()
+            .for_each(|frame_buffer| device.destroy_framebuffer(*frame_buffer, None));
+        device.destroy_render_pass(self.render_pass, None);
+
+        device.destroy_sampler(self.sampler, None);
+        self.layer_views
+            .iter()
+            .for_each(|view| device.destroy_image_view(*view, None));
+        device.destroy_image_view(self.array_view, None);
+        device.destroy_image(self.image.image, None);
+        device.free_memory(self.image.memory, None);
+    }
+}
+
+fn create_view(
+    device: &Device,
+    image: vk::Image,
+    format: vk::Format,
+    view_type: vk::ImageViewType,
+    base_layer: u32,
+    layer_count: u32,
+) -> vk::ImageView {
+    let view_info = vk::ImageViewCreateInfo::default()
+        .image(image)
+        .view_type(view_type)
+        .format(format)
+        .subresource_range(
+            vk::ImageSubresourceRange::default()
+                .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                .level_count(1)
+                .base_array_layer(base_layer)
+                .layer_count(layer_count),
+        );
+
+    unsafe {
+        device
+            .create_image_view(&view_info, None)
+            .expect("Failed to create shadow map image view!")
+    }
+}
+
+/// Moves every layer into the layout the lit shaders sample it in, so layers no
+/// light renders to this frame are still valid to bind.
+fn transition_to_shader_read(
+    device: &Device,
+    command_pool: &vk::CommandPool,
+    submit_queue: vk::Queue,
+    image: &Image,
+) {
+    let command_buffer = command_buffer::begin_single_time_command(device, command_pool);
+
+    let barrier = vk::ImageMemoryBarrier::default()
+        .old_layout(vk::ImageLayout::UNDEFINED)
+        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image.image)
+        .subresource_range(
+            vk::ImageSubresourceRange::default()
+                .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                .level_count(1)
+                .layer_count(MAX_SHADOW_LAYERS as u32),
+        );
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            std::slice::from_ref(&barrier),
+        );
+    }
+
+    command_buffer::end_single_time_command(device, submit_queue, command_pool, command_buffer);
+}
+
+fn create_shadow_render_pass(device: &Device, format: vk::Format) -> vk::RenderPass {
+    let attachment = vk::AttachmentDescription::default()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+    let depth_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+
+    let sub_pass = vk::SubpassDescription::default()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .depth_stencil_attachment(&depth_attachment_ref);
+
+    // Previous frames must have finished sampling the layer before it is cleared,
+    // and the depth writes must land before this frame's lit shaders sample them
+    let dependencies = [
+        vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+            .src_access_mask(vk::AccessFlags::SHADER_READ)
+            .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE),
+        vk::SubpassDependency::default()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ),
+    ];
+
+    let render_pass_create_info = vk::RenderPassCreateInfo::default()
+        .attachments(std::slice::from_ref(&attachment))
+        .subpasses(std::slice::from_ref(&sub_pass))
+        .dependencies(&dependencies);
+
+    unsafe {
+        device
+            .create_render_pass(&render_pass_create_info, None)
+            .expect("Failed to create shadow render pass!")
+    }
+}
+
+fn create_shadow_pipelines(
+    device: &Device,
+    render_pass: vk::RenderPass,
+) -> (
+    vk::PipelineLayout,
+    Vec<(vk::PrimitiveTopology, vk::Pipeline)>,
+) {
+    let ShaderModules { vert, frag } = graphics_pipeline::load_shader_stages("shadow", device);
+
+    let push_constant_range = vk::PushConstantRange::default()
+        .stage_flags(vk::ShaderStageFlags::VERTEX)
+        .size(mem::size_of::<Matrix4<f32>>() as u32)
+        .offset(0);
+
+    let layout_info = vk::PipelineLayoutCreateInfo::default()
+        .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+
+    let binding_description = vk::VertexInputBindingDescription::default()
+        .binding(0)
+        .stride(mem::size_of::<Vertex>() as u32)
+        .input_rate(vk::VertexInputRate::VERTEX);
+
+    let attribute_description = vk::VertexInputAttributeDescription::default()
+        .binding(0)
+        .location(0)
+        .format(vk::Format::R32G32B32_SFLOAT)
+        .offset(offset_of!(Vertex, pos) as u32);
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(std::slice::from_ref(&binding_description))
+        .vertex_attribute_descriptions(std::slice::from_ref(&attribute_description));
+
+    let view_port_state = vk::PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    // Slope scaled bias keeps lit surfaces from shadowing themselves
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .depth_bias_enable(true)
+        .depth_bias_constant_factor(1.25)
+        .depth_bias_slope_factor(1.75);
+
+    let multi_sampling = vk::PipelineMultisampleStateCreateInfo::default()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+        .depth_test_enable(true)
+        .depth_write_enable(true)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .max_depth_bounds(1.);
+
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::default();
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+    let dynamic_state_create_info =
+        vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    unsafe {
+        let layout = device
+            .create_pipeline_layout(&layout_info, None)
+            .expect("Failed to create shadow pipeline layout!");
+
+        let entry_point = c"main";
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert)
+                .name(entry_point),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag)
+                .name(entry_point),
+        ];
+
+        let pipelines = SHADOW_TOPOLOGIES
+            .iter()
+            .map(|topology| {
+                let input_assembly =
+                    vk::PipelineInputAssemblyStateCreateInfo::default().topology(*topology);
+
+                let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+                    .stages(&stages)
+                    .vertex_input_state(&vertex_input_info)
+                    .input_assembly_state(&input_assembly)
+                    .viewport_state(&view_port_state)
+                    .rasterization_state(&rasterizer)
+                    .multisample_state(&multi_sampling)
+                    .depth_stencil_state(&depth_stencil)
+                    .color_blend_state(&color_blending)
+                    .dynamic_state(&dynamic_state_create_info)
+                    .layout(layout)
+                    .render_pass(render_pass)
+                    .subpass(0);
+
+                let pipeline = device
+                    .create_graphics_pipelines(
+                        vk::PipelineCache::null(),
+                        std::slice::from_ref(&pipeline_info),
+                        None,
+                    )
+                    .expect("Failed to create shadow pipeline!")[0];
+
+                (*topology, pipeline)
+            })
+            .collect();
+
+        graphics_pipeline::destroy_shader_modules(device, vert, frag);
+
+        (layout, pipelines)
+    }
+}
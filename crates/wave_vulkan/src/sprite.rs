@@ -0,0 +1,209 @@
+use crate::{
+    any_as_u8_slice,
+    buffer::FrameVertexBuffers,
+    command_buffer::CommandPool,
+    device::Devices,
+    graphics_pipeline::PipelineLayouts,
+    profiler::DrawCounts,
+    text::{self, UiPipelineState, UiTexture, UiVertex},
+    texture, BlendMode, ModelTopology,
+};
+use ash::{vk, Device, Instance};
+use gpu_allocator::vulkan::Allocator;
+use nalgebra::Matrix4;
+use wave_window::sprite::{Camera2d, SpriteBatches, SpriteTextureId, Sprites};
+
+/// Sprites from [`Sprites`], drawn over the final image after post processing with one draw
+/// call per batch, so they keep their colours
+pub(crate) struct SpriteRenderer {
+    pipeline: vk::Pipeline,
+    /// Uploaded textures, in the order they were added to [`Sprites`]
+    textures: Vec<UiTexture>,
+    buffers: FrameVertexBuffers,
+    batches: SpriteBatches,
+    camera: Camera2d,
+}
+
+impl SpriteRenderer {
+    pub fn new(
+        devices: &Devices,
+        present_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+    ) -> Self {
+        Self {
+            pipeline: create_pipeline(devices, present_pass, layout),
+            textures: Vec::new(),
+            buffers: FrameVertexBuffers::new("sprites"),
+            batches: SpriteBatches::default(),
+            camera: Camera2d::default(),
+        }
+    }
+
+    /// Whether textures were added since they were last uploaded
+    pub fn needs_textures(&self, sprites: &Sprites) -> bool {
+        self.textures.len() < sprites.textures().len()
+    }
+
+    /// Decodes and uploads the textures added since the last upload.
+    ///
+    /// # Safety
+    ///
+    /// No command buffer may be recording.
+    pub unsafe fn upload_textures(
+        &mut self,
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        devices: &Devices,
+        layouts: &PipelineLayouts,
+        sprites: &Sprites,
+    ) {
+        let first = self.textures.len();
+
+        sprites.textures()[first..]
+            .iter()
+            .enumerate()
+            .for_each(|(offset, bytes)| {
+                let texture = texture::create_map_texture(
+                    allocator,
+                    command_pool,
+                    instance,
+                    devices,
+                    bytes,
+                    [255; 4],
+                    vk::Format::R8G8B8A8_SRGB,
+                );
+
+                self.textures.push(UiTexture::new(
+                    devices,
+                    layouts,
+                    texture,
+                    &format!("sprites/texture {}", first + offset),
+                ));
+            });
+    }
+
+    /// Replaces the sprites drawn from the next recorded frame onwards
+    pub fn set(&mut self, sprites: &Sprites) {
+        self.batches = sprites.batches();
+        self.camera = sprites.camera;
+    }
+
+    /// Copies the sprite quads into the vertex buffer of `frame`.
+    ///
+    /// # Safety
+    ///
+    /// The last submission of `frame` must have completed.
+    pub unsafe fn upload(&mut self, allocator: &mut Allocator, devices: &Devices, frame: usize) {
+        if !self.batches.vertices.is_empty() {
+            self.buffers
+                .write(allocator, devices, frame, &self.batches.vertices);
+        }
+    }
+
+    /// Draws the sprites through the 2D camera over a window `extent` pixels in size.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording inside the post processor's present pass, with the
+    /// sprites of `frame` uploaded.
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        layout: vk::PipelineLayout,
+        extent: vk::Extent2D,
+        counts: &mut DrawCounts,
+    ) {
+        let Some(buffer) = self.buffers.get(frame) else {
+            return;
+        };
+
+        if self.batches.batches.is_empty() {
+            return;
+        }
+
+        let projection = self.projection(extent);
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            any_as_u8_slice(&projection),
+        );
+        device.cmd_bind_vertex_buffers(command_buffer, 0, std::slice::from_ref(&buffer), &[0]);
+
+        self.batches.batches.iter().for_each(|batch| {
+            let Some(texture) = self.texture(batch.texture) else {
+                return;
+            };
+
+            texture.bind(device, command_buffer, layout);
+            device.cmd_draw(command_buffer, batch.count, 1, batch.first, 0);
+            counts.draw(batch.count, ModelTopology::TriangleList);
+        });
+    }
+
+    /// Projection of the 2D camera onto a window `extent` pixels in size
+    pub fn projection(&self, extent: vk::Extent2D) -> Matrix4<f32> {
+        self.camera
+            .projection(extent.width as f32, extent.height as f32)
+    }
+
+    /// Uploaded texture `id`, once the textures were uploaded
+    pub fn texture(&self, id: SpriteTextureId) -> Option<&UiTexture> {
+        self.textures.get(id.index())
+    }
+
+    /// Rebuilds the pipeline after the present pass was recreated
+    pub fn recreate(
+        &mut self,
+        devices: &Devices,
+        present_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+    ) {
+        self.pipeline = create_pipeline(devices, present_pass, layout);
+    }
+
+    /// # Safety
+    ///
+    /// The pipeline must no longer be in use by the device.
+    pub unsafe fn destroy_pipeline(&self, device: &Device) {
+        device.destroy_pipeline(self.pipeline, None);
+    }
+
+    /// Frees the textures and buffers, after the pipeline was destroyed with the swap chain.
+    ///
+    /// # Safety
+    ///
+    /// No frame may be in flight.
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator, device: &Device) {
+        self.buffers.destroy(allocator, device);
+
+        self.textures
+            .drain(..)
+            .for_each(|texture| texture.destroy(device));
+    }
+}
+
+fn create_pipeline(
+    devices: &Devices,
+    present_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+) -> vk::Pipeline {
+    // Sprites are ordered by z rather than depth, so they neither test nor write it
+    text::create_ui_pipeline(
+        devices,
+        present_pass,
+        layout,
+        UiPipelineState::present(UiVertex::Text, BlendMode::AlphaBlend),
+        "sprites/pipeline",
+    )
+}
@@ -1,8 +1,5 @@
 use crate::{
-    command_buffer,
-    device::{self, Devices},
-    frame_buffer, renderer,
-    resource::Resources,
+    device::Devices, environment::SkyboxPipeline, frame_buffer, renderer, resource::Resources,
     Vulkan,
 };
 use ash::{
@@ -40,6 +37,8 @@ pub struct SwapChain {
     pub extent: vk::Extent2D,
     pub images: Vec<vk::Image>,
     pub image_views: Vec<vk::ImageView>,
+    /// Whether the images can be copied from, which screenshots need
+    pub readable: bool,
 }
 
 impl SwapChain {
@@ -62,6 +61,16 @@ impl SwapChain {
 
         let extent = choose_swap_extent(capabilities, window);
 
+        let readable = capabilities
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC);
+
+        let image_usage = if readable {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+        } else {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        };
+
         let mut swap_chain_image_count = capabilities.min_image_count + 1;
 
         if capabilities.max_image_count > 0 && swap_chain_image_count > capabilities.max_image_count
@@ -69,6 +78,9 @@ impl SwapChain {
             swap_chain_image_count = capabilities.max_image_count;
         }
 
+        let queue_families = devices.physical.queue_families;
+        let sharing_families = [queue_families.graphics, queue_families.present];
+
         let mut create_info = vk::SwapchainCreateInfoKHR::default()
             .surface(surface)
             .min_image_count(swap_chain_image_count)
@@ -76,7 +88,7 @@ impl SwapChain {
             .image_color_space(surface_format.color_space)
             .image_extent(extent)
             .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_usage(image_usage)
             .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
             .pre_transform(capabilities.current_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
@@ -84,19 +96,12 @@ impl SwapChain {
             .clipped(true)
             .old_swapchain(vk::SwapchainKHR::null());
 
-        let queue_family_indices =
-            device::find_queue_family(instance, devices.physical.device, surface_loader, &surface);
-
-        if queue_family_indices.graphics_family != queue_family_indices.present_family {
-            create_info.image_sharing_mode = vk::SharingMode::CONCURRENT;
-            create_info.queue_family_index_count = 2;
-
-            let queue_family_indices_arr = [
-                queue_family_indices.graphics_family.unwrap(),
-                queue_family_indices.present_family.unwrap(),
-            ];
-
-            create_info.p_queue_family_indices = queue_family_indices_arr.as_ptr();
+        // Images drawn by one family and presented by another are shared rather than handed
+        // over every frame
+        if queue_families.graphics != queue_families.present {
+            create_info = create_info
+                .image_sharing_mode(vk::SharingMode::CONCURRENT)
+                .queue_family_indices(&sharing_families);
         }
 
         let swap_chain = swapchain::Device::new(instance, &devices.logical.device);
@@ -112,6 +117,16 @@ impl SwapChain {
 
             let image_views = create_image_views(devices, &swap_chain_images, &surface_format, 1);
 
+            swap_chain_images
+                .iter()
+                .zip(&image_views)
+                .enumerate()
+                .for_each(|(index, (image, view))| {
+                    let names = &devices.debug_names;
+                    names.name(*image, &format!("swap chain/image {index}"));
+                    names.name(*view, &format!("swap chain/view {index}"));
+                });
+
             SwapChain {
                 swap_chain,
                 swap_chain_khr,
@@ -119,6 +134,7 @@ impl SwapChain {
                 image_format: surface_format.format,
                 extent,
                 image_views,
+                readable,
             }
         }
     }
@@ -175,18 +191,42 @@ pub fn cleanup_swap_chain(vulkan: &Vulkan) {
         device.destroy_image(vulkan.resources.depth.image.image, None);
         device.free_memory(vulkan.resources.depth.image.memory, None);
 
-        device.destroy_image_view(vulkan.resources.colour.view, None);
-        device.destroy_image(vulkan.resources.colour.image.image, None);
-        device.free_memory(vulkan.resources.colour.image.memory, None);
+        if let Some(colour) = &vulkan.resources.colour {
+            device.destroy_image_view(colour.view, None);
+            device.destroy_image(colour.image.image, None);
+            device.free_memory(colour.image.memory, None);
+        }
 
-        vulkan.frame_buffers.iter().for_each(|frame_buffer| {
-            device.destroy_framebuffer(*frame_buffer, None);
-        });
+        device.destroy_image_view(vulkan.resources.scene.view, None);
+        device.destroy_image(vulkan.resources.scene.image.image, None);
+        device.free_memory(vulkan.resources.scene.image.memory, None);
 
-        vulkan.objects.iter().for_each(|object| {
-            device.destroy_pipeline(object.graphics_pipeline.features.pipeline, None);
-            device.destroy_pipeline_layout(object.graphics_pipeline.features.layout, None);
-        });
+        device.destroy_framebuffer(vulkan.frame_buffer, None);
+
+        vulkan.post_processor.destroy_chain(device);
+
+        vulkan
+            .materials
+            .iter()
+            .for_each(|material| material.destroy_pipelines(device));
+
+        if let Some(skybox_pipeline) = &vulkan.skybox_pipeline {
+            skybox_pipeline.destroy(device);
+        }
+
+        vulkan.debug_views.destroy_pipelines(device);
+
+        vulkan.debug_lines.destroy_pipeline(device);
+
+        vulkan.particles.destroy_pipeline(device);
+
+        vulkan.sprites.destroy_pipeline(device);
+
+        vulkan.tilemaps.destroy_pipeline(device);
+
+        vulkan.text.destroy_pipelines(device);
+
+        vulkan.gui.destroy_pipeline(device);
 
         device.destroy_render_pass(vulkan.render_pass.0, None);
 
@@ -216,6 +256,25 @@ pub fn recreate_swap_chain(vulkan: &mut Vulkan, window: &Window) {
 
     cleanup_swap_chain(vulkan);
 
+    let anisotropy_changed = vulkan
+        .devices
+        .physical
+        .apply_settings(&vulkan.instance, &vulkan.render_settings);
+
+    if anisotropy_changed {
+        vulkan.materials.iter_mut().for_each(|material| unsafe {
+            material.reload_textures(
+                &mut vulkan.allocator,
+                &vulkan.command_pool,
+                &vulkan.instance,
+                &vulkan.pipeline_layouts,
+                &vulkan.devices,
+            )
+        });
+    }
+
+    vulkan.render_settings_changed = false;
+
     vulkan.swap_chain = SwapChain::new(
         &vulkan.instance,
         &vulkan.devices,
@@ -224,39 +283,83 @@ pub fn recreate_swap_chain(vulkan: &mut Vulkan, window: &Window) {
         window,
     );
 
-    vulkan.render_pass =
-        renderer::create_render_pass(&vulkan.devices, &vulkan.swap_chain, &vulkan.instance);
+    vulkan.render_pass = renderer::create_render_pass(&vulkan.devices, &vulkan.instance);
 
-    vulkan.resources = Resources::new(&vulkan.swap_chain, &vulkan.instance, &vulkan.devices);
+    vulkan.resources = Resources::new(
+        vulkan
+            .render_settings
+            .render_extent(vulkan.swap_chain.extent),
+        &vulkan.instance,
+        &vulkan.devices,
+    );
 
-    vulkan.frame_buffers = frame_buffer::create_frame_buffers(
+    vulkan.frame_buffer =
+        frame_buffer::create_scene_frame_buffer(&vulkan.render_pass, device, &vulkan.resources);
+
+    vulkan.post_processor.recreate(
+        &vulkan.instance,
+        &vulkan.devices,
         &vulkan.swap_chain,
-        &vulkan.render_pass,
-        device,
         &vulkan.resources,
     );
 
-    vulkan.sync_objects.images_in_flight = vec![vk::Fence::null(); 1];
+    vulkan.sync_objects.images_in_flight = vec![vk::Fence::null(); vulkan.swap_chain.images.len()];
 
-    let models = Vec::new();
+    vulkan
+        .materials
+        .iter_mut()
+        .flat_map(|material| &mut material.pipelines)
+        .for_each(|pipeline| {
+            pipeline.recreate(&vulkan.swap_chain, vulkan.render_pass.0, &vulkan.devices);
+        });
 
-    vulkan.objects.iter().for_each(|object| {
-        object.graphics_pipeline.recreate(
-            &mut vulkan.allocator,
-            &vulkan.swap_chain,
-            vulkan.render_pass.0,
-            &object.texture,
+    if vulkan.skybox_pipeline.is_some() {
+        vulkan.skybox_pipeline = Some(SkyboxPipeline::new(
             &vulkan.devices,
-        );
-    });
+            vulkan.render_pass.0,
+            vulkan.devices.physical.samples,
+            vulkan.scene.set_layout,
+        ));
+    }
 
-    vulkan.command_buffers = command_buffer::create_command_buffers(
-        &vulkan.command_pool,
-        &vulkan.swap_chain,
-        device,
-        &vulkan.render_pass,
-        &vulkan.frame_buffers,
-        &models,
+    vulkan.debug_views.recreate(
+        &vulkan.devices,
+        vulkan.render_pass.0,
+        vulkan.pipeline_layouts.layout,
+    );
+
+    vulkan
+        .debug_lines
+        .recreate(&vulkan.devices, vulkan.render_pass.0);
+
+    vulkan
+        .particles
+        .recreate(&vulkan.devices, vulkan.render_pass.0);
+
+    vulkan.sprites.recreate(
+        &vulkan.devices,
+        vulkan.post_processor.present_pass(),
+        vulkan.pipeline_layouts.layout,
+    );
+
+    vulkan.tilemaps.recreate(
+        &vulkan.devices,
+        vulkan.post_processor.present_pass(),
+        vulkan.pipeline_layouts.layout,
+    );
+
+    vulkan.text.recreate(
+        &vulkan.devices,
+        vulkan.render_pass.0,
+        vulkan.post_processor.present_pass(),
+        vulkan.pipeline_layouts.layout,
+    );
+
+    vulkan.gui.recreate(
+        &vulkan.devices,
+        vulkan.post_processor.present_pass(),
+        vulkan.pipeline_layouts.layout,
+        vulkan.swap_chain.image_format,
     );
 }
 
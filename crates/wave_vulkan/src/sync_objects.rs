@@ -1,4 +1,7 @@
+use crate::{buffer::ModelBuffers, text::UiTexture};
 use ash::{vk, Device};
+use derive_more::From;
+use gpu_allocator::vulkan::Allocator;
 
 pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
 
@@ -14,7 +17,7 @@ pub struct SyncObjects {
 }
 
 impl SyncObjects {
-    pub fn new(device: &Device) -> Self {
+    pub fn new(device: &Device, swap_chain_image_count: usize) -> Self {
         let semaphore_create_info = vk::SemaphoreCreateInfo::default();
 
         let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
@@ -22,7 +25,7 @@ impl SyncObjects {
         let mut image_available_semaphores: ImageSemaphoreArray = Default::default();
         let mut render_finished_semaphores: ImageSemaphoreArray = Default::default();
         let mut in_flight_fences: FenceArray = Default::default();
-        let images_in_flight: Vec<vk::Fence> = vec![vk::Fence::null(); 3];
+        let images_in_flight: Vec<vk::Fence> = vec![vk::Fence::null(); swap_chain_image_count];
 
         unsafe {
             for i in 0..MAX_FRAMES_IN_FLIGHT {
@@ -44,3 +47,73 @@ impl SyncObjects {
         }
     }
 }
+
+/// Resource replaced while frames in flight may still use it
+#[derive(From)]
+pub(crate) enum Retired {
+    UiTexture(UiTexture),
+    Buffers(ModelBuffers),
+}
+
+impl Retired {
+    /// # Safety
+    ///
+    /// The resource must no longer be in use by the device.
+    unsafe fn destroy(self, allocator: &mut Allocator, device: &Device) {
+        match self {
+            Self::UiTexture(texture) => texture.destroy(device),
+            Self::Buffers(buffers) => buffers.destroy(allocator, device),
+        }
+    }
+}
+
+/// Resources replaced between frames, destroyed once every frame that could use them has
+/// completed rather than waiting for the device to be idle
+#[derive(Default)]
+pub(crate) struct RetiredResources {
+    /// Frames submitted so far
+    submitted: u64,
+    /// Resources with the number of frames submitted when they were replaced
+    resources: Vec<(u64, Retired)>,
+}
+
+impl RetiredResources {
+    pub fn retire<T: Into<Retired>>(&mut self, resources: impl IntoIterator<Item = T>) {
+        let submitted = self.submitted;
+        self.resources.extend(
+            resources
+                .into_iter()
+                .map(|resource| (submitted, resource.into())),
+        );
+    }
+
+    pub fn frame_submitted(&mut self) {
+        self.submitted += 1;
+    }
+
+    /// Destroys the resources no frame in flight can use. Once the fence of the frame about
+    /// to be recorded was waited for, only the last `MAX_FRAMES_IN_FLIGHT - 1` frames
+    /// submitted can still be running.
+    ///
+    /// # Safety
+    ///
+    /// Must be called after waiting for the fence of the frame about to be recorded.
+    pub unsafe fn release(&mut self, allocator: &mut Allocator, device: &Device) {
+        let running = MAX_FRAMES_IN_FLIGHT as u64 - 1;
+
+        self.resources
+            .extract_if(.., |(retired_at, _)| {
+                *retired_at + running <= self.submitted
+            })
+            .for_each(|(_, resource)| resource.destroy(allocator, device));
+    }
+
+    /// # Safety
+    ///
+    /// The device must be idle.
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator, device: &Device) {
+        self.resources
+            .drain(..)
+            .for_each(|(_, resource)| resource.destroy(allocator, device));
+    }
+}
@@ -0,0 +1,544 @@
+use crate::{
+    any_as_u8_slice,
+    buffer::FrameVertexBuffers,
+    command_buffer::CommandPool,
+    device::Devices,
+    graphics_pipeline::{self, PipelineLayouts, ShaderModules, MATERIAL_MAP_COUNT},
+    material,
+    profiler::DrawCounts,
+    texture::{self, ImageProperties, Texture},
+    BlendMode, ModelTopology,
+};
+use ash::{vk, Device, Instance};
+use gpu_allocator::vulkan::Allocator;
+use memoffset::offset_of;
+use nalgebra::Matrix4;
+use std::mem;
+use wave_space::space::Vertex;
+use wave_window::text::{GlyphAtlas, Text, TextDraw, TextSpace, TextVertex};
+
+/// Binding of the base colour map the ui shader samples, followed by the other material maps
+const BASE_COLOUR_BINDING: u32 = 1;
+
+/// Index of the material set in the shared pipeline layout
+const MATERIAL_SET: u32 = 1;
+
+/// Texture in a material set of its own, bound to every map so the ui shaders sample it as
+/// the base colour
+pub(crate) struct UiTexture {
+    texture: Texture,
+    pool: vk::DescriptorPool,
+    set: vk::DescriptorSet,
+}
+
+impl UiTexture {
+    pub fn new(devices: &Devices, layouts: &PipelineLayouts, texture: Texture, name: &str) -> Self {
+        let device = &devices.logical.device;
+
+        let pool = material::create_descriptor_pool(device);
+
+        let alloc_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(std::slice::from_ref(&layouts.material_set_layout));
+
+        let set = unsafe {
+            device
+                .allocate_descriptor_sets(&alloc_info)
+                .expect("Failed to allocate ui texture descriptor set!")[0]
+        };
+
+        texture.set_name(devices, name);
+        devices
+            .debug_names
+            .name(set, &format!("{name}/descriptor set"));
+
+        let image_info = vk::DescriptorImageInfo::default()
+            .sampler(texture.sampler)
+            .image_view(texture.view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        let image_infos = [image_info; MATERIAL_MAP_COUNT as usize];
+
+        let descriptor_write = vk::WriteDescriptorSet::default()
+            .dst_set(set)
+            .dst_binding(BASE_COLOUR_BINDING)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_infos);
+
+        unsafe { device.update_descriptor_sets(std::slice::from_ref(&descriptor_write), &[]) };
+
+        Self { texture, pool, set }
+    }
+
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording, with a pipeline of `layout` bound.
+    pub unsafe fn bind(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+    ) {
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            layout,
+            MATERIAL_SET,
+            std::slice::from_ref(&self.set),
+            &[],
+        );
+    }
+
+    /// # Safety
+    ///
+    /// The texture must no longer be in use by the device.
+    pub unsafe fn destroy(&self, device: &Device) {
+        self.texture.destroy(device);
+        device.destroy_descriptor_pool(self.pool, None);
+    }
+}
+
+/// Text from [`Text`], drawn with the ui shaders and a glyph atlas in place of the material's
+/// base colour.
+///
+/// World space text is drawn in the scene pass, hidden behind the scene. Screen space text is
+/// drawn over the final image after post processing, so it keeps its colours.
+pub(crate) struct TextRenderer {
+    screen_pipeline: vk::Pipeline,
+    world_pipeline: vk::Pipeline,
+    /// Atlas texture and the generation of the atlas it was uploaded from
+    atlas: Option<(UiTexture, u64)>,
+    buffers: FrameVertexBuffers,
+    vertices: Vec<TextVertex>,
+    draws: Vec<TextDraw>,
+}
+
+impl TextRenderer {
+    pub fn new(
+        devices: &Devices,
+        render_pass: vk::RenderPass,
+        present_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+    ) -> Self {
+        Self {
+            screen_pipeline: create_screen_pipeline(devices, present_pass, layout),
+            world_pipeline: create_world_pipeline(devices, render_pass, layout),
+            atlas: None,
+            buffers: FrameVertexBuffers::new("text"),
+            vertices: Vec::new(),
+            draws: Vec::new(),
+        }
+    }
+
+    /// Whether the atlas has glyphs the uploaded texture lacks
+    pub fn needs_atlas(&self, text: &Text) -> bool {
+        !text.draws().is_empty()
+            && self
+                .atlas
+                .as_ref()
+                .is_none_or(|(_, generation)| *generation != text.atlas().generation())
+    }
+
+    /// Uploads the atlas as white texels covered by each glyph's alpha, returning the previous
+    /// one, which frames in flight may still use.
+    ///
+    /// # Safety
+    ///
+    /// `command_pool` must allocate from `devices`.
+    pub unsafe fn upload_atlas(
+        &mut self,
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        instance: &Instance,
+        devices: &Devices,
+        layouts: &PipelineLayouts,
+        atlas: &GlyphAtlas,
+    ) -> Option<UiTexture> {
+        let texels = atlas
+            .pixels()
+            .iter()
+            .flat_map(|coverage| [255, 255, 255, *coverage])
+            .collect::<Vec<u8>>();
+
+        let size = atlas.size();
+        let texture = texture::create_sampled_texture(
+            allocator,
+            command_pool,
+            instance,
+            devices,
+            ImageProperties::new((size, size), &texels, 1, texels.len() as vk::DeviceSize),
+            vk::Format::R8G8B8A8_UNORM,
+        );
+        let texture = UiTexture::new(devices, layouts, texture, "text/glyph atlas");
+
+        self.atlas
+            .replace((texture, atlas.generation()))
+            .map(|(old, _)| old)
+    }
+
+    /// Replaces the text drawn from the next recorded frame onwards
+    pub fn set(&mut self, text: &Text) {
+        self.vertices.clear();
+        self.vertices.extend_from_slice(text.vertices());
+        self.draws.clear();
+        self.draws.extend_from_slice(text.draws());
+    }
+
+    /// Copies the glyph quads into the vertex buffer of `frame`.
+    ///
+    /// # Safety
+    ///
+    /// The last submission of `frame` must have completed.
+    pub unsafe fn upload(&mut self, allocator: &mut Allocator, devices: &Devices, frame: usize) {
+        if !self.vertices.is_empty() {
+            self.buffers
+                .write(allocator, devices, frame, &self.vertices);
+        }
+    }
+
+    /// Draws the world space text.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording inside the scene render pass, with the text of
+    /// `frame` uploaded.
+    pub unsafe fn record_world(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        layout: vk::PipelineLayout,
+        view_projection: &Matrix4<f32>,
+        counts: &mut DrawCounts,
+    ) {
+        self.record(
+            device,
+            command_buffer,
+            frame,
+            layout,
+            |space| match space {
+                TextSpace::Screen => None,
+                TextSpace::World(transform) => Some(view_projection * transform),
+            },
+            counts,
+        );
+    }
+
+    /// Draws the screen space text covering `extent` in pixels.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording inside the post processor's present pass, with the
+    /// text of `frame` uploaded.
+    pub unsafe fn record_screen(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        layout: vk::PipelineLayout,
+        extent: vk::Extent2D,
+        counts: &mut DrawCounts,
+    ) {
+        let screen =
+            Matrix4::new_orthographic(0., extent.width as f32, 0., extent.height as f32, -1., 1.);
+
+        self.record(
+            device,
+            command_buffer,
+            frame,
+            layout,
+            |space| (space == TextSpace::Screen).then_some(screen),
+            counts,
+        );
+    }
+
+    /// Draws the text `matrix` places, skipping the text it gives no matrix
+    unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        layout: vk::PipelineLayout,
+        matrix: impl Fn(TextSpace) -> Option<Matrix4<f32>>,
+        counts: &mut DrawCounts,
+    ) {
+        let (Some(buffer), Some((atlas, _))) = (self.buffers.get(frame), &self.atlas) else {
+            return;
+        };
+
+        let mut draws = self
+            .draws
+            .iter()
+            .filter_map(|draw| matrix(draw.space).map(|matrix| (draw, matrix)))
+            .peekable();
+
+        if draws.peek().is_none() {
+            return;
+        }
+
+        device.cmd_bind_vertex_buffers(command_buffer, 0, std::slice::from_ref(&buffer), &[0]);
+        atlas.bind(device, command_buffer, layout);
+
+        draws.for_each(|(draw, matrix)| {
+            let pipeline = match draw.space {
+                TextSpace::Screen => self.screen_pipeline,
+                TextSpace::World(_) => self.world_pipeline,
+            };
+
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_push_constants(
+                command_buffer,
+                layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                any_as_u8_slice(&matrix),
+            );
+            device.cmd_draw(command_buffer, draw.count, 1, draw.first, 0);
+            counts.draw(draw.count, ModelTopology::TriangleList);
+        });
+    }
+
+    /// Rebuilds the pipelines after the render passes were recreated
+    pub fn recreate(
+        &mut self,
+        devices: &Devices,
+        render_pass: vk::RenderPass,
+        present_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+    ) {
+        self.screen_pipeline = create_screen_pipeline(devices, present_pass, layout);
+        self.world_pipeline = create_world_pipeline(devices, render_pass, layout);
+    }
+
+    /// # Safety
+    ///
+    /// The pipelines must no longer be in use by the device.
+    pub unsafe fn destroy_pipelines(&self, device: &Device) {
+        device.destroy_pipeline(self.screen_pipeline, None);
+        device.destroy_pipeline(self.world_pipeline, None);
+    }
+
+    /// Frees the atlas and buffers, after the pipelines were destroyed with the swap chain.
+    ///
+    /// # Safety
+    ///
+    /// No frame may be in flight.
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator, device: &Device) {
+        self.buffers.destroy(allocator, device);
+
+        if let Some((atlas, _)) = self.atlas.take() {
+            atlas.destroy(device);
+        }
+    }
+}
+
+fn create_world_pipeline(
+    devices: &Devices,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+) -> vk::Pipeline {
+    create_ui_pipeline(
+        devices,
+        render_pass,
+        layout,
+        UiPipelineState {
+            samples: devices.physical.samples,
+            depth_test: true,
+            blend: BlendMode::AlphaBlend,
+            vertex: UiVertex::Text,
+        },
+        "text/world pipeline",
+    )
+}
+
+fn create_screen_pipeline(
+    devices: &Devices,
+    present_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+) -> vk::Pipeline {
+    create_ui_pipeline(
+        devices,
+        present_pass,
+        layout,
+        UiPipelineState::present(UiVertex::Text, BlendMode::AlphaBlend),
+        "text/screen pipeline",
+    )
+}
+
+/// Vertex layouts the ui shaders can read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum UiVertex {
+    /// [`TextVertex`], written by the text, sprites and gui
+    Text,
+    /// Mesh [`Vertex`], read in 2D with an opaque vertex colour
+    Mesh,
+}
+
+impl UiVertex {
+    fn stride(self) -> usize {
+        match self {
+            Self::Text => mem::size_of::<TextVertex>(),
+            Self::Mesh => mem::size_of::<Vertex>(),
+        }
+    }
+
+    /// Offsets of the position, texture coordinates and colour, with the colour's format
+    fn offsets(self) -> (usize, usize, usize, vk::Format) {
+        match self {
+            Self::Text => (
+                offset_of!(TextVertex, pos),
+                offset_of!(TextVertex, uv),
+                offset_of!(TextVertex, colour),
+                vk::Format::R32G32B32A32_SFLOAT,
+            ),
+            // The missing alpha is read as one
+            Self::Mesh => (
+                offset_of!(Vertex, pos),
+                offset_of!(Vertex, tex_coord),
+                offset_of!(Vertex, colour),
+                vk::Format::R32G32B32_SFLOAT,
+            ),
+        }
+    }
+}
+
+/// Render state of a pipeline drawing triangle lists with the ui shaders
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UiPipelineState {
+    pub samples: vk::SampleCountFlags,
+    pub depth_test: bool,
+    pub blend: BlendMode,
+    pub vertex: UiVertex,
+}
+
+impl UiPipelineState {
+    /// Drawn over the presented image after post processing, single sampled and without depth
+    pub fn present(vertex: UiVertex, blend: BlendMode) -> Self {
+        Self {
+            samples: vk::SampleCountFlags::TYPE_1,
+            depth_test: false,
+            blend,
+            vertex,
+        }
+    }
+}
+
+/// Pipeline of the ui shaders in `state`, which never writes depth
+pub(crate) fn create_ui_pipeline(
+    devices: &Devices,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+    state: UiPipelineState,
+    name: &str,
+) -> vk::Pipeline {
+    let device = &devices.logical.device;
+    let UiPipelineState {
+        samples,
+        depth_test,
+        blend,
+        vertex,
+    } = state;
+    let ShaderModules { vert, frag } = graphics_pipeline::load_shader_stages("ui", device);
+
+    let binding_description = vk::VertexInputBindingDescription::default()
+        .binding(0)
+        .stride(vertex.stride() as u32)
+        .input_rate(vk::VertexInputRate::VERTEX);
+
+    let (pos, uv, colour, colour_format) = vertex.offsets();
+
+    let attribute_descriptions = [
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(0)
+            .format(vk::Format::R32G32_SFLOAT)
+            .offset(pos as u32),
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(1)
+            .format(vk::Format::R32G32_SFLOAT)
+            .offset(uv as u32),
+        vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(2)
+            .format(colour_format)
+            .offset(colour as u32),
+    ];
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(std::slice::from_ref(&binding_description))
+        .vertex_attribute_descriptions(&attribute_descriptions);
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+        .topology(ModelTopology::TriangleList.into());
+
+    let view_port_state = vk::PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+
+    let multi_sampling =
+        vk::PipelineMultisampleStateCreateInfo::default().rasterization_samples(samples);
+
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+        .depth_test_enable(depth_test)
+        .depth_write_enable(false)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .max_depth_bounds(1.);
+
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::from(blend);
+
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
+        .attachments(std::slice::from_ref(&color_blend_attachment));
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+    let dynamic_state_create_info =
+        vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    unsafe {
+        let entry_point = c"main";
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert)
+                .name(entry_point),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag)
+                .name(entry_point),
+        ];
+
+        let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_info)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&view_port_state)
+            .rasterization_state(&rasterizer)
+            .multisample_state(&multi_sampling)
+            .depth_stencil_state(&depth_stencil)
+            .color_blend_state(&color_blending)
+            .dynamic_state(&dynamic_state_create_info)
+            .layout(layout)
+            .render_pass(render_pass)
+            .subpass(0);
+
+        let pipeline = device
+            .create_graphics_pipelines(
+                vk::PipelineCache::null(),
+                std::slice::from_ref(&pipeline_info),
+                None,
+            )
+            .expect("Failed to create ui pipeline!")[0];
+
+        graphics_pipeline::destroy_shader_modules(device, vert, frag);
+
+        devices.debug_names.name(pipeline, name);
+
+        pipeline
+    }
+}
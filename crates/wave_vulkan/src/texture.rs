@@ -1,6 +1,6 @@
 use crate::{
     buffer::Buffer,
-    command_buffer,
+    command_buffer::{self, CommandPool, Handoff},
     device::Devices,
     utility::{self, Image, ImageInfo},
 };
@@ -12,6 +12,11 @@ use gpu_allocator::{
 use nalgebra::Point2;
 use std::cmp;
 
+/// Texel used in place of a missing colour, metallic-roughness, occlusion or emissive map
+pub(crate) const WHITE: [u8; 4] = [255, 255, 255, 255];
+/// Texel used in place of a missing normal map, pointing straight out of the surface
+pub(crate) const FLAT_NORMAL: [u8; 4] = [128, 128, 255, 255];
+
 #[derive(Default, Debug, Clone)]
 pub struct Texture {
     pub image: Image,
@@ -23,9 +28,8 @@ impl Texture {
     pub fn new(
         allocator: &mut Allocator,
         image_properties: ImageProperties,
-        command_pool: &vk::CommandPool,
+        command_pool: &CommandPool,
         instance: &Instance,
-        physical_device: &vk::PhysicalDevice,
         devices: &Devices,
         format: vk::Format,
         image_info: ImageInfo,
@@ -47,9 +51,8 @@ impl Texture {
         );
         let sampler = create_texture_sampler(
             image.mip_levels,
-            instance,
+            devices.physical.anisotropy,
             &devices.logical.device,
-            physical_device,
         );
         Self {
             image,
@@ -57,25 +60,94 @@ impl Texture {
             sampler,
         }
     }
+
+    /// Names the image, view and sampler for graphics debuggers
+    pub(crate) fn set_name(&self, devices: &Devices, name: &str) {
+        let names = &devices.debug_names;
+        names.name(self.image.image, &format!("{name}/image"));
+        names.name(self.view, &format!("{name}/view"));
+        names.name(self.sampler, &format!("{name}/sampler"));
+    }
+
+    /// # Safety
+    ///
+    /// The texture must no longer be in use by the device.
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.destroy_sampler(self.sampler, None);
+        device.destroy_image_view(self.view, None);
+        device.destroy_image(self.image.image, None);
+        device.free_memory(self.image.memory, None);
+    }
 }
 
-fn create_texture_sampler(
-    mip_levels: u32,
+/// Decodes an encoded image, or creates a single texel of `default` when there are no bytes
+pub(crate) fn create_map_texture(
+    allocator: &mut Allocator,
+    command_pool: &CommandPool,
     instance: &Instance,
-    device: &Device,
-    physical_device: &vk::PhysicalDevice,
-) -> vk::Sampler {
-    unsafe {
-        let properties = instance.get_physical_device_properties(*physical_device);
+    devices: &Devices,
+    bytes: &[u8],
+    default: [u8; 4],
+    format: vk::Format,
+) -> Texture {
+    let image_properties = if bytes.is_empty() {
+        ImageProperties::new((1, 1), &default, 1, default.len() as vk::DeviceSize)
+    } else {
+        ImageProperties::get_image_properties_from_buffer(bytes)
+    };
+
+    create_sampled_texture(
+        allocator,
+        command_pool,
+        instance,
+        devices,
+        image_properties,
+        format,
+    )
+}
 
+/// Uploads decoded RGBA8 texels into a texture sampled by shaders
+pub(crate) fn create_sampled_texture(
+    allocator: &mut Allocator,
+    command_pool: &CommandPool,
+    instance: &Instance,
+    devices: &Devices,
+    image_properties: ImageProperties,
+    format: vk::Format,
+) -> Texture {
+    let image_info = ImageInfo::new(
+        image_properties.image_dimensions,
+        image_properties.mip_levels,
+        vk::SampleCountFlags::TYPE_1,
+        format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::TRANSFER_DST
+            | vk::ImageUsageFlags::SAMPLED,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    );
+
+    Texture::new(
+        allocator,
+        image_properties,
+        command_pool,
+        instance,
+        devices,
+        format,
+        image_info,
+    )
+}
+
+fn create_texture_sampler(mip_levels: u32, anisotropy: f32, device: &Device) -> vk::Sampler {
+    unsafe {
         let sampler_create_info = vk::SamplerCreateInfo::default()
             .mag_filter(vk::Filter::LINEAR)
             .min_filter(vk::Filter::LINEAR)
             .address_mode_u(vk::SamplerAddressMode::REPEAT)
             .address_mode_v(vk::SamplerAddressMode::REPEAT)
             .address_mode_w(vk::SamplerAddressMode::REPEAT)
-            .anisotropy_enable(true)
-            .max_anisotropy(properties.limits.max_sampler_anisotropy)
+            .anisotropy_enable(anisotropy > 1.)
+            .max_anisotropy(anisotropy)
             .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
             .unnormalized_coordinates(false)
             .compare_enable(false)
@@ -94,7 +166,7 @@ fn create_texture_sampler(
 fn create_texture_image(
     allocator: &mut Allocator,
     image_properties: ImageProperties,
-    command_pool: &vk::CommandPool,
+    command_pool: &CommandPool,
     instance: &Instance,
     devices: &Devices,
     format: vk::Format,
@@ -107,7 +179,7 @@ fn create_texture_image(
         size,
     } = image_properties;
 
-    let Buffer { buffer, allocation } = create_buffer(
+    let staging = create_buffer(
         allocator,
         size,
         vk::BufferUsageFlags::TRANSFER_SRC,
@@ -115,41 +187,35 @@ fn create_texture_image(
         "Texture Image",
     );
 
-    let device = &devices.logical.device;
-
     unsafe {
-        let mapped_ptr = allocation.mapped_ptr().unwrap().as_ptr() as *mut f32;
-        mapped_ptr.copy_from_nonoverlapping(image_data.as_ptr() as *const f32, size as usize);
+        let mapped_ptr = staging.allocation.mapped_ptr().unwrap().as_ptr() as *mut u8;
+        mapped_ptr.copy_from_nonoverlapping(image_data.as_ptr(), size as usize);
     }
 
     let image = utility::create_image(image_info, instance, devices);
 
-    transition_image_layout(
-        device,
+    upload_image(
+        devices,
         command_pool,
-        devices.logical.queues.graphics,
+        image_dimensions,
+        staging,
         image.image,
-        Point2::new(
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        ),
         mip_levels,
+        1,
     );
 
-    copy_buffer_to_image(devices, command_pool, image_dimensions, buffer, image.image);
-
-    allocator.free(allocation).unwrap();
-    unsafe { device.destroy_buffer(buffer, None) };
-
     generate_mip_maps(
-        format,
-        image.image,
+        MipChain {
+            image: image.image,
+            format,
+            extent: Point2::new(
+                image_dimensions.0.try_into().unwrap(),
+                image_dimensions.1.try_into().unwrap(),
+            ),
+            mip_levels,
+            layer_count: 1,
+        },
         command_pool,
-        Point2::new(
-            image_dimensions.0.try_into().unwrap(),
-            image_dimensions.1.try_into().unwrap(),
-        ),
-        mip_levels,
         instance,
         devices,
     );
@@ -235,16 +301,14 @@ pub(crate) fn create_buffer(
     }
 }
 
-fn transition_image_layout(
+fn record_layout_transition(
     device: &ash::Device,
-    command_pool: &vk::CommandPool,
-    submit_queue: vk::Queue,
+    command_buffer: vk::CommandBuffer,
     image: vk::Image,
     layouts: Point2<vk::ImageLayout>,
     mip_levels: u32,
+    layer_count: u32,
 ) {
-    let command_buffer = command_buffer::begin_single_time_command(device, command_pool);
-
     let src_access_mask;
     let dst_access_mask;
     let source_stage;
@@ -274,7 +338,7 @@ fn transition_image_layout(
         .base_mip_level(0)
         .level_count(mip_levels)
         .base_array_layer(0)
-        .layer_count(1);
+        .layer_count(layer_count);
 
     let image_barriers = vk::ImageMemoryBarrier::default()
         .src_access_mask(src_access_mask)
@@ -297,25 +361,27 @@ fn transition_image_layout(
             std::slice::from_ref(&image_barriers),
         );
     }
-
-    command_buffer::end_single_time_command(device, submit_queue, command_pool, command_buffer);
 }
 
-fn copy_buffer_to_image(
+/// Copies tightly packed image data from `staging` into every layer of `dst_image`, one layer
+/// after another, on the transfer queue, freeing `staging` once the copy completes. The image
+/// is left in `TRANSFER_DST_OPTIMAL` for the graphics queue, ready for [`generate_mip_maps`].
+pub(crate) fn upload_image(
     devices: &Devices,
-    command_pool: &vk::CommandPool,
+    command_pool: &CommandPool,
     image_dimensions: (u32, u32),
-    src_buffer: vk::Buffer,
+    staging: Buffer,
     dst_image: vk::Image,
+    mip_levels: u32,
+    layer_count: u32,
 ) {
-    let command_buffer =
-        command_buffer::begin_single_time_command(&devices.logical.device, command_pool);
+    let device = &devices.logical.device;
 
     let image_sub_resource = vk::ImageSubresourceLayers::default()
         .aspect_mask(vk::ImageAspectFlags::COLOR)
         .mip_level(0)
         .base_array_layer(0)
-        .layer_count(1);
+        .layer_count(layer_count);
 
     let (width, height) = image_dimensions;
 
@@ -331,34 +397,73 @@ fn copy_buffer_to_image(
             depth: 1,
         });
 
-    unsafe {
-        devices.logical.device.cmd_copy_buffer_to_image(
+    let handoff = Handoff::Image {
+        image: dst_image,
+        range: vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(mip_levels)
+            .base_array_layer(0)
+            .layer_count(layer_count),
+        layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        dst_stage: vk::PipelineStageFlags::TRANSFER,
+        dst_access: vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE,
+    };
+
+    let src_buffer = staging.buffer;
+
+    command_buffer::upload(devices, command_pool, handoff, staging, |command_buffer| {
+        record_layout_transition(
+            device,
             command_buffer,
-            src_buffer,
             dst_image,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            std::slice::from_ref(&region),
-        )
-    }
+            Point2::new(
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            ),
+            mip_levels,
+            layer_count,
+        );
 
-    command_buffer::end_single_time_command(
-        &devices.logical.device,
-        devices.logical.queues.graphics,
-        command_pool,
-        command_buffer,
-    );
+        unsafe {
+            device.cmd_copy_buffer_to_image(
+                command_buffer,
+                src_buffer,
+                dst_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                std::slice::from_ref(&region),
+            )
+        }
+    });
 }
 
-fn generate_mip_maps(
-    format: vk::Format,
-    image: vk::Image,
-    command_pool: &vk::CommandPool,
-    mip_dimension: Point2<i32>,
-    mip_levels: u32,
+/// Image whose levels below the first are generated by [`generate_mip_maps`]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct MipChain {
+    pub image: vk::Image,
+    pub format: vk::Format,
+    /// Width and height of the first level
+    pub extent: Point2<i32>,
+    pub mip_levels: u32,
+    pub layer_count: u32,
+}
+
+/// Blits each mip level from the one above on the graphics queue, leaving the image ready for
+/// shaders. Nothing waits for it, as the frames that sample the image are submitted after it.
+pub(crate) fn generate_mip_maps(
+    chain: MipChain,
+    command_pool: &CommandPool,
     instance: &Instance,
     devices: &Devices,
 ) {
     let device = &devices.logical.device;
+    let MipChain {
+        image,
+        format,
+        extent: mip_dimension,
+        mip_levels,
+        layer_count,
+    } = chain;
 
     let format_properties =
         unsafe { instance.get_physical_device_format_properties(devices.physical.device, format) };
@@ -382,7 +487,7 @@ fn generate_mip_maps(
                 .base_mip_level(mip_levels)
                 .level_count(1)
                 .base_array_layer(0)
-                .layer_count(1),
+                .layer_count(layer_count),
         );
 
     let mut x = mip_dimension.coords.data.0[0][0];
@@ -401,7 +506,7 @@ fn generate_mip_maps(
                     .aspect_mask(vk::ImageAspectFlags::COLOR)
                     .mip_level(i - 1)
                     .base_array_layer(0)
-                    .layer_count(1),
+                    .layer_count(layer_count),
             )
             .src_offsets([
                 vk::Offset3D::default(),
@@ -412,7 +517,7 @@ fn generate_mip_maps(
                     .aspect_mask(vk::ImageAspectFlags::COLOR)
                     .mip_level(i)
                     .base_array_layer(0)
-                    .layer_count(1),
+                    .layer_count(layer_count),
             )
             .dst_offsets([
                 vk::Offset3D::default(),
@@ -483,10 +588,5 @@ fn generate_mip_maps(
         );
     }
 
-    command_buffer::end_single_time_command(
-        device,
-        devices.logical.queues.graphics,
-        command_pool,
-        command_buffer,
-    );
+    command_buffer::submit_graphics_upload(devices, command_pool, command_buffer);
 }
@@ -0,0 +1,225 @@
+use crate::{
+    any_as_u8_slice,
+    buffer::ModelBuffers,
+    command_buffer::CommandPool,
+    device::Devices,
+    profiler::DrawCounts,
+    sprite::SpriteRenderer,
+    text::{self, UiPipelineState, UiVertex},
+    BlendMode, ModelTopology,
+};
+use ash::{vk, Device};
+use gpu_allocator::vulkan::Allocator;
+use nalgebra::Matrix4;
+use std::collections::HashMap;
+use wave_window::{
+    sprite::SpriteTextureId,
+    tilemap::{TileChunk, TileChunkKey},
+};
+
+/// Chunk mesh in device local buffers, kept until the chunk changes
+struct UploadedChunk {
+    generation: u64,
+    texture: SpriteTextureId,
+    buffers: ModelBuffers,
+    index_count: u32,
+}
+
+/// Tilemap chunks, drawn over the final image after post processing under the sprites,
+/// through the sprites' camera and sampling the sprite textures
+pub(crate) struct TilemapRenderer {
+    pipeline: vk::Pipeline,
+    chunks: HashMap<TileChunkKey, UploadedChunk>,
+    /// Keys of the chunks to draw, in order
+    order: Vec<TileChunkKey>,
+}
+
+impl TilemapRenderer {
+    pub fn new(
+        devices: &Devices,
+        present_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+    ) -> Self {
+        Self {
+            pipeline: create_pipeline(devices, present_pass, layout),
+            chunks: HashMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    /// Whether chunks were added, changed or removed since they were last uploaded
+    pub fn needs_upload(&self, chunks: &[&TileChunk]) -> bool {
+        chunks.len() != self.chunks.len()
+            || chunks.iter().any(|chunk| {
+                self.chunks
+                    .get(&chunk.key)
+                    .is_none_or(|uploaded| uploaded.generation != chunk.generation)
+            })
+    }
+
+    /// Uploads the chunks that are new or changed, returning the buffers of the ones replaced
+    /// or gone, which frames in flight may still use.
+    ///
+    /// # Safety
+    ///
+    /// `command_pool` must allocate from `devices`.
+    pub unsafe fn upload(
+        &mut self,
+        allocator: &mut Allocator,
+        command_pool: &CommandPool,
+        devices: &Devices,
+        chunks: &[&TileChunk],
+    ) -> Vec<ModelBuffers> {
+        let generations = chunks
+            .iter()
+            .map(|chunk| (chunk.key, chunk.generation))
+            .collect::<HashMap<_, _>>();
+
+        let replaced = self
+            .chunks
+            .extract_if(|key, uploaded| generations.get(key) != Some(&uploaded.generation))
+            .map(|(_, uploaded)| uploaded.buffers)
+            .collect();
+
+        chunks.iter().for_each(|chunk| {
+            if self.chunks.contains_key(&chunk.key) {
+                return;
+            }
+
+            let TileChunkKey {
+                map,
+                layer,
+                chunk: [x, y],
+                tileset,
+            } = chunk.key;
+
+            let buffers = ModelBuffers::new(
+                allocator,
+                &chunk.mesh,
+                command_pool,
+                1,
+                &format!("tilemap {map}/layer {layer}/chunk {x} {y}/tileset {tileset}"),
+                devices,
+            );
+
+            self.chunks.insert(
+                chunk.key,
+                UploadedChunk {
+                    generation: chunk.generation,
+                    texture: chunk.texture,
+                    buffers,
+                    index_count: chunk.mesh.indices.len() as u32,
+                },
+            );
+        });
+
+        replaced
+    }
+
+    /// Replaces the chunks drawn from the next recorded frame onwards, which must all have
+    /// been uploaded
+    pub fn set(&mut self, chunks: &[&TileChunk]) {
+        self.order.clear();
+        self.order.extend(chunks.iter().map(|chunk| chunk.key));
+    }
+
+    /// Draws the chunks with the sprites' textures.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be recording inside the post processor's present pass.
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+        projection: &Matrix4<f32>,
+        sprites: &SpriteRenderer,
+        counts: &mut DrawCounts,
+    ) {
+        if self.order.is_empty() {
+            return;
+        }
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            any_as_u8_slice(projection),
+        );
+
+        self.order.iter().for_each(|key| {
+            let Some(chunk) = self.chunks.get(key) else {
+                return;
+            };
+            let Some(texture) = sprites.texture(chunk.texture) else {
+                return;
+            };
+
+            texture.bind(device, command_buffer, layout);
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                std::slice::from_ref(&chunk.buffers.vertex.buffer),
+                &[0],
+            );
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                chunk.buffers.index.buffer,
+                0,
+                vk::IndexType::UINT16,
+            );
+            device.cmd_draw_indexed(command_buffer, chunk.index_count, 1, 0, 0, 0);
+            counts.draw(chunk.index_count, ModelTopology::TriangleList);
+        });
+    }
+
+    /// Rebuilds the pipeline after the present pass was recreated
+    pub fn recreate(
+        &mut self,
+        devices: &Devices,
+        present_pass: vk::RenderPass,
+        layout: vk::PipelineLayout,
+    ) {
+        self.pipeline = create_pipeline(devices, present_pass, layout);
+    }
+
+    /// # Safety
+    ///
+    /// The pipeline must no longer be in use by the device.
+    pub unsafe fn destroy_pipeline(&self, device: &Device) {
+        device.destroy_pipeline(self.pipeline, None);
+    }
+
+    /// Frees the chunk buffers, after the pipeline was destroyed with the swap chain.
+    ///
+    /// # Safety
+    ///
+    /// No frame may be in flight.
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator, device: &Device) {
+        self.chunks
+            .drain()
+            .for_each(|(_, uploaded)| uploaded.buffers.destroy(allocator, device));
+    }
+}
+
+fn create_pipeline(
+    devices: &Devices,
+    present_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+) -> vk::Pipeline {
+    // Like sprites, tiles are ordered by layer rather than depth
+    text::create_ui_pipeline(
+        devices,
+        present_pass,
+        layout,
+        UiPipelineState::present(UiVertex::Mesh, BlendMode::AlphaBlend),
+        "tilemap/pipeline",
+    )
+}
@@ -1,6 +1,9 @@
 use crate::VulkanObject;
 use ash::vk;
-use nalgebra::{Matrix, Matrix4, Perspective3};
+use nalgebra::{Matrix, Matrix4, Perspective3, Point3, Vector3};
+
+/// Vertical field of view of the camera projection, in radians
+pub(crate) const FIELD_OF_VIEW: f32 = std::f32::consts::FRAC_PI_4;
 
 #[derive(Debug, PartialEq, Default)]
 pub struct UniformBufferObject {
@@ -36,10 +39,50 @@ impl UniformBufferObject {
 
         let aspect = extent.width as f32 / extent.height as f32;
 
-        self.proj = *Perspective3::new(aspect, 45.0_f32.to_radians(), 0.00001, 100.).as_matrix();
+        self.proj = *Perspective3::new(aspect, FIELD_OF_VIEW, 0.00001, 100.).as_matrix();
         self.proj[(1, 1)] *= -1.;
     }
 
+    pub fn view(&self) -> Matrix4<f32> {
+        self.view
+    }
+
+    /// World space position of the camera the view matrix was built from
+    pub fn camera_position(&self) -> Point3<f32> {
+        self.view
+            .try_inverse()
+            .map_or_else(Point3::origin, |inverse| {
+                inverse.transform_point(&Point3::origin())
+            })
+    }
+
+    /// World space direction the camera is looking in
+    pub fn camera_forward(&self) -> Vector3<f32> {
+        -Vector3::new(self.view[(2, 0)], self.view[(2, 1)], self.view[(2, 2)])
+    }
+
+    /// World space direction to the right of the view
+    pub fn camera_right(&self) -> Vector3<f32> {
+        Vector3::new(self.view[(0, 0)], self.view[(0, 1)], self.view[(0, 2)])
+    }
+
+    /// World space direction to the top of the view
+    pub fn camera_up(&self) -> Vector3<f32> {
+        Vector3::new(self.view[(1, 0)], self.view[(1, 1)], self.view[(1, 2)])
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.proj * self.view
+    }
+
+    /// Projection and rotation of the view, without its translation, so the skybox
+    /// stays centred on the camera
+    pub fn skybox_view_projection(&self) -> Matrix4<f32> {
+        let mut view = self.view;
+        view.fixed_view_mut::<3, 1>(0, 3).fill(0.);
+        self.proj * view
+    }
+
     pub const fn size() -> usize {
         std::mem::size_of::<Self>()
     }
@@ -63,7 +106,7 @@ pub(crate) fn update_uniform_buffers(
         uniform_buffer.model = object.model;
 
         unsafe {
-            let mapped_ptr = object.graphics_pipeline.uniform_buffers[current_image]
+            let mapped_ptr = object.descriptors.uniform_buffers[current_image]
                 .allocation
                 .mapped_ptr()
                 .unwrap()
@@ -79,47 +122,32 @@ pub(crate) fn update_uniform_buffers(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use wave_camera::camera::Camera;
-    use wave_space::space::Pos3;
 
-    #[test]
-    fn test_new_uniform_buffer_object() {
-        let extent = vk::Extent2D::default().height(1920).width(1080);
-        let camera = Camera::default().pos(Pos3::new(5., 6., 7.)).build();
-        // let ubo = UniformBufferObject::new(&extent, &camera);
-        //
-        // let expected_ubo = UniformBufferObject {
-        //     view: Matrix4::new(
-        //         0., 0., 1., -7., 0., 1., 0., -6., -1., -0., -0., 5., 0., 0., 0., 1.,
-        //     ),
-        //     proj: Matrix4::new(
-        //         4.291935, 0., 0., 0., 0., -2.4142134, 0., 0., 0., 0., -1.0000001, -2e-5, 0., 0.,
-        //         -1., 0.,
-        //     ),
-        // };
-        //
-        // assert_eq!(ubo, expected_ubo);
+    fn ubo_looking_from(eye: Point3<f32>, target: Point3<f32>) -> UniformBufferObject {
+        let extent = vk::Extent2D::default().height(1080).width(1920);
+        let mut ubo = UniformBufferObject::default();
+        ubo.update(&extent, Matrix4::look_at_rh(&eye, &target, &Vector3::y()));
+        ubo
     }
 
     #[test]
     fn test_ubo_update() {
-        let extent = vk::Extent2D::default().height(1920).width(1080);
-        let camera = Camera::default().pos(Pos3::new(5., 6., 7.)).build();
-        // let mut ubo = UniformBufferObject::new(&extent, &camera);
-        //
-        // camera.pos = Pos3::new(-5., -1., 3.);
-        // ubo.update(&extent, &camera);
-        //
-        // let expected_ubo = UniformBufferObject {
-        //     view: Matrix4::new(
-        //         0., 0., 1., -3., 0., 1., 0., 1., -1., -0., -0., -5., 0., 0., 0., 1.,
-        //     ),
-        //     proj: Matrix4::new(
-        //         4.291935, 0., 0., 0., 0., -2.4142134, 0., 0., 0., 0., -1.0000001, -2e-5, 0., 0.,
-        //         -1., 0.,
-        //     ),
-        // };
-        //
-        // assert_eq!(ubo, expected_ubo);
+        let eye = Point3::new(5., 6., 7.);
+        let ubo = ubo_looking_from(eye, Point3::origin());
+
+        assert!((ubo.camera_position() - eye).norm() < 1e-4);
+        assert!((ubo.camera_forward() + eye.coords.normalize()).norm() < 1e-4);
+        // Vulkan's clip space y points down
+        assert!(ubo.proj[(1, 1)] < 0.);
+    }
+
+    #[test]
+    fn test_skybox_ignores_camera_position() {
+        let offset = Vector3::new(-5., -1., 3.);
+        let ubo = ubo_looking_from(Point3::new(5., 6., 7.), Point3::origin());
+        let moved = ubo_looking_from(Point3::new(5., 6., 7.) + offset, Point3::origin() + offset);
+
+        assert!((ubo.skybox_view_projection() - moved.skybox_view_projection()).norm() < 1e-4);
+        assert!((ubo.view_projection() - moved.view_projection()).norm() > 1e-2);
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    debug::{self, vulkan_debug_callback, Debugger, ENABLE_VALIDATION_LAYERS, VALIDATION_LAYERS},
+    debug::{self, Debugger, MessageSink, ENABLE_VALIDATION_LAYERS, VALIDATION_LAYERS},
     device::Devices,
     memory,
 };
@@ -7,7 +7,7 @@ use ash::{
     vk::{self, EXT_DEBUG_UTILS_NAME},
     Device, Entry, Instance,
 };
-use std::ffi::CString;
+use std::{ffi::CString, sync::Arc};
 use winit::{raw_window_handle::HasDisplayHandle, window::Window};
 
 #[derive(Default, Debug, Clone)]
@@ -41,6 +41,8 @@ pub struct ImageInfo {
     tiling: vk::ImageTiling,
     usage: vk::ImageUsageFlags,
     properties: vk::MemoryPropertyFlags,
+    array_layers: u32,
+    flags: vk::ImageCreateFlags,
 }
 
 impl ImageInfo {
@@ -61,17 +63,33 @@ impl ImageInfo {
             tiling,
             usage,
             properties,
+            array_layers: 1,
+            flags: vk::ImageCreateFlags::empty(),
         }
     }
+
+    pub fn array_layers(mut self, array_layers: u32) -> Self {
+        self.array_layers = array_layers;
+        self
+    }
+
+    /// Six layer image that can be viewed as a cube map
+    pub fn cube(mut self) -> Self {
+        self.array_layers = 6;
+        self.flags |= vk::ImageCreateFlags::CUBE_COMPATIBLE;
+        self
+    }
 }
 
 pub struct EntryInstance {
     pub entry: Entry,
     pub instance: Instance,
+    /// Receives the messages of instance creation, kept for the debug messenger
+    pub(crate) sink: Option<Arc<MessageSink>>,
 }
 
 impl EntryInstance {
-    pub fn new(window: &Window, debugging: Option<Debugger>) -> Self {
+    pub fn new(window: &Window, debugging: Option<&Debugger>) -> Self {
         let layer_names = VALIDATION_LAYERS
             .iter()
             .map(|layer_name| CString::new(*layer_name).unwrap())
@@ -114,6 +132,7 @@ impl EntryInstance {
             }
 
             let mut debug_create_info: vk::DebugUtilsMessengerCreateInfoEXT;
+            let mut sink = None;
 
             let create_flags = if cfg!(any(target_os = "macos", target_os = "ios")) {
                 vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
@@ -128,10 +147,10 @@ impl EntryInstance {
 
             if ENABLE_VALIDATION_LAYERS {
                 if let Some(debugging) = debugging {
-                    debug_create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
-                        .message_severity(debugging.message_level.flags)
-                        .message_type(debugging.message_type.flags)
-                        .pfn_user_callback(Some(vulkan_debug_callback));
+                    let instance_sink = MessageSink::new(debugging);
+                    debug_create_info = debugging.create_info(&instance_sink);
+                    sink = Some(instance_sink);
+
                     create_info = create_info
                         .enabled_layer_names(&layers_names_raw)
                         .push_next(&mut debug_create_info);
@@ -142,7 +161,11 @@ impl EntryInstance {
                 .create_instance(&create_info, None)
                 .expect("Instance creation error");
 
-            Self { instance, entry }
+            Self {
+                instance,
+                entry,
+                sink,
+            }
         }
     }
 }
@@ -156,8 +179,9 @@ pub(crate) fn create_image(info: ImageInfo, instance: &Instance, devices: &Devic
                 .height(info.dimensions.1)
                 .depth(1),
         )
+        .flags(info.flags)
         .mip_levels(info.mip_levels)
-        .array_layers(1)
+        .array_layers(info.array_layers)
         .format(info.format)
         .tiling(info.tiling)
         .initial_layout(vk::ImageLayout::UNDEFINED)
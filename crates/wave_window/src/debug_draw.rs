@@ -0,0 +1,252 @@
+use nalgebra::{vector, Point3, Vector3};
+use std::{f32::consts::TAU, time::Duration};
+
+/// Segments in each circle of a wire sphere
+const SPHERE_SEGMENTS: usize = 24;
+
+/// Length of an arrow's head as a fraction of the arrow
+const ARROW_HEAD: f32 = 0.15;
+
+/// End of a debug line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineVertex {
+    pub pos: Point3<f32>,
+    pub colour: Vector3<f32>,
+}
+
+/// Lines queued by one [`DebugDraw`] call
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugShape {
+    /// Pairs of vertices, one pair per line
+    vertices: Vec<LineVertex>,
+    remaining: Duration,
+}
+
+impl DebugShape {
+    /// Keeps drawing the shape for `duration` instead of only the next frame.
+    /// `Duration::MAX` keeps it forever.
+    pub fn duration(&mut self, duration: Duration) -> &mut Self {
+        self.remaining = duration;
+        self
+    }
+}
+
+/// Lines, boxes, spheres and grids drawn on top of the scene without defining a geometry.
+///
+/// Shapes are queued each frame and drawn in one batch. A shape is drawn for the next frame
+/// only, unless it is given a [`duration`](DebugShape::duration).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DebugDraw {
+    shapes: Vec<DebugShape>,
+}
+
+impl DebugDraw {
+    pub fn line(
+        &mut self,
+        from: Point3<f32>,
+        to: Point3<f32>,
+        colour: Vector3<f32>,
+    ) -> &mut DebugShape {
+        self.shape(colour, [(from, to)])
+    }
+
+    /// Line from `from` to `to` with a head at `to`
+    pub fn arrow(
+        &mut self,
+        from: Point3<f32>,
+        to: Point3<f32>,
+        colour: Vector3<f32>,
+    ) -> &mut DebugShape {
+        let direction = to - from;
+        let length = direction.norm() * ARROW_HEAD;
+
+        let Some(forward) = direction.try_normalize(f32::EPSILON) else {
+            return self.line(from, to, colour);
+        };
+
+        // Any axis that is not parallel to the arrow gives the plane of the head
+        let other = if forward.x.abs() < 0.9 {
+            Vector3::x()
+        } else {
+            Vector3::y()
+        };
+        let side = forward.cross(&other).normalize() * length;
+        let back = to - forward * length;
+
+        self.shape(colour, [(from, to), (to, back + side), (to, back - side)])
+    }
+
+    /// Axis aligned box between the `min` and `max` corners
+    pub fn aabb(
+        &mut self,
+        min: Point3<f32>,
+        max: Point3<f32>,
+        colour: Vector3<f32>,
+    ) -> &mut DebugShape {
+        // Bit 0 of a corner selects the x of max, bit 1 its y and bit 2 its z
+        let corner = |index: usize| {
+            Point3::new(
+                if index & 1 == 0 { min.x } else { max.x },
+                if index & 2 == 0 { min.y } else { max.y },
+                if index & 4 == 0 { min.z } else { max.z },
+            )
+        };
+
+        // Every edge joins two corners differing in a single bit
+        let edges = (0..8).flat_map(|from| {
+            [1, 2, 4]
+                .into_iter()
+                .filter(move |bit| from & bit == 0)
+                .map(move |bit| (corner(from), corner(from | bit)))
+        });
+
+        self.shape(colour, edges)
+    }
+
+    /// Circles around `centre` in the three axis planes
+    pub fn sphere(
+        &mut self,
+        centre: Point3<f32>,
+        radius: f32,
+        colour: Vector3<f32>,
+    ) -> &mut DebugShape {
+        let point = |plane: usize, segment: usize| {
+            let angle = TAU * segment as f32 / SPHERE_SEGMENTS as f32;
+            let (sin, cos) = (angle.sin() * radius, angle.cos() * radius);
+            centre
+                + match plane {
+                    0 => vector![cos, sin, 0.],
+                    1 => vector![0., cos, sin],
+                    _ => vector![cos, 0., sin],
+                }
+        };
+
+        let lines = (0..3).flat_map(|plane| {
+            (0..SPHERE_SEGMENTS)
+                .map(move |segment| (point(plane, segment), point(plane, segment + 1)))
+        });
+
+        self.shape(colour, lines)
+    }
+
+    /// The x, y and z axes from `origin`, in red, green and blue
+    pub fn axes(&mut self, origin: Point3<f32>, length: f32) -> &mut DebugShape {
+        let vertices = [Vector3::x(), Vector3::y(), Vector3::z()]
+            .into_iter()
+            .flat_map(|axis| {
+                [
+                    LineVertex {
+                        pos: origin,
+                        colour: axis,
+                    },
+                    LineVertex {
+                        pos: origin + axis * length,
+                        colour: axis,
+                    },
+                ]
+            })
+            .collect();
+
+        self.push(vertices)
+    }
+
+    /// Square grid on the ground plane through `centre`, `cells` wide in each direction and
+    /// `size` across
+    pub fn grid(
+        &mut self,
+        centre: Point3<f32>,
+        size: f32,
+        cells: u32,
+        colour: Vector3<f32>,
+    ) -> &mut DebugShape {
+        let half = size / 2.;
+        let cells = cells.max(1);
+
+        let lines = (0..=cells).flat_map(|cell| {
+            let offset = size * cell as f32 / cells as f32 - half;
+            [
+                (
+                    centre + vector![offset, 0., -half],
+                    centre + vector![offset, 0., half],
+                ),
+                (
+                    centre + vector![-half, 0., offset],
+                    centre + vector![half, 0., offset],
+                ),
+            ]
+        });
+
+        self.shape(colour, lines)
+    }
+
+    /// Ends of every queued line, in pairs
+    #[must_use]
+    pub fn vertices(&self) -> Vec<LineVertex> {
+        self.shapes
+            .iter()
+            .flat_map(|shape| shape.vertices.iter().copied())
+            .collect()
+    }
+
+    /// Removes the shapes whose duration has run out after a frame of `delta`
+    pub fn end_frame(&mut self, delta: Duration) {
+        self.shapes.retain_mut(|shape| {
+            shape.remaining = shape.remaining.saturating_sub(delta);
+            !shape.remaining.is_zero()
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+    }
+
+    fn shape(
+        &mut self,
+        colour: Vector3<f32>,
+        lines: impl IntoIterator<Item = (Point3<f32>, Point3<f32>)>,
+    ) -> &mut DebugShape {
+        let vertices = lines
+            .into_iter()
+            .flat_map(|(from, to)| {
+                [
+                    LineVertex { pos: from, colour },
+                    LineVertex { pos: to, colour },
+                ]
+            })
+            .collect();
+
+        self.push(vertices)
+    }
+
+    fn push(&mut self, vertices: Vec<LineVertex>) -> &mut DebugShape {
+        self.shapes.push(DebugShape {
+            vertices,
+            remaining: Duration::ZERO,
+        });
+        self.shapes.last_mut().expect("A shape was just pushed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shapes_last_for_their_duration() {
+        let mut draw = DebugDraw::default();
+        let white = vector![1., 1., 1.];
+
+        draw.aabb(Point3::origin(), Point3::new(1., 2., 3.), white);
+        draw.line(Point3::origin(), Point3::new(0., 1., 0.), white)
+            .duration(Duration::from_millis(30));
+
+        // Twelve edges of the box and the line
+        assert_eq!(draw.vertices().len(), 26);
+
+        draw.end_frame(Duration::from_millis(16));
+        assert_eq!(draw.vertices().len(), 2);
+
+        draw.end_frame(Duration::from_millis(16));
+        assert!(draw.vertices().is_empty());
+    }
+}
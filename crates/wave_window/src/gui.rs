@@ -0,0 +1,349 @@
+use egui::{epaint::Primitive, ClippedPrimitive, ImageData, TexturesDelta, ViewportId};
+use nalgebra::{Point2, Vector4};
+use std::collections::HashMap;
+use winit::{event::WindowEvent, window::Window};
+
+pub use egui::TextureId;
+
+/// Corner of a gui triangle, in pixels from the top left of the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GuiVertex {
+    pub pos: Point2<f32>,
+    pub uv: Point2<f32>,
+    /// sRGB colour with premultiplied alpha
+    pub colour: [u8; 4],
+}
+
+impl GuiVertex {
+    /// The colour as normalised sRGB values, for targets that store colours as they are
+    #[must_use]
+    pub fn srgb(&self) -> Vector4<f32> {
+        Vector4::from(self.colour.map(|channel| f32::from(channel) / 255.))
+    }
+
+    /// The colour with linear rgb, for targets that encode sRGB when written
+    #[must_use]
+    pub fn linear(&self) -> Vector4<f32> {
+        let [r, g, b, a] = self.colour.map(|channel| f32::from(channel) / 255.);
+        let linear = |channel: f32| {
+            if channel <= 0.04045 {
+                channel / 12.92
+            } else {
+                ((channel + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Vector4::new(linear(r), linear(g), linear(b), a)
+    }
+}
+
+/// Pixels of the window a draw may cover
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GuiClip {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Indexed triangles sampling one texture
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GuiDraw {
+    pub texture: TextureId,
+    pub clip: GuiClip,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub vertex_offset: i32,
+}
+
+/// RGBA8 texture of premultiplied sRGB texels
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuiTexture {
+    pub size: [usize; 2],
+    pub pixels: Vec<u8>,
+    /// Changes whenever the texels do, so renderers know to upload the texture again
+    pub generation: u64,
+}
+
+/// Meshes and textures of the latest gui pass, ready for a renderer
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GuiFrame {
+    vertices: Vec<GuiVertex>,
+    indices: Vec<u32>,
+    draws: Vec<GuiDraw>,
+    textures: HashMap<TextureId, GuiTexture>,
+    /// Textures egui freed, removed once the frame that might still use them was drawn
+    freed: Vec<TextureId>,
+    generation: u64,
+}
+
+impl GuiFrame {
+    #[must_use]
+    pub fn vertices(&self) -> &[GuiVertex] {
+        &self.vertices
+    }
+
+    #[must_use]
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    #[must_use]
+    pub fn draws(&self) -> &[GuiDraw] {
+        &self.draws
+    }
+
+    /// Every texture the draws may sample
+    #[must_use]
+    pub fn textures(&self) -> &HashMap<TextureId, GuiTexture> {
+        &self.textures
+    }
+
+    /// Replaces the meshes with `primitives` and applies the texture changes of a pass.
+    ///
+    /// Positions are scaled from egui points into pixels of a window `screen` pixels wide
+    /// and high.
+    pub fn update(
+        &mut self,
+        primitives: &[ClippedPrimitive],
+        textures: &TexturesDelta,
+        pixels_per_point: f32,
+        screen: [u32; 2],
+    ) {
+        self.freed.drain(..).for_each(|id| {
+            self.textures.remove(&id);
+        });
+
+        textures.set.iter().for_each(|(id, delta)| {
+            let ImageData::Color(image) = &delta.image;
+            let pixels = image.pixels.iter().flat_map(|colour| colour.to_array());
+            self.generation += 1;
+
+            match delta.pos {
+                Some([x, y]) => {
+                    let Some(texture) = self.textures.get_mut(id) else {
+                        log::warn!("Patch for unknown gui texture {id:?}");
+                        return;
+                    };
+
+                    let [width, _] = image.size;
+                    let rows = pixels.collect::<Vec<u8>>();
+                    rows.chunks_exact(width * 4)
+                        .enumerate()
+                        .for_each(|(row, texels)| {
+                            let start = ((y + row) * texture.size[0] + x) * 4;
+                            texture.pixels[start..start + texels.len()].copy_from_slice(texels);
+                        });
+                    texture.generation = self.generation;
+                }
+                None => {
+                    self.textures.insert(
+                        *id,
+                        GuiTexture {
+                            size: image.size,
+                            pixels: pixels.collect(),
+                            generation: self.generation,
+                        },
+                    );
+                }
+            }
+        });
+
+        self.freed.extend_from_slice(&textures.free);
+
+        self.vertices.clear();
+        self.indices.clear();
+        self.draws.clear();
+
+        primitives.iter().for_each(|primitive| {
+            let Primitive::Mesh(mesh) = &primitive.primitive else {
+                return;
+            };
+
+            let rect = primitive.clip_rect * pixels_per_point;
+            let (min_x, min_y) = (
+                (rect.min.x.max(0.) as u32).min(screen[0]),
+                (rect.min.y.max(0.) as u32).min(screen[1]),
+            );
+            let (max_x, max_y) = (
+                (rect.max.x.ceil().max(0.) as u32).min(screen[0]),
+                (rect.max.y.ceil().max(0.) as u32).min(screen[1]),
+            );
+
+            if mesh.indices.is_empty() || max_x <= min_x || max_y <= min_y {
+                return;
+            }
+
+            self.draws.push(GuiDraw {
+                texture: mesh.texture_id,
+                clip: GuiClip {
+                    x: min_x,
+                    y: min_y,
+                    width: max_x - min_x,
+                    height: max_y - min_y,
+                },
+                first_index: self.indices.len() as u32,
+                index_count: mesh.indices.len() as u32,
+                vertex_offset: self.vertices.len() as i32,
+            });
+
+            self.indices.extend_from_slice(&mesh.indices);
+            self.vertices
+                .extend(mesh.vertices.iter().map(|vertex| GuiVertex {
+                    pos: Point2::new(
+                        vertex.pos.x * pixels_per_point,
+                        vertex.pos.y * pixels_per_point,
+                    ),
+                    uv: Point2::new(vertex.uv.x, vertex.uv.y),
+                    colour: vertex.color.to_array(),
+                }));
+        });
+    }
+}
+
+/// egui integration: feeds it window events, runs the user interface each frame and keeps
+/// the tessellated result for the renderer.
+#[derive(Default)]
+pub struct Gui {
+    context: egui::Context,
+    /// Created with the first window the gui sees
+    state: Option<egui_winit::State>,
+    frame: GuiFrame,
+}
+
+impl Gui {
+    #[must_use]
+    pub fn context(&self) -> &egui::Context {
+        &self.context
+    }
+
+    #[must_use]
+    pub fn frame(&self) -> &GuiFrame {
+        &self.frame
+    }
+
+    /// Passes a window event to egui, returning whether egui consumed it
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state(window).on_window_event(window, event).consumed
+    }
+
+    /// Runs `ui` for a pass of the gui and tessellates what it drew into [`Gui::frame`]
+    pub fn run(&mut self, window: &Window, ui: impl FnMut(&egui::Context)) {
+        let input = self.state(window).take_egui_input(window);
+        let output = self.context.run(input, ui);
+
+        self.state(window)
+            .handle_platform_output(window, output.platform_output);
+
+        let primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        let size = window.inner_size();
+
+        self.frame.update(
+            &primitives,
+            &output.textures_delta,
+            output.pixels_per_point,
+            [size.width, size.height],
+        );
+    }
+
+    /// Whether the pointer is over a panel or dragging something in it, so it should not
+    /// move the camera
+    #[must_use]
+    pub fn wants_pointer(&self) -> bool {
+        self.context.is_pointer_over_area() || self.context.wants_pointer_input()
+    }
+
+    fn state(&mut self, window: &Window) -> &mut egui_winit::State {
+        self.state.get_or_insert_with(|| {
+            egui_winit::State::new(
+                self.context.clone(),
+                ViewportId::ROOT,
+                window,
+                Some(window.scale_factor() as f32),
+                window.theme(),
+                None,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::{
+        epaint::{ImageDelta, Mesh, Vertex},
+        pos2, Color32, ColorImage, Rect, TextureOptions,
+    };
+
+    #[test]
+    fn test_texture_patches_are_applied_and_freed_a_frame_later() {
+        let id = TextureId::Managed(0);
+        let mut frame = GuiFrame::default();
+
+        let full = ImageDelta::full(
+            ColorImage::filled([4, 2], Color32::BLACK),
+            TextureOptions::LINEAR,
+        );
+        let mut textures = TexturesDelta::default();
+        textures.set.push((id, full));
+        frame.update(&[], &textures, 1., [100, 100]);
+
+        let patch = ImageDelta::partial(
+            [2, 1],
+            ColorImage::filled([2, 1], Color32::WHITE),
+            TextureOptions::LINEAR,
+        );
+        let textures = TexturesDelta {
+            set: vec![(id, patch)],
+            free: vec![id],
+        };
+        frame.update(&[], &textures, 1., [100, 100]);
+
+        let texture = &frame.textures()[&id];
+        assert_eq!(texture.generation, 2);
+        assert_eq!(&texture.pixels[16..24], &[0, 0, 0, 255, 0, 0, 0, 255]);
+        assert_eq!(&texture.pixels[24..32], &[255; 8]);
+
+        frame.update(&[], &TexturesDelta::default(), 1., [100, 100]);
+        assert!(frame.textures().is_empty());
+    }
+
+    #[test]
+    fn test_meshes_are_scaled_into_pixels_and_clipped_to_the_window() {
+        let mut mesh = Mesh::default();
+        mesh.vertices.push(Vertex {
+            pos: pos2(10., 20.),
+            uv: pos2(0.5, 0.5),
+            color: Color32::WHITE,
+        });
+        mesh.indices.extend([0, 0, 0]);
+
+        let primitive = ClippedPrimitive {
+            clip_rect: Rect::from_min_max(pos2(-5., 10.), pos2(80., 40.)),
+            primitive: Primitive::Mesh(mesh),
+        };
+
+        let mut frame = GuiFrame::default();
+        frame.update(
+            &[primitive.clone(), primitive],
+            &TexturesDelta::default(),
+            2.,
+            [100, 100],
+        );
+
+        assert_eq!(frame.vertices()[0].pos, Point2::new(20., 40.));
+        assert_eq!(
+            frame.draws()[0].clip,
+            GuiClip {
+                x: 0,
+                y: 20,
+                width: 100,
+                height: 60,
+            }
+        );
+        assert_eq!(frame.draws()[1].first_index, 3);
+        assert_eq!(frame.draws()[1].vertex_offset, 1);
+        assert_eq!(frame.vertices()[0].linear(), Vector4::new(1., 1., 1., 1.));
+    }
+}
@@ -4,10 +4,27 @@ use winit::{
     window::Window,
 };
 
+pub mod debug_draw;
+pub mod gui;
+pub mod particles;
+pub mod settings;
+pub mod sprite;
+pub mod stats;
+pub mod text;
+pub mod tilemap;
 pub mod window;
 
+pub use egui;
+
 pub mod prelude {
-    pub use crate::window::{Display, Resolution};
+    pub use crate::{
+        debug_draw::DebugDraw,
+        egui,
+        particles::ParticleBlend,
+        sprite::{Animation, Camera2d, Sprite, SpriteSheet, SpriteTextureId, Sprites, UvRect},
+        text::{Align, Font, FontId, Text, TextStyle},
+        window::{DebugView, Display, Resolution},
+    };
 }
 
 /// Create a Vulkan surface from a window.
@@ -0,0 +1,71 @@
+use crate::sprite::SpriteTextureId;
+use nalgebra::{Point3, Vector4};
+
+/// How particles are combined with what is behind them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ParticleBlend {
+    /// Covers what is behind by the particle's alpha, for smoke and dust
+    #[default]
+    AlphaBlend,
+    /// Adds the particle's colour, for fire and sparks
+    Additive,
+}
+
+/// Live particle as drawn: a square facing the camera
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParticleInstance {
+    pub position: Point3<f32>,
+    /// Width and height in world units
+    pub size: f32,
+    /// Multiplies the texture's colour
+    pub colour: Vector4<f32>,
+}
+
+/// Particles of one emitter, as a range of [`ParticleBatches::instances`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParticleBatch {
+    pub texture: SpriteTextureId,
+    pub blend: ParticleBlend,
+    pub first: u32,
+    pub count: u32,
+}
+
+/// Every live particle, drawn as one instanced quad per particle and one draw call per batch
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParticleBatches {
+    pub instances: Vec<ParticleInstance>,
+    pub batches: Vec<ParticleBatch>,
+}
+
+impl ParticleBatches {
+    /// Appends `instances` as a batch, unless there are none
+    pub fn push(
+        &mut self,
+        texture: SpriteTextureId,
+        blend: ParticleBlend,
+        instances: impl IntoIterator<Item = ParticleInstance>,
+    ) {
+        let first = self.instances.len() as u32;
+        self.instances.extend(instances);
+        let count = self.instances.len() as u32 - first;
+
+        if count > 0 {
+            self.batches.push(ParticleBatch {
+                texture,
+                blend,
+                first,
+                count,
+            });
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.batches.clear();
+    }
+}
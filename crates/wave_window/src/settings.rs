@@ -0,0 +1,136 @@
+use ash::vk;
+
+/// Multisample anti-aliasing of the scene. Requests above what the device supports fall
+/// back to the highest supported count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Msaa {
+    Off,
+    X2,
+    #[default]
+    X4,
+    X8,
+    X16,
+}
+
+impl Msaa {
+    /// Highest count in `supported` that does not exceed the requested one
+    #[must_use]
+    pub fn sample_count(self, supported: vk::SampleCountFlags) -> vk::SampleCountFlags {
+        let requested = match self {
+            Self::Off => vk::SampleCountFlags::TYPE_1,
+            Self::X2 => vk::SampleCountFlags::TYPE_2,
+            Self::X4 => vk::SampleCountFlags::TYPE_4,
+            Self::X8 => vk::SampleCountFlags::TYPE_8,
+            Self::X16 => vk::SampleCountFlags::TYPE_16,
+        };
+
+        [
+            vk::SampleCountFlags::TYPE_16,
+            vk::SampleCountFlags::TYPE_8,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_2,
+        ]
+        .into_iter()
+        .filter(|count| count.as_raw() <= requested.as_raw())
+        .find(|count| supported.contains(*count))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+    }
+}
+
+/// Quality settings trading image quality for speed.
+///
+/// Changing them on a running renderer rebuilds the affected resources before the next
+/// frame, the same way a resize does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderSettings {
+    pub msaa: Msaa,
+    /// Maximum anisotropic filtering applied to textures, where 1 turns it off. Clamped to
+    /// the device limit.
+    pub anisotropy: f32,
+    /// Size of the scene relative to the window. Values below 1 render fewer pixels and
+    /// upscale, values above 1 supersample.
+    pub render_scale: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            msaa: Msaa::default(),
+            anisotropy: 16.,
+            render_scale: 1.,
+        }
+    }
+}
+
+impl RenderSettings {
+    #[must_use]
+    pub fn msaa(mut self, msaa: Msaa) -> Self {
+        self.msaa = msaa;
+        self
+    }
+
+    #[must_use]
+    pub fn anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    #[must_use]
+    pub fn render_scale(mut self, render_scale: f32) -> Self {
+        self.render_scale = render_scale;
+        self
+    }
+
+    #[must_use]
+    pub fn anisotropy_level(&self, max_anisotropy: f32) -> f32 {
+        self.anisotropy.clamp(1., max_anisotropy.max(1.))
+    }
+
+    /// Size of the scene targets for a window of `extent`
+    pub fn render_extent(&self, extent: vk::Extent2D) -> vk::Extent2D {
+        let scale = |size: u32| ((size as f32 * self.render_scale).round() as u32).max(1);
+
+        vk::Extent2D {
+            width: scale(extent.width),
+            height: scale(extent.height),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msaa_falls_back_to_supported_count() {
+        let supported = vk::SampleCountFlags::TYPE_1
+            | vk::SampleCountFlags::TYPE_2
+            | vk::SampleCountFlags::TYPE_4;
+
+        // Vulkan types only implement Debug with ash's debug feature, so they are compared
+        // by their raw values
+        let count = |msaa: Msaa| msaa.sample_count(supported).as_raw();
+
+        assert_eq!(count(Msaa::Off), vk::SampleCountFlags::TYPE_1.as_raw());
+        assert_eq!(count(Msaa::X2), vk::SampleCountFlags::TYPE_2.as_raw());
+        assert_eq!(count(Msaa::X16), vk::SampleCountFlags::TYPE_4.as_raw());
+    }
+
+    #[test]
+    fn test_render_extent_scales_and_never_reaches_zero() {
+        let extent = vk::Extent2D {
+            width: 1920,
+            height: 1080,
+        };
+
+        let size = |render_scale: f32| {
+            let scaled = RenderSettings::default()
+                .render_scale(render_scale)
+                .render_extent(extent);
+            (scaled.width, scaled.height)
+        };
+
+        assert_eq!(size(0.5), (960, 540));
+        assert_eq!(size(0.), (1, 1));
+    }
+}
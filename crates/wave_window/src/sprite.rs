@@ -0,0 +1,445 @@
+use crate::text::TextVertex;
+use nalgebra::{vector, Matrix4, Point2, Rotation2, Vector2, Vector4};
+use std::{path::Path, time::Duration};
+
+/// Sprites share the vertex layout of text, as both are drawn with the ui shaders
+pub type SpriteVertex = TextVertex;
+
+/// Texture added with [`Sprites::add_texture`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpriteTextureId(usize);
+
+impl SpriteTextureId {
+    /// Position of the texture in [`Sprites::textures`]
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Part of a texture, in texture coordinates from its top left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvRect {
+    pub min: Point2<f32>,
+    pub max: Point2<f32>,
+}
+
+impl Default for UvRect {
+    fn default() -> Self {
+        Self {
+            min: Point2::origin(),
+            max: Point2::new(1., 1.),
+        }
+    }
+}
+
+/// Texture divided into a grid of equally sized frames, numbered row by row from the top left
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteSheet {
+    pub texture: SpriteTextureId,
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl SpriteSheet {
+    #[must_use]
+    pub fn new(texture: SpriteTextureId, columns: u32, rows: u32) -> Self {
+        Self {
+            texture,
+            columns: columns.max(1),
+            rows: rows.max(1),
+        }
+    }
+
+    #[must_use]
+    pub fn frame_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    /// Region of frame `index`, wrapping around past the last frame
+    #[must_use]
+    pub fn frame(&self, index: u32) -> UvRect {
+        let index = index % self.frame_count();
+        let size = Vector2::new(1. / self.columns as f32, 1. / self.rows as f32);
+        let min = Point2::new(
+            (index % self.columns) as f32 * size.x,
+            (index / self.columns) as f32 * size.y,
+        );
+
+        UvRect {
+            min,
+            max: min + size,
+        }
+    }
+}
+
+/// Frames of a sprite sheet played one after another
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Animation {
+    pub first: u32,
+    pub count: u32,
+    pub fps: f32,
+    /// Starts over after the last frame instead of holding it
+    pub looping: bool,
+    elapsed: Duration,
+}
+
+impl Animation {
+    #[must_use]
+    pub fn new(first: u32, count: u32, fps: f32) -> Self {
+        Self {
+            first,
+            count: count.max(1),
+            fps,
+            looping: true,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    #[must_use]
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        self.elapsed += delta;
+    }
+
+    pub fn restart(&mut self) {
+        self.elapsed = Duration::ZERO;
+    }
+
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.played() >= self.count
+    }
+
+    /// Sheet frame to show after the time the animation was updated by
+    #[must_use]
+    pub fn frame(&self) -> u32 {
+        let played = self.played();
+
+        let offset = if self.looping {
+            played % self.count
+        } else {
+            played.min(self.count - 1)
+        };
+
+        self.first + offset
+    }
+
+    fn played(&self) -> u32 {
+        (self.elapsed.as_secs_f32() * self.fps) as u32
+    }
+}
+
+/// Textured quad in the 2D world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    pub texture: SpriteTextureId,
+    pub position: Point2<f32>,
+    /// Counter-clockwise rotation in radians around the anchor
+    pub rotation: f32,
+    pub scale: Vector2<f32>,
+    /// Width and height in world units before scaling
+    pub size: Vector2<f32>,
+    /// Multiplies the texture's colour
+    pub tint: Vector4<f32>,
+    /// Point of the sprite placed at its position, from (0, 0) at the bottom left to (1, 1)
+    /// at the top right
+    pub anchor: Point2<f32>,
+    pub region: UvRect,
+    /// Sprites with a higher z are drawn over those with a lower one
+    pub z: i32,
+}
+
+impl Sprite {
+    #[must_use]
+    pub fn new(texture: SpriteTextureId, size: Vector2<f32>) -> Self {
+        Self {
+            texture,
+            position: Point2::origin(),
+            rotation: 0.,
+            scale: vector![1., 1.],
+            size,
+            tint: vector![1., 1., 1., 1.],
+            anchor: Point2::new(0.5, 0.5),
+            region: UvRect::default(),
+            z: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn position(mut self, position: Point2<f32>) -> Self {
+        self.position = position;
+        self
+    }
+
+    #[must_use]
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    #[must_use]
+    pub fn scale(mut self, scale: Vector2<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    #[must_use]
+    pub fn tint(mut self, tint: Vector4<f32>) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    #[must_use]
+    pub fn anchor(mut self, anchor: Point2<f32>) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    #[must_use]
+    pub fn region(mut self, region: UvRect) -> Self {
+        self.region = region;
+        self
+    }
+
+    /// Shows frame `index` of `sheet`, which also sets the texture
+    #[must_use]
+    pub fn frame(mut self, sheet: &SpriteSheet, index: u32) -> Self {
+        self.texture = sheet.texture;
+        self.region = sheet.frame(index);
+        self
+    }
+
+    #[must_use]
+    pub fn z(mut self, z: i32) -> Self {
+        self.z = z;
+        self
+    }
+
+    /// Corners in world space, counter-clockwise from the bottom left
+    fn corners(&self) -> [Point2<f32>; 4] {
+        let rotation = Rotation2::new(self.rotation);
+        let extent = self.size.component_mul(&self.scale);
+
+        [(0., 0.), (1., 0.), (1., 1.), (0., 1.)].map(|(x, y)| {
+            let local = (Vector2::new(x, y) - self.anchor.coords).component_mul(&extent);
+            self.position + rotation * local
+        })
+    }
+}
+
+/// View of the 2D world, which shows one world unit per pixel at a zoom of one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera2d {
+    pub centre: Point2<f32>,
+    pub zoom: f32,
+}
+
+impl Default for Camera2d {
+    fn default() -> Self {
+        Self {
+            centre: Point2::origin(),
+            zoom: 1.,
+        }
+    }
+}
+
+impl Camera2d {
+    /// Orthographic projection onto a window `width` by `height` pixels, with y pointing up
+    #[must_use]
+    pub fn projection(&self, width: f32, height: f32) -> Matrix4<f32> {
+        let half = vector![width, height] / (2. * self.zoom);
+
+        // Clip space y points down, so the top of the view is given as its bottom
+        Matrix4::new_orthographic(
+            self.centre.x - half.x,
+            self.centre.x + half.x,
+            self.centre.y + half.y,
+            self.centre.y - half.y,
+            -1.,
+            1.,
+        )
+    }
+}
+
+/// Sprites of one texture drawn together, as a range of [`SpriteBatches::vertices`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteBatch {
+    pub texture: SpriteTextureId,
+    pub first: u32,
+    pub count: u32,
+}
+
+/// Quads of every queued sprite in drawing order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpriteBatches {
+    pub vertices: Vec<SpriteVertex>,
+    pub batches: Vec<SpriteBatch>,
+}
+
+/// Sprite textures, the camera and the sprites queued for the next frame.
+///
+/// Sprites are sorted by z and then grouped by texture, so each run of sprites sharing a
+/// texture is a single draw call.
+#[derive(Clone, Debug, Default)]
+pub struct Sprites {
+    textures: Vec<Vec<u8>>,
+    sprites: Vec<Sprite>,
+    pub camera: Camera2d,
+}
+
+impl Sprites {
+    /// Adds an encoded image, such as a PNG sprite sheet, for sprites to sample
+    pub fn add_texture_bytes(&mut self, bytes: Vec<u8>) -> SpriteTextureId {
+        self.textures.push(bytes);
+        SpriteTextureId(self.textures.len() - 1)
+    }
+
+    pub fn add_texture(&mut self, path: impl AsRef<Path>) -> SpriteTextureId {
+        self.add_texture_bytes(
+            std::fs::read(path).expect("Failed to read contents of sprite texture file"),
+        )
+    }
+
+    /// Encoded images of every texture, in the order they were added
+    #[must_use]
+    pub fn textures(&self) -> &[Vec<u8>] {
+        &self.textures
+    }
+
+    /// Draws `sprite` for the next frame
+    pub fn draw(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// Removes the queued sprites, keeping the textures and camera
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    /// Quads of the queued sprites as triangle lists, batched by texture within each z
+    #[must_use]
+    pub fn batches(&self) -> SpriteBatches {
+        let mut order = self.sprites.iter().collect::<Vec<_>>();
+        order.sort_by_key(|sprite| (sprite.z, sprite.texture));
+
+        let mut batches = SpriteBatches::default();
+
+        order.into_iter().for_each(|sprite| {
+            let [bottom_left, bottom_right, top_right, top_left] = sprite.corners();
+            let UvRect { min, max } = sprite.region;
+            let vertex = |pos: Point2<f32>, u: f32, v: f32| SpriteVertex {
+                pos,
+                uv: Point2::new(u, v),
+                colour: sprite.tint,
+            };
+
+            let first = batches.vertices.len() as u32;
+            batches.vertices.extend([
+                vertex(bottom_left, min.x, max.y),
+                vertex(bottom_right, max.x, max.y),
+                vertex(top_right, max.x, min.y),
+                vertex(bottom_left, min.x, max.y),
+                vertex(top_right, max.x, min.y),
+                vertex(top_left, min.x, min.y),
+            ]);
+
+            match batches.batches.last_mut() {
+                Some(batch) if batch.texture == sprite.texture => batch.count += 6,
+                _ => batches.batches.push(SpriteBatch {
+                    texture: sprite.texture,
+                    first,
+                    count: 6,
+                }),
+            }
+        });
+
+        batches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point3;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_sprites_are_batched_by_texture_within_each_z() {
+        let mut sprites = Sprites::default();
+        let (a, b) = (
+            sprites.add_texture_bytes(Vec::new()),
+            sprites.add_texture_bytes(Vec::new()),
+        );
+        let size = vector![1., 1.];
+
+        sprites.draw(Sprite::new(b, size));
+        sprites.draw(Sprite::new(a, size).z(1));
+        sprites.draw(Sprite::new(a, size));
+        sprites.draw(Sprite::new(b, size));
+
+        let batches = sprites.batches();
+        assert_eq!(batches.vertices.len(), 24);
+        assert_eq!(
+            batches.batches,
+            [
+                SpriteBatch {
+                    texture: a,
+                    first: 0,
+                    count: 6,
+                },
+                SpriteBatch {
+                    texture: b,
+                    first: 6,
+                    count: 12,
+                },
+                SpriteBatch {
+                    texture: a,
+                    first: 18,
+                    count: 6,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sprites_rotate_around_their_anchor_and_sheets_animate() {
+        let sheet = SpriteSheet::new(SpriteTextureId(0), 4, 2);
+        let sprite = Sprite::new(sheet.texture, vector![2., 1.])
+            .position(Point2::new(10., 10.))
+            .anchor(Point2::origin())
+            .rotation(FRAC_PI_2)
+            .frame(&sheet, 5);
+
+        let [_, bottom_right, ..] = sprite.corners();
+        assert!((bottom_right - Point2::new(10., 12.)).norm() < 1e-5);
+        assert_eq!(
+            sprite.region,
+            UvRect {
+                min: Point2::new(0.25, 0.5),
+                max: Point2::new(0.5, 1.),
+            }
+        );
+
+        let mut animation = Animation::new(4, 3, 10.).looping(false);
+        animation.update(Duration::from_millis(250));
+        assert_eq!(animation.frame(), 6);
+        assert!(!animation.is_finished());
+        animation.update(Duration::from_millis(100));
+        assert_eq!(animation.frame(), 6);
+        assert!(animation.is_finished());
+
+        let projection = Camera2d::default().projection(200., 100.);
+        let corner = projection.transform_point(&Point3::new(100., 50., 0.));
+        assert!((corner.x - 1.).abs() < 1e-6 && (corner.y + 1.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fmt::Write, time::Duration};
+
+/// Named stretch of time within a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub name: &'static str,
+    /// Offset from the start of the frame on the same clock
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+impl Span {
+    #[must_use]
+    pub const fn new(name: &'static str, start: Duration, duration: Duration) -> Self {
+        Self {
+            name,
+            start,
+            duration,
+        }
+    }
+
+    #[must_use]
+    pub fn end(&self) -> Duration {
+        self.start + self.duration
+    }
+}
+
+/// Timings and counters of one rendered frame.
+///
+/// GPU timings arrive once the frame has finished on the device, so the latest stats describe
+/// a frame a little behind the one being recorded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Number of the frame, counted from the first one rendered
+    pub frame: u64,
+    /// When the frame started, relative to when the renderer was created
+    pub started: Duration,
+    /// Time spent on the CPU updating, recording, submitting and presenting
+    pub cpu: Vec<Span>,
+    /// Time the GPU spent in each pass and draw group, empty when the device cannot time them
+    pub gpu: Vec<Span>,
+    pub draw_calls: u32,
+    pub triangles: u64,
+}
+
+impl FrameStats {
+    #[must_use]
+    pub fn cpu_time(&self) -> Duration {
+        self.cpu.iter().map(|span| span.duration).sum()
+    }
+
+    /// Time from the first to the last GPU timestamp of the frame
+    #[must_use]
+    pub fn gpu_time(&self) -> Duration {
+        self.gpu.iter().map(Span::end).max().unwrap_or_default()
+    }
+}
+
+/// Formats frames in the Chrome trace event format, which `chrome://tracing`, Perfetto and
+/// Speedscope open.
+///
+/// CPU and GPU spans go on separate tracks. The GPU track is aligned to the start of each
+/// frame, as the two clocks are not synchronised.
+#[must_use]
+pub fn chrome_trace<'a>(frames: impl IntoIterator<Item = &'a FrameStats>) -> String {
+    let mut events = Vec::new();
+
+    for stats in frames {
+        let tracks = [("CPU", 0, &stats.cpu), ("GPU", 1, &stats.gpu)];
+
+        for (category, thread, spans) in tracks {
+            for span in spans {
+                let mut event = String::new();
+                write!(
+                    event,
+                    r#"{{"name":"{}","cat":"{category}","ph":"X","ts":{},"dur":{},"pid":0,"tid":{thread},"args":{{"frame":{}}}}}"#,
+                    span.name.escape_default(),
+                    micros(stats.started + span.start),
+                    micros(span.duration),
+                    stats.frame,
+                )
+                .expect("Writing to a string cannot fail");
+                events.push(event);
+            }
+        }
+    }
+
+    let names = [(0, "CPU"), (1, "GPU")].map(|(thread, name)| {
+        format!(
+            r#"{{"name":"thread_name","ph":"M","pid":0,"tid":{thread},"args":{{"name":"{name}"}}}}"#
+        )
+    });
+
+    format!(
+        r#"{{"traceEvents":[{}]}}"#,
+        names
+            .into_iter()
+            .chain(events)
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+fn micros(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1_000_000.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chrome_trace_places_spans_at_frame_offsets() {
+        let stats = FrameStats {
+            frame: 3,
+            started: Duration::from_millis(100),
+            cpu: vec![Span::new(
+                "record",
+                Duration::from_millis(1),
+                Duration::from_micros(250),
+            )],
+            gpu: vec![
+                Span::new("shadows", Duration::ZERO, Duration::from_millis(2)),
+                Span::new("opaque", Duration::from_millis(2), Duration::from_millis(3)),
+            ],
+            draw_calls: 4,
+            triangles: 12,
+        };
+
+        assert_eq!(stats.cpu_time(), Duration::from_micros(250));
+        assert_eq!(stats.gpu_time(), Duration::from_millis(5));
+
+        let trace = chrome_trace([&stats]);
+
+        assert!(trace.starts_with(r#"{"traceEvents":["#));
+        assert!(trace.contains(
+            r#"{"name":"record","cat":"CPU","ph":"X","ts":101000.000,"dur":250.000,"pid":0,"tid":0,"args":{"frame":3}}"#
+        ));
+        assert!(trace.contains(r#""name":"opaque","cat":"GPU","ph":"X","ts":102000.000"#));
+    }
+}
@@ -0,0 +1,533 @@
+use ab_glyph::{point, Font as _, FontVec, GlyphId, InvalidFont, PxScale, ScaleFont};
+use nalgebra::{vector, Matrix4, Point2, Vector2, Vector4};
+use std::{collections::HashMap, fs, io, path::Path};
+
+/// Width and height of the glyph atlas in texels
+pub const ATLAS_SIZE: u32 = 1024;
+
+/// Empty texels around each glyph, so filtering does not bleed into its neighbours
+const ATLAS_PADDING: u32 = 1;
+
+/// TrueType or OpenType font
+#[derive(Debug)]
+pub struct Font {
+    font: FontVec,
+}
+
+impl Font {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, InvalidFont> {
+        FontVec::try_from_vec(bytes).map(|font| Self { font })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(fs::read(path)?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+/// Font added to [`Text`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Centre,
+    Right,
+}
+
+impl Align {
+    fn factor(self) -> f32 {
+        match self {
+            Self::Left => 0.,
+            Self::Centre => 0.5,
+            Self::Right => 1.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    /// Height of a line in pixels
+    pub size: f32,
+    pub colour: Vector4<f32>,
+    /// Width lines are wrapped at between words
+    pub max_width: Option<f32>,
+    /// Alignment of each line within the widest one, or within the maximum width
+    pub align: Align,
+    /// Distance between baselines, as a multiple of the font's line height
+    pub line_height: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            size: 16.,
+            colour: vector![1., 1., 1., 1.],
+            max_width: None,
+            align: Align::default(),
+            line_height: 1.,
+        }
+    }
+}
+
+impl TextStyle {
+    #[must_use]
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    #[must_use]
+    pub fn colour(mut self, colour: Vector4<f32>) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    #[must_use]
+    pub fn max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    #[must_use]
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    #[must_use]
+    pub fn line_height(mut self, line_height: f32) -> Self {
+        self.line_height = line_height;
+        self
+    }
+}
+
+/// Glyph placed by [`layout`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlacedGlyph {
+    pub id: GlyphId,
+    /// Pen position on the baseline, relative to the top left of the text
+    pub pos: Point2<f32>,
+}
+
+/// Glyph in a line being laid out
+#[derive(Clone, Copy)]
+struct LineGlyph {
+    id: GlyphId,
+    x: f32,
+    advance: f32,
+    whitespace: bool,
+}
+
+/// Places the glyphs of `text`, applying kerning and wrapping lines between words when they
+/// are wider than the style's maximum width
+#[must_use]
+pub fn layout(font: &Font, text: &str, style: &TextStyle) -> Vec<PlacedGlyph> {
+    let scaled = font.font.as_scaled(PxScale::from(style.size));
+    let line_advance = (scaled.ascent() - scaled.descent() + scaled.line_gap()) * style.line_height;
+
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut line: Vec<LineGlyph> = Vec::new();
+        // Index of the first glyph after the last space, where the line can be broken
+        let mut line_break = None;
+        let mut pen = 0.;
+
+        for character in paragraph.chars() {
+            let id = scaled.glyph_id(character);
+
+            if let Some(previous) = line.last() {
+                pen += scaled.kern(previous.id, id);
+            }
+
+            let advance = scaled.h_advance(id);
+            let whitespace = character.is_whitespace();
+            line.push(LineGlyph {
+                id,
+                x: pen,
+                advance,
+                whitespace,
+            });
+            pen += advance;
+
+            if whitespace {
+                line_break = Some(line.len());
+                continue;
+            }
+
+            let overflows = style.max_width.is_some_and(|max_width| pen > max_width);
+
+            if let Some(index) = line_break.filter(|_| overflows) {
+                line_break = None;
+                let mut rest = line.split_off(index);
+                let start = rest.first().map_or(0., |glyph| glyph.x);
+                rest.iter_mut().for_each(|glyph| glyph.x -= start);
+                pen -= start;
+
+                lines.push(line);
+                line = rest;
+            }
+        }
+
+        lines.push(line);
+    }
+
+    // Trailing spaces do not count towards the width a line is aligned by
+    let width = |line: &[LineGlyph]| {
+        line.iter()
+            .rev()
+            .find(|glyph| !glyph.whitespace)
+            .map_or(0., |glyph| glyph.x + glyph.advance)
+    };
+
+    let block_width = style
+        .max_width
+        .unwrap_or_else(|| lines.iter().map(|line| width(line)).fold(0., f32::max));
+
+    lines
+        .iter()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let offset = (block_width - width(line)) * style.align.factor();
+            let baseline = scaled.ascent() + line_advance * index as f32;
+
+            line.iter().map(move |glyph| PlacedGlyph {
+                id: glyph.id,
+                pos: Point2::new(glyph.x + offset, baseline),
+            })
+        })
+        .collect()
+}
+
+/// Glyph rasterised into the atlas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasGlyph {
+    /// Offset of the top left of the glyph's bitmap from the pen position
+    pub offset: Vector2<f32>,
+    pub size: Vector2<f32>,
+    pub uv_min: Point2<f32>,
+    pub uv_max: Point2<f32>,
+}
+
+/// Coverage bitmaps of every glyph drawn so far, packed into rows of one texture
+#[derive(Clone, Debug)]
+pub struct GlyphAtlas {
+    size: u32,
+    pixels: Vec<u8>,
+    /// Glyphs by font, glyph and pixel size, `None` for glyphs without an outline
+    glyphs: HashMap<(FontId, GlyphId, u32), Option<AtlasGlyph>>,
+    cursor: (u32, u32),
+    row_height: u32,
+    generation: u64,
+}
+
+impl GlyphAtlas {
+    #[must_use]
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            pixels: vec![0; (size * size) as usize],
+            glyphs: HashMap::new(),
+            cursor: (ATLAS_PADDING, ATLAS_PADDING),
+            row_height: 0,
+            generation: 0,
+        }
+    }
+
+    /// Width and height in texels
+    #[must_use]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Coverage of each texel from 0 to 255, row by row
+    #[must_use]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Incremented whenever glyphs are added, so renderers know to upload the atlas again
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Rasterises a glyph the first time it is drawn at a size. Glyphs that no longer fit
+    /// are not drawn.
+    pub fn glyph(
+        &mut self,
+        font_id: FontId,
+        font: &Font,
+        id: GlyphId,
+        size: f32,
+    ) -> Option<AtlasGlyph> {
+        let pixel_size = size.round().max(1.) as u32;
+
+        if let Some(glyph) = self.glyphs.get(&(font_id, id, pixel_size)) {
+            return *glyph;
+        }
+
+        let glyph = id.with_scale_and_position(pixel_size as f32, point(0., 0.));
+
+        let Some(outline) = font.font.outline_glyph(glyph) else {
+            self.glyphs.insert((font_id, id, pixel_size), None);
+            return None;
+        };
+
+        let bounds = outline.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+
+        if self.cursor.0 + width + ATLAS_PADDING > self.size {
+            self.cursor = (
+                ATLAS_PADDING,
+                self.cursor.1 + self.row_height + ATLAS_PADDING,
+            );
+            self.row_height = 0;
+        }
+
+        if self.cursor.1 + height + ATLAS_PADDING > self.size {
+            log::warn!("The glyph atlas is full, so some text is not drawn");
+            return None;
+        }
+
+        let (left, top) = self.cursor;
+        outline.draw(|x, y, coverage| {
+            let index = ((top + y) * self.size + left + x) as usize;
+            self.pixels[index] = (coverage.clamp(0., 1.) * 255.).round() as u8;
+        });
+
+        self.cursor.0 += width + ATLAS_PADDING;
+        self.row_height = self.row_height.max(height);
+        self.generation += 1;
+
+        let texel = 1. / self.size as f32;
+        let atlas_glyph = AtlasGlyph {
+            offset: vector![bounds.min.x, bounds.min.y],
+            size: vector![width as f32, height as f32],
+            uv_min: Point2::new(left as f32 * texel, top as f32 * texel),
+            uv_max: Point2::new((left + width) as f32 * texel, (top + height) as f32 * texel),
+        };
+
+        self.glyphs
+            .insert((font_id, id, pixel_size), Some(atlas_glyph));
+
+        Some(atlas_glyph)
+    }
+}
+
+/// Corner of a glyph quad
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextVertex {
+    pub pos: Point2<f32>,
+    pub uv: Point2<f32>,
+    pub colour: Vector4<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextSpace {
+    /// Pixels from the top left of the window
+    Screen,
+    /// The xy plane of the transform, with y pointing up
+    World(Matrix4<f32>),
+}
+
+/// Range of [`Text::vertices`] drawn in one space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextDraw {
+    pub space: TextSpace,
+    pub first: u32,
+    pub count: u32,
+}
+
+/// Fonts, their glyph atlas and the text queued for the next frame.
+///
+/// Glyphs are drawn as triangle lists of quads sampling the atlas.
+#[derive(Debug)]
+pub struct Text {
+    fonts: Vec<Font>,
+    atlas: GlyphAtlas,
+    vertices: Vec<TextVertex>,
+    draws: Vec<TextDraw>,
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self {
+            fonts: Vec::new(),
+            atlas: GlyphAtlas::new(ATLAS_SIZE),
+            vertices: Vec::new(),
+            draws: Vec::new(),
+        }
+    }
+}
+
+impl Text {
+    pub fn add_font(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() - 1)
+    }
+
+    /// Draws `text` for the next frame with its top left corner at `position`, in pixels
+    /// from the top left of the window
+    pub fn screen(&mut self, font: FontId, text: &str, position: Point2<f32>, style: &TextStyle) {
+        self.push(font, text, TextSpace::Screen, position, style);
+    }
+
+    /// Draws `text` for the next frame in the xy plane of `transform`, with its top left
+    /// corner at the origin. A unit of the plane is a pixel of the style's size, so the
+    /// transform usually scales the text down.
+    pub fn world(&mut self, font: FontId, text: &str, transform: Matrix4<f32>, style: &TextStyle) {
+        self.push(
+            font,
+            text,
+            TextSpace::World(transform),
+            Point2::origin(),
+            style,
+        );
+    }
+
+    #[must_use]
+    pub fn atlas(&self) -> &GlyphAtlas {
+        &self.atlas
+    }
+
+    #[must_use]
+    pub fn vertices(&self) -> &[TextVertex] {
+        &self.vertices
+    }
+
+    #[must_use]
+    pub fn draws(&self) -> &[TextDraw] {
+        &self.draws
+    }
+
+    /// Removes the queued text, keeping the fonts and the glyphs already in the atlas
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.draws.clear();
+    }
+
+    fn push(
+        &mut self,
+        font_id: FontId,
+        text: &str,
+        space: TextSpace,
+        origin: Point2<f32>,
+        style: &TextStyle,
+    ) {
+        let font = &self.fonts[font_id.0];
+        let first = self.vertices.len();
+
+        // Layout runs down the screen, while the world plane's y points up
+        let y = if matches!(space, TextSpace::World(_)) {
+            -1.
+        } else {
+            1.
+        };
+
+        for placed in layout(font, text, style) {
+            let Some(glyph) = self.atlas.glyph(font_id, font, placed.id, style.size) else {
+                continue;
+            };
+
+            // Whole pixels keep screen space glyphs sharp
+            let pen = placed.pos.coords + origin.coords;
+            let min = if matches!(space, TextSpace::Screen) {
+                (pen + glyph.offset).map(f32::round)
+            } else {
+                pen + glyph.offset
+            };
+            let max = min + glyph.size;
+
+            let corner = |x: f32, y_pos: f32, u: f32, v: f32| TextVertex {
+                pos: Point2::new(x, y_pos * y),
+                uv: Point2::new(u, v),
+                colour: style.colour,
+            };
+
+            let (u0, v0, u1, v1) = (
+                glyph.uv_min.x,
+                glyph.uv_min.y,
+                glyph.uv_max.x,
+                glyph.uv_max.y,
+            );
+            self.vertices.extend([
+                corner(min.x, min.y, u0, v0),
+                corner(min.x, max.y, u0, v1),
+                corner(max.x, max.y, u1, v1),
+                corner(min.x, min.y, u0, v0),
+                corner(max.x, max.y, u1, v1),
+                corner(max.x, min.y, u1, v0),
+            ]);
+        }
+
+        let count = self.vertices.len() - first;
+        if count > 0 {
+            self.draws.push(TextDraw {
+                space,
+                first: first as u32,
+                count: count as u32,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> Font {
+        Font::from_bytes(include_bytes!("../../../examples/assets/fonts/DejaVuSans.ttf").to_vec())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_layout_wraps_between_words_and_aligns_lines() {
+        let font = font();
+        let style = TextStyle::default().size(20.);
+
+        let single = layout(&font, "wave engine", &style);
+        let width = single.last().unwrap().pos.x;
+        assert!(single.iter().all(|glyph| glyph.pos.y == single[0].pos.y));
+
+        let wrapped = layout(&font, "wave engine", &style.max_width(width));
+        let (first, second) = (wrapped[0], wrapped[5]);
+        assert_eq!(first.pos.x, 0.);
+        assert_eq!(second.pos.x, 0.);
+        assert!(second.pos.y > first.pos.y);
+
+        let right = layout(&font, "a\nwave", &style.align(Align::Right));
+        let wave = layout(&font, "wave", &style);
+        let wave_width = wave.last().unwrap().pos.x
+            + font
+                .font
+                .as_scaled(PxScale::from(20.))
+                .h_advance(wave.last().unwrap().id);
+        let a_width = font
+            .font
+            .as_scaled(PxScale::from(20.))
+            .h_advance(right[0].id);
+        assert!((right[0].pos.x - (wave_width - a_width)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_glyphs_are_rasterised_once() {
+        let font = font();
+        let mut text = Text::default();
+        let id = text.add_font(font);
+
+        text.screen(id, "aa b", Point2::new(10., 10.), &TextStyle::default());
+
+        // The space has no outline, so three quads are drawn from two glyphs
+        assert_eq!(text.vertices().len(), 18);
+        assert_eq!(text.atlas().generation(), 2);
+        assert!(text.atlas().pixels().iter().any(|coverage| *coverage > 0));
+
+        text.clear();
+        assert!(text.vertices().is_empty() && text.draws().is_empty());
+        assert_eq!(text.atlas().generation(), 2);
+    }
+}
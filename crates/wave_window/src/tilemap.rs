@@ -0,0 +1,25 @@
+use crate::sprite::SpriteTextureId;
+use wave_space::space::VerticesAndIndices;
+
+/// Where a [`TileChunk`] belongs: its map, layer and tileset, and its position in chunks from
+/// the map's top left
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TileChunkKey {
+    pub map: usize,
+    pub layer: usize,
+    pub chunk: [u32; 2],
+    pub tileset: usize,
+}
+
+/// Tiles of one layer sampling one tileset within a square of a map, meshed into quads.
+///
+/// Renderers keep the uploaded mesh until a chunk with the same key arrives with another
+/// generation, so unchanged chunks are not uploaded again.
+#[derive(Clone, Debug)]
+pub struct TileChunk {
+    pub key: TileChunkKey,
+    pub texture: SpriteTextureId,
+    /// Changes whenever the chunk is meshed again
+    pub generation: u64,
+    pub mesh: VerticesAndIndices,
+}
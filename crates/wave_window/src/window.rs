@@ -1,4 +1,9 @@
+use crate::{
+    debug_draw::LineVertex, gui::GuiFrame, particles::ParticleBatches, settings::RenderSettings,
+    sprite::Sprites, stats::FrameStats, text::Text, tilemap::TileChunk,
+};
 use nalgebra::Matrix4;
+use std::any::Any;
 use wave_space::space;
 use winit::{
     application::ApplicationHandler,
@@ -13,6 +18,17 @@ pub trait Drawable {
     fn draw(&mut self, window: &Window, input: &mut Input, renderer: &mut Box<dyn RenderBackend>);
 
     fn create_renderer(&self, window: &Window) -> Box<dyn RenderBackend>;
+
+    /// Sees every window event before the display does, returning whether it was consumed
+    /// and should not reach the camera
+    fn window_event(&mut self, _window: &Window, _event: &WindowEvent) -> bool {
+        false
+    }
+
+    /// Called once when the event loop is about to exit, after the renderer is idle, so
+    /// resources created on it can be destroyed. There is no renderer when no window was
+    /// ever created.
+    fn exiting(&mut self, _renderer: Option<&mut Box<dyn RenderBackend>>) {}
 }
 
 pub trait RenderBackend {
@@ -24,7 +40,112 @@ pub trait RenderBackend {
 
     fn update(&mut self, view: Matrix4<f32>);
 
+    /// Replaces the model matrix of each object, in the order they were created
+    fn set_models(&mut self, models: &[Matrix4<f32>]);
+
+    /// Places each light by a world matrix, in the order they were created. Lights without
+    /// one keep their own position and direction.
+    fn set_light_transforms(&mut self, transforms: &[Matrix4<f32>]);
+
+    /// Shows or hides the object at `index`, in the order they were created, from the next
+    /// frame onwards
+    fn set_visible(&mut self, index: usize, visible: bool);
+
+    /// Copies the next rendered frame into host memory, to be read with
+    /// [`RenderBackend::capture`]
+    fn request_capture(&mut self);
+
+    /// Waits for the frame copied after [`RenderBackend::request_capture`] and returns it, or
+    /// `None` when no frame has been copied since the last capture, such as when the swap
+    /// chain was recreated instead, or the backend cannot read its images.
+    fn capture(&mut self) -> Option<FrameCapture>;
+
+    /// Timings and counters of the latest frame the GPU has finished
+    fn frame_stats(&self) -> &FrameStats;
+
+    /// Measures GPU timings of the next frames, which costs timestamp queries, or stops
+    fn set_profiling(&mut self, enabled: bool);
+
+    /// Draws every object without its own debug view in `view` from the next frame onwards
+    fn set_debug_view(&mut self, view: DebugView);
+
+    /// Draws the object at `index` in `view` from the next frame onwards, or in the global
+    /// view when `None`
+    fn set_object_debug_view(&mut self, index: usize, view: Option<DebugView>);
+
+    /// Replaces the quality settings. The affected resources are rebuilt along with the swap
+    /// chain after the next rendered frame.
+    fn set_render_settings(&mut self, render_settings: &RenderSettings);
+
+    /// Replaces the debug lines drawn over the scene, given as pairs of vertices
+    fn set_debug_lines(&mut self, vertices: &[LineVertex]);
+
+    /// Replaces the text drawn over the scene, uploading the glyph atlas when it changed
+    fn set_text(&mut self, text: &Text);
+
+    /// Replaces the sprites drawn in the scene, uploading textures added since the last call
+    fn set_sprites(&mut self, sprites: &Sprites);
+
+    /// Replaces the tilemap chunks drawn under the sprites, uploading only the chunks that are
+    /// new or changed
+    fn set_tile_chunks(&mut self, chunks: &[&TileChunk]);
+
+    /// Replaces the particles drawn in the scene, which sample the sprite textures
+    fn set_particles(&mut self, particles: &ParticleBatches);
+
+    /// Replaces the gui drawn over the final image, uploading the textures that changed
+    fn set_gui(&mut self, frame: &GuiFrame);
+
     fn destroy(&self);
+
+    /// The concrete backend, for what only it understands, such as its materials
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Rendered frame read back from the GPU
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameCapture {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed RGBA8 rows, top row first
+    pub pixels: Vec<u8>,
+}
+
+/// How geometry is drawn, for inspecting meshes instead of shading them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    #[default]
+    Shaded,
+    /// Triangle edges only. Devices that cannot rasterise polygons as lines keep shading.
+    Wireframe,
+    /// Shaded, with every vertex normal drawn as a line
+    Normals,
+    /// Checkerboard over the texture coordinates, showing stretching and seams
+    UvChecker,
+    VertexColours,
+    /// Distance from the camera, brighter when closer
+    Depth,
+    /// Every fragment adds heat, so surfaces drawn over many times glow
+    Overdraw,
+}
+
+impl DebugView {
+    pub const ALL: [Self; 7] = [
+        Self::Shaded,
+        Self::Wireframe,
+        Self::Normals,
+        Self::UvChecker,
+        Self::VertexColours,
+        Self::Depth,
+        Self::Overdraw,
+    ];
+
+    /// The view after this one, wrapping around, which the debug view key cycles through
+    #[must_use]
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|view| *view == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -97,9 +218,14 @@ impl ApplicationHandler for Display {
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        let consumed = self
+            .window
+            .as_ref()
+            .is_some_and(|window| self.drawable.window_event(window, &event));
+
         match event {
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
+                log::info!("The close button was pressed; stopping");
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
@@ -126,8 +252,8 @@ impl ApplicationHandler for Display {
                 event,
                 is_synthetic: false,
                 ..
-            } => {
-                // Dispatch actions only on press.
+            } if !consumed || !event.state.is_pressed() => {
+                // Dispatch actions only on press. Releases always pass so no key sticks.
                 process_keyboard(
                     &mut self.input,
                     &event.logical_key,
@@ -149,6 +275,10 @@ impl ApplicationHandler for Display {
         _device_id: DeviceId,
         event: DeviceEvent,
     ) {
+        if self.input.pointer_captured {
+            return;
+        }
+
         match event {
             DeviceEvent::MouseWheel { delta, .. } => {
                 self.input.mouse_scroll = -match delta {
@@ -170,6 +300,8 @@ impl ApplicationHandler for Display {
         if let Some(renderer) = &self.renderer {
             renderer.destroy();
         }
+
+        self.drawable.exiting(self.renderer.as_mut());
     }
 }
 
@@ -197,7 +329,7 @@ impl Display {
 
             match event_loop.run_app(self) {
                 Ok(()) => (),
-                Err(e) => eprintln!("Error: {e}"),
+                Err(e) => log::error!("Event loop stopped: {e}"),
             }
         }
     }
@@ -222,6 +354,14 @@ fn process_keyboard(input: &mut Input, key: &Key, is_pressed: bool) {
         Key::Named(NamedKey::Shift) => {
             input.look.set_down(amount);
         }
+        #[cfg(debug_assertions)]
+        Key::Named(NamedKey::F12) if is_pressed => {
+            input.screenshot = true;
+        }
+        #[cfg(debug_assertions)]
+        Key::Named(NamedKey::F3) if is_pressed => {
+            input.next_debug_view = true;
+        }
         _ => (),
     }
 }
@@ -240,6 +380,13 @@ pub struct Input {
     pub mouse_scroll: f64,
     pub mouse_delta: (f64, f64),
     pub look: space::LookDirection,
+    /// Set when the screenshot key was pressed in a debug build, until the drawable takes the
+    /// screenshot
+    pub screenshot: bool,
+    /// Set when the debug view key was pressed, until the drawable switches to the next view
+    pub next_debug_view: bool,
+    /// Set while the gui uses the pointer, so mouse motion and scrolling skip the camera
+    pub pointer_captured: bool,
     first_mouse_event: FirstCheck,
 }
 
@@ -281,4 +428,17 @@ mod tests {
         //
         // assert_eq!(res_custom, Self::new(3_442, 2_349));
     }
+
+    #[test]
+    fn test_debug_views_cycle_back_to_shaded() {
+        use super::DebugView;
+
+        let mut view = DebugView::Shaded;
+        for expected in DebugView::ALL.iter().skip(1) {
+            view = view.next();
+            assert_eq!(view, *expected);
+        }
+
+        assert_eq!(view.next(), DebugView::Shaded);
+    }
 }
@@ -0,0 +1,92 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+use wave_engine::{prelude::*, wave_vulkan::compute::ComputeDevice};
+
+/// Compiled from `wave.comp` by `make compile_shaders`
+const WAVE: &str = "./examples/assets/shaders/wave.spv";
+const POINTS: u32 = 256;
+const LOCAL_SIZE: u32 = 64;
+const VULKAN: &str = "The engine renders with Vulkan";
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct WaveConstants {
+    time: f32,
+    count: u32,
+}
+
+/// Heights of a travelling wave, computed on the GPU each frame
+struct Wave {
+    pipeline: ComputePipeline,
+    heights: StorageBuffer,
+    time: Duration,
+}
+
+impl Wave {
+    fn new(device: &mut ComputeDevice) -> Self {
+        let pipeline = device
+            .load_pipeline(
+                WAVE,
+                &[ComputeBinding::StorageBuffer],
+                size_of::<WaveConstants>() as u32,
+                "wave",
+            )
+            .expect("Failed to load the wave shader, compile it with `make compile_shaders`");
+        let heights = device.create_buffer_with(
+            &[0f32; POINTS as usize],
+            BufferLocation::Device,
+            "wave/heights",
+        );
+        device.bind(&pipeline, &[ComputeResource::Buffer(&heights)]);
+
+        Self {
+            pipeline,
+            heights,
+            time: Duration::ZERO,
+        }
+    }
+}
+
+fn main() {
+    let wave = Rc::new(RefCell::new(None::<Wave>));
+    let exit_wave = Rc::clone(&wave);
+
+    Engine::default()
+        .on_frame(move |frame| {
+            let mut wave = wave.borrow_mut();
+            let wave = wave.get_or_insert_with(|| Wave::new(&mut frame.compute().expect(VULKAN)));
+            wave.time += frame.frame_time;
+
+            // Heights from the previous frame's dispatch, as reading waits for it
+            let heights = frame.compute().expect(VULKAN).read::<f32>(&wave.heights);
+            let point = |index: usize| {
+                let x = index as f32 / (POINTS - 1) as f32;
+                Point3::new(2. * x - 1., heights[index], 0.)
+            };
+            (1..heights.len()).for_each(|index| {
+                frame
+                    .debug_draw
+                    .line(point(index - 1), point(index), Vector3::new(0.2, 0.6, 1.));
+            });
+
+            frame
+                .dispatch(
+                    ComputeStage::BeforeRender,
+                    Dispatch::covering(&wave.pipeline, [POINTS, 1, 1], [LOCAL_SIZE, 1, 1])
+                        .push_constants(&WaveConstants {
+                            time: wave.time.as_secs_f32(),
+                            count: POINTS,
+                        }),
+                )
+                .expect(VULKAN);
+        })
+        .on_exit(move |frame| {
+            if let Some(wave) = exit_wave.borrow_mut().take() {
+                let mut device = frame.compute().expect(VULKAN);
+                unsafe {
+                    device.destroy_buffer(wave.heights);
+                    device.destroy_pipeline(wave.pipeline);
+                }
+            }
+        })
+        .run()
+}
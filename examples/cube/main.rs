@@ -1,5 +1,7 @@
 use wave_engine::prelude::*;
 
+const FONT: &str = "./examples/assets/fonts/DejaVuSans.ttf";
+
 #[geometry(Cube)]
 struct BoxGeom;
 
@@ -21,5 +23,24 @@ fn main() {
             .build(),
     );
 
-    Engine::default().with_geometry(&[cube]).run()
+    let mut engine = Engine::default();
+    let font = engine.add_font(Font::load(FONT).expect("Failed to load font!"));
+
+    engine
+        .with_geometry(&[cube])
+        .on_frame(move |frame| {
+            let fps = 1. / frame.frame_time.as_secs_f32().max(f32::EPSILON);
+            frame.text.screen(
+                font,
+                &format!("{fps:.0} fps"),
+                Point2::new(10., 10.),
+                &TextStyle::default(),
+            );
+        })
+        .ui(|ctx| {
+            egui::Window::new("Cube").show(ctx, |ui| {
+                ui.label("Drag the camera outside this window");
+            });
+        })
+        .run()
 }
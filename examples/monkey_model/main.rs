@@ -1,7 +1,9 @@
+use std::time::Duration;
 use wave_engine::prelude::*;
 
 const CUBE_MODEL: &str = "./examples/assets/models/monkey_model/monkey_head.obj";
 const SATURN_TEXTURE: &str = "./examples/assets/textures/2k_saturn.jpg";
+const POLISH_INTERVAL: Duration = Duration::from_secs(2);
 
 #[geometry(Model)]
 struct ModelGeom;
@@ -13,7 +15,18 @@ impl Behavior for ModelGeom {
 #[geometry_system(ModelGeom)]
 struct Geom;
 
+fn monkey_material(roughness: f32) -> Material {
+    Material::new(Shader::Pbr)
+        .pbr(PbrMaterial::default().metallic(0.2).roughness(roughness))
+        .texture(SATURN_TEXTURE)
+        .cull_mode(CullMode::Back)
+}
+
 fn main() {
+    let mut engine = Engine::default();
+
+    let material = engine.add_material(monkey_material(0.4));
+
     let monkey_model = Geom::ModelGeom(
         ModelGeom::default()
             .properties(
@@ -22,12 +35,29 @@ fn main() {
                     .model_path(CUBE_MODEL)
                     .build(),
             )
-            .texture(SATURN_TEXTURE)
-            .shader(Shader::LightTexture)
-            .cull_mode(CullMode::Back)
+            .material(material)
             .no_index()
             .build(),
     );
 
-    Engine::default().with_geometry(&[monkey_model]).run()
+    // The shared material is swapped between rough and polished every couple of seconds
+    let mut since_polish = Duration::ZERO;
+    let mut polished = false;
+
+    engine
+        .with_geometry(&[monkey_model])
+        .on_frame(move |frame| {
+            since_polish += frame.frame_time;
+
+            if since_polish >= POLISH_INTERVAL {
+                since_polish = Duration::ZERO;
+                polished = !polished;
+
+                let roughness = if polished { 0.05 } else { 0.4 };
+                frame
+                    .set_material(material, &monkey_material(roughness))
+                    .expect("The engine renders with Vulkan");
+            }
+        })
+        .run()
 }
@@ -0,0 +1,77 @@
+use wave_engine::prelude::*;
+
+const PARTICLE: &str = "./examples/assets/sprites/particle.png";
+
+fn main() {
+    let mut engine = Engine::default().with_post_process(
+        PostProcess::default()
+            .tone_mapping(ToneMapping::Aces)
+            .bloom(Bloom::default()),
+    );
+    let texture = engine.add_sprite_texture(PARTICLE);
+
+    let fire = ParticleEmitterBuilder::default()
+        .texture(texture)
+        .blend(ParticleBlend::Additive)
+        .shape(EmitterShape::Sphere { radius: 0.1 })
+        .rate(120.)
+        .lifetime(0.8)
+        .lifetime_variation(0.3)
+        .spread(0.3)
+        .speed(1.)
+        .speed_variation(0.4)
+        .size(Curve::linear(0.3, 0.05))
+        .colour(
+            Curve::linear(
+                Vector4::new(1., 0.8, 0.3, 1.),
+                Vector4::new(0.8, 0.1, 0., 0.),
+            )
+            .key(0.3, Vector4::new(1., 0.4, 0.1, 0.8)),
+        )
+        .seed(1)
+        .build();
+
+    let smoke = ParticleEmitterBuilder::default()
+        .texture(texture)
+        .position(Point3::new(0., 0.6, 0.))
+        .rate(12.)
+        .lifetime(3.)
+        .spread(0.4)
+        .speed(0.4)
+        .drag(0.3)
+        .gravity(Vector3::new(0.1, 0., 0.))
+        .size(Curve::linear(0.2, 0.9))
+        .colour(Curve::linear(
+            Vector4::new(0.3, 0.3, 0.3, 0.5),
+            Vector4::new(0.5, 0.5, 0.5, 0.),
+        ))
+        .seed(2)
+        .build();
+
+    // A pop of sparks every second and a half
+    let sparks = ParticleEmitterBuilder::default()
+        .texture(texture)
+        .blend(ParticleBlend::Additive)
+        .bursts(vec![Burst::new(0., 40)])
+        .duration(1.5)
+        .lifetime(1.2)
+        .lifetime_variation(0.5)
+        .spread(0.6)
+        .speed(3.)
+        .speed_variation(0.5)
+        .gravity(Vector3::new(0., -9.8, 0.))
+        .drag(0.5)
+        .size(Curve::constant(0.05))
+        .colour(Curve::linear(
+            Vector4::new(1., 0.9, 0.5, 1.),
+            Vector4::new(1., 0.3, 0., 0.),
+        ))
+        .seed(3)
+        .build();
+
+    engine.add_particle_emitter(smoke);
+    engine.add_particle_emitter(fire);
+    engine.add_particle_emitter(sparks);
+
+    engine.run()
+}
@@ -2,6 +2,7 @@ use wave_engine::prelude::*;
 
 const SATURN_TEXTURE: &str = "./examples/assets/textures/2k_saturn.jpg";
 const RING_TEXTURE: &str = "./examples/assets/textures/2k_saturn_ring_alpha.png";
+const DUST_TEXTURE: &str = "./examples/assets/sprites/particle.png";
 
 #[geometry(Sphere)]
 struct SphereGeom;
@@ -32,6 +33,7 @@ fn main() {
                     .stack_count(sections)
                     .build(),
             )
+            .label("saturn")
             .texture(SATURN_TEXTURE)
             .shader(Shader::LightTexture)
             .cull_mode(CullMode::Back)
@@ -47,13 +49,70 @@ fn main() {
                     .sector_count(sections)
                     .build(),
             )
+            .label("ring")
             .texture(RING_TEXTURE)
             .shader(Shader::LightTexture)
             .topology(ModelTopology::TriangleStrip)
             .cull_mode(CullMode::None)
+            .blend_mode(BlendMode::AlphaBlend)
             .no_index()
             .build(),
     );
 
-    Engine::default().with_geometry(&[sphere, ring]).run()
+    let mut engine = Engine::default()
+        .with_geometry(&[sphere, ring])
+        .with_skybox(Skybox::starfield(1024, 0.002, 7))
+        .with_light(DirectionalLight::new(Vector3::new(-1., -0.4, -0.2)).intensity(1.5))
+        .with_post_process(
+            PostProcess::default()
+                .tone_mapping(ToneMapping::Aces)
+                .bloom(Bloom::default())
+                .vignette(Vignette::default())
+                .fxaa(true),
+        );
+
+    let axial_tilt = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), -1.1);
+
+    // Dust glinting across the ring
+    let dust = engine.add_sprite_texture(DUST_TEXTURE);
+    engine.add_particle_emitter(
+        ParticleEmitterBuilder::default()
+            .texture(dust)
+            .blend(ParticleBlend::Additive)
+            .rotation(axial_tilt)
+            .shape(EmitterShape::Ring {
+                inner_radius: 0.5,
+                outer_radius: 1.,
+            })
+            .rate(150.)
+            .lifetime(2.)
+            .lifetime_variation(0.5)
+            .spread(std::f32::consts::PI)
+            .speed(0.01)
+            .size(Curve::linear(0.015, 0.))
+            .colour(Curve::linear(
+                Vector4::new(1., 0.9, 0.7, 0.8),
+                Vector4::new(1., 0.9, 0.7, 0.),
+            ))
+            .build(),
+    );
+
+    // The ring is attached to the same node as the planet, so they spin together about the
+    // tilted axis
+    let tilt = engine
+        .scene()
+        .add(None, Transform::from_rotation(axial_tilt));
+    let spin = engine.scene().add(Some(tilt), Transform::default());
+    engine.scene().attach(spin, Attachment::Geometry(0));
+    engine.scene().attach(spin, Attachment::Geometry(1));
+
+    engine
+        .on_frame(move |frame| {
+            let angle = 0.2 * frame.frame_time.as_secs_f32();
+            frame
+                .scene
+                .local_mut(spin)
+                .rotate(UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle));
+        })
+        .run()
 }
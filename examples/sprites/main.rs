@@ -0,0 +1,36 @@
+use wave_engine::prelude::*;
+
+const ORB: &str = "./examples/assets/sprites/orb.png";
+const SATURN: &str = "./examples/assets/textures/2k_saturn.jpg";
+
+fn main() {
+    let mut engine = Engine::default();
+    let orb = engine.add_sprite_texture(ORB);
+    let saturn = engine.add_sprite_texture(SATURN);
+
+    let sheet = SpriteSheet::new(orb, 4, 1);
+    let mut spin = Animation::new(0, sheet.frame_count(), 8.).looping(true);
+    let mut angle = 0f32;
+
+    engine
+        .on_frame(move |frame| {
+            spin.update(frame.frame_time);
+            angle += frame.frame_time.as_secs_f32();
+
+            frame.sprites.draw(
+                Sprite::new(saturn, Vector2::new(256., 128.))
+                    .rotation(angle * 0.25)
+                    .z(-1),
+            );
+
+            (0..8).for_each(|index| {
+                let around = angle + index as f32 * std::f32::consts::FRAC_PI_4;
+                frame.sprites.draw(
+                    Sprite::new(orb, Vector2::new(32., 32.))
+                        .position(Point2::new(around.cos(), around.sin()) * 160.)
+                        .frame(&sheet, spin.frame()),
+                );
+            });
+        })
+        .run()
+}
@@ -0,0 +1,32 @@
+use std::time::Duration;
+use wave_engine::prelude::*;
+
+const MAP: &str = "./examples/assets/maps/arena.tmx";
+
+fn main() {
+    let mut engine = Engine::default();
+    let arena = engine.load_tilemap(MAP).expect("Failed to load tilemap!");
+
+    let mut since_change = Duration::ZERO;
+    let mut column = 1;
+
+    engine
+        .on_frame(move |frame| {
+            let map = frame.tilemaps.get_mut(arena);
+
+            // Centre the camera on the map
+            let size =
+                Vector2::new(map.width as f32, map.height as f32).component_mul(&map.tile_size);
+            frame.sprites.camera.centre = map.position + Vector2::new(size.x, -size.y) / 2.;
+
+            // Walk a tile along the second row, so only the chunk it is in is meshed again
+            since_change += frame.frame_time;
+            if since_change > Duration::from_millis(250) {
+                since_change = Duration::ZERO;
+                map.set_tile(0, column, 1, Tile::EMPTY);
+                column = column % (map.width - 2) + 1;
+                map.set_tile(0, column, 1, Tile(2));
+            }
+        })
+        .run()
+}